@@ -0,0 +1,110 @@
+use crate::prelude::{DiffOptions, KeyChange, Operation, Registry, RegistryDiff};
+
+/// The number of differences listed by [assert_registries_equal].
+const REPORTED: usize = 10;
+
+/// Asserts that two registries hold the same keys and values, such as the registry an
+/// installer produced and the one it is expected to produce.
+///
+/// # Panics
+///
+/// If the registries differ, with a message listing the first differences like
+/// [compare_report] does.
+#[track_caller]
+pub fn assert_registries_equal(expected: &Registry, actual: &Registry) {
+    if let Some(report) = compare_report(expected, actual, REPORTED) {
+        panic!("{}", report);
+    }
+}
+
+/// Compares two registries, describing how they differ in a readable report.
+///
+/// Each added or deleted key, and each added, deleted or modified value, is a difference,
+/// listed on its own line: lines starting with `+` show what only `actual` holds, lines
+/// starting with `-` what only `expected` holds, and modified values show the expected data
+/// followed by the actual data.
+///
+/// # Arguments
+///
+/// * `expected` - The registry holding the expected keys and values.
+/// * `actual` - The registry to check.
+/// * `limit` - The maximum number of differences listed, the others only being counted.
+///
+/// # Returns
+///
+/// `None` if the registries hold the same keys and values, or the report of their
+/// differences.
+pub fn compare_report(expected: &Registry, actual: &Registry, limit: usize) -> Option<String> {
+    let diff = RegistryDiff::new(expected, actual, &DiffOptions::default());
+    let mut differences = Vec::new();
+    for key in diff.keys().values() {
+        let name = key.name().raw();
+        match key.change() {
+            KeyChange::Added => differences.push(format!("+[{}]", name)),
+            KeyChange::Deleted => differences.push(format!("-[{}]", name)),
+            KeyChange::Modified | KeyChange::Unchanged => {}
+        }
+        let mut values: Vec<_> = key
+            .values()
+            .iter()
+            .filter(|(_, operation)| !matches!(operation, Operation::Unchanged))
+            .collect();
+        values.sort_by_key(|(name, _)| *name);
+        differences.extend(
+            values
+                .into_iter()
+                .map(|(_, operation)| format!("[{}] {}", name, operation)),
+        );
+        if let Some(link) = key.link() {
+            differences.push(format!("[{}] link {}", name, link));
+        }
+    }
+    if differences.is_empty() {
+        return None;
+    }
+
+    let mut report = format!(
+        "registries differ in {} places (+ actual only, - expected only):",
+        differences.len()
+    );
+    for difference in differences.iter().take(limit) {
+        report.push_str("\n  ");
+        report.push_str(difference);
+    }
+    if differences.len() > limit {
+        report.push_str(&format!("\n  ... and {} more", differences.len() - limit));
+    }
+    Some(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Hive;
+
+    #[test]
+    fn test_compare_report() {
+        let expected = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=\"a\"\n\"B\"=dword:00000001\n\n\
+            [HKEY_CURRENT_USER\\Old]\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let actual = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=\"b\"\n\"C\"=\"c\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+
+        assert_eq!(compare_report(&expected, &expected, 10), None);
+        assert_registries_equal(&actual, &actual);
+        assert_eq!(
+            compare_report(&expected, &actual, 2).unwrap(),
+            "registries differ in 4 places (+ actual only, - expected only):\n  \
+            [HKEY_CURRENT_USER\\App] \"A\"=\"a\" -> \"A\"=\"b\"\n  \
+            [HKEY_CURRENT_USER\\App] -\"B\"=dword:00000001\n  ... and 2 more"
+        );
+        let panic = std::panic::catch_unwind(|| assert_registries_equal(&expected, &actual));
+        assert!(panic.is_err());
+    }
+}
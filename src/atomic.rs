@@ -0,0 +1,153 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers the temporary files created by the process, so threads writing the same file at
+/// once each get their own.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A file written through a temporary file next to it, which only replaces the file when
+/// committed. Readers see either the old or the new content, never a partial write, even if
+/// the process is interrupted or the system crashes while writing.
+///
+/// The temporary file is removed if the [AtomicFile] is dropped without being committed.
+pub(crate) struct AtomicFile {
+    /// The file to replace.
+    path: PathBuf,
+    /// The temporary file written to, next to the file to replace.
+    temp: PathBuf,
+    /// The open temporary file, `None` once committed.
+    file: Option<BufWriter<File>>,
+}
+
+impl AtomicFile {
+    /// Creates the temporary file to write the content of a file to.
+    pub(crate) fn create<T: AsRef<Path>>(path: T) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let name = path.file_name().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("'{}' is not a file path", path.display()),
+            )
+        })?;
+        loop {
+            let temp = path.with_file_name(format!(
+                ".{}.{}.{}.tmp",
+                name.to_string_lossy(),
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            // Never reuse a file left behind by a process that had the same id.
+            match OpenOptions::new().write(true).create_new(true).open(&temp) {
+                Ok(file) => {
+                    return Ok(Self {
+                        path,
+                        temp,
+                        file: Some(BufWriter::new(file)),
+                    })
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Flushes the written content to disk and replaces the file with it.
+    pub(crate) fn commit(mut self) -> std::io::Result<()> {
+        if let Some(file) = self.file.take() {
+            let file = file.into_inner().map_err(|error| error.into_error())?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&self.temp, &self.path)?;
+        // Persist the rename itself, which lives in the directory.
+        #[cfg(unix)]
+        if let Some(parent) = self.path.parent() {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the open temporary file.
+    fn file(&mut self) -> &mut BufWriter<File> {
+        self.file.as_mut().expect("atomic file already committed")
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file().flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Writes a file atomically, like [std::fs::write] but through an [AtomicFile].
+pub(crate) fn write<T: AsRef<Path>, C: AsRef<[u8]>>(path: T, contents: C) -> std::io::Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(contents.as_ref())?;
+    file.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atomic_write() {
+        let dir = std::env::temp_dir().join(format!("regdiff-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("user.reg");
+        write(&path, "old").unwrap();
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"partial").unwrap();
+        drop(file);
+        let kept = std::fs::read_to_string(&path).unwrap();
+
+        write(&path, "new").unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(kept, "old");
+        assert_eq!(written, "new");
+        assert_eq!(files, 1);
+    }
+
+    #[test]
+    fn test_concurrent_atomic_writes() {
+        let dir = std::env::temp_dir().join(format!("regdiff-threads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("user.reg");
+        let contents: Vec<String> = (0..8).map(|index| index.to_string().repeat(4096)).collect();
+
+        std::thread::scope(|scope| {
+            for content in &contents {
+                let path = &path;
+                scope.spawn(move || write(path, content).unwrap());
+            }
+        });
+        let written = std::fs::read_to_string(&path).unwrap();
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(contents.contains(&written));
+        assert_eq!(files, 1);
+    }
+}
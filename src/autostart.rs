@@ -0,0 +1,221 @@
+use crate::prelude::{KeyPattern, Operation, RegistryDiff, Value};
+use regashii::{KeyName, ValueName};
+
+/// The keys whose values are commands run at logon, with the location they belong to.
+const RUN_KEYS: [(&str, AutostartLocation); 4] = [
+    (
+        "*\\Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+        AutostartLocation::Run,
+    ),
+    (
+        "*\\Software\\Wow6432Node\\Microsoft\\Windows\\CurrentVersion\\Run",
+        AutostartLocation::Run,
+    ),
+    (
+        "*\\Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+        AutostartLocation::RunOnce,
+    ),
+    (
+        "*\\Software\\Wow6432Node\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+        AutostartLocation::RunOnce,
+    ),
+];
+
+/// The key holding the programs started by Winlogon.
+const WINLOGON_KEY: &str = "*\\Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon";
+
+/// The Winlogon values naming programs started at logon.
+const WINLOGON_VALUES: [&str; 2] = ["Shell", "Userinit"];
+
+/// A location programs are started from at logon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AutostartLocation {
+    /// A `Run` key, whose commands are run at every logon.
+    Run,
+    /// A `RunOnce` key, whose commands are run at the next logon and then removed.
+    RunOnce,
+    /// The `Shell` and `Userinit` values of the `Winlogon` key.
+    Winlogon,
+}
+
+impl std::fmt::Display for AutostartLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Run => write!(f, "Run"),
+            Self::RunOnce => write!(f, "RunOnce"),
+            Self::Winlogon => write!(f, "Winlogon"),
+        }
+    }
+}
+
+/// A change to a program started at logon.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AutostartChange {
+    /// The full name of the key holding the entry.
+    key: KeyName,
+    /// The location the entry belongs to.
+    location: AutostartLocation,
+    /// The name of the entry.
+    name: String,
+    /// The command before the change, or `None` if the entry was added.
+    before: Option<String>,
+    /// The command after the change, or `None` if the entry was removed.
+    after: Option<String>,
+}
+
+impl AutostartChange {
+    /// Returns the full name of the key holding the entry.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the location the entry belongs to.
+    pub fn location(&self) -> AutostartLocation {
+        self.location
+    }
+
+    /// Returns the name of the entry.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the command before the change, or `None` if the entry was added.
+    pub fn before(&self) -> Option<&str> {
+        self.before.as_deref()
+    }
+
+    /// Returns the command after the change, or `None` if the entry was removed.
+    pub fn after(&self) -> Option<&str> {
+        self.after.as_deref()
+    }
+}
+
+impl std::fmt::Display for AutostartChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}: {} -> {}",
+            self.location,
+            self.name,
+            self.before.as_deref().unwrap_or("(none)"),
+            self.after.as_deref().unwrap_or("(none)")
+        )
+    }
+}
+
+/// Returns the command held by a value.
+fn command(value: &Value) -> Option<String> {
+    match value.value() {
+        regashii::Value::Sz(data) | regashii::Value::ExpandSz(data) => Some(data.clone()),
+        _ => None,
+    }
+}
+
+impl RegistryDiff {
+    /// Returns the changes to programs started at logon, from the `Run` and `RunOnce` keys of
+    /// every hive and the `Winlogon` key.
+    pub fn autostart(&self) -> Vec<AutostartChange> {
+        let run_keys = RUN_KEYS.map(|(pattern, location)| (KeyPattern::new(pattern), location));
+        let winlogon = KeyPattern::new(WINLOGON_KEY);
+        let mut changes = Vec::new();
+
+        for key in self.keys().values() {
+            let location = match run_keys
+                .iter()
+                .find(|(pattern, _)| pattern.matches(key.name()))
+            {
+                Some((_, location)) => *location,
+                None if winlogon.matches(key.name()) => AutostartLocation::Winlogon,
+                None => continue,
+            };
+
+            for (name, operation) in key.values() {
+                let name = match name {
+                    ValueName::Named(name) => name.clone(),
+                    ValueName::Default => continue,
+                };
+                if location == AutostartLocation::Winlogon
+                    && !WINLOGON_VALUES
+                        .iter()
+                        .any(|value| value.eq_ignore_ascii_case(&name))
+                {
+                    continue;
+                }
+
+                let (before, after) = match operation {
+                    Operation::Add { data } => (None, command(data)),
+                    Operation::Delete { data } => (command(data), None),
+                    Operation::Modify { old_data, new_data }
+                    | Operation::Volatile { old_data, new_data }
+                    | Operation::Reordered { old_data, new_data } => {
+                        (command(old_data), command(new_data))
+                    }
+                    Operation::Unchanged => continue,
+                };
+                if before != after {
+                    changes.push(AutostartChange {
+                        key: key.name().clone(),
+                        location,
+                        name,
+                        before,
+                        after,
+                    });
+                }
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::registry;
+    use crate::prelude::{DiffOptions, Hive};
+
+    #[test]
+    fn test_autostart_changes() {
+        let winlogon = "Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon";
+        let old = registry(
+            Hive::LocalMachine,
+            &[
+                (winlogon, "Shell", "explorer.exe"),
+                (winlogon, "AutoRestartShell", "1"),
+            ],
+        );
+        let new = registry(
+            Hive::LocalMachine,
+            &[
+                (
+                    "Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+                    "Updater",
+                    "updater.exe",
+                ),
+                (
+                    "Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+                    "Setup",
+                    "setup.exe /finish",
+                ),
+                (winlogon, "Shell", "explorer.exe,payload.exe"),
+                (winlogon, "AutoRestartShell", "0"),
+            ],
+        );
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+
+        let mut changes: Vec<String> = diff
+            .autostart()
+            .iter()
+            .map(AutostartChange::to_string)
+            .collect();
+        changes.sort();
+        assert_eq!(
+            changes,
+            vec![
+                "Run: Updater: (none) -> updater.exe",
+                "RunOnce: Setup: (none) -> setup.exe /finish",
+                "Winlogon: Shell: explorer.exe -> explorer.exe,payload.exe",
+            ]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use crate::atomic;
+use crate::prelude::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The extension of backup files.
+const EXTENSION: &str = "bak";
+
+/// Where registry files are backed up before being rewritten and how many backups are kept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupPolicy {
+    /// Whether backups are created.
+    enabled: bool,
+    /// The directory backups are stored in, `None` for the directory of the backed up file.
+    directory: Option<PathBuf>,
+    /// The number of most recent backups kept per file, if limited.
+    keep: Option<usize>,
+    /// The age after which backups are removed, if limited.
+    max_age: Option<Duration>,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: None,
+            keep: Some(5),
+            max_age: None,
+        }
+    }
+}
+
+impl BackupPolicy {
+    /// Constructs a new [BackupPolicy] keeping the 5 most recent backups of each file, next
+    /// to it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether backups are created. Disabled policies leave files unprotected.
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Returns whether backups are created.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Sets the directory backups are stored in, created if missing. By default, backups are
+    /// stored next to the file, where Wine ignores them.
+    pub fn with_directory<T: Into<PathBuf>>(mut self, directory: T) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Returns the directory backups are stored in, `None` for the directory of the file.
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+
+    /// Sets the number of most recent backups kept per file, or `None` to keep them all. The
+    /// backup just created is always kept.
+    pub fn with_keep(mut self, keep: Option<usize>) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// Returns the number of most recent backups kept per file, if limited.
+    pub fn keep(&self) -> Option<usize> {
+        self.keep
+    }
+
+    /// Sets the age after which backups are removed, or `None` to keep them regardless of
+    /// their age. The backup just created is always kept.
+    pub fn with_max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Returns the age after which backups are removed, if limited.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Returns the directory the backups of a file are stored in.
+    fn directory_of(&self, file: &Path) -> PathBuf {
+        match (&self.directory, file.parent()) {
+            (Some(directory), _) => directory.clone(),
+            (None, Some(parent)) => parent.to_path_buf(),
+            (None, None) => PathBuf::new(),
+        }
+    }
+}
+
+/// A copy of a registry file taken before it was rewritten, named after the file and the
+/// time it was taken, such as `user.reg.1760601600.bak`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backup {
+    /// The path of the backed up file.
+    original: PathBuf,
+    /// The path of the backup.
+    path: PathBuf,
+    /// The time the backup was taken, in seconds since the Unix epoch.
+    timestamp: u64,
+    /// Tells apart backups taken within the same second.
+    sequence: u64,
+}
+
+impl Backup {
+    /// Returns the path of the backed up file, which [restore_backup] overwrites.
+    pub fn original(&self) -> &Path {
+        &self.original
+    }
+
+    /// Returns the path of the backup.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the time the backup was taken, to the second.
+    pub fn created(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+}
+
+/// Backs up a file, then removes the backups of the file falling outside the retention
+/// options of the policy.
+///
+/// # Arguments
+///
+/// * `file` - The file to back up, such as the `user.reg` of a Wine prefix.
+/// * `policy` - Where the backup is stored and which backups are kept.
+///
+/// # Returns
+///
+/// The backup, `None` if the policy is disabled or the file doesn't exist, or an error if it
+/// couldn't be copied.
+pub fn create_backup<T: AsRef<Path>>(
+    file: T,
+    policy: &BackupPolicy,
+) -> Result<Option<Backup>, Error> {
+    let file = file.as_ref();
+    if !policy.enabled || !file.exists() {
+        return Ok(None);
+    }
+    let name = file_name(file)?;
+    let directory = policy.directory_of(file);
+    std::fs::create_dir_all(&directory)?;
+
+    let now = SystemTime::now();
+    let timestamp = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let mut backup = Backup {
+        original: file.to_path_buf(),
+        path: PathBuf::new(),
+        timestamp,
+        sequence: 0,
+    };
+    loop {
+        let suffix = match backup.sequence {
+            0 => timestamp.to_string(),
+            sequence => format!("{}-{}", timestamp, sequence),
+        };
+        backup.path = directory.join(format!("{}.{}.{}", name, suffix, EXTENSION));
+        if !backup.path.exists() {
+            break;
+        }
+        backup.sequence += 1;
+    }
+    atomic::write(&backup.path, std::fs::read(file)?)?;
+
+    for (index, old) in backups(file, policy)?.into_iter().enumerate().skip(1) {
+        let too_many = policy.keep.is_some_and(|keep| index >= keep);
+        let too_old = policy.max_age.is_some_and(|max_age| {
+            now.duration_since(old.created())
+                .is_ok_and(|age| age > max_age)
+        });
+        if too_many || too_old {
+            std::fs::remove_file(&old.path)?;
+        }
+    }
+    Ok(Some(backup))
+}
+
+/// Lists the backups of a file stored where a policy puts them.
+///
+/// # Returns
+///
+/// The backups, the most recent first.
+pub fn backups<T: AsRef<Path>>(file: T, policy: &BackupPolicy) -> Result<Vec<Backup>, Error> {
+    let file = file.as_ref();
+    let prefix = format!("{}.", file_name(file)?);
+    let directory = policy.directory_of(file);
+    let entries = match std::fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let entry_name = entry.file_name();
+        let stamp = match entry_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|name| name.strip_suffix(EXTENSION))
+            .and_then(|name| name.strip_suffix('.'))
+        {
+            Some(stamp) => stamp,
+            None => continue,
+        };
+        let (timestamp, sequence) = stamp.split_once('-').unwrap_or((stamp, "0"));
+        if let (Ok(timestamp), Ok(sequence)) = (timestamp.parse(), sequence.parse()) {
+            backups.push(Backup {
+                original: file.to_path_buf(),
+                path: entry.path(),
+                timestamp,
+                sequence,
+            });
+        }
+    }
+    backups.sort_by_key(|backup| std::cmp::Reverse((backup.timestamp, backup.sequence)));
+    Ok(backups)
+}
+
+/// Restores a file from a backup, replacing it atomically. The backup is kept.
+pub fn restore_backup(backup: &Backup) -> Result<(), Error> {
+    atomic::write(&backup.original, std::fs::read(&backup.path)?)?;
+    Ok(())
+}
+
+/// Returns the name of a file, as its backups are named after it.
+fn file_name(file: &Path) -> Result<String, Error> {
+    file.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("'{}' is not a file path", file.display()),
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_and_restore() {
+        let dir = std::env::temp_dir().join(format!("regdiff-backup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("user.reg");
+        let policy = BackupPolicy::new()
+            .with_directory(dir.join("backups"))
+            .with_keep(Some(2));
+
+        let mut created = Vec::new();
+        for content in ["first", "second", "third"] {
+            std::fs::write(&file, content).unwrap();
+            created.push(create_backup(&file, &policy).unwrap().unwrap());
+        }
+        std::fs::write(&file, "broken").unwrap();
+        let listed = backups(&file, &policy).unwrap();
+        restore_backup(&listed[1]).unwrap();
+        let restored = std::fs::read_to_string(&file).unwrap();
+        let disabled = create_backup(&file, &policy.clone().with_enabled(false)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(listed, [created[2].clone(), created[1].clone()]);
+        assert_eq!(restored, "second");
+        assert!(disabled.is_none());
+    }
+}
@@ -0,0 +1,291 @@
+use crate::prelude::{DiffOptions, Error, Hive, KeyChange, Operation, Registry, RegistryDiff};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The diff of one pair of files of a [BatchDiff].
+#[derive(Debug)]
+pub struct BatchResult {
+    /// The path of the file before the changes.
+    old: PathBuf,
+    /// The path of the file after the changes.
+    new: PathBuf,
+    /// The hive both files were loaded into.
+    hive: Hive,
+    /// The diff, or the error loading either file.
+    diff: Result<RegistryDiff, Error>,
+}
+
+impl BatchResult {
+    /// Returns the path of the file before the changes.
+    pub fn old_file(&self) -> &Path {
+        &self.old
+    }
+
+    /// Returns the path of the file after the changes.
+    pub fn new_file(&self) -> &Path {
+        &self.new
+    }
+
+    /// Returns the hive both files were loaded into.
+    pub fn hive(&self) -> Hive {
+        self.hive
+    }
+
+    /// Returns the diff between the files, or the error loading either of them.
+    pub fn diff(&self) -> Result<&RegistryDiff, &Error> {
+        self.diff.as_ref()
+    }
+}
+
+/// The totals of a [BatchDiff] over all of its pairs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    /// The number of pairs diffed.
+    pairs: usize,
+    /// The number of pairs whose files couldn't be loaded.
+    failed: usize,
+    /// The number of pairs whose files differ.
+    changed: usize,
+    /// The number of keys added.
+    added_keys: usize,
+    /// The number of keys deleted.
+    deleted_keys: usize,
+    /// The number of keys modified.
+    modified_keys: usize,
+    /// The number of values added, modified or deleted.
+    value_changes: usize,
+}
+
+impl BatchSummary {
+    /// Returns the number of pairs diffed.
+    pub fn pairs(&self) -> usize {
+        self.pairs
+    }
+
+    /// Returns the number of pairs whose files couldn't be loaded.
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    /// Returns the number of pairs whose files differ.
+    pub fn changed(&self) -> usize {
+        self.changed
+    }
+
+    /// Returns the number of keys added, over all pairs.
+    pub fn added_keys(&self) -> usize {
+        self.added_keys
+    }
+
+    /// Returns the number of keys deleted, over all pairs.
+    pub fn deleted_keys(&self) -> usize {
+        self.deleted_keys
+    }
+
+    /// Returns the number of keys modified, over all pairs.
+    pub fn modified_keys(&self) -> usize {
+        self.modified_keys
+    }
+
+    /// Returns the number of values added, modified or deleted, over all pairs.
+    pub fn value_changes(&self) -> usize {
+        self.value_changes
+    }
+
+    /// Counts the changes of a pair.
+    fn add(&mut self, diff: &Result<RegistryDiff, Error>) {
+        self.pairs += 1;
+        let diff = match diff {
+            Ok(diff) => diff,
+            Err(_) => {
+                self.failed += 1;
+                return;
+            }
+        };
+        if !diff.is_empty() {
+            self.changed += 1;
+        }
+        for key in diff.keys().values() {
+            match key.change() {
+                KeyChange::Added => self.added_keys += 1,
+                KeyChange::Deleted => self.deleted_keys += 1,
+                KeyChange::Modified => self.modified_keys += 1,
+                KeyChange::Unchanged => {}
+            }
+            self.value_changes += key
+                .values()
+                .values()
+                .filter(|operation| {
+                    matches!(
+                        operation,
+                        Operation::Add { .. } | Operation::Modify { .. } | Operation::Delete { .. }
+                    )
+                })
+                .count();
+        }
+    }
+}
+
+impl std::fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} pairs changed ({} failed): {} keys added, {} deleted, {} modified, {} value changes",
+            self.changed,
+            self.pairs,
+            self.failed,
+            self.added_keys,
+            self.deleted_keys,
+            self.modified_keys,
+            self.value_changes
+        )
+    }
+}
+
+/// The diffs of many pairs of registry files, as returned by [diff_batch].
+#[derive(Debug)]
+pub struct BatchDiff {
+    /// The diff of each pair, in the order the pairs were given.
+    results: Vec<BatchResult>,
+    /// The totals over all pairs.
+    summary: BatchSummary,
+}
+
+impl BatchDiff {
+    /// Returns the diff of each pair, in the order the pairs were given.
+    pub fn results(&self) -> &[BatchResult] {
+        &self.results
+    }
+
+    /// Returns the totals over all pairs.
+    pub fn summary(&self) -> &BatchSummary {
+        &self.summary
+    }
+}
+
+/// Diffs many pairs of registry files, such as the files of many prefixes before and after
+/// an update, one after the other.
+///
+/// See [diff_batch_with] for details.
+pub fn diff_batch(pairs: &[(PathBuf, PathBuf, Hive)]) -> BatchDiff {
+    diff_batch_with(pairs, &DiffOptions::default(), 1)
+}
+
+/// Diffs many pairs of registry files according to `options`, on up to `threads` threads.
+///
+/// A pair whose files can't be loaded doesn't stop the others: its error is returned in its
+/// result instead.
+///
+/// # Arguments
+///
+/// * `pairs` - The paths of the files before and after the changes, and the hive to load
+///   them into.
+/// * `options` - How values are compared.
+/// * `threads` - The number of pairs diffed at the same time, `0` for the parallelism
+///   available to the process.
+///
+/// # Returns
+///
+/// The diff of each pair, in the order the pairs were given, and the totals over all pairs.
+pub fn diff_batch_with(
+    pairs: &[(PathBuf, PathBuf, Hive)],
+    options: &DiffOptions,
+    threads: usize,
+) -> BatchDiff {
+    let threads = match threads {
+        0 => std::thread::available_parallelism().map_or(1, usize::from),
+        threads => threads,
+    }
+    .min(pairs.len())
+    .max(1);
+
+    let next = AtomicUsize::new(0);
+    let worker = || {
+        let mut results = Vec::new();
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let (old, new, hive) = match pairs.get(index) {
+                Some(pair) => pair,
+                None => return results,
+            };
+            let diff = Registry::open(old, *hive).and_then(|old_registry| {
+                let new_registry = Registry::open(new, *hive)?;
+                Ok(RegistryDiff::new(&old_registry, &new_registry, options))
+            });
+            results.push((
+                index,
+                BatchResult {
+                    old: old.clone(),
+                    new: new.clone(),
+                    hive: *hive,
+                    diff,
+                },
+            ));
+        }
+    };
+    let mut results: Vec<(usize, BatchResult)> = if threads == 1 {
+        worker()
+    } else {
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads).map(|_| scope.spawn(worker)).collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("batch diff worker panicked"))
+                .collect()
+        })
+    };
+    results.sort_by_key(|(index, _)| *index);
+
+    let mut summary = BatchSummary::default();
+    for (_, result) in &results {
+        summary.add(&result.diff);
+    }
+    BatchDiff {
+        results: results.into_iter().map(|(_, result)| result).collect(),
+        summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_batch() {
+        let dir = std::env::temp_dir().join(format!("regdiff-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, format!("REGEDIT4\n\n{}", text)).unwrap();
+            path
+        };
+        let base = write("base.reg", "[HKEY_CURRENT_USER\\App]\n\"A\"=\"a\"\n");
+        let changed = write(
+            "changed.reg",
+            "[HKEY_CURRENT_USER\\App]\n\"A\"=\"b\"\n\n[HKEY_CURRENT_USER\\New]\n",
+        );
+        let pairs = vec![
+            (base.clone(), changed.clone(), Hive::CurrentUser),
+            (base.clone(), base.clone(), Hive::CurrentUser),
+            (base.clone(), dir.join("missing.reg"), Hive::CurrentUser),
+        ];
+
+        let sequential = diff_batch(&pairs);
+        let parallel = diff_batch_with(&pairs, &DiffOptions::default(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(sequential.summary(), parallel.summary());
+        assert_eq!(
+            sequential.summary().to_string(),
+            "1 of 3 pairs changed (1 failed): 1 keys added, 0 deleted, 1 modified, 1 value changes"
+        );
+        assert_eq!(
+            parallel.results()[1]
+                .diff()
+                .map(RegistryDiff::is_empty)
+                .ok(),
+            Some(true)
+        );
+        assert!(parallel.results()[2].diff().is_err());
+    }
+}
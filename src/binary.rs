@@ -0,0 +1,482 @@
+use crate::prelude::{
+    Error, KeyChange, KeyDiff, Operation, PatchMetadata, RegistryDiff, SecurityDescriptor, Value,
+    ValueMap,
+};
+use regashii::{KeyName, ValueName};
+use std::io::{ErrorKind, Read};
+
+/// The bytes every binary diff starts with.
+const MAGIC: &[u8; 4] = b"RGDF";
+
+/// The version of the binary format written by this crate. Version 1 diffs, without
+/// metadata, are still read.
+const VERSION: u8 = 2;
+
+/// Writes the parts of a binary diff to a buffer.
+///
+/// Lengths and numbers are LEB128 varints. Key names are sorted, so each one is written as
+/// the length of the prefix it shares with the previous name followed by the rest of it.
+struct Writer {
+    /// The bytes written so far.
+    out: Vec<u8>,
+    /// The previous key name written.
+    previous: String,
+}
+
+impl Writer {
+    fn varint(&mut self, mut number: u64) {
+        loop {
+            let byte = (number & 0x7f) as u8;
+            number >>= 7;
+            if number == 0 {
+                self.out.push(byte);
+                return;
+            }
+            self.out.push(byte | 0x80);
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u64);
+        self.out.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.bytes(string.as_bytes());
+    }
+
+    fn key_name(&mut self, name: &KeyName) {
+        let name = name.raw();
+        let mut shared = name
+            .bytes()
+            .zip(self.previous.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !name.is_char_boundary(shared) {
+            shared -= 1;
+        }
+        self.varint(shared as u64);
+        self.string(&name[shared..]);
+        self.previous = name.to_string();
+    }
+
+    fn value_name(&mut self, name: &ValueName) {
+        match name {
+            ValueName::Default => self.out.push(0),
+            ValueName::Named(name) => {
+                self.out.push(1);
+                self.string(name);
+            }
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        if let (Some(raw_type), regashii::Value::Binary(bytes)) = (value.raw_type(), value.value())
+        {
+            self.out.push(6);
+            self.varint(raw_type.into());
+            self.bytes(bytes);
+            return;
+        }
+        match value.value() {
+            regashii::Value::Sz(string) => {
+                self.out.push(0);
+                self.string(string);
+            }
+            regashii::Value::ExpandSz(string) => {
+                self.out.push(1);
+                self.string(string);
+            }
+            regashii::Value::Binary(bytes) => {
+                self.out.push(2);
+                self.bytes(bytes);
+            }
+            regashii::Value::Dword(dword) => {
+                self.out.push(3);
+                self.varint((*dword).into());
+            }
+            regashii::Value::MultiSz(strings) => {
+                self.out.push(4);
+                self.varint(strings.len() as u64);
+                for string in strings {
+                    self.string(string);
+                }
+            }
+            regashii::Value::Qword(qword) => {
+                self.out.push(5);
+                self.varint(*qword);
+            }
+            _ => self.out.push(7),
+        }
+    }
+
+    fn operation<T>(&mut self, operation: &Operation<T>, data: fn(&mut Self, &T)) {
+        match operation {
+            Operation::Unchanged => self.out.push(0),
+            Operation::Add { data: new } => {
+                self.out.push(1);
+                data(self, new);
+            }
+            Operation::Delete { data: old } => {
+                self.out.push(2);
+                data(self, old);
+            }
+            Operation::Modify { old_data, new_data } => {
+                self.out.push(3);
+                data(self, old_data);
+                data(self, new_data);
+            }
+            Operation::Volatile { old_data, new_data } => {
+                self.out.push(4);
+                data(self, old_data);
+                data(self, new_data);
+            }
+            Operation::Reordered { old_data, new_data } => {
+                self.out.push(5);
+                data(self, old_data);
+                data(self, new_data);
+            }
+        }
+    }
+
+    fn optional_operation<T>(&mut self, operation: Option<&Operation<T>>, data: fn(&mut Self, &T)) {
+        match operation {
+            Some(operation) => {
+                self.out.push(1);
+                self.operation(operation, data);
+            }
+            None => self.out.push(0),
+        }
+    }
+
+    fn metadata(&mut self, metadata: &PatchMetadata) {
+        for field in metadata.fields() {
+            match field {
+                Some(field) => {
+                    self.out.push(1);
+                    self.string(field);
+                }
+                None => self.out.push(0),
+            }
+        }
+    }
+
+    fn key(&mut self, key: &KeyDiff) {
+        self.key_name(key.name());
+        self.out.push(match key.change() {
+            KeyChange::Added => 0,
+            KeyChange::Deleted => 1,
+            KeyChange::Modified => 2,
+            KeyChange::Unchanged => 3,
+        });
+        self.varint(key.values().len() as u64);
+        for (name, operation) in key.values() {
+            self.value_name(name);
+            self.operation(operation, Self::value);
+        }
+        self.optional_operation(key.security(), |writer, security| {
+            writer.bytes(security.raw())
+        });
+        self.optional_operation(key.link(), |writer, link| writer.string(link));
+    }
+}
+
+/// Reads the parts of a binary diff, in the layout of [Writer].
+struct Reader<R> {
+    /// The source of the diff.
+    source: R,
+    /// The offset of the next byte to read.
+    offset: usize,
+    /// The previous key name read.
+    previous: String,
+}
+
+impl<R: Read> Reader<R> {
+    fn error<T>(&self, message: &str) -> Result<T, Error> {
+        Err(Error::Binary {
+            offset: self.offset,
+            message: message.to_string(),
+        })
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        let mut byte = [0];
+        match self.source.read_exact(&mut byte) {
+            Ok(()) => {
+                self.offset += 1;
+                Ok(byte[0])
+            }
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => {
+                self.error("unexpected end of data")
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut number = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            number |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(number);
+            }
+        }
+        self.error("varint too long")
+    }
+
+    fn length(&mut self) -> Result<usize, Error> {
+        let length = self.varint()?;
+        usize::try_from(length).or_else(|_| self.error("length exceeds data"))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let length = self.length()?;
+        // Read up to `length` bytes rather than allocating them upfront, so a corrupt length
+        // can't allocate more than the data holds.
+        let mut bytes = Vec::new();
+        (&mut self.source)
+            .take(length as u64)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != length {
+            return self.error("length exceeds data");
+        }
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes).or_else(|_| self.error("invalid UTF-8 string"))
+    }
+
+    fn metadata(&mut self) -> Result<PatchMetadata, Error> {
+        let mut fields: [Option<String>; 4] = Default::default();
+        for field in &mut fields {
+            *field = match self.byte()? {
+                0 => None,
+                1 => Some(self.string()?),
+                _ => return self.error("invalid metadata field"),
+            };
+        }
+        Ok(PatchMetadata::from_fields(fields))
+    }
+
+    fn key_name(&mut self) -> Result<KeyName, Error> {
+        let shared = self.varint()?;
+        let mut name = match usize::try_from(shared) {
+            Ok(shared) if self.previous.is_char_boundary(shared) => {
+                self.previous[..shared].to_string()
+            }
+            _ => return self.error("invalid shared key name prefix"),
+        };
+        name.push_str(&self.string()?);
+        self.previous = name.clone();
+        Ok(KeyName::new(name))
+    }
+
+    fn value_name(&mut self) -> Result<ValueName, Error> {
+        match self.byte()? {
+            0 => Ok(ValueName::Default),
+            1 => Ok(ValueName::Named(self.string()?)),
+            _ => self.error("invalid value name"),
+        }
+    }
+
+    fn value(&mut self, name: &ValueName) -> Result<Value, Error> {
+        let value = match self.byte()? {
+            0 => regashii::Value::Sz(self.string()?),
+            1 => regashii::Value::ExpandSz(self.string()?),
+            2 => regashii::Value::Binary(self.bytes()?),
+            3 => match u32::try_from(self.varint()?) {
+                Ok(dword) => regashii::Value::Dword(dword),
+                Err(_) => return self.error("dword out of range"),
+            },
+            4 => {
+                let count = self.length()?;
+                let strings = (0..count)
+                    .map(|_| self.string())
+                    .collect::<Result<_, _>>()?;
+                regashii::Value::MultiSz(strings)
+            }
+            5 => regashii::Value::Qword(self.varint()?),
+            6 => {
+                let raw_type = match u32::try_from(self.varint()?) {
+                    Ok(raw_type) => raw_type,
+                    Err(_) => return self.error("value type out of range"),
+                };
+                return Ok(Value::raw(name.clone(), raw_type, self.bytes()?));
+            }
+            7 => regashii::Value::Delete,
+            _ => return self.error("invalid value data"),
+        };
+        Ok(Value::new(name.clone(), value))
+    }
+
+    fn operation<T>(
+        &mut self,
+        mut data: impl FnMut(&mut Self) -> Result<T, Error>,
+    ) -> Result<Operation<T>, Error> {
+        let operation = match self.byte()? {
+            0 => Operation::Unchanged,
+            1 => Operation::Add { data: data(self)? },
+            2 => Operation::Delete { data: data(self)? },
+            3 => Operation::Modify {
+                old_data: data(self)?,
+                new_data: data(self)?,
+            },
+            4 => Operation::Volatile {
+                old_data: data(self)?,
+                new_data: data(self)?,
+            },
+            5 => Operation::Reordered {
+                old_data: data(self)?,
+                new_data: data(self)?,
+            },
+            _ => return self.error("invalid operation"),
+        };
+        Ok(operation)
+    }
+
+    fn optional_operation<T>(
+        &mut self,
+        data: impl FnMut(&mut Self) -> Result<T, Error>,
+    ) -> Result<Option<Operation<T>>, Error> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => self.operation(data).map(Some),
+            _ => self.error("invalid operation"),
+        }
+    }
+
+    fn key(&mut self) -> Result<KeyDiff, Error> {
+        let name = self.key_name()?;
+        let change = match self.byte()? {
+            0 => KeyChange::Added,
+            1 => KeyChange::Deleted,
+            2 => KeyChange::Modified,
+            3 => KeyChange::Unchanged,
+            _ => return self.error("invalid key change"),
+        };
+
+        let mut values = ValueMap::default();
+        for _ in 0..self.length()? {
+            let value_name = self.value_name()?;
+            let operation = self.operation(|reader| reader.value(&value_name))?;
+            values.insert(value_name, operation);
+        }
+        let security =
+            self.optional_operation(|reader| reader.bytes().map(SecurityDescriptor::new))?;
+        let link = self.optional_operation(Self::string)?;
+
+        Ok(KeyDiff::from_parts(name, change, values, security, link))
+    }
+}
+
+impl RegistryDiff {
+    /// Encodes the diff in a compact, versioned binary format, keeping every change along with
+    /// its old and new data.
+    ///
+    /// The result is usually much smaller than the patch returned by [RegistryDiff::serialize],
+    /// and can be read back with [RegistryDiff::from_binary].
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut writer = Writer {
+            out: MAGIC.to_vec(),
+            previous: String::new(),
+        };
+        writer.out.push(VERSION);
+        writer.metadata(self.metadata());
+        writer.varint(self.keys().len() as u64);
+        for key in self.keys().values() {
+            writer.key(key);
+        }
+        writer.out
+    }
+
+    /// Decodes a diff encoded by [RegistryDiff::to_binary].
+    ///
+    /// # Returns
+    ///
+    /// The diff, or [Error::Binary] if the data is malformed, truncated or of a newer version.
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, Error> {
+        Self::read_binary(bytes)
+    }
+
+    /// Decodes a diff encoded by [RegistryDiff::to_binary] while reading it from `source`,
+    /// such as a file or a decompressing stream, without reading it into memory first.
+    pub(crate) fn read_binary<R: Read>(source: R) -> Result<Self, Error> {
+        let mut reader = Reader {
+            source,
+            offset: 0,
+            previous: String::new(),
+        };
+        let mut magic = [0; MAGIC.len()];
+        match reader.source.read_exact(&mut magic) {
+            Ok(()) if magic == *MAGIC => reader.offset = MAGIC.len(),
+            Err(error) if error.kind() != ErrorKind::UnexpectedEof => return Err(error.into()),
+            _ => return reader.error("not a binary diff"),
+        }
+        let metadata = match reader.byte()? {
+            1 => PatchMetadata::default(),
+            VERSION => reader.metadata()?,
+            _ => return reader.error("unsupported version"),
+        };
+
+        let count = reader.length()?;
+        let keys = (0..count)
+            .map(|_| reader.key())
+            .collect::<Result<RegistryDiff, _>>()?;
+        if reader.source.read(&mut [0])? != 0 {
+            return reader.error("trailing data");
+        }
+        Ok(keys.with_metadata(metadata))
+    }
+
+    /// Writes the diff encoded by [RegistryDiff::to_binary] to a file, replacing it atomically
+    /// like [RegistryDiff::serialize_file].
+    #[cfg(feature = "fs")]
+    pub fn write_binary_file<T: AsRef<std::path::Path>>(&self, file: T) -> std::io::Result<()> {
+        crate::atomic::write(file, self.to_binary())
+    }
+
+    /// Reads a diff from a file written by [RegistryDiff::write_binary_file].
+    #[cfg(feature = "fs")]
+    pub fn read_binary_file<T: AsRef<std::path::Path>>(file: T) -> Result<Self, Error> {
+        Self::read_binary(std::io::BufReader::new(std::fs::File::open(file)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::prelude::{DiffOptions, Hive, Registry};
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_binary_round_trip() {
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new().with_security(true));
+
+        let bytes = diff.to_binary();
+        assert!(bytes.len() < diff.serialize().unwrap().len());
+        assert_eq!(RegistryDiff::from_binary(&bytes).unwrap(), diff);
+    }
+
+    #[test]
+    fn test_binary_rejects_malformed_data() {
+        let bytes = RegistryDiff::default().to_binary();
+        assert!(RegistryDiff::from_binary(&bytes).unwrap().is_empty());
+        assert!(matches!(
+            RegistryDiff::from_binary(&bytes[..4]),
+            Err(Error::Binary { offset: 4, .. })
+        ));
+        assert!(RegistryDiff::from_binary(b"REGEDIT4").is_err());
+        let trailing = [bytes.as_slice(), &[0]].concat();
+        assert!(matches!(
+            RegistryDiff::from_binary(&trailing),
+            Err(Error::Binary { offset, .. }) if offset == bytes.len()
+        ));
+    }
+}
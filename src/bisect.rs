@@ -0,0 +1,149 @@
+use crate::prelude::{Commit, DiffOptions, Error, History, Registry, RegistryDiff};
+
+/// The result of [History::bisect]: the pair of consecutive commits where a predicate
+/// changed, and the changes between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bisection {
+    /// The last commit still giving the first commit's result.
+    before: Commit,
+    /// The first commit giving the other result.
+    after: Commit,
+    /// What the predicate returned for the `after` commit.
+    result: bool,
+    /// The changes between the two commits.
+    diff: RegistryDiff,
+}
+
+impl Bisection {
+    /// Returns the last commit still giving the first commit's result.
+    pub fn before(&self) -> &Commit {
+        &self.before
+    }
+
+    /// Returns the first commit giving the other result.
+    pub fn after(&self) -> &Commit {
+        &self.after
+    }
+
+    /// Returns what the predicate returned for [Bisection::after].
+    pub fn result(&self) -> bool {
+        self.result
+    }
+
+    /// Returns the changes between the two commits, among which is the culprit.
+    pub fn diff(&self) -> &RegistryDiff {
+        &self.diff
+    }
+}
+
+impl History {
+    /// Finds the first commit where a predicate over the registry state stops giving the
+    /// result it gives for the first commit, e.g. where a value stopped holding some data.
+    ///
+    /// Like `git bisect`, the predicate is assumed to change only once over the history, and
+    /// is evaluated for about log2(n) of the n commits.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - The test run on the registry state of a commit.
+    /// * `options` - How the states of the two commits found are compared.
+    ///
+    /// # Returns
+    ///
+    /// The two commits around the change with the diff between them, or `None` if the
+    /// predicate gives the same result for the first and latest commits.
+    pub fn bisect<F: FnMut(&Registry) -> bool>(
+        &self,
+        mut predicate: F,
+        options: &DiffOptions,
+    ) -> Result<Option<Bisection>, Error> {
+        let mut commits = self.log()?;
+        commits.reverse();
+        let (first, last) = match (commits.first(), commits.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(None),
+        };
+
+        let initial = predicate(&self.load(first)?);
+        if predicate(&self.load(last)?) == initial {
+            return Ok(None);
+        }
+
+        // `good` always gives the initial result and `bad` the other one.
+        let (mut good, mut bad) = (0, commits.len() - 1);
+        while bad - good > 1 {
+            let middle = good + (bad - good) / 2;
+            if predicate(&self.load(&commits[middle])?) == initial {
+                good = middle;
+            } else {
+                bad = middle;
+            }
+        }
+
+        let before = self.load(&commits[good])?;
+        let after = self.load(&commits[bad])?;
+        Ok(Some(Bisection {
+            diff: RegistryDiff::new(&before, &after, options),
+            before: commits[good].clone(),
+            after: commits[bad].clone(),
+            result: !initial,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RegistryBuilder;
+    use crate::prelude::Hive;
+    use regashii::{KeyName, ValueName};
+
+    #[test]
+    fn test_bisect() {
+        let root = std::env::temp_dir().join(format!("regdiff-bisect-{}", std::process::id()));
+        let history = History::open(&root).unwrap();
+        let registry = |renderer: &str, other: u32| {
+            RegistryBuilder::new(Hive::CurrentUser)
+                .with_sz("Software\\Wine\\Direct3D", "renderer", renderer)
+                .with(
+                    "Software\\Wine\\Direct3D",
+                    ValueName::named("Other"),
+                    regashii::Value::Dword(other),
+                )
+                .build()
+        };
+        for (index, renderer) in ["gl", "gl", "gl", "vulkan", "vulkan"].iter().enumerate() {
+            let message = format!("commit {}", index);
+            history
+                .commit(&registry(renderer, index as u32), &message)
+                .unwrap();
+        }
+
+        let uses_gl = |registry: &Registry| {
+            registry.keys().values().any(|key| {
+                key.values()
+                    .get(&ValueName::named("renderer"))
+                    .map(|v| v.value())
+                    == Some(&regashii::Value::Sz("gl".to_string()))
+            })
+        };
+        let bisection = history
+            .bisect(uses_gl, &DiffOptions::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(bisection.before().message(), "commit 2");
+        assert_eq!(bisection.after().message(), "commit 3");
+        assert!(!bisection.result());
+        let key = bisection
+            .diff()
+            .key(&KeyName::new("HKEY_CURRENT_USER\\Software\\Wine\\Direct3D"))
+            .unwrap();
+        assert_eq!(key.values().len(), 2);
+
+        assert!(history
+            .bisect(|_| true, &DiffOptions::new())
+            .unwrap()
+            .is_none());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,411 @@
+use crate::parse::{self, Syntax};
+#[cfg(feature = "fs")]
+use crate::prelude::Encoding;
+use crate::prelude::{DiffOptions, Error, PatchMetadata, Registry, RegistryDiff};
+use regashii::KeyName;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A named `.reg` patch of a [Catalog].
+///
+/// Besides the [PatchMetadata] comments, the header of a patch may list the names of the
+/// patches it needs applied first in `; Requires:` comments and those it can't be combined
+/// with in `; Conflicts:` comments, separated by commas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CatalogPatch {
+    /// The name of the patch, the stem of its file name when loaded from a directory.
+    name: String,
+    /// The text of the patch.
+    text: String,
+    /// The metadata comments of the patch.
+    metadata: PatchMetadata,
+    /// The names of the patches to apply first.
+    requires: Vec<String>,
+    /// The names of the patches this one can't be combined with.
+    conflicts: Vec<String>,
+    /// The full names of the keys the patch touches, with whether it deletes them.
+    keys: Vec<(KeyName, bool)>,
+}
+
+impl CatalogPatch {
+    /// Returns the name of the patch.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the text of the patch.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the metadata comments of the patch.
+    pub fn metadata(&self) -> &PatchMetadata {
+        &self.metadata
+    }
+
+    /// Returns the names of the patches to apply first.
+    pub fn requires(&self) -> &[String] {
+        &self.requires
+    }
+
+    /// Returns the names of the patches this one can't be combined with.
+    pub fn conflicts(&self) -> &[String] {
+        &self.conflicts
+    }
+
+    /// Returns the full names of the keys the patch sets or deletes, in file order.
+    pub fn keys(&self) -> impl Iterator<Item = &KeyName> {
+        self.keys.iter().map(|(name, _)| name)
+    }
+
+    /// Returns the keys both patches touch, where applying them in a different order gives a
+    /// different result. Deleting a key touches its subkeys too.
+    fn overlap(&self, other: &CatalogPatch) -> Vec<KeyName> {
+        let contains = |(parent, deleted): &(KeyName, bool), name: &KeyName| {
+            let (parent, name) = (parent.raw().to_lowercase(), name.raw().to_lowercase());
+            parent == name || (*deleted && name.starts_with(&format!("{}\\", parent)))
+        };
+        let mut keys = BTreeSet::new();
+        for a in &self.keys {
+            for b in &other.keys {
+                if contains(a, &b.0) {
+                    keys.insert(b.0.clone());
+                } else if contains(b, &a.0) {
+                    keys.insert(a.0.clone());
+                }
+            }
+        }
+        keys.into_iter().collect()
+    }
+}
+
+/// Two patches of an [ApplyOrder] touching the same keys, where the later one wins.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Overlap {
+    /// The name of the patch applied first.
+    first: String,
+    /// The name of the patch applied last.
+    second: String,
+    /// The full names of the keys both touch.
+    keys: Vec<KeyName>,
+}
+
+impl Overlap {
+    /// Returns the name of the patch applied first.
+    pub fn first(&self) -> &str {
+        &self.first
+    }
+
+    /// Returns the name of the patch applied last, whose changes win.
+    pub fn second(&self) -> &str {
+        &self.second
+    }
+
+    /// Returns the full names of the keys both patches touch.
+    pub fn keys(&self) -> &[KeyName] {
+        &self.keys
+    }
+}
+
+/// The patches selected from a [Catalog] along with their requirements, in the order they
+/// are applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApplyOrder {
+    /// The patches, requirements first.
+    patches: Vec<CatalogPatch>,
+    /// The pairs of patches touching the same keys.
+    overlaps: Vec<Overlap>,
+}
+
+impl ApplyOrder {
+    /// Returns the patches in the order they are applied.
+    pub fn patches(&self) -> &[CatalogPatch] {
+        &self.patches
+    }
+
+    /// Returns the pairs of patches touching the same keys, in apply order.
+    pub fn overlaps(&self) -> &[Overlap] {
+        &self.overlaps
+    }
+
+    /// Applies the patches in order to a copy of `base`, the registry they are meant for.
+    ///
+    /// # Returns
+    ///
+    /// The combined changes, as a single diff against `base` that can be serialized into one
+    /// patch, or an [Error] if a patch is malformed.
+    pub fn diff(&self, base: &Registry) -> Result<RegistryDiff, Error> {
+        let mut patched = base.clone();
+        for patch in &self.patches {
+            patched.apply(&RegistryDiff::from_patch_str(&patch.text, &patched)?);
+        }
+        Ok(RegistryDiff::new(base, &patched, &DiffOptions::new()))
+    }
+}
+
+/// A collection of named, known-good `.reg` patches, such as the tweaks a launcher ships, that
+/// resolves what a selection of them needs and the order to apply it in.
+#[derive(Clone, Debug, Default)]
+pub struct Catalog {
+    /// The patches, keyed by name.
+    patches: BTreeMap<String, CatalogPatch>,
+}
+
+impl Catalog {
+    /// Constructs an empty catalog.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the `.reg` files of a directory, named after their file stem.
+    #[cfg(feature = "fs")]
+    pub fn open<T: AsRef<std::path::Path>>(dir: T) -> Result<Self, Error> {
+        let mut catalog = Self::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) if path.extension().is_some_and(|ext| ext == "reg") => name,
+                _ => continue,
+            };
+            let bytes = std::fs::read(&path)?;
+            let text = Encoding::detect(&bytes).decode(&bytes)?;
+            catalog = catalog.with_patch(name, text)?;
+        }
+        Ok(catalog)
+    }
+
+    /// Adds a patch to the catalog, replacing any patch of the same name.
+    ///
+    /// # Returns
+    ///
+    /// The catalog, or an [Error] if the patch is malformed or is a Wine registry file, whose
+    /// key names lack their hive.
+    pub fn with_patch<N: Into<String>, T: Into<String>>(
+        mut self,
+        name: N,
+        text: T,
+    ) -> Result<Self, Error> {
+        let (name, text) = (name.into(), text.into());
+        let document = parse::parse(&text, true)?;
+        if document.syntax == Syntax::Wine {
+            return Err(catalog_error(&name, "is a Wine registry file, not a patch"));
+        }
+
+        let list = |label: &str| -> Vec<String> {
+            header_comments(&text, label)
+                .flat_map(|line| line.split(','))
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let patch = CatalogPatch {
+            metadata: PatchMetadata::from_patch(&text),
+            requires: list("Requires"),
+            conflicts: list("Conflicts"),
+            keys: document
+                .sections
+                .iter()
+                .map(|section| (KeyName::new(&section.name), section.deleted))
+                .collect(),
+            name: name.clone(),
+            text,
+        };
+        self.patches.insert(name, patch);
+        Ok(self)
+    }
+
+    /// Returns the patches, sorted by name.
+    pub fn patches(&self) -> impl Iterator<Item = &CatalogPatch> {
+        self.patches.values()
+    }
+
+    /// Retrieves a patch by name.
+    pub fn patch(&self, name: &str) -> Option<&CatalogPatch> {
+        self.patches.get(name)
+    }
+
+    /// Resolves the order to apply a selection of patches in, adding the patches they
+    /// require.
+    ///
+    /// Required patches come before the patches requiring them; otherwise patches keep the
+    /// order they are selected in. Patches touching the same keys are reported as
+    /// [ApplyOrder::overlaps], the later one winning.
+    ///
+    /// # Arguments
+    ///
+    /// * `names` - The names of the selected patches.
+    ///
+    /// # Returns
+    ///
+    /// The apply order, or [Error::Catalog] if a patch is missing, requirements form a cycle
+    /// or two of the patches declare a conflict.
+    pub fn resolve<S: AsRef<str>>(&self, names: &[S]) -> Result<ApplyOrder, Error> {
+        let mut order: Vec<&CatalogPatch> = Vec::new();
+        for name in names {
+            self.visit(name.as_ref(), &mut Vec::new(), &mut order)?;
+        }
+
+        for (index, patch) in order.iter().enumerate() {
+            for other in &order[index + 1..] {
+                if patch.conflicts.contains(&other.name) || other.conflicts.contains(&patch.name) {
+                    let message = format!("conflicts with patch '{}'", other.name);
+                    return Err(catalog_error(&patch.name, &message));
+                }
+            }
+        }
+
+        let mut overlaps = Vec::new();
+        for (index, first) in order.iter().enumerate() {
+            for second in &order[index + 1..] {
+                let keys = first.overlap(second);
+                if !keys.is_empty() {
+                    overlaps.push(Overlap {
+                        first: first.name.clone(),
+                        second: second.name.clone(),
+                        keys,
+                    });
+                }
+            }
+        }
+
+        Ok(ApplyOrder {
+            patches: order.into_iter().cloned().collect(),
+            overlaps,
+        })
+    }
+
+    /// Appends a patch to `order` after the patches it requires, unless already there.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The patches being visited, to detect cycles.
+    fn visit<'a>(
+        &'a self,
+        name: &str,
+        path: &mut Vec<&'a str>,
+        order: &mut Vec<&'a CatalogPatch>,
+    ) -> Result<(), Error> {
+        if order.iter().any(|patch| patch.name == name) {
+            return Ok(());
+        }
+        let patch = match self.patches.get(name) {
+            Some(patch) => patch,
+            None => {
+                let message = match path.last() {
+                    Some(parent) => format!("is missing but required by patch '{}'", parent),
+                    None => "is missing".to_string(),
+                };
+                return Err(catalog_error(name, &message));
+            }
+        };
+        if path.contains(&patch.name.as_str()) {
+            return Err(catalog_error(name, "requires itself"));
+        }
+
+        path.push(&patch.name);
+        for required in &patch.requires {
+            self.visit(required, path, order)?;
+        }
+        path.pop();
+        order.push(patch);
+        Ok(())
+    }
+}
+
+/// Returns the text of the comments of a patch header labeled `label`, such as
+/// `; Requires: a, b`.
+fn header_comments<'a>(text: &'a str, label: &'a str) -> impl Iterator<Item = &'a str> {
+    text.lines()
+        .map(str::trim)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.strip_prefix(';')?.split_once(':'))
+        .filter(move |(name, _)| name.trim().eq_ignore_ascii_case(label))
+        .map(|(_, value)| value)
+}
+
+/// Builds an [Error::Catalog] for a patch.
+fn catalog_error(name: &str, message: &str) -> Error {
+    Error::Catalog {
+        name: name.to_string(),
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Hive;
+
+    fn catalog() -> Catalog {
+        let patch = |header: &str, body: &str| {
+            format!("Windows Registry Editor Version 5.00\n{}\n{}", header, body)
+        };
+        Catalog::new()
+            .with_patch(
+                "dxvk",
+                patch(
+                    "; Name: DXVK\n; Requires: base",
+                    "[HKEY_CURRENT_USER\\Software\\Wine\\DllOverrides]\n\"d3d11\"=\"native\"\n",
+                ),
+            )
+            .unwrap()
+            .with_patch(
+                "base",
+                patch(
+                    "",
+                    "[-HKEY_CURRENT_USER\\Software\\Wine\\DllOverrides]\n\n\
+                    [HKEY_CURRENT_USER\\Software\\Wine]\n\"Version\"=\"win10\"\n",
+                ),
+            )
+            .unwrap()
+            .with_patch(
+                "wined3d",
+                patch(
+                    "; Conflicts: dxvk",
+                    "[HKEY_CURRENT_USER\\Software\\Wine\\Direct3D]\n\"renderer\"=\"gl\"\n",
+                ),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_catalog_resolve() {
+        let catalog = catalog();
+        assert_eq!(
+            catalog.patch("dxvk").unwrap().metadata().name(),
+            Some("DXVK")
+        );
+
+        let order = catalog.resolve(&["dxvk"]).unwrap();
+        let names: Vec<&str> = order.patches().iter().map(CatalogPatch::name).collect();
+        assert_eq!(names, ["base", "dxvk"]);
+        assert_eq!(order.overlaps().len(), 1);
+        assert_eq!(
+            order.overlaps()[0].keys(),
+            [KeyName::new(
+                "HKEY_CURRENT_USER\\Software\\Wine\\DllOverrides"
+            )]
+        );
+
+        let base = Registry::try_from_str(
+            "Windows Registry Editor Version 5.00\n\n\
+            [HKEY_CURRENT_USER\\Software\\Wine\\DllOverrides]\n\"d3d9\"=\"native\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let patch = order.diff(&base).unwrap().serialize().unwrap();
+        assert!(patch.contains("\"d3d9\"=-"));
+        assert!(patch.contains("\"d3d11\"=\"native\""));
+        assert!(patch.contains("\"Version\"=\"win10\""));
+
+        assert!(matches!(
+            catalog.resolve(&["dxvk", "wined3d"]),
+            Err(Error::Catalog { .. })
+        ));
+        assert!(catalog.resolve(&["missing"]).is_err());
+        let cyclic = catalog
+            .with_patch("base", "REGEDIT4\n; Requires: dxvk\n")
+            .unwrap();
+        assert!(cyclic.resolve(&["dxvk"]).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use crate::prelude::{Hive, Key, Registry};
+use regashii::ValueName;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The verb used when a class sets no default verb, as done by the shell.
+const DEFAULT_VERB: &str = "open";
+
+/// A programmatic identifier (ProgID), the class files of associated extensions belong to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgId {
+    /// The name of the class, as written in the registry.
+    name: String,
+    /// The description of the class, from the default value of its key.
+    description: Option<String>,
+    /// The verb run when opening files, from the default value of the `shell` key.
+    default_verb: Option<String>,
+    /// The commands of the shell verbs, by verb.
+    verbs: BTreeMap<String, String>,
+}
+
+impl ProgId {
+    /// Returns the name of the class, as written in the registry.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the description of the class, if any.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Returns the commands of the shell verbs, such as `open` or `edit`, by verb.
+    pub fn verbs(&self) -> &BTreeMap<String, String> {
+        &self.verbs
+    }
+
+    /// Returns the command run when opening files of the class.
+    ///
+    /// This is the command of the verb set as default, or of `open` if none is set.
+    pub fn open_command(&self) -> Option<&str> {
+        let verb = self.default_verb.as_deref().unwrap_or(DEFAULT_VERB);
+        self.verbs
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(verb))
+            .map(|(_, command)| command.as_str())
+    }
+}
+
+/// What files with an extension open with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Association {
+    /// The class the extension belongs to.
+    prog_id: String,
+    /// The command opening files of the class, if the class registers one.
+    command: Option<String>,
+}
+
+impl Association {
+    /// Returns the class the extension belongs to.
+    pub fn prog_id(&self) -> &str {
+        &self.prog_id
+    }
+
+    /// Returns the command opening files of the class, if the class registers one.
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+}
+
+impl std::fmt::Display for Association {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.command {
+            Some(command) => write!(f, "{} ({})", self.prog_id, command),
+            None => write!(f, "{}", self.prog_id),
+        }
+    }
+}
+
+/// A change to what files with an extension open with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssociationChange {
+    /// The extension, lowercased and including its leading dot.
+    extension: String,
+    /// The association before the change, or `None` if the extension was not associated.
+    before: Option<Association>,
+    /// The association after the change, or `None` if the extension is no longer associated.
+    after: Option<Association>,
+}
+
+impl AssociationChange {
+    /// Returns the extension, lowercased and including its leading dot.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Returns the association before the change, or `None` if there was none.
+    pub fn before(&self) -> Option<&Association> {
+        self.before.as_ref()
+    }
+
+    /// Returns the association after the change, or `None` if it was removed.
+    pub fn after(&self) -> Option<&Association> {
+        self.after.as_ref()
+    }
+}
+
+impl std::fmt::Display for AssociationChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let describe = |association: Option<&Association>| match association {
+            Some(association) => association.to_string(),
+            None => "(none)".to_string(),
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.extension,
+            describe(self.before.as_ref()),
+            describe(self.after.as_ref())
+        )
+    }
+}
+
+/// The file classes registered below a hive's `Software\Classes` key, the keys merged into
+/// `HKEY_CLASSES_ROOT`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Classes {
+    /// The class of each extension, by lowercased extension.
+    extensions: BTreeMap<String, String>,
+    /// The classes, by lowercased name.
+    prog_ids: BTreeMap<String, ProgId>,
+}
+
+impl Classes {
+    /// Returns the class of each extension, by lowercased extension including its leading dot.
+    pub fn extensions(&self) -> &BTreeMap<String, String> {
+        &self.extensions
+    }
+
+    /// Returns the class with the given name, matched case-insensitively.
+    pub fn prog_id(&self, name: &str) -> Option<&ProgId> {
+        self.prog_ids.get(&name.to_lowercase())
+    }
+
+    /// Returns what files with the given extension, including its leading dot, open with.
+    pub fn association(&self, extension: &str) -> Option<Association> {
+        let prog_id = self.extensions.get(&extension.to_lowercase())?;
+        Some(Association {
+            prog_id: prog_id.clone(),
+            command: self
+                .prog_id(prog_id)
+                .and_then(ProgId::open_command)
+                .map(str::to_string),
+        })
+    }
+
+    /// Compares the associations of every extension with those of newer classes.
+    ///
+    /// An extension is reported once, whether it was moved to another class or its class
+    /// now opens files with another command.
+    pub fn diff(&self, new: &Classes) -> Vec<AssociationChange> {
+        let extensions: BTreeSet<&String> = self
+            .extensions
+            .keys()
+            .chain(new.extensions.keys())
+            .collect();
+        extensions
+            .into_iter()
+            .filter_map(|extension| {
+                let before = self.association(extension);
+                let after = new.association(extension);
+                (before != after).then(|| AssociationChange {
+                    extension: extension.clone(),
+                    before,
+                    after,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the class named by the first component of a class path, creating it if missing.
+    fn entry(&mut self, name: &str) -> &mut ProgId {
+        self.prog_ids
+            .entry(name.to_lowercase())
+            .or_insert_with(|| ProgId {
+                name: name.to_string(),
+                ..ProgId::default()
+            })
+    }
+}
+
+/// Returns the string held by the default value of a key.
+fn default_value(key: &Key) -> Option<String> {
+    match key.values().get(&ValueName::Default)?.value() {
+        regashii::Value::Sz(data) | regashii::Value::ExpandSz(data) => Some(data.clone()),
+        _ => None,
+    }
+}
+
+impl Registry {
+    /// Returns the file classes registered in the registry's `Software\Classes` key.
+    ///
+    /// Only the classes of one hive are returned. Windows lets the classes of
+    /// `HKEY_CURRENT_USER` take precedence over those of `HKEY_LOCAL_MACHINE`.
+    pub fn classes(&self) -> Classes {
+        let mut classes = Classes::default();
+
+        for key in self.keys().values() {
+            let components: Vec<&str> = match Hive::split(key.name().raw()) {
+                Some((_, path)) => path.split('\\').collect(),
+                None => continue,
+            };
+            let path = match components.as_slice() {
+                [software, classes, path @ ..]
+                    if software.eq_ignore_ascii_case("Software")
+                        && classes.eq_ignore_ascii_case("Classes") =>
+                {
+                    path
+                }
+                _ => continue,
+            };
+            let data = match default_value(key) {
+                Some(data) => data,
+                None => continue,
+            };
+
+            match path {
+                [extension] if extension.starts_with('.') => {
+                    classes.extensions.insert(extension.to_lowercase(), data);
+                }
+                [name] => classes.entry(name).description = Some(data),
+                [name, shell] if shell.eq_ignore_ascii_case("shell") => {
+                    classes.entry(name).default_verb = Some(data);
+                }
+                [name, shell, verb, command]
+                    if shell.eq_ignore_ascii_case("shell")
+                        && command.eq_ignore_ascii_case("command") =>
+                {
+                    classes.entry(name).verbs.insert(verb.to_string(), data);
+                }
+                _ => {}
+            }
+        }
+
+        classes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RegistryBuilder;
+
+    fn registry(keys: &[(&str, &str)]) -> Registry {
+        keys.iter()
+            .fold(
+                RegistryBuilder::new(Hive::LocalMachine),
+                |builder, (name, data)| {
+                    builder.with(
+                        &format!("Software\\Classes\\{}", name),
+                        ValueName::Default,
+                        regashii::Value::Sz(data.to_string()),
+                    )
+                },
+            )
+            .build()
+    }
+
+    #[test]
+    fn test_association_changes() {
+        let old = registry(&[
+            (".pdf", "AcroExch.Document"),
+            (
+                "AcroExch.Document\\shell\\open\\command",
+                "acrord32.exe \"%1\"",
+            ),
+            (".txt", "txtfile"),
+            ("txtfile\\shell\\open\\command", "notepad.exe %1"),
+        ]);
+        let new = registry(&[
+            (".PDF", "SumatraPDF"),
+            ("SumatraPDF", "PDF Document"),
+            ("SumatraPDF\\shell", "read"),
+            ("SumatraPDF\\shell\\read\\command", "sumatrapdf.exe \"%1\""),
+            (".txt", "txtfile"),
+            ("txtfile\\shell\\open\\command", "notepad.exe %1"),
+        ]);
+
+        let classes = new.classes();
+        assert_eq!(
+            classes.prog_id("sumatrapdf").unwrap().description(),
+            Some("PDF Document")
+        );
+
+        let changes: Vec<String> = old
+            .classes()
+            .diff(&classes)
+            .iter()
+            .map(AssociationChange::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ".pdf: AcroExch.Document (acrord32.exe \"%1\") -> SumatraPDF (sumatrapdf.exe \"%1\")"
+            ]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::prelude::{KeyPattern, RegistryDiff};
+use regashii::KeyName;
+use std::collections::BTreeMap;
+
+/// Labels of the registry areas written by well-known components, used by [Classifier::wine].
+const WINE_LABELS: &[(&str, &str)] = &[
+    ("*\\Software\\Wine", "Wine configuration"),
+    ("*\\Software\\Wine\\Fonts", "Wine font config"),
+    ("*\\Software\\Wine\\Direct3D", "Wine Direct3D settings"),
+    ("*\\Software\\Wine\\DllOverrides", "Wine DLL overrides"),
+    ("*\\Software\\Wine\\Drivers", "Wine drivers"),
+    ("*\\Software\\Wine\\X11 Driver", "Wine X11 driver"),
+    ("*\\Software\\Wine\\Mac Driver", "Wine Mac driver"),
+    ("*\\Software\\Valve\\Steam", "Steam"),
+    (
+        "*\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall",
+        "Installed programs",
+    ),
+    ("HKEY_LOCAL_MACHINE\\Software\\Classes", "File associations"),
+    (
+        "HKEY_LOCAL_MACHINE\\System\\CurrentControlSet\\Services",
+        "Services",
+    ),
+];
+
+/// Tags registry keys with the label of the component responsible for them, such as
+/// "Wine font config", so reports can group changes by origin.
+///
+/// Labels are assigned by key patterns, each covering the matching keys and their subkeys.
+/// When several patterns match, the one with the most components wins, so a rule for
+/// `Software\Wine\Fonts` takes precedence over one for `Software\Wine`; among equally specific
+/// patterns the last one added wins, letting custom rules override built-in ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Classifier {
+    /// The patterns and their labels, in the order they were added.
+    rules: Vec<(KeyPattern, String)>,
+}
+
+impl Classifier {
+    /// Constructs a new [Classifier] without any rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs a new [Classifier] recognizing well-known Wine, Steam and Windows areas.
+    pub fn wine() -> Self {
+        WINE_LABELS
+            .iter()
+            .fold(Self::new(), |classifier, (pattern, label)| {
+                classifier.with_rule(*pattern, *label)
+            })
+    }
+
+    /// Returns the classifier with keys matching `pattern`, and their subkeys, labeled `label`.
+    pub fn with_rule<P: Into<KeyPattern>, T: Into<String>>(mut self, pattern: P, label: T) -> Self {
+        self.rules.push((pattern.into(), label.into()));
+        self
+    }
+
+    /// Returns the label of a key, from its full name.
+    ///
+    /// # Returns
+    ///
+    /// `None` if no rule covers the key.
+    pub fn classify(&self, name: &KeyName) -> Option<&str> {
+        self.rules
+            .iter()
+            .filter(|(pattern, _)| pattern.matches_subtree(name))
+            .max_by_key(|(pattern, _)| pattern.raw().split('\\').count())
+            .map(|(_, label)| label.as_str())
+    }
+
+    /// Splits a diff by the labels of its keys.
+    ///
+    /// # Returns
+    ///
+    /// The changes of each label, with the changes to keys no rule covers under `None`.
+    pub fn group(&self, diff: &RegistryDiff) -> BTreeMap<Option<String>, RegistryDiff> {
+        let mut groups: BTreeMap<Option<String>, Vec<_>> = BTreeMap::new();
+        for key in diff.keys().values() {
+            groups
+                .entry(self.classify(key.name()).map(str::to_string))
+                .or_default()
+                .push(key.clone());
+        }
+        groups
+            .into_iter()
+            .map(|(label, keys)| (label, keys.into_iter().collect()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::prelude::{DiffOptions, Hive, Registry};
+
+    #[test]
+    fn test_most_specific_rule_wins() {
+        let classifier = Classifier::wine().with_rule("*\\Software\\Wine\\Fonts", "Bottles fonts");
+        let label = |name: &str| classifier.classify(&KeyName::new(name));
+
+        assert_eq!(
+            label("HKEY_CURRENT_USER\\Software\\Wine\\Fonts\\Replacements"),
+            Some("Bottles fonts")
+        );
+        assert_eq!(
+            label("HKEY_CURRENT_USER\\Software\\Wine\\AppDefaults"),
+            Some("Wine configuration")
+        );
+        assert_eq!(label("HKEY_CURRENT_USER\\Control Panel\\Desktop"), None);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_group_diff() {
+        let empty = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4),
+            Hive::CurrentUser,
+        );
+        let user = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let diff = RegistryDiff::new(&empty, &user, &DiffOptions::new());
+
+        let groups = Classifier::wine().group(&diff);
+        let fonts = &groups[&Some("Wine font config".to_string())];
+        assert!(fonts
+            .keys()
+            .keys()
+            .all(|name| name.raw().contains("\\Wine\\Fonts")));
+        assert_eq!(
+            groups
+                .values()
+                .map(|group| group.keys().len())
+                .sum::<usize>(),
+            diff.keys().len()
+        );
+    }
+}
@@ -0,0 +1,179 @@
+#[cfg(feature = "fs")]
+use crate::prelude::Orphan;
+use crate::prelude::{DiffOptions, Hive, Key, KeyPattern, Registry, RegistryDiff};
+use regashii::{KeyName, ValueName};
+use std::collections::BTreeSet;
+use std::time::SystemTime;
+
+/// What [Registry::cleanup_patch] removes from a registry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CleanupRules {
+    /// The patterns of the keys to delete.
+    patterns: Vec<KeyPattern>,
+    /// The full names of the keys and the names of the values to delete.
+    values: Vec<(KeyName, ValueName)>,
+    /// The time before which keys were last written to be deleted.
+    older_than: Option<SystemTime>,
+}
+
+impl CleanupRules {
+    /// Constructs empty [CleanupRules], removing nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the rules deleting the keys matching a pattern, along with their subkeys.
+    pub fn with_pattern<T: Into<KeyPattern>>(mut self, pattern: T) -> Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    /// Returns the rules deleting a value.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The full name of the key holding the value.
+    /// * `value` - The name of the value.
+    pub fn with_value(mut self, key: KeyName, value: ValueName) -> Self {
+        self.values.push((key, value));
+        self
+    }
+
+    /// Returns the rules deleting the values referring to missing files.
+    #[cfg(feature = "fs")]
+    pub fn with_orphans(mut self, orphans: &[Orphan]) -> Self {
+        self.values.extend(
+            orphans
+                .iter()
+                .map(|orphan| (orphan.key().clone(), orphan.value().clone())),
+        );
+        self
+    }
+
+    /// Returns the rules deleting the keys last written before a time, as recorded in Wine
+    /// registry files. A key is only deleted if its subkeys are old enough too, and keys
+    /// without a recorded time are kept.
+    pub fn with_older_than(mut self, time: SystemTime) -> Self {
+        self.older_than = Some(time);
+        self
+    }
+
+    /// Returns the patterns of the keys to delete.
+    pub fn patterns(&self) -> &[KeyPattern] {
+        &self.patterns
+    }
+
+    /// Returns the full names of the keys and the names of the values to delete.
+    pub fn values(&self) -> &[(KeyName, ValueName)] {
+        &self.values
+    }
+
+    /// Returns the time before which keys were last written to be deleted.
+    pub fn older_than(&self) -> Option<SystemTime> {
+        self.older_than
+    }
+
+    /// Returns whether a key was last written before the age threshold.
+    fn is_stale(&self, key: &Key) -> bool {
+        match (self.older_than, key.modified()) {
+            (Some(threshold), Some(modified)) => modified < threshold,
+            _ => false,
+        }
+    }
+}
+
+impl Registry {
+    /// Builds the patch removing what matches cleanup rules, such as the results of
+    /// [Registry::orphans], to review and import with regedit.
+    ///
+    /// # Returns
+    ///
+    /// The diff deleting the matching keys, with their subkeys, and values. Serializing it
+    /// gives the `.reg` patch.
+    pub fn cleanup_patch(&self, rules: &CleanupRules) -> RegistryDiff {
+        // The keys with a recent subkey, which age thresholds must keep.
+        let mut recent = BTreeSet::new();
+        for key in self.keys().values() {
+            if key.modified().is_none() || rules.is_stale(key) {
+                continue;
+            }
+            let mut name = key.name().raw().to_lowercase();
+            while let Some((parent, _)) = name.rsplit_once('\\') {
+                name = parent.to_string();
+                recent.insert(name.clone());
+            }
+        }
+
+        let mut cleaned = self.clone();
+        for (path, key) in self.keys() {
+            let stale = rules.is_stale(key) && !recent.contains(&key.name().raw().to_lowercase());
+            if stale || rules.patterns.iter().any(|p| p.matches(key.name())) {
+                cleaned.remove_key(path.raw());
+            }
+        }
+        for (name, value) in &rules.values {
+            let exists = cleaned
+                .keys()
+                .values()
+                .any(|key| key.name().raw().eq_ignore_ascii_case(name.raw()));
+            match Hive::split(name.raw()) {
+                Some((hive, path)) if hive == self.hive() && exists => {
+                    cleaned
+                        .key_mut(path)
+                        .values_mut()
+                        .retain(|name, _| name != value);
+                }
+                _ => {}
+            }
+        }
+        RegistryDiff::new(self, &cleaned, &DiffOptions::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_cleanup_patch() {
+        let registry = Registry::try_from_str(
+            "WINE REGISTRY Version 2\n\n\
+            [Software\\\\Old] 1000\n\"A\"=\"a\"\n\n\
+            [Software\\\\Old\\\\Sub] 1500\n\n\
+            [Software\\\\Mixed] 1000\n\n\
+            [Software\\\\Mixed\\\\New] 3000\n\n\
+            [Software\\\\App] 3000\n\"Keep\"=\"k\"\n\"Path\"=\"C:\\\\gone.exe\"\n\n\
+            [Software\\\\Cache\\\\Fonts] 3000\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let rules = CleanupRules::new()
+            .with_pattern("HKEY_CURRENT_USER\\Software\\Cache\\*")
+            .with_older_than(UNIX_EPOCH + Duration::from_secs(2000))
+            .with_value(
+                KeyName::new("HKEY_CURRENT_USER\\Software\\App"),
+                ValueName::named("Path"),
+            );
+
+        let patch = registry.cleanup_patch(&rules);
+        let mut cleaned = registry.clone();
+        cleaned.apply(&patch);
+        let names: Vec<&str> = cleaned
+            .keys()
+            .values()
+            .map(|key| key.name().raw())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "HKEY_CURRENT_USER\\Software\\App",
+                "HKEY_CURRENT_USER\\Software\\Mixed",
+                "HKEY_CURRENT_USER\\Software\\Mixed\\New",
+            ]
+        );
+        let app = cleaned.keys().values().next().unwrap();
+        assert_eq!(app.values().len(), 1);
+        assert!(registry.cleanup_patch(&CleanupRules::new()).is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use crate::prelude::{KeyPattern, ValueKind};
+use regashii::KeyName;
+use std::sync::Arc;
+
+/// A strategy deciding whether the old and new data of a registry value are equal.
+///
+/// Comparators are registered on [DiffOptions](crate::prelude::DiffOptions) for a
+/// [ComparatorScope], and are consulted instead of exact equality for matching values.
+/// Any `Fn(&regashii::Value, &regashii::Value) -> bool` closure is a comparator.
+pub trait Comparator: Send + Sync {
+    /// Returns whether `old` and `new` should be considered equal.
+    fn equal(&self, old: &regashii::Value, new: &regashii::Value) -> bool;
+}
+
+impl<F> Comparator for F
+where
+    F: Fn(&regashii::Value, &regashii::Value) -> bool + Send + Sync,
+{
+    fn equal(&self, old: &regashii::Value, new: &regashii::Value) -> bool {
+        self(old, new)
+    }
+}
+
+/// Selects the values a [Comparator] applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComparatorScope {
+    /// Values whose old and new data are both of the given kind.
+    Kind(ValueKind),
+    /// Values of keys whose full name matches the given pattern.
+    Key(KeyPattern),
+}
+
+impl ComparatorScope {
+    /// Returns whether a value of the key `key` changing from `old` to `new` is in scope.
+    ///
+    /// Key scopes never match when the key is unknown.
+    pub fn matches(
+        &self,
+        key: Option<&KeyName>,
+        old: &regashii::Value,
+        new: &regashii::Value,
+    ) -> bool {
+        match self {
+            ComparatorScope::Kind(kind) => {
+                ValueKind::of(old) == *kind && ValueKind::of(new) == *kind
+            }
+            ComparatorScope::Key(pattern) => key.is_some_and(|key| pattern.matches(key)),
+        }
+    }
+}
+
+/// A [Comparator] registered for a scope.
+#[derive(Clone)]
+pub(crate) struct ScopedComparator {
+    pub(crate) scope: ComparatorScope,
+    pub(crate) comparator: Arc<dyn Comparator>,
+}
+
+impl std::fmt::Debug for ScopedComparator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ScopedComparator")
+            .field("scope", &self.scope)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [Comparator] treating strings as equal when they only differ in whitespace.
+///
+/// Leading and trailing whitespace is ignored and inner runs of whitespace are collapsed.
+/// Applies to `Sz`, `ExpandSz` and `MultiSz` data; other data is compared exactly.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IgnoreWhitespace;
+
+impl Comparator for IgnoreWhitespace {
+    fn equal(&self, old: &regashii::Value, new: &regashii::Value) -> bool {
+        let collapse = |data: &str| data.split_whitespace().collect::<Vec<_>>().join(" ");
+        match (old, new) {
+            (regashii::Value::Sz(old), regashii::Value::Sz(new))
+            | (regashii::Value::ExpandSz(old), regashii::Value::ExpandSz(new)) => {
+                collapse(old) == collapse(new)
+            }
+            (regashii::Value::MultiSz(old), regashii::Value::MultiSz(new)) => {
+                old.len() == new.len()
+                    && old
+                        .iter()
+                        .zip(new)
+                        .all(|(old, new)| collapse(old) == collapse(new))
+            }
+            _ => old == new,
+        }
+    }
+}
+
+/// A [Comparator] treating strings as equal when they are equal under Unicode NFC
+/// normalization, such as a precomposed `é` and an `e` followed by a combining acute accent.
+///
+/// Registries exported by different tools may encode the same strings differently, which
+/// this hides. Applies to `Sz`, `ExpandSz` and `MultiSz` data; other data is compared exactly.
+#[cfg(feature = "unicode")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NormalizeUnicode {
+    /// Whether strings are also compared case-insensitively.
+    ignore_case: bool,
+}
+
+#[cfg(feature = "unicode")]
+impl NormalizeUnicode {
+    /// Constructs a new [NormalizeUnicode] comparing normalized strings case-sensitively.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether normalized strings are also compared case-insensitively.
+    pub fn with_ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Returns whether normalized strings are also compared case-insensitively.
+    pub fn ignore_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    /// Returns the form of a string the comparison is made on.
+    fn normalize(&self, data: &str) -> String {
+        use unicode_normalization::UnicodeNormalization;
+        let normalized: String = data.nfc().collect();
+        match self.ignore_case {
+            // Lowercasing can denormalize some strings, so normalize again.
+            true => normalized.to_lowercase().nfc().collect(),
+            false => normalized,
+        }
+    }
+}
+
+#[cfg(feature = "unicode")]
+impl Comparator for NormalizeUnicode {
+    fn equal(&self, old: &regashii::Value, new: &regashii::Value) -> bool {
+        match (old, new) {
+            (regashii::Value::Sz(old), regashii::Value::Sz(new))
+            | (regashii::Value::ExpandSz(old), regashii::Value::ExpandSz(new)) => {
+                self.normalize(old) == self.normalize(new)
+            }
+            (regashii::Value::MultiSz(old), regashii::Value::MultiSz(new)) => {
+                old.len() == new.len()
+                    && old
+                        .iter()
+                        .zip(new)
+                        .all(|(old, new)| self.normalize(old) == self.normalize(new))
+            }
+            _ => old == new,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_whitespace() {
+        let old = regashii::Value::Sz(" native,  builtin ".to_string());
+        let new = regashii::Value::Sz("native, builtin".to_string());
+        assert!(IgnoreWhitespace.equal(&old, &new));
+        assert!(!IgnoreWhitespace.equal(&old, &regashii::Value::Sz("builtin".to_string())));
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_normalize_unicode() {
+        let composed = regashii::Value::Sz("Caf\u{e9}".to_string());
+        let decomposed = regashii::Value::Sz("Cafe\u{301}".to_string());
+        let upper = regashii::Value::Sz("CAFE\u{301}".to_string());
+        assert_ne!(composed, decomposed);
+        assert!(NormalizeUnicode::new().equal(&composed, &decomposed));
+        assert!(!NormalizeUnicode::new().equal(&composed, &upper));
+        assert!(NormalizeUnicode::new()
+            .with_ignore_case(true)
+            .equal(&composed, &upper));
+        assert!(!NormalizeUnicode::new().equal(&composed, &regashii::Value::Dword(1)));
+    }
+
+    #[test]
+    fn test_scope_matches_kind_and_key() {
+        let old = regashii::Value::Dword(1);
+        let new = regashii::Value::Dword(2);
+        let key = KeyName::new("HKEY_CURRENT_USER\\Software\\Wine");
+
+        assert!(ComparatorScope::Kind(ValueKind::Dword).matches(None, &old, &new));
+        assert!(!ComparatorScope::Kind(ValueKind::Sz).matches(None, &old, &new));
+
+        let scope = ComparatorScope::Key(KeyPattern::new("HKEY_CURRENT_USER\\Software\\*"));
+        assert!(scope.matches(Some(&key), &old, &new));
+        assert!(!scope.matches(None, &old, &new));
+    }
+}
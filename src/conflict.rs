@@ -0,0 +1,231 @@
+use crate::prelude::{KeyChange, KeyDiff, Operation, RegistryDiff, Value};
+use regashii::{KeyName, ValueName};
+use std::collections::BTreeMap;
+
+/// How two diffs disagree, as found by [RegistryDiff::conflicts_with].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both diffs set or delete the value, leaving it with different data. `None` stands for a
+    /// deleted value.
+    Value {
+        /// The data this diff leaves the value with.
+        ours: Option<Value>,
+        /// The data the other diff leaves the value with.
+        theirs: Option<Value>,
+    },
+    /// This diff deletes the key, or one of its parents, while the other changes it.
+    DeletedByOurs,
+    /// The other diff deletes the key, or one of its parents, while this one changes it.
+    DeletedByTheirs,
+}
+
+/// A change two diffs make with different outcomes, so the result of applying both depends on
+/// their order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    /// The full name of the key, the changed one for deletion conflicts.
+    key: KeyName,
+    /// The name of the value, for value conflicts.
+    value: Option<ValueName>,
+    /// How the diffs disagree.
+    kind: ConflictKind,
+}
+
+impl Conflict {
+    /// Constructs a conflict.
+    pub(crate) fn new(key: KeyName, value: Option<ValueName>, kind: ConflictKind) -> Self {
+        Self { key, value, kind }
+    }
+
+    /// Returns the full name of the key. For deletion conflicts, this is the key one diff
+    /// changes, which may be a subkey of the key the other deletes.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the name of the value, for value conflicts.
+    pub fn value(&self) -> Option<&ValueName> {
+        self.value.as_ref()
+    }
+
+    /// Returns how the diffs disagree.
+    pub fn kind(&self) -> &ConflictKind {
+        &self.kind
+    }
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let value = match &self.value {
+            Some(ValueName::Named(name)) => format!("value '{}'", name),
+            Some(ValueName::Default) => "default value".to_string(),
+            None => String::new(),
+        };
+        match &self.kind {
+            ConflictKind::Value { .. } => write!(
+                f,
+                "both diffs change {} of key '{}' differently",
+                value,
+                self.key.raw()
+            ),
+            ConflictKind::DeletedByOurs => {
+                write!(f, "key '{}' is deleted by this diff", self.key.raw())
+            }
+            ConflictKind::DeletedByTheirs => {
+                write!(f, "key '{}' is deleted by the other diff", self.key.raw())
+            }
+        }
+    }
+}
+
+impl RegistryDiff {
+    /// Finds the changes this diff and `other` both make with different outcomes, to detect
+    /// incompatible tweaks before applying them.
+    ///
+    /// A value conflicts when both diffs set it to different data, or one sets it while the
+    /// other deletes it. A key conflicts when one diff deletes it or one of its parents while
+    /// the other changes it; its values aren't reported then. Changes with the same outcome,
+    /// such as both diffs deleting a value, don't conflict. Key names are compared
+    /// case-insensitively, while value names must match exactly.
+    ///
+    /// # Returns
+    ///
+    /// The conflicts, by key name.
+    pub fn conflicts_with(&self, other: &RegistryDiff) -> Vec<Conflict> {
+        let ours = changed_keys(self);
+        let theirs = changed_keys(other);
+        let mut conflicts = Vec::new();
+
+        for (name, key) in &ours {
+            if key.change() == KeyChange::Deleted {
+                continue;
+            }
+            if deletes(&theirs, name) {
+                conflicts.push(Conflict {
+                    key: key.name().clone(),
+                    value: None,
+                    kind: ConflictKind::DeletedByTheirs,
+                });
+                continue;
+            }
+            let other = match theirs.get(name) {
+                Some(other) => other,
+                None => continue,
+            };
+            for (value, operation) in key.values() {
+                let (ours, theirs) = match (outcome(operation), other.values().get(value)) {
+                    (Some(ours), Some(operation)) => match outcome(operation) {
+                        Some(theirs) => (ours, theirs),
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                if !same_data(ours, theirs) {
+                    conflicts.push(Conflict {
+                        key: key.name().clone(),
+                        value: Some(value.clone()),
+                        kind: ConflictKind::Value {
+                            ours: ours.cloned(),
+                            theirs: theirs.cloned(),
+                        },
+                    });
+                }
+            }
+        }
+
+        for (name, key) in &theirs {
+            if key.change() != KeyChange::Deleted && deletes(&ours, name) {
+                conflicts.push(Conflict {
+                    key: key.name().clone(),
+                    value: None,
+                    kind: ConflictKind::DeletedByOurs,
+                });
+            }
+        }
+        conflicts.sort_by(|a, b| a.key.cmp(&b.key));
+        conflicts
+    }
+}
+
+/// Indexes the keys a diff changes by their lowercased name.
+fn changed_keys(diff: &RegistryDiff) -> BTreeMap<String, &KeyDiff> {
+    diff.keys()
+        .values()
+        .filter(|key| key.change() != KeyChange::Unchanged)
+        .map(|key| (key.name().raw().to_lowercase(), key))
+        .collect()
+}
+
+/// Returns whether a diff deletes a key or one of its parents, by lowercased name.
+fn deletes(keys: &BTreeMap<String, &KeyDiff>, name: &str) -> bool {
+    let mut current = Some(name);
+    while let Some(name) = current {
+        if keys.get(name).map(|key| key.change()) == Some(KeyChange::Deleted) {
+            return true;
+        }
+        current = name.rsplit_once('\\').map(|(parent, _)| parent);
+    }
+    false
+}
+
+/// Returns the data an operation leaves a value with, `Some(None)` if it deletes the value
+/// and `None` if it leaves the value alone.
+pub(crate) fn outcome(operation: &Operation<Value>) -> Option<Option<&Value>> {
+    match operation {
+        Operation::Add { data } | Operation::Modify { new_data: data, .. } => Some(Some(data)),
+        Operation::Delete { .. } => Some(None),
+        Operation::Unchanged | Operation::Volatile { .. } | Operation::Reordered { .. } => None,
+    }
+}
+
+/// Returns whether two outcomes leave a value with the same data.
+pub(crate) fn same_data(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.value() == b.value() && a.raw_type() == b.raw_type(),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{Hive, Registry};
+
+    #[test]
+    fn test_conflicts_with() {
+        let base = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000001\n\"B\"=\"b\"\n\n\
+            [HKEY_CURRENT_USER\\App\\Sub]\n\"C\"=\"c\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let patch = |text: &str| {
+            RegistryDiff::from_patch_str(&format!("REGEDIT4\n\n{}\n", text), &base).unwrap()
+        };
+
+        let ours = patch("[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000002\n\"B\"=-");
+        let same = patch("[HKEY_CURRENT_USER\\APP]\n\"B\"=-");
+        assert!(ours.conflicts_with(&same).is_empty());
+
+        let theirs = patch("[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000003\n\"B\"=\"x\"");
+        let conflicts = ours.conflicts_with(&theirs);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].value(), Some(&ValueName::named("A")));
+        assert_eq!(
+            conflicts[1].to_string(),
+            "both diffs change value 'B' of key 'HKEY_CURRENT_USER\\App' differently"
+        );
+
+        let sub = patch("[HKEY_CURRENT_USER\\App\\Sub]\n\"C\"=\"d\"");
+        let deleting = patch("[-HKEY_CURRENT_USER\\App]");
+        let conflicts = deleting.conflicts_with(&sub);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].key().raw(), "HKEY_CURRENT_USER\\App\\Sub");
+        assert_eq!(conflicts[0].kind(), &ConflictKind::DeletedByOurs);
+        assert_eq!(
+            sub.conflicts_with(&deleting)[0].kind(),
+            &ConflictKind::DeletedByTheirs
+        );
+    }
+}
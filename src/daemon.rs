@@ -0,0 +1,290 @@
+use crate::atomic;
+use crate::prelude::{DiffOptions, Hive, Registry, RegistryDiff, RegistryFile};
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+/// A resident process serving diff, apply and snapshot operations over a Unix domain socket,
+/// so front ends don't have to spawn a process for every operation.
+///
+/// Clients send one JSON request per line and get one JSON response per line back:
+///
+/// - `{"id": 1, "method": "diff", "params": {"old": "...", "new": "...", "hive": "HKEY_CURRENT_USER"}}`
+///   returns `{"id": 1, "result": {"patch": "...", "keys": 3}}`. `"security": true` also
+///   compares security descriptors.
+/// - `apply` takes the `registry` file to change, the `patch` file to apply and the `hive`, and
+///   writes the result to `output` in the encoding of `registry`. It returns the `undo` patch.
+///   Without `output`, `registry` is saved in place like [RegistryFile::save] does, failing if
+///   another program changed it meanwhile. Wine's own registry files, such as the `user.reg`
+///   of a prefix, are only loaded by Wine in their own syntax, so they require an `output`.
+/// - `snapshot` commits the `registry` file of a `hive` to the [History](crate::prelude::History)
+///   in the `history` directory with a `message`, and returns the `commit` and `snapshot` IDs.
+///   It requires the `store` feature.
+/// - `ping` returns `"pong"`.
+///
+/// Failed requests get `{"id": 1, "error": "..."}` back instead. Each connection is served on
+/// its own thread.
+#[derive(Debug)]
+pub struct Daemon {
+    /// The listening socket.
+    listener: UnixListener,
+    /// The path of the socket, removed when the daemon is dropped.
+    path: PathBuf,
+}
+
+impl Daemon {
+    /// Binds a daemon to a socket path, replacing a stale socket left there.
+    pub fn bind<T: AsRef<Path>>(path: T) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() && UnixStream::connect(&path).is_err() {
+            std::fs::remove_file(&path)?;
+        }
+        Ok(Self {
+            listener: UnixListener::bind(&path)?,
+            path,
+        })
+    }
+
+    /// Returns the path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Serves connections until accepting one fails.
+    pub fn serve(&self) -> std::io::Result<()> {
+        std::thread::scope(|scope| {
+            for stream in self.listener.incoming() {
+                let stream = stream?;
+                scope.spawn(move || serve_connection(stream));
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Answers the requests of a client until it disconnects.
+fn serve_connection(stream: UnixStream) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut response = handle(&line).to_string();
+        response.push('\n');
+        writer.write_all(response.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Answers a single request.
+///
+/// # Arguments
+///
+/// * `request` - The JSON request, without its line break.
+///
+/// # Returns
+///
+/// The JSON response, carrying the ID of the request if it had one.
+pub(crate) fn handle(request: &str) -> Value {
+    let request: Value = match serde_json::from_str(request) {
+        Ok(request) => request,
+        Err(error) => return json!({ "id": null, "error": format!("invalid request: {}", error) }),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let empty = Map::new();
+    let params = request
+        .get("params")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+
+    let result = match request.get("method").and_then(Value::as_str) {
+        Some("diff") => diff(params),
+        Some("apply") => apply(params),
+        Some("snapshot") => snapshot(params),
+        Some("ping") => Ok(json!("pong")),
+        Some(method) => Err(format!("unknown method '{}'", method)),
+        None => Err("missing method".to_string()),
+    };
+    match result {
+        Ok(result) => json!({ "id": id, "result": result }),
+        Err(error) => json!({ "id": id, "error": error }),
+    }
+}
+
+/// Computes the diff between two registry files.
+fn diff(params: &Map<String, Value>) -> Result<Value, String> {
+    let hive = hive(params)?;
+    let old = load(string(params, "old")?, hive)?;
+    let new = load(string(params, "new")?, hive)?;
+    let security = params
+        .get("security")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let diff = RegistryDiff::new(&old, &new, &DiffOptions::new().with_security(security));
+    let patch = diff.serialize().map_err(|error| error.to_string())?;
+    Ok(json!({ "patch": patch, "keys": diff.keys().len() }))
+}
+
+/// Applies a patch file to a registry file.
+fn apply(params: &Map<String, Value>) -> Result<Value, String> {
+    let hive = hive(params)?;
+    let path = string(params, "registry")?;
+    let mut file =
+        RegistryFile::open(path, hive).map_err(|error| format!("{}: {}", path, error))?;
+    let output = match params.get("output") {
+        Some(_) => Some(string(params, "output")?),
+        None if file.is_wine() => {
+            return Err(format!(
+                "{}: Wine registry files can't be written in place, pass an 'output'",
+                path
+            ))
+        }
+        None => None,
+    };
+    let diff = RegistryDiff::from_patch_file(string(params, "patch")?, file.registry())
+        .map_err(|error| error.to_string())?;
+    let undo = file.registry_mut().apply(&diff);
+    let undo = undo.serialize().map_err(|error| error.to_string())?;
+    match output {
+        Some(output) => {
+            let text = file
+                .registry()
+                .serialize()
+                .map_err(|error| error.to_string())?;
+            atomic::write(output, file.encoding().encode(&text))
+                .map_err(|error| error.to_string())?;
+        }
+        None => file.save().map_err(|error| error.to_string())?,
+    }
+    Ok(json!({ "undo": undo }))
+}
+
+/// Commits a registry file to a history.
+#[cfg(feature = "store")]
+fn snapshot(params: &Map<String, Value>) -> Result<Value, String> {
+    let registry = load(string(params, "registry")?, hive(params)?)?;
+    let message = params.get("message").and_then(Value::as_str).unwrap_or("");
+    let commit = crate::prelude::History::open(string(params, "history")?)
+        .and_then(|history| history.commit(&registry, message))
+        .map_err(|error| error.to_string())?;
+    Ok(json!({ "commit": commit.id().as_str(), "snapshot": commit.snapshot().as_str() }))
+}
+
+/// Rejects snapshots, which need a snapshot store.
+#[cfg(not(feature = "store"))]
+fn snapshot(_params: &Map<String, Value>) -> Result<Value, String> {
+    Err("snapshots require the 'store' feature".to_string())
+}
+
+/// Returns a required string parameter.
+fn string<'a>(params: &'a Map<String, Value>, name: &str) -> Result<&'a str, String> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("missing string parameter '{}'", name))
+}
+
+/// Returns the hive named by the `hive` parameter.
+fn hive(params: &Map<String, Value>) -> Result<Hive, String> {
+    let name = string(params, "hive")?;
+    [Hive::LocalMachine, Hive::CurrentUser, Hive::DefaultUser]
+        .into_iter()
+        .find(|hive| hive.to_string().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown hive '{}'", name))
+}
+
+/// Loads a registry file, describing failures for the response.
+fn load(path: &str, hive: Hive) -> Result<Registry, String> {
+    Registry::open(path, hive).map_err(|error| format!("{}: {}", path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regashii::{KeyName, ValueName};
+
+    #[test]
+    fn test_handle_requests() {
+        let response = handle(
+            r#"{"id": 7, "method": "diff", "params": {"old": "./registries/old.reg", "new": "./registries/new.reg", "hive": "HKEY_CURRENT_USER"}}"#,
+        );
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"]["patch"], diff.serialize().unwrap());
+        assert_eq!(response["result"]["keys"], diff.keys().len());
+
+        let response = handle(r#"{"id": "a", "method": "diff", "params": {"old": "x.reg"}}"#);
+        assert_eq!(response["id"], "a");
+        assert_eq!(response["error"], "missing string parameter 'hive'");
+        assert_eq!(handle(r#"{"method": "ping"}"#)["result"], "pong");
+        assert!(handle("not json")["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request"));
+    }
+
+    #[test]
+    fn test_apply_request() {
+        let dir = std::env::temp_dir().join(format!("regdiff-daemon-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let registry = dir.join("app.reg");
+        let patch = dir.join("patch.reg");
+        let wine = dir.join("user.reg");
+        let output = dir.join("output.reg");
+        std::fs::write(
+            &registry,
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=\"a\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &patch,
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=\"b\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &wine,
+            "WINE REGISTRY Version 2\n\n[App] 1740588656\n\"A\"=\"a\"\n",
+        )
+        .unwrap();
+        let request = |registry: &Path, output: Option<&Path>| {
+            let mut params =
+                json!({ "registry": registry, "patch": &patch, "hive": "HKEY_CURRENT_USER" });
+            if let Some(output) = output {
+                params["output"] = json!(output);
+            }
+            handle(&json!({ "id": 1, "method": "apply", "params": params }).to_string())
+        };
+
+        let applied = request(&registry, None);
+        let saved = Registry::open(&registry, Hive::CurrentUser).unwrap();
+        let refused = request(&wine, None);
+        let kept = std::fs::read_to_string(&wine).unwrap();
+        let written = request(&wine, Some(&output));
+        let converted = Registry::open(&output, Hive::CurrentUser).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(applied["result"]["undo"]
+            .as_str()
+            .unwrap()
+            .contains("\"A\"=\"a\""));
+        let value = &saved.key(&KeyName::new("App")).unwrap().values()[&ValueName::named("A")];
+        assert_eq!(value.value(), &regashii::Value::Sz("b".to_string()));
+        assert!(refused["error"]
+            .as_str()
+            .unwrap()
+            .contains("pass an 'output'"));
+        assert!(kept.starts_with("WINE REGISTRY Version 2"));
+        assert!(written.get("error").is_none());
+        assert!(converted.key(&KeyName::new("App")).is_some());
+    }
+}
@@ -1,14 +1,63 @@
-use crate::prelude::{Key, Registry, Value};
+use crate::compare::ScopedComparator;
+use crate::path;
+use crate::prelude::{
+    Comparator, ComparatorScope, DriveMapping, Error, Hive, Key, NoiseAction, PatchMetadata,
+    Registry, RulePreset, SecurityDescriptor, Value, ValueMap, WriteOptions,
+};
+#[cfg(feature = "fs")]
+use crate::prelude::{Encoding, PatchWriter};
+use crate::registry::hash_values;
+use crate::write;
 use regashii::{KeyName, ValueName};
+use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 /// Enum representing possible operations for modifying registry values.
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Operation<Data> {
     Unchanged,
-    Add { data: Data },
-    Delete { data: Data },
-    Modify { old_data: Data, new_data: Data },
+    Add {
+        data: Data,
+    },
+    Delete {
+        data: Data,
+    },
+    Modify {
+        old_data: Data,
+        new_data: Data,
+    },
+    /// A modification recognized as noise by a [RulePreset], such as a regenerated GUID.
+    Volatile {
+        old_data: Data,
+        new_data: Data,
+    },
+    /// A `REG_MULTI_SZ` value whose strings were only reordered, reported instead of a
+    /// modification when [DiffOptions::with_multi_sz_ordered] is disabled.
+    Reordered {
+        old_data: Data,
+        new_data: Data,
+    },
+}
+
+impl<Data: std::fmt::Display> std::fmt::Display for Operation<Data> {
+    /// Renders added data as `+data`, deleted data as `-data` and modifications as
+    /// `old -> new`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Operation::Unchanged => write!(f, "unchanged"),
+            Operation::Add { data } => write!(f, "+{}", data),
+            Operation::Delete { data } => write!(f, "-{}", data),
+            Operation::Modify { old_data, new_data } => write!(f, "{} -> {}", old_data, new_data),
+            Operation::Volatile { old_data, new_data } => {
+                write!(f, "{} -> {} (volatile)", old_data, new_data)
+            }
+            Operation::Reordered { old_data, new_data } => {
+                write!(f, "{} -> {} (reordered)", old_data, new_data)
+            }
+        }
+    }
 }
 
 /// A trait defining how to compute a diff between two items.
@@ -20,32 +69,362 @@ pub trait Diff {
     type Output<'a>;
 
     fn diff<'a>(old: Self::Input<'a>, new: Self::Input<'a>) -> Self::Output<'a>;
+
+    /// Computes the diff between two items, comparing them according to `options`, such as
+    /// with the comparators registered through [DiffOptions::with_comparator].
+    ///
+    /// By default the options are ignored and [Diff::diff] is used.
+    fn diff_with<'a>(
+        old: Self::Input<'a>,
+        new: Self::Input<'a>,
+        _options: &DiffOptions,
+    ) -> Self::Output<'a> {
+        Self::diff(old, new)
+    }
+}
+
+/// Options controlling how two registries are compared.
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    /// The drive mapping used to compare path-valued strings by the host location they refer to.
+    drive_mapping: Option<DriveMapping>,
+    /// The rule presets recognizing noisy value modifications.
+    presets: Vec<RulePreset>,
+    /// What to do with modifications matched by one of the presets.
+    noise_action: NoiseAction,
+    /// The custom comparators, in registration order.
+    comparators: Vec<ScopedComparator>,
+    /// Whether changes to key security descriptors are reported.
+    include_security: bool,
+    /// Whether symbolic link keys are compared by the contents of the keys they point to.
+    follow_links: bool,
+    /// Whether unchanged keys and values are reported alongside the changes.
+    include_unchanged: bool,
+    /// Whether the order of the strings of `REG_MULTI_SZ` values is ignored.
+    multi_sz_unordered: bool,
+    /// Whether repeated strings of `REG_MULTI_SZ` values are ignored.
+    multi_sz_deduplicated: bool,
+}
+
+impl DiffOptions {
+    /// Constructs a new [DiffOptions] with the default behaviour of exact comparisons.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the options with string values compared modulo the given drive mapping,
+    /// so that `C:\windows` and `Z:\<prefix>\drive_c\windows` are considered equal.
+    pub fn with_drive_mapping(mut self, mapping: DriveMapping) -> Self {
+        self.drive_mapping = Some(mapping);
+        self
+    }
+
+    /// Returns a reference to the drive mapping, if any.
+    pub fn drive_mapping(&self) -> Option<&DriveMapping> {
+        self.drive_mapping.as_ref()
+    }
+
+    /// Returns the options with the given rule preset enabled.
+    pub fn with_preset(mut self, preset: RulePreset) -> Self {
+        if !self.presets.contains(&preset) {
+            self.presets.push(preset);
+        }
+        self
+    }
+
+    /// Returns the options with modifications matched by a preset handled as `action`.
+    pub fn with_noise_action(mut self, action: NoiseAction) -> Self {
+        self.noise_action = action;
+        self
+    }
+
+    /// Returns the enabled rule presets.
+    pub fn presets(&self) -> &[RulePreset] {
+        &self.presets
+    }
+
+    /// Returns what happens to modifications matched by a preset.
+    pub fn noise_action(&self) -> NoiseAction {
+        self.noise_action
+    }
+
+    /// Returns the options with changes to key security descriptors reported or ignored.
+    ///
+    /// Security descriptors are ignored by default, as most registry files don't record them.
+    pub fn with_security(mut self, include: bool) -> Self {
+        self.include_security = include;
+        self
+    }
+
+    /// Returns whether changes to key security descriptors are reported.
+    pub fn include_security(&self) -> bool {
+        self.include_security
+    }
+
+    /// Returns the options with symbolic link keys compared by the contents of the keys they
+    /// point to, instead of by their targets.
+    ///
+    /// Links are resolved within each registry with [Registry::resolve_link]; links that can't
+    /// be resolved are compared by their targets.
+    pub fn with_follow_links(mut self, follow: bool) -> Self {
+        self.follow_links = follow;
+        self
+    }
+
+    /// Returns whether symbolic link keys are compared by the contents of the keys they point to.
+    pub fn follow_links(&self) -> bool {
+        self.follow_links
+    }
+
+    /// Returns the options with unchanged keys and values reported alongside the changes, for
+    /// completeness reports and coverage statistics.
+    ///
+    /// Keys present in both registries without changes are then reported as
+    /// [KeyChange::Unchanged], and unchanged values as [Operation::Unchanged]. Neither is
+    /// written to patches, and [RegistryDiff::is_empty] still only considers changes.
+    pub fn with_include_unchanged(mut self, include: bool) -> Self {
+        self.include_unchanged = include;
+        self
+    }
+
+    /// Returns whether unchanged keys and values are reported alongside the changes.
+    pub fn include_unchanged(&self) -> bool {
+        self.include_unchanged
+    }
+
+    /// Returns the options with the order of the strings of `REG_MULTI_SZ` values mattering,
+    /// the default, or not.
+    ///
+    /// Some producers rewrite lists such as search paths in another order. When order doesn't
+    /// matter, values whose strings were only reordered are reported as
+    /// [Operation::Reordered], which isn't written to patches.
+    pub fn with_multi_sz_ordered(mut self, ordered: bool) -> Self {
+        self.multi_sz_unordered = !ordered;
+        self
+    }
+
+    /// Returns whether the order of the strings of `REG_MULTI_SZ` values matters.
+    pub fn multi_sz_ordered(&self) -> bool {
+        !self.multi_sz_unordered
+    }
+
+    /// Returns the options with repeated strings of `REG_MULTI_SZ` values mattering, the
+    /// default, or not.
+    ///
+    /// When they don't, only the first occurrence of each string is compared, so values
+    /// only gaining or losing repeated strings are unchanged.
+    pub fn with_multi_sz_duplicates(mut self, duplicates: bool) -> Self {
+        self.multi_sz_deduplicated = !duplicates;
+        self
+    }
+
+    /// Returns whether repeated strings of `REG_MULTI_SZ` values matter.
+    pub fn multi_sz_duplicates(&self) -> bool {
+        !self.multi_sz_deduplicated
+    }
+
+    /// Returns the strings of a `REG_MULTI_SZ` value as compared, without repeated strings
+    /// unless they matter, or `None` for other values.
+    fn multi_sz<'a>(&self, value: &'a Value) -> Option<Vec<&'a String>> {
+        let strings = match (value.raw_type(), value.value()) {
+            (None, regashii::Value::MultiSz(strings)) => strings,
+            _ => return None,
+        };
+        let mut compared: Vec<&String> = Vec::with_capacity(strings.len());
+        for string in strings {
+            if !self.multi_sz_deduplicated || !compared.contains(&string) {
+                compared.push(string);
+            }
+        }
+        Some(compared)
+    }
+
+    /// Returns whether two `REG_MULTI_SZ` values only differ in the order of their strings,
+    /// when that order doesn't matter.
+    fn is_reordered(&self, old: &Value, new: &Value) -> bool {
+        if !self.multi_sz_unordered {
+            return false;
+        }
+        match (self.multi_sz(old), self.multi_sz(new)) {
+            (Some(mut old), Some(mut new)) => {
+                old.sort();
+                new.sort();
+                old == new
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether a modification is matched by one of the enabled presets.
+    fn is_noise(&self, old: &Value, new: &Value) -> bool {
+        self.presets.iter().any(|preset| preset.matches(old, new))
+    }
+
+    /// Returns the options with `comparator` deciding equality for values in `scope`.
+    ///
+    /// When several comparators match a value, the most recently registered one is used.
+    /// Values it finds different are still equal if they are paths to the same location
+    /// under the drive mapping.
+    pub fn with_comparator<C: Comparator + 'static>(
+        mut self,
+        scope: ComparatorScope,
+        comparator: C,
+    ) -> Self {
+        self.comparators.push(ScopedComparator {
+            scope,
+            comparator: Arc::new(comparator),
+        });
+        self
+    }
+
+    /// Returns whether two values with the same name should be considered equal.
+    ///
+    /// `key` is the full name of the key holding the values, if known.
+    fn values_equal(&self, key: Option<&KeyName>, old: &Value, new: &Value) -> bool {
+        if old == new {
+            return true;
+        }
+
+        let comparator = self
+            .comparators
+            .iter()
+            .rev()
+            .find(|entry| entry.scope.matches(key, old.value(), new.value()));
+        // A comparator can only make values equal, so paths to the same location still are.
+        if comparator.is_some_and(|entry| entry.comparator.equal(old.value(), new.value())) {
+            return true;
+        }
+
+        if self.multi_sz_deduplicated {
+            if let (Some(old), Some(new)) = (self.multi_sz(old), self.multi_sz(new)) {
+                return old == new;
+            }
+        }
+
+        self.drive_mapping
+            .as_ref()
+            .is_some_and(|mapping| mapping.same_location(old.value(), new.value()))
+    }
+}
+
+/// The map types holding keys and values, which [combine] pairs up.
+trait Map<K, V> {
+    /// Pairs the entries of two maps with matching keys, like [combine].
+    fn pairs<'a>(
+        old: &'a Self,
+        new: &'a Self,
+    ) -> impl Iterator<Item = (Option<&'a V>, Option<&'a V>)>
+    where
+        K: 'a,
+        V: 'a;
 }
 
-/// Combines two BTreeMaps (an "old" and a "new" version) by pairing
+impl<K: Ord, V> Map<K, V> for BTreeMap<K, V> {
+    fn pairs<'a>(
+        old: &'a Self,
+        new: &'a Self,
+    ) -> impl Iterator<Item = (Option<&'a V>, Option<&'a V>)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        merge_join(old, new).map(|(_, old, new)| (old, new))
+    }
+}
+
+#[cfg(feature = "preserve-order")]
+impl<K: std::hash::Hash + Eq, V> Map<K, V> for indexmap::IndexMap<K, V> {
+    /// Insertion-ordered maps can't be merged in lock-step, so entries follow the order of
+    /// the old map, followed by the entries only present in the new map.
+    fn pairs<'a>(
+        old: &'a Self,
+        new: &'a Self,
+    ) -> impl Iterator<Item = (Option<&'a V>, Option<&'a V>)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        let kept = old.iter().map(|(name, value)| (Some(value), new.get(name)));
+        let added = new
+            .iter()
+            .filter(|(name, _)| !old.contains_key(*name))
+            .map(|(_, value)| (None, Some(value)));
+        kept.chain(added)
+    }
+}
+
+/// Combines two maps (an "old" and a "new" version) by pairing
 /// values with matching keys. For keys only in the old map, the new value is None;
 /// and for keys only in the new map, the old value is None.
 ///
-/// Returns a Vec of tuples, each containing an Option referencing a value from old and new.
-fn combine<'a, 'b, K: std::cmp::Ord, V>(
-    old: &'a BTreeMap<K, V>,
-    new: &'b BTreeMap<K, V>,
-) -> Vec<(Option<&'a V>, Option<&'b V>)> {
-    let mut pairs: Vec<(Option<&V>, Option<&V>)> = Vec::new();
+/// Returns an iterator over tuples, each containing an Option referencing a value from old and new.
+fn combine<'a, K: 'a, V: 'a, M: Map<K, V>>(
+    old: &'a M,
+    new: &'a M,
+) -> impl Iterator<Item = (Option<&'a V>, Option<&'a V>)> {
+    M::pairs(old, new)
+}
 
-    // For every entry present in the old map, pair it with the corresponding value in the new map (if it exists)
-    for (name, value) in old.iter() {
-        pairs.push((Some(value), new.get(name)));
+/// Walks two sorted maps in lock-step, pairing the entries with matching keys.
+///
+/// Each key of either map is yielded once in ascending order, with its entry in the old map
+/// and its entry in the new map, `None` where a map lacks it. No lookups or allocations are
+/// made, so this is the cheapest way to compare two maps, such as the keys of two registries.
+///
+/// # Arguments
+///
+/// * `old` - The map whose entries come first in the pairs.
+/// * `new` - The map whose entries come second in the pairs.
+pub fn merge_join<'a, K: Ord, A, B>(
+    old: &'a BTreeMap<K, A>,
+    new: &'a BTreeMap<K, B>,
+) -> MergeJoin<'a, K, A, B> {
+    MergeJoin {
+        old: old.iter().peekable(),
+        new: new.iter().peekable(),
     }
+}
 
-    // For every entry in the new map that is not present in the old map, add a pair with None as the old value.
-    for (name, value) in new.iter() {
-        if !old.contains_key(name) {
-            pairs.push((None, Some(value)));
+/// The iterator returned by [merge_join].
+#[derive(Clone, Debug)]
+pub struct MergeJoin<'a, K, A, B> {
+    /// The remaining entries of the old map.
+    old: std::iter::Peekable<std::collections::btree_map::Iter<'a, K, A>>,
+    /// The remaining entries of the new map.
+    new: std::iter::Peekable<std::collections::btree_map::Iter<'a, K, B>>,
+}
+
+impl<'a, K: Ord, A, B> Iterator for MergeJoin<'a, K, A, B> {
+    type Item = (&'a K, Option<&'a A>, Option<&'a B>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.old.peek(), self.new.peek()) {
+            (Some((old, _)), Some((new, _))) => old.cmp(new),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => return None,
+        };
+        match order {
+            std::cmp::Ordering::Less => {
+                let (key, old) = self.old.next()?;
+                Some((key, Some(old), None))
+            }
+            std::cmp::Ordering::Greater => {
+                let (key, new) = self.new.next()?;
+                Some((key, None, Some(new)))
+            }
+            std::cmp::Ordering::Equal => {
+                let (key, old) = self.old.next()?;
+                let (_, new) = self.new.next()?;
+                Some((key, Some(old), Some(new)))
+            }
         }
     }
 
-    pairs
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (old, new) = (self.old.len(), self.new.len());
+        (old.max(new), Some(old + new))
+    }
 }
 
 impl Diff for Value {
@@ -59,10 +438,47 @@ impl Diff for Value {
     /// If both exist but the values differ, a [Operation::Modify] operation is generated.
     /// Otherwise a [Operation::Unchanged] operation is generated.
     fn diff<'a>(old: Self::Input<'a>, new: Self::Input<'a>) -> Self::Output<'a> {
+        Value::diff_with(old, new, &DiffOptions::default())
+    }
+
+    /// Computes the difference between two values, comparing them according to `options`.
+    ///
+    /// See [Value::diff] for the operations that are generated. Comparators scoped to a key
+    /// pattern don't apply, as the key holding the values is unknown; use [Key::diff_with]
+    /// or [RegistryDiff::new] for those.
+    fn diff_with<'a>(
+        old: Self::Input<'a>,
+        new: Self::Input<'a>,
+        options: &DiffOptions,
+    ) -> Self::Output<'a> {
+        Value::diff_in(None, old, new, options)
+    }
+}
+
+impl Value {
+    /// Computes the difference between two values of the key named `key`.
+    fn diff_in<'a>(
+        key: Option<&KeyName>,
+        old: Option<&'a Value>,
+        new: Option<&'a Value>,
+        options: &DiffOptions,
+    ) -> Operation<&'a Value> {
         match (old, new) {
             (Some(old), None) => Operation::Delete { data: old },
             (None, Some(new)) => Operation::Add { data: new },
-            (Some(old), Some(new)) if old != new => Operation::Modify {
+            (Some(old), Some(new)) if options.values_equal(key, old, new) => Operation::Unchanged,
+            (Some(old), Some(new)) if options.is_reordered(old, new) => Operation::Reordered {
+                old_data: old,
+                new_data: new,
+            },
+            (Some(old), Some(new)) if options.is_noise(old, new) => match options.noise_action() {
+                NoiseAction::Mark => Operation::Volatile {
+                    old_data: old,
+                    new_data: new,
+                },
+                NoiseAction::Omit => Operation::Unchanged,
+            },
+            (Some(old), Some(new)) => Operation::Modify {
                 old_data: old,
                 new_data: new,
             },
@@ -80,11 +496,32 @@ impl Operation<&Value> {
             _ => None,
         }
     }
+
+    /// Converts the borrowed operation into one owning its values.
+    fn cloned(self) -> Operation<Value> {
+        match self {
+            Operation::Unchanged => Operation::Unchanged,
+            Operation::Add { data } => Operation::Add { data: data.clone() },
+            Operation::Delete { data } => Operation::Delete { data: data.clone() },
+            Operation::Modify { old_data, new_data } => Operation::Modify {
+                old_data: old_data.clone(),
+                new_data: new_data.clone(),
+            },
+            Operation::Volatile { old_data, new_data } => Operation::Volatile {
+                old_data: old_data.clone(),
+                new_data: new_data.clone(),
+            },
+            Operation::Reordered { old_data, new_data } => Operation::Reordered {
+                old_data: old_data.clone(),
+                new_data: new_data.clone(),
+            },
+        }
+    }
 }
 
 impl Diff for Key {
     type Input<'a> = Option<&'a Self>;
-    type Output<'a> = Operation<Self>;
+    type Output<'a> = Operation<Cow<'a, Self>>;
 
     /// Computes the diff between two keys.
     ///
@@ -95,28 +532,53 @@ impl Diff for Key {
     ///     - If the key names are different i.e the keys are not the same, a [Operation::Modify] operation is generated.
     ///     - If there are differences in their values, each value difference is computed and a [Operation::Add] operation is generated.
     /// - If no differences are found, a [Operation::Unchanged] operation is generated.
+    ///
+    /// Operations borrow the keys they carry, except for the key holding the changed values,
+    /// so diffing large keys doesn't copy their data.
     fn diff<'a>(old: Self::Input<'a>, new: Self::Input<'a>) -> Self::Output<'a> {
+        Key::diff_with(old, new, &DiffOptions::default())
+    }
+
+    /// Computes the diff between two keys, comparing their values according to `options`.
+    ///
+    /// See [Key::diff] for the operations that are generated.
+    fn diff_with<'a>(
+        old: Self::Input<'a>,
+        new: Self::Input<'a>,
+        options: &DiffOptions,
+    ) -> Self::Output<'a> {
         match (old, new) {
-            (Some(old), None) => Operation::Delete { data: old.clone() },
-            (None, Some(new)) => Operation::Add { data: new.clone() },
+            (Some(old), None) => Operation::Delete {
+                data: Cow::Borrowed(old),
+            },
+            (None, Some(new)) => Operation::Add {
+                data: Cow::Borrowed(new),
+            },
             (Some(old), Some(new)) if old.name() != new.name() => Operation::Modify {
-                old_data: old.clone(),
-                new_data: new.clone(),
+                old_data: Cow::Borrowed(old),
+                new_data: Cow::Borrowed(new),
             },
             (Some(old), Some(new)) if old != new => {
+                let name = new.name();
                 let ops: Vec<Operation<&Value>> = combine(old.values(), new.values())
-                    .into_iter()
-                    .map(|(old, new)| Value::diff(old, new))
+                    .map(|(old, new)| Value::diff_in(Some(name), old, new, options))
                     .collect();
 
                 let mut key = regashii::Key::new();
+                let mut changed = false;
                 for op in ops {
                     if let Some((name, value)) = op.to_value() {
                         key = key.with(name, value);
+                        changed = true;
                     }
                 }
+
+                // Values may differ without being different under the options in use.
+                if !changed {
+                    return Operation::Unchanged;
+                }
                 Operation::Add {
-                    data: Key::new(new.name().clone(), key),
+                    data: Cow::Owned(Key::new(new.name().clone(), key)),
                 }
             }
             _ => Operation::Unchanged,
@@ -124,19 +586,6 @@ impl Diff for Key {
     }
 }
 
-impl Operation<Key> {
-    fn to_keys(self) -> Vec<(KeyName, regashii::Key)> {
-        match self {
-            Operation::Unchanged => Vec::new(),
-            Operation::Add { data } => vec![data.into_regashii_key()],
-            Operation::Delete { data } => vec![data.into_deleted_key()],
-            Operation::Modify { old_data, new_data } => {
-                vec![old_data.into_deleted_key(), new_data.into_regashii_key()]
-            }
-        }
-    }
-}
-
 impl Diff for Registry {
     type Input<'a> = &'a Self;
     type Output<'a> = regashii::Registry;
@@ -146,31 +595,558 @@ impl Diff for Registry {
     /// This function iterates over the keys of both registries, calculates
     /// their individual differences, and then constructs a new registry patch containing all changes.
     fn diff<'a>(old: Self::Input<'a>, new: Self::Input<'a>) -> Self::Output<'a> {
-        let mut patch = regashii::Registry::new(regashii::Format::Regedit4);
+        Registry::diff_with(old, new, &DiffOptions::default())
+    }
+
+    /// Computes the diff between two registries, comparing their values according to `options`.
+    ///
+    /// See [Registry::diff] for how the patch is constructed.
+    fn diff_with<'a>(
+        old: Self::Input<'a>,
+        new: Self::Input<'a>,
+        options: &DiffOptions,
+    ) -> Self::Output<'a> {
+        RegistryDiff::new(old, new, options).to_patch()
+    }
+}
+
+/// The kind of change made to a registry key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KeyChange {
+    /// The key only exists in the new registry.
+    Added,
+    /// The key only exists in the old registry.
+    Deleted,
+    /// The key exists in both registries, but some of its values differ.
+    Modified,
+    /// The key exists unchanged in both registries, only reported with
+    /// [DiffOptions::with_include_unchanged].
+    Unchanged,
+}
+
+/// Represents the changes made to a single registry key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyDiff {
+    /// The full registry key name/path.
+    name: KeyName,
+    /// How the key itself changed.
+    change: KeyChange,
+    /// The operations applied to the values of the key, excluding unchanged values unless
+    /// [DiffOptions::with_include_unchanged] is used.
+    values: ValueMap<Operation<Value>>,
+    /// The change made to the key's security descriptor, if any and if requested.
+    security: Option<Operation<SecurityDescriptor>>,
+    /// The change made to the target of a symbolic link key, if any.
+    link: Option<Operation<String>>,
+}
+
+impl Hash for KeyDiff {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.change.hash(state);
+        hash_values(&self.values, state);
+        self.security.hash(state);
+        self.link.hash(state);
+    }
+}
+
+impl KeyDiff {
+    /// Computes the changes between two versions of a key.
+    ///
+    /// # Returns
+    ///
+    /// `Some(KeyDiff)` if the key was added, deleted or had values changed, or `None` otherwise.
+    pub fn new(old: Option<&Key>, new: Option<&Key>, options: &DiffOptions) -> Option<Self> {
+        let (name, change) = match (old, new) {
+            (Some(old), None) => (old.name(), KeyChange::Deleted),
+            (None, Some(new)) => (new.name(), KeyChange::Added),
+            (Some(_), Some(new)) => (new.name(), KeyChange::Modified),
+            (None, None) => return None,
+        };
+        span!(TRACE, "diff_key", key = name.raw());
+
+        let empty = ValueMap::default();
+        let old_values = old.map_or(&empty, Key::values);
+        let new_values = new.map_or(&empty, Key::values);
+
+        let mut values = ValueMap::default();
+        for (old, new) in combine(old_values, new_values) {
+            let value_name = match old.or(new) {
+                Some(value) => value.name().clone(),
+                None => continue,
+            };
+            match Value::diff_in(Some(name), old, new, options) {
+                Operation::Unchanged if !options.include_unchanged() => {}
+                operation => {
+                    values.insert(value_name, operation.cloned());
+                }
+            }
+        }
+
+        let security = match (old.and_then(Key::security), new.and_then(Key::security)) {
+            _ if !options.include_security() => None,
+            (Some(old), None) => Some(Operation::Delete { data: old.clone() }),
+            (None, Some(new)) => Some(Operation::Add { data: new.clone() }),
+            (Some(old), Some(new)) if old != new => Some(Operation::Modify {
+                old_data: old.clone(),
+                new_data: new.clone(),
+            }),
+            _ => None,
+        };
+
+        let link = match (old.and_then(Key::link), new.and_then(Key::link)) {
+            (Some(old), None) => Some(Operation::Delete {
+                data: old.to_string(),
+            }),
+            (None, Some(new)) => Some(Operation::Add {
+                data: new.to_string(),
+            }),
+            (Some(old), Some(new)) if old != new => Some(Operation::Modify {
+                old_data: old.to_string(),
+                new_data: new.to_string(),
+            }),
+            _ => None,
+        };
+
+        let change = match change {
+            KeyChange::Modified
+                if values
+                    .values()
+                    .all(|operation| matches!(operation, Operation::Unchanged))
+                    && security.is_none()
+                    && link.is_none() =>
+            {
+                if !options.include_unchanged() {
+                    return None;
+                }
+                KeyChange::Unchanged
+            }
+            change => change,
+        };
+
+        Some(Self {
+            name: name.clone(),
+            change,
+            values,
+            security,
+            link,
+        })
+    }
+
+    /// Assembles a key diff from its parts, e.g. when decoding a stored diff.
+    pub(crate) fn from_parts(
+        name: KeyName,
+        change: KeyChange,
+        values: ValueMap<Operation<Value>>,
+        security: Option<Operation<SecurityDescriptor>>,
+        link: Option<Operation<String>>,
+    ) -> Self {
+        Self {
+            name,
+            change,
+            values,
+            security,
+            link,
+        }
+    }
+
+    /// Returns a reference to the registry key's name.
+    pub fn name(&self) -> &KeyName {
+        &self.name
+    }
+
+    /// Returns how the key itself changed.
+    pub fn change(&self) -> KeyChange {
+        self.change
+    }
+
+    /// Returns a reference to the operations applied to the key's values.
+    pub fn values(&self) -> &ValueMap<Operation<Value>> {
+        &self.values
+    }
+
+    /// Returns the change made to the key's security descriptor.
+    ///
+    /// Always `None` unless [DiffOptions::with_security] was enabled. Security changes can't be
+    /// expressed in `.reg` patches and are left out of [KeyDiff::to_patch_key].
+    pub fn security(&self) -> Option<&Operation<SecurityDescriptor>> {
+        self.security.as_ref()
+    }
+
+    /// Returns the change made to the target of a symbolic link key, including the key
+    /// becoming or ceasing to be a link.
+    ///
+    /// Always `None` for links followed with [DiffOptions::with_follow_links]. Links can't be
+    /// expressed in `.reg` patches and are left out of [KeyDiff::to_patch_key].
+    pub fn link(&self) -> Option<&Operation<String>> {
+        self.link.as_ref()
+    }
+
+    /// Converts the changes into the regashii key that applies them.
+    ///
+    /// # Returns
+    ///
+    /// `None` if nothing in the key needs to be written, e.g. when only volatile values changed.
+    pub fn to_patch_key(&self) -> Option<regashii::Key> {
+        if self.change == KeyChange::Deleted {
+            return Some(regashii::Key::deleted());
+        }
+
+        let mut key = regashii::Key::new();
+        let mut written = false;
+        for (name, operation) in &self.values {
+            let data = match operation {
+                Operation::Add { data } => data.regashii_data(),
+                Operation::Modify { new_data, .. } => new_data.regashii_data(),
+                Operation::Delete { .. } => regashii::Value::Delete,
+                Operation::Unchanged | Operation::Volatile { .. } | Operation::Reordered { .. } => {
+                    continue
+                }
+            };
+            key = key.with(name.clone(), data);
+            written = true;
+        }
+
+        match (self.change, written) {
+            (KeyChange::Modified | KeyChange::Unchanged, false) => None,
+            _ => Some(key),
+        }
+    }
+
+    /// Appends the lines applying the changes to a `.reg` patch, following the same rules
+    /// as [KeyDiff::to_patch_key].
+    ///
+    /// # Returns
+    ///
+    /// [Error::InvalidName] if a key or value name can't be written, in which case nothing is
+    /// appended.
+    pub(crate) fn write(&self, out: &mut String, options: &WriteOptions) -> Result<(), Error> {
+        if self.change == KeyChange::Deleted {
+            return write::key_header(out, &self.name, true, options);
+        }
+
+        let mut values: Vec<_> = self.values.iter().collect();
+        options.sort_values(&mut values, |(name, _)| name);
+        let mut lines = String::new();
+        for (name, operation) in values {
+            match operation {
+                Operation::Add { data } | Operation::Modify { new_data: data, .. } => {
+                    write::value(&mut lines, data, options)?;
+                }
+                Operation::Delete { .. } => write::deleted_value(&mut lines, name, options)?,
+                Operation::Unchanged | Operation::Volatile { .. } | Operation::Reordered { .. } => {
+                }
+            }
+        }
+
+        if self.change == KeyChange::Added || !lines.is_empty() {
+            write::key_header(out, &self.name, false, options)?;
+            out.push_str(&lines);
+        }
+        Ok(())
+    }
+
+    /// Returns the length in bytes of what [KeyDiff::write] appends, without writing it.
+    fn written_len(&self) -> usize {
+        if self.change == KeyChange::Deleted {
+            return write::key_header_len(&self.name, true);
+        }
+
+        let lines: usize = self
+            .values
+            .iter()
+            .map(|(name, operation)| match operation {
+                Operation::Add { data } | Operation::Modify { new_data: data, .. } => {
+                    write::value_len(data)
+                }
+                Operation::Delete { .. } => write::deleted_value_len(name),
+                Operation::Unchanged | Operation::Volatile { .. } | Operation::Reordered { .. } => {
+                    0
+                }
+            })
+            .sum();
+
+        if self.change == KeyChange::Added || lines > 0 {
+            write::key_header_len(&self.name, false) + lines
+        } else {
+            0
+        }
+    }
+}
+
+/// The structured result of diffing two registries.
+///
+/// Unlike the patch returned by [Registry::diff], this keeps the old and new data of
+/// every change so it can be inspected before being turned into a patch.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RegistryDiff {
+    /// A map of changed registry keys keyed by their full name.
+    keys: BTreeMap<KeyName, KeyDiff>,
+    /// The description of the diff as a patch.
+    metadata: PatchMetadata,
+}
+
+impl RegistryDiff {
+    /// Computes the structured diff between two registries, comparing values according to `options`.
+    ///
+    /// Keys loaded from identical text in both registries are skipped without comparing
+    /// their values, which makes diffing files with few changed keys much faster.
+    pub fn new(old: &Registry, new: &Registry, options: &DiffOptions) -> Self {
+        span!(
+            INFO,
+            "diff",
+            old_keys = old.keys().len(),
+            new_keys = new.keys().len()
+        );
+        let keys = combine(old.keys(), new.keys())
+            .filter_map(|(old_key, new_key)| {
+                if let (Some(old_key), Some(new_key)) = (old_key, new_key) {
+                    if !options.include_unchanged() && unchanged_text(old_key, new_key, options) {
+                        return None;
+                    }
+                }
+                if !options.follow_links() {
+                    return KeyDiff::new(old_key, new_key, options);
+                }
+                let old_key = old_key.map(|key| old.follow_link(key));
+                let new_key = new_key.map(|key| new.follow_link(key));
+                KeyDiff::new(old_key.as_deref(), new_key.as_deref(), options)
+            })
+            .map(|key| (key.name().clone(), key))
+            .collect();
+
+        Self {
+            keys,
+            metadata: PatchMetadata::default(),
+        }
+    }
+
+    /// Returns a reference to the changed keys.
+    pub fn keys(&self) -> &BTreeMap<KeyName, KeyDiff> {
+        &self.keys
+    }
+
+    /// Retrieves the changes made to a specific registry key by its full name.
+    pub fn key(&self, name: &KeyName) -> Option<&KeyDiff> {
+        self.keys.get(name)
+    }
+
+    /// Sets the metadata describing the diff as a patch, written along with it by
+    /// [RegistryDiff::serialize] and [RegistryDiff::to_binary].
+    pub fn with_metadata(mut self, metadata: PatchMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Returns the metadata describing the diff as a patch, empty unless set with
+    /// [RegistryDiff::with_metadata] or read from a patch.
+    pub fn metadata(&self) -> &PatchMetadata {
+        &self.metadata
+    }
+
+    /// Returns whether the diff contains no changes, ignoring unchanged keys reported with
+    /// [DiffOptions::with_include_unchanged].
+    pub fn is_empty(&self) -> bool {
+        self.keys
+            .values()
+            .all(|key| key.change() == KeyChange::Unchanged)
+    }
 
-        let pairs = combine(old.keys(), new.keys());
-        for (this, other) in pairs {
-            for (name, key) in Key::diff(this, other).to_keys() {
-                patch = patch.with(name, key);
+    /// Splits the diff into one diff per hive.
+    ///
+    /// Each part can be turned into its own patch with [RegistryDiff::to_patch]. Registries
+    /// name their keys starting with their hive, so every key of their diffs falls in one;
+    /// keys named outside of the hives, which only diffs collected from [KeyDiff]s can hold,
+    /// are left out.
+    pub fn split_by_hive(&self) -> BTreeMap<Hive, RegistryDiff> {
+        let mut parts: BTreeMap<Hive, RegistryDiff> = BTreeMap::new();
+        for (name, key) in &self.keys {
+            if let Some((hive, _)) = Hive::split(name.raw()) {
+                parts
+                    .entry(hive)
+                    .or_default()
+                    .keys
+                    .insert(name.clone(), key.clone());
+            }
+        }
+        parts
+    }
+
+    /// Splits the diff into one diff per subtree, keyed by the first `depth` components of
+    /// the key names.
+    ///
+    /// With a depth of 3, changes to `HKEY_CURRENT_USER\Software\Wine\Fonts` and
+    /// `HKEY_CURRENT_USER\Software\Wine\X11 Driver` are both put under
+    /// `HKEY_CURRENT_USER\Software\Wine`. Keys with fewer components are put under their own name.
+    pub fn split_by_prefix(&self, depth: usize) -> BTreeMap<KeyName, RegistryDiff> {
+        let mut parts: BTreeMap<KeyName, RegistryDiff> = BTreeMap::new();
+        for (name, key) in &self.keys {
+            let prefix: Vec<&str> = name.raw().split('\\').take(depth.max(1)).collect();
+            parts
+                .entry(KeyName::new(prefix.join("\\")))
+                .or_default()
+                .keys
+                .insert(name.clone(), key.clone());
+        }
+        parts
+    }
+
+    /// Groups the changes by subtree, keyed by the hive followed by the first `depth`
+    /// components of the key names within it, to organize a report by application.
+    ///
+    /// Unlike [RegistryDiff::split_by_prefix], depth is counted from the hive, so with a depth
+    /// of 2 changes to `HKEY_CURRENT_USER\Software\Foo\Settings` and
+    /// `HKEY_USERS\.Default\Software\Foo` are put under `HKEY_CURRENT_USER\Software\Foo` and
+    /// `HKEY_USERS\.Default\Software\Foo`. Unchanged keys are left out, so the number of keys
+    /// of each group is its number of changed keys.
+    pub fn group_by(&self, depth: usize) -> BTreeMap<KeyName, RegistryDiff> {
+        let mut groups: BTreeMap<KeyName, RegistryDiff> = BTreeMap::new();
+        let changed = self
+            .keys
+            .iter()
+            .filter(|(_, key)| key.change() != KeyChange::Unchanged);
+        for (name, key) in changed {
+            groups
+                .entry(path::subtree(name, depth))
+                .or_default()
+                .keys
+                .insert(name.clone(), key.clone());
+        }
+        groups
+    }
+
+    /// Converts the diff into a regashii registry patch.
+    ///
+    /// Volatile and reordered value changes are left out of the patch. Raw values, such as
+    /// REG_NONE data, are turned into regashii hex data of their type.
+    pub fn to_patch(&self) -> regashii::Registry {
+        let mut patch = regashii::Registry::new(regashii::Format::Regedit4);
+        for (name, key) in &self.keys {
+            if let Some(key) = key.to_patch_key() {
+                patch = patch.with(name.clone(), key);
             }
         }
         patch
     }
+
+    /// Serializes the diff as a `Windows Registry Editor Version 5.00` patch.
+    ///
+    /// The patch applies the same changes as [RegistryDiff::to_patch].
+    ///
+    /// # Returns
+    ///
+    /// The text of the patch, or [Error::InvalidName] if a key or value name holds a line
+    /// break, which registry files can't represent.
+    pub fn serialize(&self) -> Result<String, Error> {
+        self.serialize_with(&WriteOptions::default())
+    }
+
+    /// Serializes the diff like [RegistryDiff::serialize], written according to `options`.
+    pub fn serialize_with(&self, options: &WriteOptions) -> Result<String, Error> {
+        span!(DEBUG, "serialize", keys = self.keys.len());
+        let mut keys: Vec<&KeyDiff> = self.keys.values().collect();
+        options.sort_keys(&mut keys, |key| &key.name);
+        let mut out = format!("{}\n", write::HEADER);
+        out.push_str(&self.metadata.comments());
+        for key in keys {
+            let mut lines = String::new();
+            key.write(&mut lines, options)?;
+            if !lines.is_empty() {
+                out.push('\n');
+                out.push_str(&lines);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Computes the size of the patch returned by [RegistryDiff::serialize], in bytes of
+    /// UTF-8, without writing it. Patches written with other [WriteOptions] may differ.
+    ///
+    /// Callers can use this to warn about huge patches, or to pick a compact format such as
+    /// [RegistryDiff::to_binary] instead. Files written by [RegistryDiff::serialize_file] are
+    /// UTF-16LE, taking about twice as many bytes.
+    pub fn estimated_patch_size(&self) -> usize {
+        let lines: usize = self
+            .keys
+            .values()
+            .map(KeyDiff::written_len)
+            .filter(|len| *len > 0)
+            .map(|len| len + 1)
+            .sum();
+        write::HEADER.len() + 1 + self.metadata.comments().len() + lines
+    }
+
+    /// Writes the patch returned by [RegistryDiff::serialize] to a file, encoded as UTF-16LE
+    /// like regedit's own exports.
+    ///
+    /// The file is replaced atomically, through a temporary file next to it, so an
+    /// interrupted write leaves the previous file intact.
+    #[cfg(feature = "fs")]
+    pub fn serialize_file<T: AsRef<std::path::Path>>(&self, file: T) -> std::io::Result<()> {
+        span!(DEBUG, "serialize", keys = self.keys.len(), file = %file.as_ref().display());
+        let file = crate::atomic::AtomicFile::create(file)?;
+        let mut patch = PatchWriter::new(file)
+            .with_encoding(Encoding::Utf16Le)
+            .with_metadata(&self.metadata);
+        for key in self.keys.values() {
+            patch.write_key(key)?;
+        }
+        patch.finish()?.commit()
+    }
+}
+
+/// Returns whether two keys were loaded from identical text, so comparing their values can
+/// be skipped. Links are compared by their targets when following them, which may differ.
+fn unchanged_text(old: &Key, new: &Key, options: &DiffOptions) -> bool {
+    let linked = old.link().is_some() || new.link().is_some();
+    old.fingerprint().is_some()
+        && old.fingerprint() == new.fingerprint()
+        && !(linked && options.follow_links())
+}
+
+impl FromIterator<RegistryDiff> for RegistryDiff {
+    /// Combines several diffs into one, such as the diffs of different hives.
+    ///
+    /// Changes to a key present in more than one diff are taken from the last of them.
+    fn from_iter<I: IntoIterator<Item = RegistryDiff>>(diffs: I) -> Self {
+        let keys = diffs.into_iter().flat_map(|diff| diff.keys).collect();
+        Self {
+            keys,
+            metadata: PatchMetadata::default(),
+        }
+    }
+}
+
+impl FromIterator<KeyDiff> for RegistryDiff {
+    /// Collects changed keys into a diff, later changes to the same key replacing earlier ones.
+    fn from_iter<I: IntoIterator<Item = KeyDiff>>(keys: I) -> Self {
+        let keys = keys
+            .into_iter()
+            .map(|key| (key.name().clone(), key))
+            .collect();
+        Self {
+            keys,
+            metadata: PatchMetadata::default(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "fs")]
     use regashii::KeyKind;
 
     use super::*;
-    use crate::prelude::Hive;
+    use crate::prelude::{Hive, IgnoreWhitespace, KeyPattern, ValueKind};
 
+    #[cfg(feature = "fs")]
     fn generate_diff(hive: Hive) -> regashii::Registry {
-        let o_reg = Registry::try_from("./registries/old.reg", hive).unwrap();
-        let n_reg = Registry::try_from("./registries/new.reg", hive).unwrap();
+        let o_reg = Registry::open("./registries/old.reg", hive).unwrap();
+        let n_reg = Registry::open("./registries/new.reg", hive).unwrap();
         Registry::diff(&o_reg, &n_reg)
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_diff_delete_key() {
         let hive = Hive::LocalMachine;
@@ -184,6 +1160,7 @@ mod tests {
         assert_eq!(key.values().len(), 0);
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_diff_create_key() {
         let hive = Hive::LocalMachine;
@@ -196,6 +1173,7 @@ mod tests {
         assert_eq!(key.kind(), KeyKind::Add);
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_diff_value_create() {
         let hive = Hive::LocalMachine;
@@ -215,6 +1193,7 @@ mod tests {
         assert_eq!(value, &regashii::Value::Sz("new".to_string()));
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_diff_value_delete() {
         let hive = Hive::LocalMachine;
@@ -234,6 +1213,7 @@ mod tests {
         assert_eq!(value, &regashii::Value::Delete);
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_diff_value_update() {
         let hive = Hive::LocalMachine;
@@ -253,6 +1233,356 @@ mod tests {
         assert_eq!(value, &regashii::Value::Sz("new".to_string()));
     }
 
+    #[test]
+    fn test_diff_with_drive_mapping_ignores_equivalent_paths() {
+        let key = |path: &str| {
+            regashii::Key::new().with(
+                regashii::ValueName::named("Path"),
+                regashii::Value::Sz(path.to_string()),
+            )
+        };
+        let name = regashii::KeyName::new("Software\\Test");
+        let o_reg = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(name.clone(), key("C:\\windows"));
+        let n_reg = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(name.clone(), key("Z:\\prefix\\drive_c\\windows"));
+        let o_reg = Registry::from_regashii(o_reg, Hive::CurrentUser);
+        let n_reg = Registry::from_regashii(n_reg, Hive::CurrentUser);
+
+        let diff = Registry::diff(&o_reg, &n_reg);
+        assert_eq!(diff.keys().len(), 1);
+
+        let mapping = DriveMapping::new()
+            .with('c', "/prefix/drive_c")
+            .with('z', "/");
+        let options = DiffOptions::new().with_drive_mapping(mapping);
+        let diff = Registry::diff_with(&o_reg, &n_reg, &options);
+        assert!(diff.keys().is_empty());
+    }
+
+    #[test]
+    fn test_registry_diff_marks_regenerated_guids_volatile() {
+        let key = |id: &str| {
+            regashii::Key::new().with(
+                regashii::ValueName::named("MachineGuid"),
+                regashii::Value::Sz(id.to_string()),
+            )
+        };
+        let name = regashii::KeyName::new("Software\\Microsoft\\Cryptography");
+        let o_reg = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(name.clone(), key("0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0"));
+        let n_reg = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(name.clone(), key("11111111-2222-3333-4444-555555555555"));
+        let o_reg = Registry::from_regashii(o_reg, Hive::LocalMachine);
+        let n_reg = Registry::from_regashii(n_reg, Hive::LocalMachine);
+
+        let options = DiffOptions::new().with_preset(RulePreset::Guids);
+        let diff = RegistryDiff::new(&o_reg, &n_reg, &options);
+        let full_name = regashii::KeyName::new(format!("{}\\{}", Hive::LocalMachine, name.raw()));
+        let key = diff.key(&full_name).unwrap();
+        let operation = key
+            .values()
+            .get(&regashii::ValueName::named("MachineGuid"))
+            .unwrap();
+        assert!(matches!(operation, Operation::Volatile { .. }));
+        assert!(diff.to_patch().keys().is_empty());
+
+        let options = options.with_noise_action(NoiseAction::Omit);
+        assert!(RegistryDiff::new(&o_reg, &n_reg, &options).is_empty());
+    }
+
+    #[test]
+    fn test_registry_diff_uses_scoped_comparator() {
+        let key = |data: &str| {
+            regashii::Key::new().with(
+                regashii::ValueName::named("Mode"),
+                regashii::Value::Sz(data.to_string()),
+            )
+        };
+        let name = regashii::KeyName::new("Software\\Wine\\DllOverrides");
+        let o_reg = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(name.clone(), key("native, builtin"));
+        let n_reg = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(name.clone(), key("native,builtin "));
+        let o_reg = Registry::from_regashii(o_reg, Hive::CurrentUser);
+        let n_reg = Registry::from_regashii(n_reg, Hive::CurrentUser);
+
+        let scope = ComparatorScope::Key(KeyPattern::new("*\\Software\\Wine\\*"));
+        let ignore_commas = |old: &regashii::Value, new: &regashii::Value| match (old, new) {
+            (regashii::Value::Sz(old), regashii::Value::Sz(new)) => {
+                old.replace([' ', ','], "") == new.replace([' ', ','], "")
+            }
+            _ => old == new,
+        };
+        let options = DiffOptions::new().with_comparator(scope, ignore_commas);
+        assert!(RegistryDiff::new(&o_reg, &n_reg, &options).is_empty());
+
+        let options = DiffOptions::new()
+            .with_comparator(ComparatorScope::Kind(ValueKind::Sz), IgnoreWhitespace);
+        assert!(!RegistryDiff::new(&o_reg, &n_reg, &options).is_empty());
+    }
+
+    #[test]
+    fn test_comparators_apply_through_the_diff_trait() {
+        let value = |path: &str| {
+            Value::new(
+                regashii::ValueName::named("Path"),
+                regashii::Value::Sz(path.to_string()),
+            )
+        };
+        let (old, padded, mapped) = (
+            value("C:\\windows"),
+            value("C:\\windows "),
+            value("Z:\\prefix\\drive_c\\windows"),
+        );
+        let mapping = DriveMapping::new()
+            .with('c', "/prefix/drive_c")
+            .with('z', "/");
+        let options = DiffOptions::new()
+            .with_comparator(ComparatorScope::Kind(ValueKind::Sz), IgnoreWhitespace)
+            .with_drive_mapping(mapping);
+
+        let diff = |new| <Value as Diff>::diff_with(Some(&old), Some(new), &options);
+        assert!(matches!(
+            Value::diff(Some(&old), Some(&padded)),
+            Operation::Modify { .. }
+        ));
+        assert!(matches!(diff(&padded), Operation::Unchanged));
+        // Values the comparator tells apart still name the same location.
+        assert!(matches!(diff(&mapped), Operation::Unchanged));
+    }
+
+    #[test]
+    fn test_multi_sz_order_and_duplicates() {
+        let value = |strings: &[&str]| {
+            Value::new(
+                regashii::ValueName::named("Path"),
+                regashii::Value::MultiSz(strings.iter().map(|s| s.to_string()).collect()),
+            )
+        };
+        let (old, reordered, repeated) = (
+            value(&["a", "b"]),
+            value(&["b", "a"]),
+            value(&["a", "b", "a"]),
+        );
+        let diff =
+            |old, new, options: &DiffOptions| Value::diff_with(Some(old), Some(new), options);
+
+        let exact = DiffOptions::new();
+        assert!(matches!(
+            diff(&old, &reordered, &exact),
+            Operation::Modify { .. }
+        ));
+        assert!(matches!(
+            diff(&old, &repeated, &exact),
+            Operation::Modify { .. }
+        ));
+
+        let unordered = DiffOptions::new().with_multi_sz_ordered(false);
+        assert!(matches!(
+            diff(&old, &reordered, &unordered),
+            Operation::Reordered { .. }
+        ));
+        assert!(matches!(
+            diff(&old, &repeated, &unordered),
+            Operation::Modify { .. }
+        ));
+
+        let sets = unordered.with_multi_sz_duplicates(false);
+        assert_eq!(diff(&old, &repeated, &sets), Operation::Unchanged);
+        assert!(matches!(
+            diff(&repeated, &reordered, &sets),
+            Operation::Reordered { .. }
+        ));
+
+        let key = |value: Value| {
+            let (name, data) = value.into_regashii_value();
+            let registry = regashii::Registry::new(regashii::Format::Regedit4).with(
+                regashii::KeyName::new("Software\\App"),
+                regashii::Key::new().with(name, data),
+            );
+            Registry::from_regashii(registry, Hive::CurrentUser)
+        };
+        let registry_diff = RegistryDiff::new(&key(old), &key(reordered), &sets);
+        assert!(registry_diff
+            .to_patch()
+            .keys()
+            .values()
+            .all(|key| key.values().is_empty()));
+    }
+
+    #[test]
+    fn test_registry_diff_split_by_hive_and_prefix() {
+        let registry = |hive: Hive, name: &str| {
+            let registry = regashii::Registry::new(regashii::Format::Regedit4)
+                .with(regashii::KeyName::new(name), regashii::Key::new());
+            Registry::from_regashii(registry, hive)
+        };
+        let diff = |hive: Hive, new: &str| {
+            let (old, new) = (registry(hive, "A"), registry(hive, new));
+            RegistryDiff::new(&old, &new, &DiffOptions::new())
+        };
+
+        let parts = [
+            diff(Hive::LocalMachine, "Software\\Foo\\Bar"),
+            diff(Hive::CurrentUser, "Software\\Foo\\Baz"),
+            diff(Hive::DefaultUser, "Software\\Foo"),
+        ];
+        let diff: RegistryDiff = parts
+            .iter()
+            .flat_map(|part| part.keys().values().cloned())
+            .collect();
+
+        let hives = diff.split_by_hive();
+        let expected = [Hive::LocalMachine, Hive::CurrentUser, Hive::DefaultUser];
+        assert!(hives.keys().eq(&expected));
+        for (hive, part) in expected.iter().zip(&parts) {
+            assert_eq!(&hives[hive], part);
+        }
+
+        let parts = diff.split_by_prefix(3);
+        assert_eq!(parts.len(), 6);
+        assert!(parts.contains_key(&regashii::KeyName::new("HKEY_LOCAL_MACHINE\\Software\\Foo")));
+        assert!(parts.contains_key(&regashii::KeyName::new("HKEY_LOCAL_MACHINE\\A")));
+    }
+
+    #[test]
+    fn test_registry_diff_group_by() {
+        let registry = |hive: Hive, names: &[&str]| {
+            let registry = names.iter().fold(
+                regashii::Registry::new(regashii::Format::Regedit4),
+                |registry, name| registry.with(KeyName::new(*name), regashii::Key::new()),
+            );
+            Registry::from_regashii(registry, hive)
+        };
+        let mut diff = RegistryDiff::new(
+            &registry(Hive::CurrentUser, &["Software\\Same"]),
+            &registry(
+                Hive::CurrentUser,
+                &[
+                    "Software\\Same",
+                    "Software\\Foo\\A",
+                    "Software\\Foo\\B\\C",
+                    "Top",
+                ],
+            ),
+            &DiffOptions::new().with_include_unchanged(true),
+        );
+        diff.keys.extend(
+            RegistryDiff::new(
+                &registry(Hive::DefaultUser, &[]),
+                &registry(Hive::DefaultUser, &["Software\\Foo"]),
+                &DiffOptions::new(),
+            )
+            .keys,
+        );
+
+        let groups = diff.group_by(2);
+        let counts: Vec<(&str, usize)> = groups
+            .iter()
+            .map(|(prefix, group)| (prefix.raw(), group.keys().len()))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("HKEY_CURRENT_USER\\Software\\Foo", 2),
+                ("HKEY_CURRENT_USER\\Top", 1),
+                ("HKEY_USERS\\.Default\\Software\\Foo", 1),
+            ]
+        );
+        assert_eq!(diff.group_by(0).len(), 2);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_registry_diff_reports_security_changes_on_request() {
+        let dir = std::env::temp_dir().join(format!("regdiff-security-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let load = |file: &str, security: &str| {
+            let path = dir.join(file);
+            let text = format!(
+                "WINE REGISTRY Version 2\n\n[Software\\\\Test] 1740588656\n\
+                #security=hex:{}\n\"Name\"=\"value\"\n",
+                security
+            );
+            std::fs::write(&path, text).unwrap();
+            Registry::open(&path, Hive::CurrentUser).unwrap()
+        };
+        let old = load("old.reg", "01,00,04,80");
+        let new = load("new.reg", "01,00,14,80");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(RegistryDiff::new(&old, &new, &DiffOptions::new()).is_empty());
+
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new().with_security(true));
+        let key = diff
+            .key(&KeyName::new("HKEY_CURRENT_USER\\Software\\Test"))
+            .unwrap();
+        assert_eq!(key.change(), KeyChange::Modified);
+        assert!(key.values().is_empty());
+        assert!(matches!(key.security(), Some(Operation::Modify { .. })));
+        assert!(diff.to_patch().keys().is_empty());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_registry_diff_links() {
+        let dir = std::env::temp_dir().join(format!("regdiff-links-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let load = |file: &str, version: u32, target: &str| {
+            let path = dir.join(file);
+            let target: Vec<String> = target
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let text = format!(
+                "WINE REGISTRY Version 2\n\n[Software\\\\Classes] 1740588656\n\
+                \"Version\"=dword:{:08x}\n\n[Software\\\\Wow6432Node\\\\Classes] 1740588656\n\
+                #link\n\"SymbolicLinkValue\"=hex(6):{}\n",
+                version,
+                target.join(",")
+            );
+            std::fs::write(&path, text).unwrap();
+            Registry::open(&path, Hive::LocalMachine).unwrap()
+        };
+        let old = load("old.reg", 1, "\\Registry\\Machine\\Software\\Classes");
+        let new = load("new.reg", 2, "\\Registry\\Machine\\Software\\Classes");
+        let moved = load("moved.reg", 1, "\\Registry\\Machine\\Software\\Other");
+        std::fs::remove_dir_all(&dir).unwrap();
+        let link = KeyName::new("HKEY_LOCAL_MACHINE\\Software\\Wow6432Node\\Classes");
+
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        assert!(diff.key(&link).is_none());
+
+        let followed = RegistryDiff::new(&old, &new, &DiffOptions::new().with_follow_links(true));
+        let key = followed.key(&link).unwrap();
+        assert!(key.link().is_none());
+        assert!(matches!(
+            key.values().get(&ValueName::named("Version")),
+            Some(Operation::Modify { .. })
+        ));
+
+        let retargeted = RegistryDiff::new(&old, &moved, &DiffOptions::new());
+        let key = retargeted.key(&link).unwrap();
+        assert!(key.values().is_empty());
+        assert!(matches!(key.link(), Some(Operation::Modify { .. })));
+
+        let serialized = old.serialize().unwrap();
+        assert!(serialized.contains(
+            "[HKEY_LOCAL_MACHINE\\Software\\Wow6432Node\\Classes]\n\"SymbolicLinkValue\"=hex(6):"
+        ));
+        let reloaded = Registry::try_from_str(&serialized, Hive::LocalMachine).unwrap();
+        assert_eq!(reloaded.keys(), old.keys());
+        assert_eq!(
+            reloaded
+                .key(&KeyName::new("Software\\Wow6432Node\\Classes"))
+                .and_then(Key::link),
+            Some("\\Registry\\Machine\\Software\\Classes")
+        );
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn test_diff_no_change() {
         let hive = Hive::LocalMachine;
@@ -262,4 +1592,174 @@ mod tests {
         let key = diff.keys().get(&test_key);
         assert!(key.is_none());
     }
+
+    #[test]
+    fn test_registry_diff_skips_identical_text() {
+        let text = "REGEDIT4\n\n[Same]\n\"A\"=dword:00000001\n\n[Changed]\n\"B\"=\"x\"\n";
+        let old = Registry::try_from_str(text, Hive::CurrentUser).unwrap();
+        let new =
+            Registry::try_from_str(&text.replace("\"x\"", "\"y\""), Hive::CurrentUser).unwrap();
+        let same = KeyName::new("Same");
+        let changed = KeyName::new("Changed");
+        assert_eq!(
+            old.key(&same).unwrap().fingerprint(),
+            new.key(&same).unwrap().fingerprint()
+        );
+        assert_ne!(
+            old.key(&changed).unwrap().fingerprint(),
+            new.key(&changed).unwrap().fingerprint()
+        );
+
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        let names: Vec<_> = diff.keys().keys().map(|name| name.raw()).collect();
+        assert_eq!(names, vec!["HKEY_CURRENT_USER\\Changed"]);
+
+        // Keys modified in memory are compared by their values again.
+        let mut modified = old.clone();
+        modified.apply(&diff);
+        assert_eq!(modified.key(&changed).unwrap().fingerprint(), None);
+        assert!(RegistryDiff::new(&modified, &new, &DiffOptions::new()).is_empty());
+    }
+
+    #[cfg(all(feature = "tracing", feature = "fs"))]
+    #[test]
+    fn test_diff_emits_tracing_spans() {
+        use std::sync::Mutex;
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Records the names of the spans created.
+        struct Recorder(Mutex<Vec<&'static str>>);
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(span.metadata().name());
+                Id::from_u64(names.len() as u64)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+            let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+            RegistryDiff::new(&old, &new, &DiffOptions::new())
+                .serialize()
+                .unwrap();
+        });
+        let names = recorder.0.lock().unwrap();
+        for name in ["load", "parse", "diff", "diff_key", "serialize"] {
+            assert!(names.contains(&name), "missing span {}", name);
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_registry_diff_includes_unchanged_on_request() {
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let changes = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        let options = DiffOptions::new().with_include_unchanged(true);
+        let complete = RegistryDiff::new(&old, &new, &options);
+
+        let unchanged: Vec<_> = complete
+            .keys()
+            .values()
+            .filter(|key| key.change() == KeyChange::Unchanged)
+            .collect();
+        assert!(!unchanged.is_empty());
+        assert_eq!(
+            complete.keys().len(),
+            changes.keys().len() + unchanged.len()
+        );
+        assert!(unchanged.iter().all(|key| key
+            .values()
+            .values()
+            .all(|operation| matches!(operation, Operation::Unchanged))));
+        assert_eq!(complete.serialize().unwrap(), changes.serialize().unwrap());
+        assert!(RegistryDiff::new(&old, &old, &options).is_empty());
+    }
+
+    #[test]
+    fn test_key_diff_borrows_keys() {
+        let name = KeyName::new("HKEY_CURRENT_USER\\Test");
+        let old = Key::new(
+            name.clone(),
+            regashii::Key::new().with(ValueName::named("A"), regashii::Value::Dword(1)),
+        );
+        let new = Key::new(
+            name,
+            regashii::Key::new().with(ValueName::named("A"), regashii::Value::Dword(2)),
+        );
+        assert!(matches!(
+            Key::diff(Some(&old), None),
+            Operation::Delete {
+                data: Cow::Borrowed(_)
+            }
+        ));
+        match Key::diff(Some(&old), Some(&new)) {
+            Operation::Add {
+                data: Cow::Owned(key),
+            } => assert_eq!(key.values().len(), 1),
+            other => panic!("unexpected operation {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_merge_join() {
+        let old: BTreeMap<u32, &str> = [(1, "a"), (3, "c"), (4, "d")].into_iter().collect();
+        let new: BTreeMap<u32, u8> = [(2, 20), (3, 30), (5, 50)].into_iter().collect();
+        let joined: Vec<_> = merge_join(&old, &new).collect();
+        assert_eq!(
+            joined,
+            vec![
+                (&1, Some(&"a"), None),
+                (&2, None, Some(&20)),
+                (&3, Some(&"c"), Some(&30)),
+                (&4, Some(&"d"), None),
+                (&5, None, Some(&50)),
+            ]
+        );
+        assert_eq!(merge_join(&old, &BTreeMap::<u32, u8>::new()).count(), 3);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_estimated_patch_size() {
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        assert_eq!(diff.estimated_patch_size(), diff.serialize().unwrap().len());
+
+        let mut key = Key::new(
+            KeyName::new("HKEY_CURRENT_USER\\Software\\\"Ünïcode\""),
+            regashii::Key::new()
+                .with(
+                    ValueName::named("Path\\\"x\""),
+                    regashii::Value::Sz("C:\\a\nb".to_string()),
+                )
+                .with(
+                    ValueName::Default,
+                    regashii::Value::MultiSz(vec!["é".to_string(), String::new()]),
+                )
+                .with(ValueName::named("q"), regashii::Value::Qword(7))
+                .with(ValueName::named("b"), regashii::Value::Binary(vec![])),
+        );
+        key.values_mut().insert(
+            ValueName::named("raw"),
+            Value::raw(ValueName::named("raw"), 0x20000, vec![1, 2, 3]),
+        );
+        let diff: RegistryDiff = KeyDiff::new(None, Some(&key), &DiffOptions::new())
+            .into_iter()
+            .collect();
+        assert_eq!(diff.estimated_patch_size(), diff.serialize().unwrap().len());
+    }
 }
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Maps Windows drive letters to host directories.
+///
+/// Wine exposes drives to Windows programs through symbolic links in the prefix's
+/// `dosdevices` directory (`c:` -> `../drive_c`, `z:` -> `/`). This mapping allows
+/// Windows paths stored in string values to be rewritten to the host paths they refer to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DriveMapping {
+    /// A map of lowercase drive letters to their host directories.
+    drives: BTreeMap<char, PathBuf>,
+}
+
+impl DriveMapping {
+    /// Constructs an empty [DriveMapping].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the mapping with the given drive letter pointing at a host directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `letter` - The drive letter, in any case.
+    /// * `target` - The host directory the drive refers to.
+    pub fn with<T: Into<PathBuf>>(mut self, letter: char, target: T) -> Self {
+        self.drives
+            .insert(letter.to_ascii_lowercase(), normalize(&target.into()));
+        self
+    }
+
+    /// Reads the drive mapping of a Wine prefix.
+    ///
+    /// Every `<letter>:` symbolic link in `<prefix>/dosdevices` is resolved relative to
+    /// the `dosdevices` directory. Other entries (such as `com1` or `c::` device links)
+    /// are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The path of the Wine prefix.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the [DriveMapping] or an `std::io::Error` if the directory can't be read.
+    #[cfg(feature = "fs")]
+    pub fn from_prefix<T: AsRef<Path>>(prefix: T) -> std::io::Result<Self> {
+        let devices = prefix.as_ref().join("dosdevices");
+        let mut mapping = Self::new();
+
+        for entry in std::fs::read_dir(&devices)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let mut chars = name.chars();
+            let letter = match (chars.next(), chars.next(), chars.next()) {
+                (Some(letter), Some(':'), None) if letter.is_ascii_alphabetic() => letter,
+                _ => continue,
+            };
+
+            let target = match std::fs::read_link(entry.path()) {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+            mapping = mapping.with(letter, devices.join(target));
+        }
+
+        Ok(mapping)
+    }
+
+    /// Returns a reference to the map of drive letters to host directories.
+    pub fn drives(&self) -> &BTreeMap<char, PathBuf> {
+        &self.drives
+    }
+
+    /// Translates a Windows path such as `C:\users\steamuser` into a host path.
+    ///
+    /// # Returns
+    ///
+    /// `Some(PathBuf)` if the string is an absolute path on a mapped drive, or `None` otherwise.
+    pub fn to_host(&self, path: &str) -> Option<PathBuf> {
+        let mut chars = path.chars();
+        let letter = chars.next()?.to_ascii_lowercase();
+        if chars.next()? != ':' {
+            return None;
+        }
+        let rest = chars.as_str();
+        if !rest.is_empty() && !rest.starts_with(['\\', '/']) {
+            return None;
+        }
+
+        let mut host = self.drives.get(&letter)?.clone();
+        for component in rest.split(['\\', '/']).filter(|c| !c.is_empty()) {
+            host.push(component);
+        }
+        Some(normalize(&host))
+    }
+
+    /// Rewrites a string value holding a Windows path to hold the corresponding host path.
+    ///
+    /// Values that are not `Sz`/`ExpandSz` paths on a mapped drive are returned unchanged.
+    pub fn translate_value(&self, value: &regashii::Value) -> regashii::Value {
+        match value {
+            regashii::Value::Sz(data) => match self.to_host(data) {
+                Some(host) => regashii::Value::Sz(host.to_string_lossy().to_string()),
+                None => value.clone(),
+            },
+            regashii::Value::ExpandSz(data) => match self.to_host(data) {
+                Some(host) => regashii::Value::ExpandSz(host.to_string_lossy().to_string()),
+                None => value.clone(),
+            },
+            _ => value.clone(),
+        }
+    }
+
+    /// Returns whether two values are path strings referring to the same host location.
+    pub fn same_location(&self, a: &regashii::Value, b: &regashii::Value) -> bool {
+        let (a, b) = match (a, b) {
+            (regashii::Value::Sz(a), regashii::Value::Sz(b)) => (a, b),
+            (regashii::Value::ExpandSz(a), regashii::Value::ExpandSz(b)) => (a, b),
+            _ => return false,
+        };
+
+        match (self.to_host(a), self.to_host(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Lexically normalizes a path by removing `.` components and resolving `..` components.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping() -> DriveMapping {
+        DriveMapping::new()
+            .with('C', "/home/user/prefix/dosdevices/../drive_c")
+            .with('z', "/")
+    }
+
+    #[test]
+    fn test_to_host_translates_mapped_drive() {
+        let host = mapping().to_host("C:\\users\\steamuser\\Documents");
+        assert_eq!(
+            host,
+            Some(PathBuf::from(
+                "/home/user/prefix/drive_c/users/steamuser/Documents"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_to_host_unmapped_drive_returns_none() {
+        assert!(mapping().to_host("D:\\games").is_none());
+        assert!(mapping().to_host("not a path").is_none());
+    }
+
+    #[test]
+    fn test_same_location_across_drives() {
+        let a = regashii::Value::Sz("c:\\windows".to_string());
+        let b = regashii::Value::Sz("Z:\\home\\user\\prefix\\drive_c\\windows".to_string());
+        assert!(mapping().same_location(&a, &b));
+    }
+
+    #[cfg(all(feature = "fs", unix))]
+    #[test]
+    fn test_from_prefix_reads_dosdevices() {
+        let prefix = std::env::temp_dir().join("regdiff-drives-test");
+        let devices = prefix.join("dosdevices");
+        let _ = std::fs::remove_dir_all(&prefix);
+        std::fs::create_dir_all(&devices).unwrap();
+        std::os::unix::fs::symlink("../drive_c", devices.join("c:")).unwrap();
+        std::os::unix::fs::symlink("/dev/ttyS0", devices.join("com1")).unwrap();
+
+        let mapping = DriveMapping::from_prefix(&prefix).unwrap();
+        std::fs::remove_dir_all(&prefix).unwrap();
+
+        assert_eq!(mapping.drives().len(), 1);
+        assert_eq!(mapping.drives().get(&'c'), Some(&prefix.join("drive_c")));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::prelude::{Registry, ValueKind, WriteOptions};
+use crate::write;
+use regashii::{KeyName, ValueName};
+use std::collections::HashMap;
+
+/// Identical data stored by several values of a registry, as found by
+/// [Registry::duplicate_data].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateData {
+    /// The kind of the data.
+    kind: ValueKind,
+    /// The size of the data as written in a `.reg` file.
+    size: usize,
+    /// The full names of the keys and the names of the values holding the data.
+    locations: Vec<(KeyName, ValueName)>,
+}
+
+impl DuplicateData {
+    /// Returns the kind of the data.
+    pub fn kind(&self) -> ValueKind {
+        self.kind
+    }
+
+    /// Returns the size in bytes of the data as written in a `.reg` file, such as
+    /// `hex:01,02`.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the full names of the keys and the names of the values holding the data, in
+    /// key order.
+    pub fn locations(&self) -> &[(KeyName, ValueName)] {
+        &self.locations
+    }
+
+    /// Returns how many bytes storing the data only once would save.
+    pub fn wasted(&self) -> usize {
+        self.size * (self.locations.len() - 1)
+    }
+}
+
+impl std::fmt::Display for DuplicateData {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} values hold the same {} bytes of {:?} data, wasting {} bytes:",
+            self.locations.len(),
+            self.size,
+            self.kind,
+            self.wasted()
+        )?;
+        for (key, value) in &self.locations {
+            match value {
+                ValueName::Named(name) => write!(f, "\n  {}\\\"{}\"", key.raw(), name)?,
+                ValueName::Default => write!(f, "\n  {}\\@", key.raw())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Registry {
+    /// Finds the data stored identically by several values, such as repeated caches, to
+    /// understand what makes a registry big.
+    ///
+    /// Data is compared with its type, so a string and binary data holding the same bytes
+    /// differ.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_size` - The size in bytes of the smallest data to report, as written in a
+    ///   `.reg` file, leaving out small data such as flags shared by many values.
+    ///
+    /// # Returns
+    ///
+    /// The duplicated data, the most wasteful first.
+    pub fn duplicate_data(&self, min_size: usize) -> Vec<DuplicateData> {
+        let options = WriteOptions::default();
+        let mut groups: HashMap<String, DuplicateData> = HashMap::new();
+        for key in self.keys().values() {
+            for value in key.values().values() {
+                let data = write::data(value, &options);
+                if data.len() < min_size {
+                    continue;
+                }
+                let kind = match value.raw_type() {
+                    Some(raw_type) => ValueKind::from_type(raw_type),
+                    None => value.kind(),
+                };
+                let size = data.len();
+                groups
+                    .entry(data)
+                    .or_insert_with(|| DuplicateData {
+                        kind,
+                        size,
+                        locations: Vec::new(),
+                    })
+                    .locations
+                    .push((key.name().clone(), value.name().clone()));
+            }
+        }
+
+        let mut duplicates: Vec<DuplicateData> = groups
+            .into_values()
+            .filter(|group| group.locations.len() > 1)
+            .collect();
+        duplicates.sort_by(|a, b| {
+            b.wasted()
+                .cmp(&a.wasted())
+                .then_with(|| a.locations.cmp(&b.locations))
+        });
+        duplicates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Hive;
+
+    #[test]
+    fn test_duplicate_data() {
+        let registry = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\A]\n\"Cache\"=hex:01,02,03,04,05,06\n\"S\"=\"shared\"\n\
+            \"Flag\"=dword:00000001\n\n[HKEY_CURRENT_USER\\B]\n@=hex:01,02,03,04,05,06\n\
+            \"Flag\"=dword:00000001\n\"Other\"=\"shared\"\n\n[HKEY_CURRENT_USER\\C]\n\
+            \"Cache\"=hex:01,02,03,04,05,06\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+
+        let duplicates = registry.duplicate_data(15);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].kind(), ValueKind::Binary);
+        assert_eq!(duplicates[0].locations().len(), 3);
+        assert_eq!(duplicates[0].wasted(), 2 * "hex:01,02,03,04,05,06".len());
+        assert!(duplicates[0]
+            .to_string()
+            .ends_with("\n  HKEY_CURRENT_USER\\C\\\"Cache\""));
+
+        let duplicates = registry.duplicate_data(0);
+        assert_eq!(duplicates.len(), 3);
+        assert_eq!(duplicates[0].kind(), ValueKind::Binary);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::prelude::Error;
+#[cfg(feature = "fs")]
+use std::borrow::Cow;
+
+/// The character encodings registry files are stored in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8, as written by Wine and most tools.
+    Utf8,
+    /// UTF-16 little endian, as written by regedit for `Windows Registry Editor Version 5.00` exports.
+    Utf16Le,
+    /// The Windows-1252 ANSI code page, as written by regedit for `REGEDIT4` exports.
+    Windows1252,
+}
+
+impl Encoding {
+    /// Guesses the encoding of a registry file from its contents.
+    ///
+    /// A byte order mark is trusted if present. Otherwise files whose leading characters are
+    /// interleaved with NUL bytes are UTF-16LE, valid UTF-8 is UTF-8, and anything else is
+    /// assumed to use the ANSI code page.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&[0xff, 0xfe]) {
+            return Encoding::Utf16Le;
+        }
+        if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+            return Encoding::Utf8;
+        }
+
+        // Registry headers start with ASCII letters, so UTF-16LE text has NUL high bytes.
+        let head = &bytes[..bytes.len().min(64)];
+        let odd_nuls = head.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+        if head.len() >= 2 && odd_nuls * 2 >= head.len() / 2 {
+            return Encoding::Utf16Le;
+        }
+
+        match std::str::from_utf8(bytes) {
+            Ok(_) => Encoding::Utf8,
+            Err(_) => Encoding::Windows1252,
+        }
+    }
+
+    /// Decodes the contents of a registry file, removing any byte order mark.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the text, or [Error::Encoding] if the bytes are not valid in this encoding.
+    pub fn decode(&self, bytes: &[u8]) -> Result<String, Error> {
+        match self {
+            Encoding::Utf8 => {
+                let bytes = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]).unwrap_or(bytes);
+                String::from_utf8(bytes.to_vec()).map_err(|_| Error::Encoding(*self))
+            }
+            Encoding::Utf16Le => {
+                let bytes = bytes.strip_prefix(&[0xff, 0xfe]).unwrap_or(bytes);
+                let pairs = bytes.chunks_exact(2);
+                if !pairs.remainder().is_empty() {
+                    return Err(Error::Encoding(*self));
+                }
+                let units: Vec<u16> = pairs
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect();
+                String::from_utf16(&units).map_err(|_| Error::Encoding(*self))
+            }
+            Encoding::Windows1252 => Ok(bytes.iter().map(|&b| windows_1252(b)).collect()),
+        }
+    }
+
+    /// Decodes the contents of a registry file like [Encoding::decode], borrowing UTF-8 text
+    /// from `bytes` instead of copying it.
+    #[cfg(feature = "fs")]
+    pub(crate) fn decode_borrowed<'a>(&self, bytes: &'a [u8]) -> Result<Cow<'a, str>, Error> {
+        match self {
+            Encoding::Utf8 => {
+                let bytes = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]).unwrap_or(bytes);
+                std::str::from_utf8(bytes)
+                    .map(Cow::Borrowed)
+                    .map_err(|_| Error::Encoding(*self))
+            }
+            _ => self.decode(bytes).map(Cow::Owned),
+        }
+    }
+
+    /// Encodes text for a registry file, starting UTF-16LE text with a byte order mark as
+    /// regedit does.
+    ///
+    /// Characters missing from the Windows-1252 code page are replaced with `?`.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            Encoding::Utf16Le => [0xff, 0xfe]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+            Encoding::Windows1252 => text
+                .chars()
+                .map(
+                    |c| match WINDOWS_1252_HIGH.iter().position(|&high| high == c) {
+                        Some(index) => 0x80 + index as u8,
+                        None => match c as u32 {
+                            code @ (0..=0x7f | 0xa0..=0xff) => code as u8,
+                            _ => b'?',
+                        },
+                    },
+                )
+                .collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Encoding::Utf8 => "UTF-8",
+                Encoding::Utf16Le => "UTF-16LE",
+                Encoding::Windows1252 => "Windows-1252",
+            }
+        )
+    }
+}
+
+/// Characters of the Windows-1252 code page for the bytes 0x80 to 0x9f, which differ from Latin-1.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+/// Decodes a single Windows-1252 byte.
+fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9f => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+/// Repairs mojibake: text encoded as UTF-8 then decoded as Windows-1252 or Latin-1, such as
+/// `cafÃ©` for `café`, as found in prefixes written by old Wine versions.
+///
+/// # Returns
+///
+/// The repaired text, undoing as many layers of re-encoding as found, or `None` if the text
+/// isn't mojibake, which is when its characters don't all map to bytes or the bytes aren't
+/// valid UTF-8.
+pub(crate) fn repair_mojibake(text: &str) -> Option<String> {
+    let mut repaired = undo_mojibake(text)?;
+    while let Some(again) = undo_mojibake(&repaired) {
+        repaired = again;
+    }
+    Some(repaired)
+}
+
+/// Undoes one layer of mojibake.
+fn undo_mojibake(text: &str) -> Option<String> {
+    if text.is_ascii() {
+        return None;
+    }
+    let bytes = text
+        .chars()
+        .map(
+            |c| match WINDOWS_1252_HIGH.iter().position(|&high| high == c) {
+                Some(index) => Some(0x80 + index as u8),
+                None => u8::try_from(u32::from(c)).ok(),
+            },
+        )
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_detect_and_decode_utf16() {
+        let text = "Windows Registry Editor Version 5.00\r\n";
+        let mut with_bom = vec![0xff, 0xfe];
+        with_bom.extend(utf16(text));
+
+        assert_eq!(Encoding::detect(&with_bom), Encoding::Utf16Le);
+        assert_eq!(Encoding::detect(&utf16(text)), Encoding::Utf16Le);
+        assert_eq!(Encoding::Utf16Le.decode(&with_bom).unwrap(), text);
+    }
+
+    #[test]
+    fn test_detect_utf8_and_ansi() {
+        assert_eq!(
+            Encoding::detect("REGEDIT4\n\"é\"".as_bytes()),
+            Encoding::Utf8
+        );
+
+        let ansi = b"REGEDIT4\n\"\xe9\x80\"";
+        assert_eq!(Encoding::detect(ansi), Encoding::Windows1252);
+        assert_eq!(
+            Encoding::Windows1252.decode(ansi).unwrap(),
+            "REGEDIT4\n\"é€\""
+        );
+    }
+
+    #[test]
+    fn test_encode_round_trips() {
+        let text = "Ünïcödé €";
+        for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Windows1252] {
+            assert_eq!(encoding.decode(&encoding.encode(text)).unwrap(), text);
+        }
+        assert_eq!(Encoding::Windows1252.encode("中"), b"?");
+    }
+
+    #[test]
+    fn test_repair_mojibake() {
+        assert_eq!(repair_mojibake("cafÃ©").as_deref(), Some("café"));
+        assert_eq!(repair_mojibake("â‚¬ Ã¼ber").as_deref(), Some("€ über"));
+        assert_eq!(repair_mojibake("cafÃƒÂ©").as_deref(), Some("café"));
+        assert_eq!(repair_mojibake("café"), None);
+        assert_eq!(repair_mojibake("中文"), None);
+        assert_eq!(repair_mojibake("plain"), None);
+    }
+
+    #[test]
+    fn test_decode_invalid_data_fails() {
+        assert!(Encoding::Utf8.decode(b"\xff\xff").is_err());
+        assert!(Encoding::Utf16Le.decode(b"abc").is_err());
+    }
+}
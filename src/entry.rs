@@ -0,0 +1,242 @@
+use crate::prelude::{KeyChange, KeyDiff, Operation, Registry, RegistryDiff, Value, ValueMap};
+use regashii::{KeyName, ValueName};
+use std::collections::BTreeSet;
+
+/// A stable identifier of a [DiffEntry], such as to remember user decisions about it.
+///
+/// It hashes the full name of the key, lowercased, the name of the value and the kind of the
+/// change, but not the data, so the same change found by a later diff keeps its identifier
+/// even if the data churns. It is displayed as 16 hex digits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntryId(u64);
+
+impl EntryId {
+    /// Reads an identifier displayed as hex digits.
+    pub fn from_hex(text: &str) -> Option<Self> {
+        u64::from_str_radix(text, 16).ok().map(Self)
+    }
+
+    /// Returns the identifier as a number.
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for EntryId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl std::fmt::Display for EntryId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// A single change of a [RegistryDiff], such as a line of a checkbox list: either the change
+/// of a key itself, or the change of one of its values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiffEntry<'a> {
+    /// The changes of the key.
+    key: &'a KeyDiff,
+    /// The name and change of the value, for value entries.
+    value: Option<(&'a ValueName, &'a Operation<Value>)>,
+}
+
+impl<'a> DiffEntry<'a> {
+    /// Returns the full name of the key.
+    pub fn key_name(&self) -> &'a KeyName {
+        self.key.name()
+    }
+
+    /// Returns how the key itself changed.
+    pub fn change(&self) -> KeyChange {
+        self.key.change()
+    }
+
+    /// Returns all the changes of the key, including those of its other values.
+    pub fn key(&self) -> &'a KeyDiff {
+        self.key
+    }
+
+    /// Returns the name of the value, for value entries.
+    pub fn value_name(&self) -> Option<&'a ValueName> {
+        self.value.map(|(name, _)| name)
+    }
+
+    /// Returns the change of the value, for value entries.
+    pub fn operation(&self) -> Option<&'a Operation<Value>> {
+        self.value.map(|(_, operation)| operation)
+    }
+
+    /// Returns the stable identifier of the entry.
+    pub fn id(&self) -> EntryId {
+        // FNV-1a, whose output doesn't depend on the Rust version, unlike the hashers of std.
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut write = |bytes: &[u8]| {
+            for &byte in bytes.iter().chain(&[0]) {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        write(self.key_name().raw().to_lowercase().as_bytes());
+        match self.value {
+            None => write(match self.change() {
+                KeyChange::Added => b"added",
+                KeyChange::Deleted => b"deleted",
+                KeyChange::Modified => b"modified",
+                KeyChange::Unchanged => b"unchanged",
+            }),
+            Some((name, operation)) => {
+                match name {
+                    ValueName::Default => write(b"@"),
+                    ValueName::Named(name) => write(format!("\"{}\"", name).as_bytes()),
+                }
+                write(match operation {
+                    Operation::Unchanged => b"unchanged",
+                    Operation::Add { .. } => b"add",
+                    Operation::Delete { .. } => b"delete",
+                    Operation::Modify { .. } => b"modify",
+                    Operation::Volatile { .. } => b"volatile",
+                    Operation::Reordered { .. } => b"reordered",
+                });
+            }
+        }
+        EntryId(hash)
+    }
+}
+
+impl RegistryDiff {
+    /// Iterates over the changes of the diff: for each changed key, the entry of the key
+    /// itself followed by the entries of its changed values.
+    pub fn entries(&self) -> impl Iterator<Item = DiffEntry<'_>> {
+        self.keys()
+            .values()
+            .filter(|key| key.change() != KeyChange::Unchanged)
+            .flat_map(|key| {
+                let values = key
+                    .values()
+                    .iter()
+                    .filter(|(_, operation)| **operation != Operation::Unchanged)
+                    .map(move |value| DiffEntry {
+                        key,
+                        value: Some(value),
+                    });
+                std::iter::once(DiffEntry { key, value: None }).chain(values)
+            })
+    }
+
+    /// Returns the entry, as listed by [RegistryDiff::entries], with the given identifier.
+    pub fn entry(&self, id: EntryId) -> Option<DiffEntry<'_>> {
+        self.entries().find(|entry| entry.id() == id)
+    }
+
+    /// Returns the diff keeping only the entries, as listed by [RegistryDiff::entries], that
+    /// satisfy `predicate`, so only approved changes get applied.
+    ///
+    /// Rejecting the entry of a key drops all of its changes. A key left without changed
+    /// values nor security or link changes is dropped unless it is added or deleted.
+    /// Unchanged keys are dropped.
+    pub fn filtered<F: FnMut(&DiffEntry) -> bool>(&self, mut predicate: F) -> Self {
+        let mut keys = Vec::new();
+        for key in self.keys().values() {
+            if key.change() == KeyChange::Unchanged || !predicate(&DiffEntry { key, value: None }) {
+                continue;
+            }
+            let mut values = ValueMap::default();
+            for value in key.values() {
+                let entry = DiffEntry {
+                    key,
+                    value: Some(value),
+                };
+                if *value.1 != Operation::Unchanged && predicate(&entry) {
+                    values.insert(value.0.clone(), value.1.clone());
+                }
+            }
+            let empty = values.is_empty() && key.security().is_none() && key.link().is_none();
+            if key.change() == KeyChange::Modified && empty {
+                continue;
+            }
+            keys.push(KeyDiff::from_parts(
+                key.name().clone(),
+                key.change(),
+                values,
+                key.security().cloned(),
+                key.link().cloned(),
+            ));
+        }
+        keys.into_iter()
+            .collect::<RegistryDiff>()
+            .with_metadata(self.metadata().clone())
+    }
+}
+
+impl Registry {
+    /// Applies the changes of a diff to the keys of `selected` only, like [Registry::apply].
+    ///
+    /// # Arguments
+    ///
+    /// * `diff` - The changes to pick from.
+    /// * `selected` - The full names of the keys whose changes are applied, matched
+    ///   case-insensitively.
+    ///
+    /// # Returns
+    ///
+    /// The undo diff of the applied changes.
+    pub fn apply_selected(
+        &mut self,
+        diff: &RegistryDiff,
+        selected: &BTreeSet<KeyName>,
+    ) -> RegistryDiff {
+        let selected: BTreeSet<String> = selected
+            .iter()
+            .map(|name| name.raw().to_lowercase())
+            .collect();
+        self.apply(
+            &diff.filtered(|entry| selected.contains(&entry.key_name().raw().to_lowercase())),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+    use crate::prelude::{DiffOptions, Hive};
+
+    #[test]
+    fn test_filtered_and_apply_selected() {
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        assert!(diff.entries().count() > diff.keys().len());
+
+        assert_eq!(diff.filtered(|_| true), diff);
+        assert!(diff.filtered(|_| false).is_empty());
+        let keys_only = diff.filtered(|entry| entry.value_name().is_none());
+        assert!(keys_only
+            .entries()
+            .all(|entry| entry.value_name().is_none()));
+        assert!(keys_only.keys().len() < diff.keys().len());
+
+        let name = diff.keys().keys().next().unwrap().clone();
+        let mut registry = old.clone();
+        registry.apply_selected(&diff, &BTreeSet::from([name.clone()]));
+        let remaining = RegistryDiff::new(&registry, &new, &DiffOptions::new());
+        assert!(remaining.key(&name).is_none());
+        assert_eq!(remaining.keys().len(), diff.keys().len() - 1);
+    }
+
+    #[test]
+    fn test_entry_ids_are_stable() {
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        let entry = diff.entries().nth(1).unwrap();
+        assert_eq!(diff.entry(entry.id()), Some(entry));
+        assert_eq!(EntryId::from_hex(&entry.id().to_string()), Some(entry.id()));
+        let again = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        let ids: Vec<EntryId> = diff.entries().map(|entry| entry.id()).collect();
+        assert_eq!(again.entries().map(|e| e.id()).collect::<Vec<_>>(), ids);
+        assert_eq!(ids.iter().collect::<BTreeSet<_>>().len(), ids.len());
+    }
+}
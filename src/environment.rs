@@ -0,0 +1,250 @@
+use crate::prelude::{KeyPattern, Operation, RegistryDiff, Value};
+use regashii::{KeyName, ValueName};
+
+/// The keys holding environment variables, for the user, the default user and the system.
+const ENVIRONMENT_KEYS: [&str; 3] = [
+    "HKEY_CURRENT_USER\\Environment",
+    "HKEY_USERS\\.Default\\Environment",
+    "HKEY_LOCAL_MACHINE\\System\\CurrentControlSet\\Control\\Session Manager\\Environment",
+];
+
+/// Variables holding `;`-separated lists, even when they hold a single entry.
+const LIST_VARIABLES: [&str; 6] = [
+    "PATH",
+    "PATHEXT",
+    "PSModulePath",
+    "INCLUDE",
+    "LIB",
+    "LIBPATH",
+];
+
+/// A change to an environment variable.
+///
+/// For list variables such as `PATH`, the entries added and removed are reported in addition
+/// to the whole data, comparing entries case-insensitively like Windows paths.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvironmentChange {
+    /// The full name of the key holding the variable.
+    key: KeyName,
+    /// The name of the variable.
+    variable: String,
+    /// The data before the change, or `None` if the variable was added.
+    before: Option<String>,
+    /// The data after the change, or `None` if the variable was deleted.
+    after: Option<String>,
+    /// The list entries added, in order.
+    added: Vec<String>,
+    /// The list entries removed, in order.
+    removed: Vec<String>,
+}
+
+impl EnvironmentChange {
+    /// Returns the full name of the key holding the variable.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the name of the variable.
+    pub fn variable(&self) -> &str {
+        &self.variable
+    }
+
+    /// Returns the data before the change, or `None` if the variable was added.
+    pub fn before(&self) -> Option<&str> {
+        self.before.as_deref()
+    }
+
+    /// Returns the data after the change, or `None` if the variable was deleted.
+    pub fn after(&self) -> Option<&str> {
+        self.after.as_deref()
+    }
+
+    /// Returns whether the variable holds a `;`-separated list.
+    pub fn is_list(&self) -> bool {
+        is_list(&self.variable, self.before.as_deref())
+            || is_list(&self.variable, self.after.as_deref())
+    }
+
+    /// Returns the list entries added, in order. Empty unless [EnvironmentChange::is_list].
+    pub fn added(&self) -> &[String] {
+        &self.added
+    }
+
+    /// Returns the list entries removed, in order. Empty unless [EnvironmentChange::is_list].
+    pub fn removed(&self) -> &[String] {
+        &self.removed
+    }
+
+    /// Returns whether a list only had its entries reordered.
+    pub fn is_reordered(&self) -> bool {
+        self.is_list() && self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl std::fmt::Display for EnvironmentChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if !self.is_list() || self.before.is_none() || self.after.is_none() {
+            return write!(
+                f,
+                "{}: {} -> {}",
+                self.variable,
+                self.before.as_deref().unwrap_or("(unset)"),
+                self.after.as_deref().unwrap_or("(unset)")
+            );
+        }
+        if self.is_reordered() {
+            return write!(f, "{}: reordered", self.variable);
+        }
+
+        let entries: Vec<String> = self
+            .added
+            .iter()
+            .map(|entry| format!("+{}", entry))
+            .chain(self.removed.iter().map(|entry| format!("-{}", entry)))
+            .collect();
+        write!(f, "{}: {}", self.variable, entries.join(" "))
+    }
+}
+
+/// Returns whether a variable holds a `;`-separated list.
+fn is_list(variable: &str, data: Option<&str>) -> bool {
+    LIST_VARIABLES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(variable))
+        || data.is_some_and(|data| data.contains(';'))
+}
+
+/// Splits a list variable into its non-empty entries.
+fn entries(data: Option<&str>) -> Vec<&str> {
+    data.unwrap_or_default()
+        .split(';')
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+/// Returns the data of a string value.
+fn data(value: &Value) -> Option<String> {
+    match value.value() {
+        regashii::Value::Sz(data) | regashii::Value::ExpandSz(data) => Some(data.clone()),
+        _ => None,
+    }
+}
+
+impl RegistryDiff {
+    /// Returns the changes to environment variables, of the user, the default user and the
+    /// system.
+    pub fn environment(&self) -> Vec<EnvironmentChange> {
+        let patterns = ENVIRONMENT_KEYS.map(KeyPattern::new);
+        let mut changes = Vec::new();
+
+        for key in self.keys().values() {
+            if !patterns.iter().any(|pattern| pattern.matches(key.name())) {
+                continue;
+            }
+
+            for (name, operation) in key.values() {
+                let variable = match name {
+                    ValueName::Named(variable) => variable.clone(),
+                    ValueName::Default => continue,
+                };
+                let (before, after) = match operation {
+                    Operation::Add { data: new } => (None, data(new)),
+                    Operation::Delete { data: old } => (data(old), None),
+                    Operation::Modify { old_data, new_data }
+                    | Operation::Volatile { old_data, new_data }
+                    | Operation::Reordered { old_data, new_data } => {
+                        (data(old_data), data(new_data))
+                    }
+                    Operation::Unchanged => continue,
+                };
+                if before == after {
+                    continue;
+                }
+
+                let (mut added, mut removed) = (Vec::new(), Vec::new());
+                if is_list(&variable, before.as_deref()) || is_list(&variable, after.as_deref()) {
+                    let old = entries(before.as_deref());
+                    let new = entries(after.as_deref());
+                    let contains = |list: &[&str], entry: &str| {
+                        list.iter().any(|other| other.eq_ignore_ascii_case(entry))
+                    };
+                    added.extend(
+                        new.iter()
+                            .filter(|entry| !contains(&old, entry))
+                            .map(|entry| entry.to_string()),
+                    );
+                    removed.extend(
+                        old.iter()
+                            .filter(|entry| !contains(&new, entry))
+                            .map(|entry| entry.to_string()),
+                    );
+                }
+
+                changes.push(EnvironmentChange {
+                    key: key.name().clone(),
+                    variable,
+                    before,
+                    after,
+                    added,
+                    removed,
+                });
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RegistryBuilder;
+    use crate::prelude::{DiffOptions, Hive, Registry};
+
+    fn registry(variables: &[(&str, &str)]) -> Registry {
+        variables
+            .iter()
+            .fold(
+                RegistryBuilder::new(Hive::CurrentUser),
+                |builder, (name, data)| {
+                    builder.with(
+                        "Environment",
+                        ValueName::named(*name),
+                        regashii::Value::ExpandSz(data.to_string()),
+                    )
+                },
+            )
+            .build()
+    }
+
+    #[test]
+    fn test_environment_changes() {
+        let old = registry(&[
+            ("PATH", "C:\\windows;C:\\windows\\system32;C:\\Old"),
+            ("PATHEXT", ".COM;.EXE"),
+            ("TEMP", "C:\\temp"),
+        ]);
+        let new = registry(&[
+            ("PATH", "c:\\windows;C:\\windows\\system32;C:\\Tools"),
+            ("PATHEXT", ".EXE;.COM"),
+            ("GAME_DIR", "C:\\Games"),
+        ]);
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+
+        let mut changes: Vec<String> = diff
+            .environment()
+            .iter()
+            .map(EnvironmentChange::to_string)
+            .collect();
+        changes.sort();
+        assert_eq!(
+            changes,
+            vec![
+                "GAME_DIR: (unset) -> C:\\Games",
+                "PATH: +C:\\Tools -C:\\Old",
+                "PATHEXT: reordered",
+                "TEMP: C:\\temp -> (unset)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use crate::prelude::Encoding;
+use regashii::KeyName;
+
+/// The error type for loading registries and applying changes.
+#[derive(Debug)]
+pub enum Error {
+    /// The registry file couldn't be read.
+    Io(std::io::Error),
+    /// The registry file isn't valid text in the given encoding.
+    Encoding(Encoding),
+    /// The registry file contains malformed data.
+    Parse {
+        /// The 1-based line number of the malformed data. For values continued over several
+        /// lines, this is the line holding the malformed part.
+        line: usize,
+        /// The 1-based column of the malformed data, counted in characters.
+        column: usize,
+        /// The malformed text.
+        text: String,
+        /// A description of the problem.
+        message: String,
+    },
+    /// A key is defined more than once while [DuplicateKeyPolicy::Error](crate::prelude::DuplicateKeyPolicy::Error)
+    /// was requested.
+    DuplicateKey {
+        /// The name of the key, as written in the file.
+        name: KeyName,
+        /// The 1-based line number of the second definition.
+        line: usize,
+    },
+    /// A change of a diff is blocked by a [Policy](crate::prelude::Policy).
+    Blocked {
+        /// The full name of the changed key.
+        name: KeyName,
+        /// Why the change is blocked.
+        reason: String,
+    },
+    /// A binary diff is malformed or of an unsupported version.
+    Binary {
+        /// The offset of the malformed data, in bytes.
+        offset: usize,
+        /// A description of the problem.
+        message: String,
+    },
+    /// A name can't be represented in a registry file.
+    InvalidName {
+        /// The offending name or name component.
+        name: String,
+        /// Why the name can't be represented.
+        message: String,
+    },
+    /// A patch template uses a variable missing from the expansion map.
+    UndefinedVariable {
+        /// The name of the variable, without `${` and `}`.
+        name: String,
+        /// The full name of the key using it.
+        key: KeyName,
+    },
+    /// A [Template](crate::prelude::Template) declares a malformed parameter, or is
+    /// instantiated with invalid parameter values.
+    Template {
+        /// A description of the problem.
+        message: String,
+    },
+    /// A selection of [Catalog](crate::prelude::Catalog) patches can't be resolved.
+    Catalog {
+        /// The name of the offending patch.
+        name: String,
+        /// Why the selection can't be resolved.
+        message: String,
+    },
+    /// A Wine prefix stayed busy for longer than a [LockPolicy](crate::prelude::LockPolicy)
+    /// allows.
+    #[cfg(feature = "fs")]
+    Locked {
+        /// The path of the prefix directory.
+        path: std::path::PathBuf,
+        /// Why the prefix is busy.
+        reason: String,
+    },
+    /// A registry file changed since it was loaded by a
+    /// [RegistryFile](crate::prelude::RegistryFile), so writing it back would lose changes.
+    #[cfg(feature = "fs")]
+    StaleRegistry {
+        /// The path of the file.
+        path: std::path::PathBuf,
+    },
+    /// A TOML or YAML representation of a registry is malformed.
+    #[cfg(any(feature = "toml", feature = "yaml"))]
+    Structured {
+        /// The format of the representation, `TOML` or `YAML`.
+        format: &'static str,
+        /// A description of the problem.
+        message: String,
+    },
+    /// A signed patch failed verification.
+    #[cfg(feature = "signing")]
+    Signature {
+        /// Why the patch was rejected.
+        message: String,
+    },
+    /// A file of ignore decisions holds invalid JSON.
+    #[cfg(feature = "ignore")]
+    Ignore {
+        /// A description of the problem.
+        message: String,
+    },
+    /// A snapshot store is missing a requested snapshot or holds corrupt data.
+    #[cfg(feature = "store")]
+    Store {
+        /// A description of the problem.
+        message: String,
+    },
+    /// `wine regedit` failed to import a patch.
+    #[cfg(feature = "wine")]
+    Wine {
+        /// The exit code of regedit, if it exited normally.
+        status: Option<i32>,
+        /// What regedit wrote to its standard error.
+        stderr: String,
+    },
+    /// A key of an imported patch isn't reflected by the prefix's registry afterwards.
+    #[cfg(feature = "wine")]
+    NotApplied {
+        /// The full name of the key.
+        name: KeyName,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "failed to read registry file: {}", error),
+            Error::Encoding(encoding) => {
+                write!(f, "registry file is not valid {} text", encoding)
+            }
+            Error::Parse {
+                line,
+                column,
+                text,
+                message,
+            } => write!(
+                f,
+                "line {}, column {}: {} near '{}'",
+                line, column, message, text
+            ),
+            Error::DuplicateKey { name, line } => {
+                write!(
+                    f,
+                    "line {}: key '{}' is defined more than once",
+                    line,
+                    name.raw()
+                )
+            }
+            Error::Blocked { name, reason } => {
+                write!(f, "change to key '{}' is blocked: {}", name.raw(), reason)
+            }
+            Error::Binary { offset, message } => {
+                write!(f, "invalid binary diff at byte {}: {}", offset, message)
+            }
+            Error::InvalidName { name, message } => {
+                write!(f, "invalid name '{}': {}", name, message)
+            }
+            Error::UndefinedVariable { name, key } => write!(
+                f,
+                "variable '${{{}}}' used in key '{}' is not defined",
+                name,
+                key.raw()
+            ),
+            Error::Template { message } => write!(f, "invalid template: {}", message),
+            Error::Catalog { name, message } => write!(f, "catalog patch '{}' {}", name, message),
+            #[cfg(feature = "fs")]
+            Error::Locked { path, reason } => {
+                write!(f, "prefix '{}' is busy: {}", path.display(), reason)
+            }
+            #[cfg(feature = "fs")]
+            Error::StaleRegistry { path } => write!(
+                f,
+                "registry file '{}' changed since it was loaded",
+                path.display()
+            ),
+            #[cfg(any(feature = "toml", feature = "yaml"))]
+            Error::Structured { format, message } => {
+                write!(f, "invalid {} registry: {}", format, message)
+            }
+            #[cfg(feature = "signing")]
+            Error::Signature { message } => {
+                write!(f, "patch signature verification failed: {}", message)
+            }
+            #[cfg(feature = "ignore")]
+            Error::Ignore { message } => write!(f, "invalid ignore decisions: {}", message),
+            #[cfg(feature = "store")]
+            Error::Store { message } => write!(f, "snapshot store error: {}", message),
+            #[cfg(feature = "wine")]
+            Error::Wine { status, stderr } => match status {
+                Some(status) => write!(f, "wine regedit exited with {}: {}", status, stderr),
+                None => write!(f, "wine regedit was terminated: {}", stderr),
+            },
+            #[cfg(feature = "wine")]
+            Error::NotApplied { name } => {
+                write!(f, "key '{}' was not applied to the prefix", name.raw())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
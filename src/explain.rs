@@ -0,0 +1,235 @@
+use crate::prelude::{DiffOptions, Key, KeyChange, KeyDiff, Operation, Value, ValueKind};
+use regashii::ValueName;
+
+impl Key {
+    /// Explains what changed between two versions of a key, one line per change, for
+    /// tooltips and detailed views.
+    ///
+    /// See [Key::explain_diff_with] for details.
+    pub fn explain_diff(old: Option<&Key>, new: Option<&Key>) -> Vec<String> {
+        Key::explain_diff_with(old, new, &DiffOptions::default())
+    }
+
+    /// Explains what changed between two versions of a key, comparing them according to
+    /// `options`.
+    ///
+    /// The first line tells whether the key was added, deleted or modified. It is followed by
+    /// a line per changed value, then by indented lines detailing the change where the data
+    /// allows it: a changed type, the entries added to or removed from a REG_MULTI_SZ list, or
+    /// the bytes that differ in binary data of the same length. Changes of the security
+    /// descriptor and link target come last.
+    ///
+    /// # Returns
+    ///
+    /// The lines of the explanation, empty if the key didn't change.
+    pub fn explain_diff_with(
+        old: Option<&Key>,
+        new: Option<&Key>,
+        options: &DiffOptions,
+    ) -> Vec<String> {
+        let diff = match KeyDiff::new(old, new, options) {
+            Some(diff) if diff.change() != KeyChange::Unchanged => diff,
+            _ => return Vec::new(),
+        };
+
+        let name = diff.name().raw();
+        let mut lines = vec![match diff.change() {
+            KeyChange::Added => format!("key {} was added", name),
+            KeyChange::Deleted => format!("key {} was deleted", name),
+            KeyChange::Modified | KeyChange::Unchanged => format!("key {} was modified", name),
+        }];
+
+        for (name, operation) in diff.values() {
+            let name = value_name(name);
+            match operation {
+                Operation::Unchanged => {}
+                Operation::Add { data } => {
+                    lines.push(format!("{} was added: {}", name, describe(data)))
+                }
+                Operation::Delete { data } => {
+                    lines.push(format!("{} was deleted, it was {}", name, describe(data)))
+                }
+                Operation::Modify { old_data, new_data } => {
+                    lines.push(format!(
+                        "{} changed from {} to {}",
+                        name,
+                        describe(old_data),
+                        describe(new_data)
+                    ));
+                    details(&mut lines, old_data, new_data);
+                }
+                Operation::Volatile { old_data, new_data } => lines.push(format!(
+                    "{} changed from {} to {}, which is usually noise",
+                    name,
+                    describe(old_data),
+                    describe(new_data)
+                )),
+                Operation::Reordered { .. } => {
+                    lines.push(format!("{} only had its strings reordered", name))
+                }
+            }
+        }
+
+        match diff.security() {
+            Some(Operation::Add { .. }) => lines.push("a security descriptor was added".into()),
+            Some(Operation::Delete { .. }) => {
+                lines.push("the security descriptor was removed".into())
+            }
+            Some(_) => lines.push("the security descriptor changed".into()),
+            None => {}
+        }
+        match diff.link() {
+            Some(Operation::Add { data }) => lines.push(format!("the key now links to {}", data)),
+            Some(Operation::Delete { data }) => {
+                lines.push(format!("the key no longer links to {}", data))
+            }
+            Some(Operation::Modify { old_data, new_data })
+            | Some(Operation::Volatile { old_data, new_data })
+            | Some(Operation::Reordered { old_data, new_data }) => lines.push(format!(
+                "the link target changed from {} to {}",
+                old_data, new_data
+            )),
+            Some(Operation::Unchanged) | None => {}
+        }
+        lines
+    }
+}
+
+/// Names a value for an explanation.
+fn value_name(name: &ValueName) -> String {
+    match name {
+        ValueName::Default => "the default value".to_string(),
+        ValueName::Named(name) => format!("value {:?}", name),
+    }
+}
+
+/// Describes the data of a value in a readable form, unlike the hex data of `.reg` files.
+fn describe(value: &Value) -> String {
+    if let (Some(raw_type), regashii::Value::Binary(bytes)) = (value.raw_type(), value.value()) {
+        return format!(
+            "{:?} data {}",
+            ValueKind::from_type(raw_type),
+            bytes_text(bytes)
+        );
+    }
+    match value.value() {
+        regashii::Value::Sz(string) => format!("{:?}", string),
+        regashii::Value::ExpandSz(string) => format!("{:?} (expandable)", string),
+        regashii::Value::Dword(dword) => format!("{} (0x{:08x})", dword, dword),
+        regashii::Value::Qword(qword) => format!("{} (0x{:016x})", qword, qword),
+        regashii::Value::MultiSz(strings) => format!("{:?}", strings),
+        regashii::Value::Binary(bytes) => bytes_text(bytes),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Formats bytes as space-separated hex pairs.
+fn bytes_text(bytes: &[u8]) -> String {
+    match bytes.is_empty() {
+        true => "no bytes".to_string(),
+        false => {
+            let pairs: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            pairs.join(" ")
+        }
+    }
+}
+
+/// Appends the indented lines detailing a modification.
+fn details(lines: &mut Vec<String>, old: &Value, new: &Value) {
+    if old.kind() != new.kind() {
+        lines.push(format!(
+            "  the type changed from {:?} to {:?}",
+            old.kind(),
+            new.kind()
+        ));
+        return;
+    }
+
+    match (old.value(), new.value()) {
+        (regashii::Value::MultiSz(old), regashii::Value::MultiSz(new)) => {
+            for entry in new.iter().filter(|entry| !old.contains(entry)) {
+                lines.push(format!("  entry {:?} was added", entry));
+            }
+            for entry in old.iter().filter(|entry| !new.contains(entry)) {
+                lines.push(format!("  entry {:?} was removed", entry));
+            }
+            if old.len() == new.len() && old.iter().all(|entry| new.contains(entry)) {
+                lines.push("  the entries were reordered".to_string());
+            }
+        }
+        (regashii::Value::Binary(old), regashii::Value::Binary(new)) if old.len() == new.len() => {
+            let offsets: Vec<usize> = (0..old.len()).filter(|&i| old[i] != new[i]).collect();
+            if let Some(first) = offsets.first() {
+                lines.push(format!(
+                    "  {} of {} bytes differ, starting at offset {}",
+                    offsets.len(),
+                    old.len(),
+                    first
+                ));
+            }
+        }
+        (regashii::Value::Binary(old), regashii::Value::Binary(new)) => lines.push(format!(
+            "  the length changed from {} to {} bytes",
+            old.len(),
+            new.len()
+        )),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{Hive, Registry};
+    use regashii::KeyName;
+
+    fn key(values: Vec<(&str, regashii::Value)>) -> Key {
+        let mut key = regashii::Key::new();
+        for (name, value) in values {
+            key = key.with(ValueName::named(name), value);
+        }
+        let registry = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4).with(KeyName::new("App"), key),
+            Hive::CurrentUser,
+        );
+        registry.key(&KeyName::new("App")).unwrap().clone()
+    }
+
+    #[test]
+    fn test_explain_diff() {
+        let paths = |last: &str| regashii::Value::MultiSz(vec!["a".into(), last.into()]);
+        let old = key(vec![
+            ("Blob", regashii::Value::Binary(vec![0, 1, 2, 3])),
+            ("Gone", regashii::Value::Sz("x".into())),
+            ("Level", regashii::Value::Dword(1)),
+            ("Paths", paths("b")),
+        ]);
+        let new = key(vec![
+            ("Blob", regashii::Value::Binary(vec![0, 9, 2, 9])),
+            ("Level", regashii::Value::Sz("1".into())),
+            ("Paths", paths("c")),
+            ("Size", regashii::Value::Dword(16)),
+        ]);
+
+        assert_eq!(
+            Key::explain_diff(Some(&old), Some(&new)),
+            [
+                "key HKEY_CURRENT_USER\\App was modified",
+                "value \"Blob\" changed from 00 01 02 03 to 00 09 02 09",
+                "  2 of 4 bytes differ, starting at offset 1",
+                "value \"Gone\" was deleted, it was \"x\"",
+                "value \"Level\" changed from 1 (0x00000001) to \"1\"",
+                "  the type changed from Dword to Sz",
+                "value \"Paths\" changed from [\"a\", \"b\"] to [\"a\", \"c\"]",
+                "  entry \"c\" was added",
+                "  entry \"b\" was removed",
+                "value \"Size\" was added: 16 (0x00000010)",
+            ]
+        );
+        assert!(Key::explain_diff(Some(&old), Some(&old)).is_empty());
+        assert_eq!(
+            Key::explain_diff(None, Some(&new))[0],
+            "key HKEY_CURRENT_USER\\App was added"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use crate::atomic;
+use crate::parse::{self, Syntax};
+use crate::prelude::{
+    Conflict, DiffOptions, Encoding, Error, Hive, LoadOptions, Registry, RegistryDiff,
+};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A registry loaded from a file to be changed and written back, remembering the file as it
+/// was loaded so changes made to it meanwhile by other programs aren't overwritten.
+#[derive(Clone, Debug)]
+pub struct RegistryFile {
+    /// The path of the file.
+    path: PathBuf,
+    /// How the file is loaded.
+    options: LoadOptions,
+    /// The encoding of the file, which it is written back in.
+    encoding: Encoding,
+    /// The syntax of the file, as loaded.
+    syntax: Syntax,
+    /// When the file was last modified, as loaded.
+    modified: Option<SystemTime>,
+    /// The hash of the content of the file, as loaded.
+    hash: u64,
+    /// The registry as loaded.
+    base: Registry,
+    /// The registry with the changes made since it was loaded.
+    registry: Registry,
+}
+
+impl RegistryFile {
+    /// Loads a registry file to change it.
+    ///
+    /// See [RegistryFile::open_with] for details.
+    pub fn open<T: AsRef<Path>>(path: T, hive: Hive) -> Result<Self, Error> {
+        Self::open_with(path, hive, &LoadOptions::default())
+    }
+
+    /// Loads a registry file according to `options`, recording when it was last modified and
+    /// the hash of its content.
+    ///
+    /// The registry is kept as loaded as well, to rebase the changes made to it, so this
+    /// takes twice the memory of [Registry::open].
+    ///
+    /// # Returns
+    ///
+    /// The loaded file, or an [Error] if it can't be read or parsed.
+    pub fn open_with<T: AsRef<Path>>(
+        path: T,
+        hive: Hive,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let (bytes, modified) = read(&path)?;
+        let encoding = options
+            .encoding()
+            .unwrap_or_else(|| Encoding::detect(&bytes));
+        let text = encoding.decode_borrowed(&bytes)?;
+        let document = parse::parse(&text, options.strict())?;
+        let syntax = document.syntax;
+        let registry = Registry::from_document(document, hive, options)?;
+        Ok(Self {
+            path,
+            options: options.clone(),
+            encoding,
+            syntax,
+            modified,
+            hash: hash(&bytes),
+            base: registry.clone(),
+            registry,
+        })
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns when the file was last modified as it was loaded, if the platform records it.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Returns the encoding of the file, which it is written back in.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Returns whether the file is one of Wine's own registry files, which [RegistryFile::save]
+    /// would rewrite in a syntax Wine doesn't load.
+    pub fn is_wine(&self) -> bool {
+        self.syntax == Syntax::Wine
+    }
+
+    /// Returns the registry, with the changes made since it was loaded.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Returns the registry to change it, e.g. with [Registry::apply].
+    pub fn registry_mut(&mut self) -> &mut Registry {
+        &mut self.registry
+    }
+
+    /// Returns the changes made to the registry since it was loaded, or last saved.
+    pub fn changes(&self) -> RegistryDiff {
+        RegistryDiff::new(
+            &self.base,
+            &self.registry,
+            &DiffOptions::new().with_security(true),
+        )
+    }
+
+    /// Returns whether the content of the file changed since it was loaded, or last saved,
+    /// including if it was deleted. Its content is hashed again rather than trusting its
+    /// modification time, which can miss writes made within the same clock tick.
+    pub fn is_stale(&self) -> Result<bool, Error> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => Ok(hash(&bytes) != self.hash),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(true),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Writes the registry back to the file, in the syntax of [Registry::serialize] and the
+    /// encoding it was loaded in, replacing it atomically.
+    ///
+    /// Wine only loads its own registry files in their own syntax, so the files of prefixes
+    /// must be changed with `apply_with_wine` instead.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` once written, or [Error::StaleRegistry] if the file changed since it was loaded,
+    /// in which case nothing is written and [RegistryFile::rebase] can bring the changes onto
+    /// its new content. Registries that can't be written without losing data, such as names
+    /// holding line breaks, fail with an [Error] and leave the file as it is.
+    pub fn save(&mut self) -> Result<(), Error> {
+        if self.is_stale()? {
+            return Err(Error::StaleRegistry {
+                path: self.path.clone(),
+            });
+        }
+        let bytes = self.encoding.encode(&self.registry.serialize()?);
+        atomic::write(&self.path, &bytes)?;
+        self.modified = std::fs::metadata(&self.path)?.modified().ok();
+        self.hash = hash(&bytes);
+        self.syntax = Syntax::Regedit5;
+        self.base = self.registry.clone();
+        Ok(())
+    }
+
+    /// Loads the file again and applies the changes made to the registry since it was loaded
+    /// on top of its new content with [RegistryDiff::rebase], so it can be saved without
+    /// losing the changes made by other programs. Conflicting changes win over the other
+    /// programs'.
+    ///
+    /// # Returns
+    ///
+    /// The rebased changes and their conflicts, or an [Error] if the file can't be loaded
+    /// anymore, in which case the registry is left as it is.
+    pub fn rebase(&mut self) -> Result<(RegistryDiff, Vec<Conflict>), Error> {
+        let mut reloaded = Self::open_with(&self.path, self.registry.hive(), &self.options)?;
+        let (changes, conflicts) = self.changes().rebase(&reloaded.registry);
+        reloaded.registry.apply(&changes);
+        *self = reloaded;
+        Ok((changes, conflicts))
+    }
+}
+
+/// Reads a file along with its modification time.
+fn read(path: &Path) -> Result<(Vec<u8>, Option<SystemTime>), Error> {
+    let file = std::fs::File::open(path)?;
+    let modified = file.metadata()?.modified().ok();
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut &file, &mut bytes)?;
+    Ok((bytes, modified))
+}
+
+/// Hashes the content of a file with FNV-1a.
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regashii::{KeyName, ValueName};
+
+    #[test]
+    fn test_stale_registry_file() {
+        let dir = std::env::temp_dir().join(format!("regdiff-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.reg");
+        std::fs::write(&path, "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=\"a\"\n").unwrap();
+
+        let mut file = RegistryFile::open(&path, Hive::CurrentUser).unwrap();
+        let patch = "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"B\"=\"b\"\n";
+        let diff = RegistryDiff::from_patch_str(patch, file.registry()).unwrap();
+        file.registry_mut().apply(&diff);
+        std::fs::write(&path, "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"C\"=\"c\"\n").unwrap();
+
+        let stale = file.save();
+        let (changes, conflicts) = file.rebase().unwrap();
+        file.save().unwrap();
+        let saved = Registry::open(&path, Hive::CurrentUser).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(stale, Err(Error::StaleRegistry { .. })));
+        assert_eq!(changes.keys().len(), 1);
+        assert!(conflicts.is_empty());
+        let key = saved.key(&KeyName::new("App")).unwrap();
+        let mut names: Vec<&ValueName> = key.values().keys().collect();
+        names.sort();
+        assert_eq!(names, [&ValueName::named("B"), &ValueName::named("C")]);
+    }
+
+    #[test]
+    fn test_save_keeps_the_file_when_the_registry_cant_be_written() {
+        let dir = std::env::temp_dir().join(format!("regdiff-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.reg");
+        let content = "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=hex(5):00,00,00,01\n";
+        std::fs::write(&path, content).unwrap();
+
+        let mut file = RegistryFile::open(&path, Hive::CurrentUser).unwrap();
+        file.save().unwrap();
+        let saved = Registry::open(&path, Hive::CurrentUser).unwrap();
+        let patch = "WINE REGISTRY Version 2\n\n[App]\n\"\\n\"=dword:1\n";
+        let diff = RegistryDiff::from_patch_str(patch, file.registry()).unwrap();
+        file.registry_mut().apply(&diff);
+        let failed = file.save();
+        let kept = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(saved.keys(), file.base.keys());
+        assert!(matches!(failed, Err(Error::InvalidName { .. })));
+        assert!(kept.contains("hex(5):00,00,00,01"));
+    }
+}
@@ -0,0 +1,58 @@
+use crate::prelude::{Hive, Registry};
+use regashii::{KeyName, ValueName};
+
+/// Builds the registries used by tests value by value.
+pub(crate) struct RegistryBuilder {
+    /// The hive the registry is built in.
+    hive: Hive,
+    /// The keys built so far, named relative to the hive.
+    registry: regashii::Registry,
+}
+
+impl RegistryBuilder {
+    /// Starts an empty registry in `hive`.
+    pub(crate) fn new(hive: Hive) -> Self {
+        Self {
+            hive,
+            registry: regashii::Registry::new(regashii::Format::Regedit4),
+        }
+    }
+
+    /// Returns the builder with a value added to the key named `key` relative to the hive,
+    /// creating the key if it doesn't exist yet.
+    pub(crate) fn with(self, key: &str, name: ValueName, data: regashii::Value) -> Self {
+        let key = KeyName::new(key);
+        let values = self
+            .registry
+            .keys()
+            .get(&key)
+            .cloned()
+            .unwrap_or_else(regashii::Key::new);
+        Self {
+            hive: self.hive,
+            registry: self.registry.with(key, values.with(name, data)),
+        }
+    }
+
+    /// Returns the builder with a string value added to the key named `key`.
+    pub(crate) fn with_sz(self, key: &str, name: &str, data: &str) -> Self {
+        let data = regashii::Value::Sz(data.to_string());
+        self.with(key, ValueName::named(name), data)
+    }
+
+    /// Returns the built registry.
+    pub(crate) fn build(self) -> Registry {
+        Registry::from_regashii(self.registry, self.hive)
+    }
+}
+
+/// Builds a registry of string values from `(key, value name, data)` entries, with keys named
+/// relative to `hive`.
+pub(crate) fn registry(hive: Hive, entries: &[(&str, &str, &str)]) -> Registry {
+    entries
+        .iter()
+        .fold(RegistryBuilder::new(hive), |builder, (key, name, data)| {
+            builder.with_sz(key, name, data)
+        })
+        .build()
+}
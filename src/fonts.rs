@@ -0,0 +1,158 @@
+use crate::prelude::{KeyPattern, Operation, Registry, RegistryDiff, Value};
+use regashii::ValueName;
+use std::collections::BTreeMap;
+
+/// The key holding Wine's font replacements.
+const REPLACEMENTS_KEY: &str = "*\\Software\\Wine\\Fonts\\Replacements";
+
+/// The fonts used in place of requested fonts, by requested font name.
+///
+/// Wine accepts a single replacement as a string, or several as a list tried in order.
+pub type FontReplacements = BTreeMap<String, Vec<String>>;
+
+/// Returns the replacement fonts held by a value, if it holds strings.
+fn replacements(value: &Value) -> Option<Vec<String>> {
+    match value.value() {
+        regashii::Value::Sz(font) => Some(vec![font.clone()]),
+        regashii::Value::MultiSz(fonts) => Some(fonts.clone()),
+        _ => None,
+    }
+}
+
+/// The changes made to Wine's font replacement table.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FontReplacementChanges {
+    /// The replacements added, by requested font.
+    added: FontReplacements,
+    /// The replacements removed, by requested font.
+    removed: FontReplacements,
+    /// The replacements changed, by requested font, with their old and new fonts.
+    changed: BTreeMap<String, (Vec<String>, Vec<String>)>,
+}
+
+impl FontReplacementChanges {
+    /// Returns the replacements added, by requested font.
+    pub fn added(&self) -> &FontReplacements {
+        &self.added
+    }
+
+    /// Returns the replacements removed, by requested font.
+    pub fn removed(&self) -> &FontReplacements {
+        &self.removed
+    }
+
+    /// Returns the replacements changed, by requested font, with their old and new fonts.
+    pub fn changed(&self) -> &BTreeMap<String, (Vec<String>, Vec<String>)> {
+        &self.changed
+    }
+
+    /// Returns whether the table is unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Registry {
+    /// Returns Wine's font replacement table, from the `Software\Wine\Fonts\Replacements` key.
+    ///
+    /// The table is empty if the key is missing, e.g. for registries of other hives.
+    pub fn font_replacements(&self) -> FontReplacements {
+        let pattern = KeyPattern::new(REPLACEMENTS_KEY);
+        self.keys()
+            .values()
+            .filter(|key| pattern.matches(key.name()))
+            .flat_map(|key| key.values().values())
+            .filter_map(|value| match value.name() {
+                ValueName::Named(font) => Some((font.clone(), replacements(value)?)),
+                ValueName::Default => None,
+            })
+            .collect()
+    }
+}
+
+impl RegistryDiff {
+    /// Returns the changes made to Wine's font replacement table.
+    pub fn font_replacements(&self) -> FontReplacementChanges {
+        let pattern = KeyPattern::new(REPLACEMENTS_KEY);
+        let mut changes = FontReplacementChanges::default();
+
+        for key in self.keys().values() {
+            if !pattern.matches(key.name()) {
+                continue;
+            }
+
+            for (name, operation) in key.values() {
+                let font = match name {
+                    ValueName::Named(font) => font.clone(),
+                    ValueName::Default => continue,
+                };
+                let (before, after) = match operation {
+                    Operation::Add { data } => (None, replacements(data)),
+                    Operation::Delete { data } => (replacements(data), None),
+                    Operation::Modify { old_data, new_data }
+                    | Operation::Volatile { old_data, new_data }
+                    | Operation::Reordered { old_data, new_data } => {
+                        (replacements(old_data), replacements(new_data))
+                    }
+                    Operation::Unchanged => continue,
+                };
+                match (before, after) {
+                    (None, Some(after)) => {
+                        changes.added.insert(font, after);
+                    }
+                    (Some(before), None) => {
+                        changes.removed.insert(font, before);
+                    }
+                    (Some(before), Some(after)) if before != after => {
+                        changes.changed.insert(font, (before, after));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+    use crate::prelude::{DiffOptions, Hive};
+    use regashii::KeyName;
+
+    #[test]
+    fn test_font_replacements() {
+        let user = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let table = user.font_replacements();
+        assert_eq!(
+            table["Arial Unicode MS"],
+            vec!["Droid Sans Fallback".to_string()]
+        );
+
+        let replacements = regashii::Key::new()
+            .with(
+                ValueName::named("Arial Unicode MS"),
+                regashii::Value::MultiSz(vec!["Noto Sans".to_string(), "Droid Sans".to_string()]),
+            )
+            .with(
+                ValueName::named("Tahoma"),
+                regashii::Value::Sz("DejaVu Sans".to_string()),
+            );
+        let edited = user.overlay(&Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4).with(
+                KeyName::new("Software\\Wine\\Fonts\\Replacements"),
+                replacements,
+            ),
+            Hive::CurrentUser,
+        ));
+        let changes = RegistryDiff::new(&user, &edited, &DiffOptions::new()).font_replacements();
+
+        assert_eq!(changes.added()["Tahoma"], vec!["DejaVu Sans".to_string()]);
+        assert!(changes.removed().is_empty());
+        assert_eq!(
+            changes.changed()["Arial Unicode MS"].1,
+            vec!["Noto Sans".to_string(), "Droid Sans".to_string()]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use crate::prelude::{Error, KeyChange, KeyDiff, KeyPattern, Registry, RegistryDiff};
+use regashii::KeyName;
+
+/// What [Registry::apply_frozen] does with changes to frozen keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrozenAction {
+    /// Fail without applying anything.
+    #[default]
+    Reject,
+    /// Apply the other changes, reporting the keys whose changes were skipped.
+    Skip,
+}
+
+/// Key paths protected from changes when applying patches, such as critical Wine
+/// configuration.
+///
+/// A key is frozen when a pattern matches it or one of its parents, so freezing
+/// `HKEY_CURRENT_USER\Software\Wine` protects its whole subtree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FreezeList {
+    /// The patterns of the frozen keys.
+    patterns: Vec<KeyPattern>,
+    /// What to do with changes to frozen keys.
+    action: FrozenAction,
+}
+
+impl FreezeList {
+    /// Constructs an empty [FreezeList], rejecting changes to frozen keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the list with the keys matching a pattern, and their subkeys, frozen.
+    pub fn with_pattern<T: Into<KeyPattern>>(mut self, pattern: T) -> Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    /// Sets what to do with changes to frozen keys.
+    pub fn with_action(mut self, action: FrozenAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Returns the patterns of the frozen keys.
+    pub fn patterns(&self) -> &[KeyPattern] {
+        &self.patterns
+    }
+
+    /// Returns what is done with changes to frozen keys.
+    pub fn action(&self) -> FrozenAction {
+        self.action
+    }
+
+    /// Returns whether a key is frozen, given its full name.
+    pub fn is_frozen(&self, name: &KeyName) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches_subtree(name))
+    }
+
+    /// Returns the first frozen key a change would alter in a registry: the changed key
+    /// itself, or for deletions any existing subkey.
+    fn touched<'a>(&self, change: &'a KeyDiff, registry: &'a Registry) -> Option<&'a KeyName> {
+        if change.change() == KeyChange::Unchanged {
+            return None;
+        }
+        if self.is_frozen(change.name()) {
+            return Some(change.name());
+        }
+        if change.change() != KeyChange::Deleted {
+            return None;
+        }
+        let subkeys = format!("{}\\", change.name().raw().to_lowercase());
+        registry
+            .keys()
+            .values()
+            .map(|key| key.name())
+            .filter(|name| name.raw().to_lowercase().starts_with(&subkeys))
+            .find(|name| self.is_frozen(name))
+    }
+}
+
+impl Registry {
+    /// Applies a diff like [Registry::apply], protecting the keys of a freeze list.
+    ///
+    /// Deleting a key alters its subkeys too, so it is a change to any frozen subkey.
+    ///
+    /// # Returns
+    ///
+    /// The undo diff and the full names of the keys whose changes were skipped with
+    /// [FrozenAction::Skip], or [Error::Blocked] without changing the registry with
+    /// [FrozenAction::Reject] if the diff changes a frozen key.
+    pub fn apply_frozen(
+        &mut self,
+        diff: &RegistryDiff,
+        freeze: &FreezeList,
+    ) -> Result<(RegistryDiff, Vec<KeyName>), Error> {
+        let mut skipped = Vec::new();
+        for change in diff.keys().values() {
+            let frozen = match freeze.touched(change, self) {
+                Some(frozen) => frozen,
+                None => continue,
+            };
+            if freeze.action == FrozenAction::Reject {
+                return Err(Error::Blocked {
+                    name: frozen.clone(),
+                    reason: "the key is frozen".to_string(),
+                });
+            }
+            skipped.push(change.name().clone());
+        }
+        let undo = if skipped.is_empty() {
+            self.apply(diff)
+        } else {
+            self.apply(&diff.filtered(|entry| !skipped.contains(entry.key_name())))
+        };
+        Ok((undo, skipped))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{DiffOptions, Hive};
+
+    #[test]
+    fn test_apply_frozen() {
+        let registry = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\Wine\\Direct3D]\n\"renderer\"=\"gl\"\n\n\
+            [HKEY_CURRENT_USER\\Software\\App]\n\"A\"=\"a\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let patch = |text: &str| {
+            RegistryDiff::from_patch_str(&format!("REGEDIT4\n\n{}\n", text), &registry).unwrap()
+        };
+        let freeze = FreezeList::new().with_pattern("HKEY_CURRENT_USER\\Software\\wine");
+        let tweak = patch(
+            "[HKEY_CURRENT_USER\\Software\\Wine\\Direct3D]\n\"renderer\"=\"vulkan\"\n\n\
+            [HKEY_CURRENT_USER\\Software\\App]\n\"A\"=\"b\"",
+        );
+
+        let mut frozen = registry.clone();
+        assert!(matches!(
+            frozen.apply_frozen(&tweak, &freeze),
+            Err(Error::Blocked { .. })
+        ));
+        assert!(matches!(
+            frozen.apply_frozen(&patch("[-HKEY_CURRENT_USER\\Software]"), &freeze),
+            Err(Error::Blocked { name, .. }) if name.raw().ends_with("Direct3D")
+        ));
+        assert!(RegistryDiff::new(&registry, &frozen, &DiffOptions::new()).is_empty());
+
+        let freeze = freeze.with_action(FrozenAction::Skip);
+        let (_, skipped) = frozen.apply_frozen(&tweak, &freeze).unwrap();
+        assert_eq!(skipped.len(), 1);
+        let changes = RegistryDiff::new(&registry, &frozen, &DiffOptions::new());
+        assert_eq!(changes.keys().len(), 1);
+        assert!(!freeze.is_frozen(changes.keys().values().next().unwrap().name()));
+    }
+}
@@ -0,0 +1,136 @@
+//! Entry points of the fuzz targets under `fuzz/`, taking arbitrary bytes and panicking when
+//! an invariant of the parsers or the diff engine breaks. Not part of the stable API.
+
+use crate::prelude::{Encoding, Hive, LoadOptions, Registry, RegistryDiff};
+
+/// The hives parsed input is loaded into.
+const HIVES: [Hive; 3] = [Hive::LocalMachine, Hive::CurrentUser, Hive::DefaultUser];
+
+/// The keys the operations of [apply] act on.
+const KEYS: [&str; 4] = ["App", "App\\Sub", "App\\Sub\\Deep", "Other"];
+
+/// The values the operations of [apply] act on, `@` being the default value.
+const VALUES: [&str; 3] = ["\"A\"", "\"B\"", "@"];
+
+/// Parses arbitrary bytes as a `.reg` file, in any encoding, and checks that whatever loads
+/// serializes to a file loading back into the same registry.
+pub fn parse(data: &[u8]) {
+    if let Ok(text) = Encoding::detect(data).decode(data) {
+        round_trip(&text);
+    }
+}
+
+/// Parses arbitrary bytes as the body of a Wine registry file, such as `user.reg`, like
+/// [parse] does.
+pub fn parse_wine(data: &[u8]) {
+    let text = String::from_utf8_lossy(data);
+    round_trip(&format!("WINE REGISTRY Version 2\n{}", text));
+}
+
+/// Decodes arbitrary bytes as a binary diff, and checks that whatever decodes encodes back
+/// into the same diff.
+pub fn binary(data: &[u8]) {
+    if let Ok(diff) = RegistryDiff::from_binary(data) {
+        let decoded =
+            RegistryDiff::from_binary(&diff.to_binary()).expect("encoded diff fails to decode");
+        assert_eq!(decoded.keys(), diff.keys());
+    }
+}
+
+/// Interprets arbitrary bytes as a sequence of patches, three bytes each, applied one after
+/// the other to a registry, and checks that every patch is undone by the diff
+/// [Registry::apply] returns and that applying it twice changes nothing more.
+///
+/// The first byte selects the operation: setting a string, dword or binary value, deleting
+/// a value or deleting a key. The second selects the key and the third the value and its
+/// data.
+pub fn apply(data: &[u8]) {
+    let mut registry = Registry::from_regashii(
+        regashii::Registry::new(regashii::Format::Regedit4),
+        Hive::CurrentUser,
+    );
+    for operation in data.chunks_exact(3) {
+        let key = format!(
+            "HKEY_CURRENT_USER\\{}",
+            KEYS[operation[1] as usize % KEYS.len()]
+        );
+        let value = VALUES[operation[2] as usize % VALUES.len()];
+        let line = match operation[0] % 5 {
+            0 => format!("[{}]\n{}=\"{}\"", key, value, operation[2]),
+            1 => format!("[{}]\n{}=dword:{:08x}", key, value, operation[2]),
+            2 => format!("[{}]\n{}=hex:{:02x}", key, value, operation[2]),
+            3 => format!("[{}]\n{}=-", key, value),
+            _ => format!("[-{}]", key),
+        };
+        let patch = format!("REGEDIT4\n\n{}\n", line);
+
+        let before = registry.clone();
+        let diff = RegistryDiff::from_patch_str(&patch, &registry)
+            .expect("generated patch fails to parse");
+        let undo = registry.apply(&diff);
+        let again = RegistryDiff::from_patch_str(&patch, &registry)
+            .expect("generated patch fails to parse");
+        assert!(again.is_empty(), "patch is not idempotent: {}", patch);
+
+        let mut undone = registry.clone();
+        undone.apply(&undo);
+        assert_eq!(
+            undone.keys(),
+            before.keys(),
+            "patch is not undone: {}",
+            patch
+        );
+    }
+}
+
+/// Loads text into every hive, strictly and leniently, checking the serialized registries.
+fn round_trip(text: &str) {
+    for strict in [true, false] {
+        let options = LoadOptions::new().with_strict(strict);
+        for hive in HIVES {
+            let registry = match Registry::try_from_str_with(text, hive, &options) {
+                Ok(registry) => registry,
+                Err(_) => continue,
+            };
+            // Names holding line breaks can't be written, which serializing reports.
+            let text = match registry.serialize() {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+            let reloaded =
+                Registry::try_from_str(&text, hive).expect("serialized registry fails to parse");
+            assert_eq!(reloaded.keys(), registry.keys());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_entry_points() {
+        parse(include_bytes!("../registries/user.reg"));
+        parse(b"REGEDIT4\n\n[HKEY_CURRENT_USER\\A]\n\"x\"=hex(7):41,00,00,00\n[");
+        parse(b"\xff\xfe\x00");
+        parse_wine(b"[Software\\\\A] 1700000000\n\"x\"=str(2):\"%PATH%\"\n\"y\"=dword:1");
+        // Raw ANSI strings of REGEDIT4 files, and names that can't be written.
+        parse(b"REGEDIT4\n\n[HKEY_CURRENT_USER\\A]\n\"h\"=hex(1):41,00,00,00\n");
+        parse_wine(b"[A\\x0a]\n\"\\n\"=dword:1\n");
+        binary(
+            &RegistryDiff::from_patch_str(
+                "REGEDIT4\n\n[HKEY_CURRENT_USER\\A]\n\"x\"=\"y\"\n",
+                &Registry::try_from_str("REGEDIT4\n", Hive::CurrentUser).unwrap(),
+            )
+            .unwrap()
+            .to_binary(),
+        );
+        binary(b"\x00\x01garbage");
+
+        // A deterministic sequence exercising every operation on every key and value.
+        let operations: Vec<u8> = (0u32..600)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        apply(&operations);
+    }
+}
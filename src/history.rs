@@ -0,0 +1,404 @@
+use crate::atomic;
+use crate::names::same_value_name;
+use crate::prelude::{
+    DiffOptions, Error, Key, KeyChange, KeyDiff, Operation, Registry, SnapshotId, Store, Value,
+};
+use crate::store::{hash, store_error};
+use regashii::{KeyName, ValueName};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The identifier of a [Commit], the SHA-256 hash of its record.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommitId(String);
+
+impl CommitId {
+    /// Returns the identifier as lowercase hexadecimal.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for CommitId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A snapshot recorded in a [History], with when and why it was taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Commit {
+    /// The identifier of the commit.
+    id: CommitId,
+    /// The snapshot of the registry state.
+    snapshot: SnapshotId,
+    /// The previous commit, or `None` for the first one.
+    parent: Option<CommitId>,
+    /// When the snapshot was taken, in seconds since the Unix epoch.
+    timestamp: u64,
+    /// The message describing the snapshot.
+    message: String,
+}
+
+impl Commit {
+    /// Returns the identifier of the commit.
+    pub fn id(&self) -> &CommitId {
+        &self.id
+    }
+
+    /// Returns the snapshot of the registry state.
+    pub fn snapshot(&self) -> &SnapshotId {
+        &self.snapshot
+    }
+
+    /// Returns the previous commit, or `None` for the first one.
+    pub fn parent(&self) -> Option<&CommitId> {
+        self.parent.as_ref()
+    }
+
+    /// Returns when the snapshot was taken, to the second.
+    pub fn timestamp(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+
+    /// Returns the message describing the snapshot.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Formats the record the commit is stored as.
+    fn record(&self) -> String {
+        let mut record = format!("snapshot {}\n", self.snapshot);
+        if let Some(parent) = &self.parent {
+            record.push_str(&format!("parent {}\n", parent));
+        }
+        record.push_str(&format!("timestamp {}\n\n{}", self.timestamp, self.message));
+        record
+    }
+
+    /// Parses a stored record.
+    fn parse(id: CommitId, record: &str) -> Option<Self> {
+        let (header, message) = record.split_once("\n\n")?;
+        let mut commit = Self {
+            id,
+            snapshot: SnapshotId::new(String::new()),
+            parent: None,
+            timestamp: 0,
+            message: message.to_string(),
+        };
+        for line in header.lines() {
+            match line.split_once(' ')? {
+                ("snapshot", snapshot) => commit.snapshot = SnapshotId::new(snapshot.to_string()),
+                ("parent", parent) => commit.parent = Some(CommitId(parent.to_string())),
+                ("timestamp", timestamp) => commit.timestamp = timestamp.parse().ok()?,
+                _ => return None,
+            }
+        }
+        Some(commit)
+    }
+}
+
+/// A linear history of registry snapshots with messages and tags, on top of a [Store].
+///
+/// Commits are stored in `commits/<id>` next to the store's own files, the latest one is
+/// named by `HEAD` and tags are stored in `tags/<name>`.
+#[derive(Clone, Debug)]
+pub struct History {
+    /// The store holding the snapshots.
+    store: Store,
+}
+
+impl History {
+    /// Opens the history in a directory, creating it if needed.
+    pub fn open<T: AsRef<Path>>(root: T) -> Result<Self, Error> {
+        let store = Store::open(root)?;
+        std::fs::create_dir_all(store.root().join("commits"))?;
+        std::fs::create_dir_all(store.root().join("tags"))?;
+        Ok(Self { store })
+    }
+
+    /// Returns the store holding the snapshots.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Records the current state of a registry on top of the latest commit.
+    pub fn commit(&self, registry: &Registry, message: &str) -> Result<Commit, Error> {
+        self.commit_at(registry, message, SystemTime::now())
+    }
+
+    /// Records the state of a registry taken at the given time on top of the latest commit.
+    pub fn commit_at(
+        &self,
+        registry: &Registry,
+        message: &str,
+        timestamp: SystemTime,
+    ) -> Result<Commit, Error> {
+        let mut commit = Commit {
+            id: CommitId(String::new()),
+            snapshot: self.store.commit(registry)?,
+            parent: self.head()?.map(|head| head.id),
+            timestamp: timestamp
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            message: message.to_string(),
+        };
+        let record = commit.record();
+        commit.id = CommitId(hash(record.as_bytes()));
+
+        let root = self.store.root();
+        atomic::write(root.join("commits").join(commit.id.as_str()), record)?;
+        atomic::write(root.join("HEAD"), commit.id.as_str())?;
+        Ok(commit)
+    }
+
+    /// Returns the latest commit, or `None` if nothing was committed yet.
+    pub fn head(&self) -> Result<Option<Commit>, Error> {
+        match std::fs::read_to_string(self.store.root().join("HEAD")) {
+            Ok(id) => self
+                .commit_by_id(&CommitId(id.trim().to_string()))
+                .map(Some),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Reads a commit by its full identifier.
+    fn commit_by_id(&self, id: &CommitId) -> Result<Commit, Error> {
+        let path = self.store.root().join("commits").join(id.as_str());
+        let record = std::fs::read_to_string(path).map_err(|error| match error.kind() {
+            std::io::ErrorKind::NotFound => store_error(format!("unknown commit '{}'", id)),
+            _ => error.into(),
+        })?;
+        Commit::parse(id.clone(), &record)
+            .ok_or_else(|| store_error(format!("commit '{}' is corrupt", id)))
+    }
+
+    /// Tags a commit, moving the tag if it already exists.
+    pub fn tag(&self, name: &str, commit: &CommitId) -> Result<(), Error> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(store_error(format!("invalid tag name '{}'", name)));
+        }
+        self.commit_by_id(commit)?;
+        atomic::write(self.store.root().join("tags").join(name), commit.as_str())?;
+        Ok(())
+    }
+
+    /// Returns the tags, by name.
+    pub fn tags(&self) -> Result<BTreeMap<String, CommitId>, Error> {
+        let mut tags = BTreeMap::new();
+        for entry in std::fs::read_dir(self.store.root().join("tags"))? {
+            let entry = entry?;
+            let id = std::fs::read_to_string(entry.path())?;
+            tags.insert(
+                entry.file_name().to_string_lossy().into_owned(),
+                CommitId(id.trim().to_string()),
+            );
+        }
+        Ok(tags)
+    }
+
+    /// Finds a commit by tag, or by a prefix of its identifier like git's abbreviated hashes.
+    pub fn find(&self, name: &str) -> Result<Commit, Error> {
+        if let Some(id) = self.tags()?.get(name) {
+            return self.commit_by_id(id);
+        }
+
+        let prefix = name.to_lowercase();
+        let mut matches = self
+            .log()?
+            .into_iter()
+            .filter(|commit| !prefix.is_empty() && commit.id.as_str().starts_with(&prefix));
+        match (matches.next(), matches.next()) {
+            (Some(commit), None) => Ok(commit),
+            (Some(_), Some(_)) => Err(store_error(format!("commit '{}' is ambiguous", name))),
+            (None, _) => Err(store_error(format!("unknown commit '{}'", name))),
+        }
+    }
+
+    /// Returns the commits from the latest to the first.
+    pub fn log(&self) -> Result<Vec<Commit>, Error> {
+        let mut commits = Vec::new();
+        let mut next = self.head()?;
+        while let Some(commit) = next {
+            next = match commit.parent() {
+                Some(parent) => Some(self.commit_by_id(parent)?),
+                None => None,
+            };
+            commits.push(commit);
+        }
+        Ok(commits)
+    }
+
+    /// Loads the registry state recorded by a commit.
+    pub fn load(&self, commit: &Commit) -> Result<Registry, Error> {
+        self.store.load(commit.snapshot())
+    }
+
+    /// Returns every change recorded to a key, from the first commit to the latest, with the
+    /// commit recording it.
+    ///
+    /// The first commit is compared to an empty registry, so the commit creating the key is
+    /// reported too.
+    pub fn key_log(&self, name: &KeyName) -> Result<Vec<(Commit, KeyDiff)>, Error> {
+        let options = DiffOptions::new().with_security(true);
+        let mut changes = Vec::new();
+        let mut previous: Option<Registry> = None;
+
+        for commit in self.log()?.into_iter().rev() {
+            let registry = self.load(&commit)?;
+            let old = previous
+                .as_ref()
+                .and_then(|previous| find_key(previous, name));
+            if let Some(change) = KeyDiff::new(old, find_key(&registry, name), &options) {
+                changes.push((commit, change));
+            }
+            previous = Some(registry);
+        }
+        Ok(changes)
+    }
+
+    /// Returns every change recorded to a value of a key, from the first commit to the latest,
+    /// with the commit recording it and so when it was recorded.
+    ///
+    /// Value names are matched case-insensitively like the registry does. Deleting the key
+    /// is reported as deleting the value.
+    pub fn value_history(
+        &self,
+        key: &KeyName,
+        value: &ValueName,
+    ) -> Result<Vec<(Commit, Operation<Value>)>, Error> {
+        let changes = self
+            .key_log(key)?
+            .into_iter()
+            .filter_map(|(commit, change)| {
+                let operation = change
+                    .values()
+                    .iter()
+                    .find(|(name, _)| same_value_name(name, value))
+                    .map(|(_, operation)| operation.clone())?;
+                Some((commit, operation))
+            });
+        Ok(changes.collect())
+    }
+
+    /// Returns, for every value of a key in the latest commit, the commit that last set it,
+    /// like `git blame` for values.
+    pub fn blame(&self, name: &KeyName) -> Result<BTreeMap<ValueName, Commit>, Error> {
+        let mut blame = BTreeMap::new();
+        for (commit, change) in self.key_log(name)? {
+            if change.change() == KeyChange::Deleted {
+                blame.clear();
+                continue;
+            }
+            for (value, operation) in change.values() {
+                match operation {
+                    Operation::Delete { .. } => {
+                        blame.remove(value);
+                    }
+                    _ => {
+                        blame.insert(value.clone(), commit.clone());
+                    }
+                }
+            }
+        }
+        Ok(blame)
+    }
+}
+
+/// Finds a key of a registry by its full name, case-insensitively.
+fn find_key<'a>(registry: &'a Registry, name: &KeyName) -> Option<&'a Key> {
+    registry
+        .keys()
+        .values()
+        .find(|key| key.name().raw().eq_ignore_ascii_case(name.raw()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RegistryBuilder;
+    use crate::prelude::Hive;
+
+    #[test]
+    fn test_history_log_and_blame() {
+        let root = std::env::temp_dir().join(format!("regdiff-history-{}", std::process::id()));
+        let history = History::open(&root).unwrap();
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let registry = |version: &str| {
+            RegistryBuilder::new(Hive::CurrentUser)
+                .with_sz("Software\\App", "Version", version)
+                .with(
+                    "Software\\App",
+                    ValueName::named("Fixed"),
+                    regashii::Value::Dword(1),
+                )
+                .build()
+        };
+
+        let first = history
+            .commit_at(&registry("1.0"), "install", at(10))
+            .unwrap();
+        history
+            .commit_at(&registry("1.0"), "reboot", at(20))
+            .unwrap();
+        let third = history
+            .commit_at(&registry("2.0"), "update", at(30))
+            .unwrap();
+        history.tag("v2", third.id()).unwrap();
+
+        let log: Vec<String> = history
+            .log()
+            .unwrap()
+            .iter()
+            .map(|commit| commit.message().to_string())
+            .collect();
+        assert_eq!(log, vec!["update", "reboot", "install"]);
+        assert_eq!(history.find("v2").unwrap(), third);
+        assert_eq!(history.find(&first.id().as_str()[..10]).unwrap(), first);
+        assert_eq!(third.timestamp(), at(30));
+
+        let name = KeyName::new("HKEY_CURRENT_USER\\Software\\App");
+        let changes = history.key_log(&name).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].0, third);
+
+        let versions: Vec<(SystemTime, Operation<Value>)> = history
+            .value_history(&name, &ValueName::named("version"))
+            .unwrap()
+            .into_iter()
+            .map(|(commit, operation)| (commit.timestamp(), operation))
+            .collect();
+        let version = |data: &str| {
+            Value::new(
+                ValueName::named("Version"),
+                regashii::Value::Sz(data.to_string()),
+            )
+        };
+        assert_eq!(
+            versions,
+            vec![
+                (
+                    at(10),
+                    Operation::Add {
+                        data: version("1.0")
+                    }
+                ),
+                (
+                    at(30),
+                    Operation::Modify {
+                        old_data: version("1.0"),
+                        new_data: version("2.0")
+                    }
+                ),
+            ]
+        );
+
+        let blame = history.blame(&name).unwrap();
+        assert_eq!(blame[&ValueName::named("Version")], third);
+        assert_eq!(blame[&ValueName::named("Fixed")], first);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
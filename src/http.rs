@@ -0,0 +1,437 @@
+use crate::prelude::{
+    DiffOptions, Encoding, Error, Hive, KeyChange, Operation, Registry, RegistryDiff, Value,
+};
+use regashii::ValueName;
+use serde_json::{json, Map};
+use std::io::Read;
+
+/// The size of the largest request body served by default, in bytes.
+const MAX_BODY_SIZE: u64 = 128 * 1024 * 1024;
+
+/// An HTTP service computing diffs between uploaded registry files, so CI pipelines can
+/// compare prefix states across machines.
+///
+/// The service answers `POST /diff` requests with a `multipart/form-data` body, such as sent
+/// by `curl -F old=@old.reg -F new=@new.reg -F hive=HKEY_CURRENT_USER`. Its fields are:
+///
+/// - `old` and `new`: the registry files to compare, in any encoding [Encoding::detect]
+///   recognizes. With the `store` feature and a [Store](crate::prelude::Store) attached,
+///   `old_snapshot` and `new_snapshot` name stored snapshots by ID or ID prefix instead.
+/// - `hive`: the hive of uploaded files, such as `HKEY_LOCAL_MACHINE`.
+/// - `format`: `json`, the default, or `reg` for a `.reg` patch.
+/// - `security`: `true` to also compare security descriptors.
+///
+/// JSON diffs list the changed keys with their `name`, `change` and changed `values`, each
+/// with its `name` (`null` for the default value), `operation` and `old` and `new` data.
+/// They also hold the `metadata` of the diff, with its `name`, `description`, `author` and
+/// `target_version`, `null` when unset.
+/// Failed requests get a 4xx status with a JSON `error` message, such as 413 for bodies larger
+/// than [HttpService::with_max_body_size] allows.
+#[derive(Clone, Debug)]
+pub struct HttpService {
+    /// The store snapshot IDs are resolved in.
+    #[cfg(feature = "store")]
+    store: Option<crate::prelude::Store>,
+    /// The size of the largest request body served, in bytes.
+    max_body_size: u64,
+}
+
+impl Default for HttpService {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "store")]
+            store: None,
+            max_body_size: MAX_BODY_SIZE,
+        }
+    }
+}
+
+/// A response to send back to a client.
+#[derive(Debug)]
+struct Reply {
+    /// The HTTP status code.
+    status: u16,
+    /// The media type of the body.
+    content_type: &'static str,
+    /// The body.
+    body: String,
+}
+
+impl Reply {
+    /// Constructs an error response carrying a JSON message.
+    fn error(status: u16, message: String) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: json!({ "error": message }).to_string(),
+        }
+    }
+}
+
+impl HttpService {
+    /// Creates a service diffing uploaded files only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets requests name snapshots of a store instead of uploading files.
+    #[cfg(feature = "store")]
+    pub fn with_store(mut self, store: crate::prelude::Store) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Returns the service refusing request bodies larger than `size` bytes, 128 MiB by default.
+    pub fn with_max_body_size(mut self, size: u64) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Returns the size of the largest request body served, in bytes.
+    pub fn max_body_size(&self) -> u64 {
+        self.max_body_size
+    }
+
+    /// Listens on an address, such as `0.0.0.0:8080`, and answers requests one at a time
+    /// until the listener fails.
+    pub fn serve<A: std::net::ToSocketAddrs>(&self, address: A) -> Result<(), Error> {
+        let server = tiny_http::Server::http(address).map_err(std::io::Error::other)?;
+        loop {
+            let mut request = server.recv()?;
+            let reply = match read_body(request.as_reader(), self.max_body_size) {
+                Ok(body) => {
+                    let content_type = request
+                        .headers()
+                        .iter()
+                        .find(|header| header.field.equiv("Content-Type"))
+                        .map(|header| header.value.as_str().to_string());
+                    self.handle(
+                        request.method().as_str(),
+                        request.url(),
+                        content_type.as_deref(),
+                        &body,
+                    )
+                }
+                Err(reply) => reply,
+            };
+
+            let header = tiny_http::Header::from_bytes("Content-Type", reply.content_type)
+                .expect("static header is valid");
+            let response = tiny_http::Response::from_string(reply.body)
+                .with_status_code(reply.status)
+                .with_header(header);
+            // A client hanging up early is no reason to stop serving others.
+            let _ = request.respond(response);
+        }
+    }
+
+    /// Answers a single request.
+    fn handle(&self, method: &str, url: &str, content_type: Option<&str>, body: &[u8]) -> Reply {
+        match (method, url.split('?').next().unwrap_or(url)) {
+            ("POST", "/diff") => {}
+            (_, "/diff") => return Reply::error(405, format!("method {} not allowed", method)),
+            (_, path) => return Reply::error(404, format!("no such endpoint '{}'", path)),
+        }
+        let boundary = match content_type.and_then(boundary) {
+            Some(boundary) => boundary,
+            None => return Reply::error(400, "expected a multipart/form-data body".to_string()),
+        };
+        let fields = multipart(body, &boundary);
+        match self.diff(&fields) {
+            Ok(reply) => reply,
+            Err(message) => Reply::error(400, message),
+        }
+    }
+
+    /// Computes the diff requested by the fields of a form.
+    fn diff(&self, fields: &[(String, Vec<u8>)]) -> Result<Reply, String> {
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, data)| String::from_utf8_lossy(data).into_owned())
+        };
+        let security = field("security").is_some_and(|security| security.trim() == "true");
+        let old = self.side(fields, "old", field("hive").as_deref())?;
+        let new = self.side(fields, "new", field("hive").as_deref())?;
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new().with_security(security));
+
+        match field("format").as_deref().map(str::trim) {
+            None | Some("json") => Ok(Reply {
+                status: 200,
+                content_type: "application/json",
+                body: diff_json(&diff).to_string(),
+            }),
+            Some("reg") => Ok(Reply {
+                status: 200,
+                content_type: "text/plain; charset=utf-8",
+                body: diff.serialize().map_err(|error| error.to_string())?,
+            }),
+            Some(format) => Err(format!("unknown format '{}'", format)),
+        }
+    }
+
+    /// Loads one side of a diff from an uploaded file or a stored snapshot.
+    fn side(
+        &self,
+        fields: &[(String, Vec<u8>)],
+        name: &str,
+        hive: Option<&str>,
+    ) -> Result<Registry, String> {
+        #[cfg(feature = "store")]
+        if let Some((_, id)) = fields
+            .iter()
+            .find(|(field, _)| *field == format!("{}_snapshot", name))
+        {
+            let store = self
+                .store
+                .as_ref()
+                .ok_or_else(|| "no snapshot store is attached".to_string())?;
+            let id = String::from_utf8_lossy(id);
+            return store
+                .resolve(id.trim())
+                .and_then(|id| store.load(&id))
+                .map_err(|error| error.to_string());
+        }
+
+        let (_, bytes) = fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .ok_or_else(|| format!("missing file '{}'", name))?;
+        let hive = hive.ok_or_else(|| "missing field 'hive'".to_string())?;
+        let hive = [Hive::LocalMachine, Hive::CurrentUser, Hive::DefaultUser]
+            .into_iter()
+            .find(|known| known.to_string().eq_ignore_ascii_case(hive.trim()))
+            .ok_or_else(|| format!("unknown hive '{}'", hive))?;
+        Encoding::detect(bytes)
+            .decode(bytes)
+            .and_then(|text| Registry::try_from_str(&text, hive))
+            .map_err(|error| format!("{}: {}", name, error))
+    }
+}
+
+/// Reads a request body of at most `limit` bytes.
+///
+/// # Returns
+///
+/// The body, or the error response to send if it can't be read or is too large.
+fn read_body<R: Read>(reader: R, limit: u64) -> Result<Vec<u8>, Reply> {
+    let mut body = Vec::new();
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut body)
+        .map_err(|error| Reply::error(400, format!("failed to read body: {}", error)))?;
+    if body.len() as u64 > limit {
+        return Err(Reply::error(
+            413,
+            format!("request body larger than {} bytes", limit),
+        ));
+    }
+    Ok(body)
+}
+
+/// Returns the boundary of a `multipart/form-data` content type.
+fn boundary(content_type: &str) -> Option<String> {
+    let mut parts = content_type.split(';').map(str::trim);
+    if !parts.next()?.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    parts
+        .find_map(|part| part.strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"').to_string())
+}
+
+/// Splits a `multipart/form-data` body into the names and contents of its fields.
+fn multipart(body: &[u8], boundary: &str) -> Vec<(String, Vec<u8>)> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut fields = Vec::new();
+    let mut rest = match find(body, &delimiter) {
+        Some(start) => &body[start + delimiter.len()..],
+        None => return fields,
+    };
+
+    // Each part starts after a delimiter line and ends before the CRLF of the next one.
+    while let Some(end) = find(rest, &delimiter) {
+        let part = rest[..end].strip_prefix(b"\r\n").unwrap_or(&rest[..end]);
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+        rest = &rest[end + delimiter.len()..];
+        let Some(split) = find(part, b"\r\n\r\n") else {
+            continue;
+        };
+        let headers = String::from_utf8_lossy(&part[..split]);
+        let name = headers
+            .lines()
+            .filter(|line| {
+                line.to_ascii_lowercase()
+                    .starts_with("content-disposition:")
+            })
+            .flat_map(|line| line.split(';'))
+            .find_map(|parameter| parameter.trim().strip_prefix("name="))
+            .map(|name| name.trim_matches('"').to_string());
+        if let Some(name) = name {
+            fields.push((name, part[split + 4..].to_vec()));
+        }
+    }
+    fields
+}
+
+/// Returns the offset of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Converts a diff into its JSON representation.
+fn diff_json(diff: &RegistryDiff) -> serde_json::Value {
+    let keys = diff
+        .keys()
+        .values()
+        .map(|key| {
+            let change = match key.change() {
+                KeyChange::Added => "added",
+                KeyChange::Deleted => "deleted",
+                KeyChange::Modified => "modified",
+                KeyChange::Unchanged => "unchanged",
+            };
+            let values = key
+                .values()
+                .iter()
+                .filter_map(|(name, operation)| {
+                    let (operation, old, new) = match operation {
+                        Operation::Unchanged => return None,
+                        Operation::Add { data } => ("add", None, Some(data)),
+                        Operation::Delete { data } => ("delete", Some(data), None),
+                        Operation::Modify { old_data, new_data } => {
+                            ("modify", Some(old_data), Some(new_data))
+                        }
+                        Operation::Volatile { old_data, new_data } => {
+                            ("volatile", Some(old_data), Some(new_data))
+                        }
+                        Operation::Reordered { old_data, new_data } => {
+                            ("reordered", Some(old_data), Some(new_data))
+                        }
+                    };
+                    let name = match name {
+                        ValueName::Named(name) => json!(name),
+                        _ => serde_json::Value::Null,
+                    };
+                    Some(json!({
+                        "name": name,
+                        "operation": operation,
+                        "old": old.map(value_json),
+                        "new": new.map(value_json),
+                    }))
+                })
+                .collect::<Vec<_>>();
+            json!({ "name": key.name().raw(), "change": change, "values": values })
+        })
+        .collect::<Vec<_>>();
+    let [name, description, author, target_version] = diff.metadata().fields();
+    json!({
+        "metadata": {
+            "name": name,
+            "description": description,
+            "author": author,
+            "target_version": target_version,
+        },
+        "keys": keys,
+    })
+}
+
+/// Converts the data of a value into a JSON object holding its `type` and `data`.
+fn value_json(value: &Value) -> serde_json::Value {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+    };
+    let mut object = Map::new();
+    let (kind, data) = match (value.raw_type(), value.value()) {
+        (Some(code), regashii::Value::Binary(bytes)) => {
+            (format!("hex({:x})", code), json!(hex(bytes)))
+        }
+        (_, regashii::Value::Sz(text)) => ("sz".to_string(), json!(text)),
+        (_, regashii::Value::ExpandSz(text)) => ("expand_sz".to_string(), json!(text)),
+        (_, regashii::Value::Binary(bytes)) => ("binary".to_string(), json!(hex(bytes))),
+        (_, regashii::Value::Dword(number)) => ("dword".to_string(), json!(number)),
+        (_, regashii::Value::MultiSz(strings)) => ("multi_sz".to_string(), json!(strings)),
+        (_, regashii::Value::Qword(number)) => ("qword".to_string(), json!(number)),
+        _ => ("delete".to_string(), serde_json::Value::Null),
+    };
+    object.insert("type".to_string(), json!(kind));
+    object.insert("data".to_string(), data);
+    serde_json::Value::Object(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form(fields: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, data) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--XyZ\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"f\"\r\n\r\n",
+                    name
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--XyZ--\r\n");
+        body
+    }
+
+    #[test]
+    fn test_diff_uploads() {
+        let old = std::fs::read("./registries/old.reg").unwrap();
+        let new = std::fs::read("./registries/new.reg").unwrap();
+        let service = HttpService::new();
+        let content_type = Some("multipart/form-data; boundary=XyZ");
+        let load = |bytes: &[u8]| {
+            let text = Encoding::detect(bytes).decode(bytes).unwrap();
+            Registry::try_from_str(&text, Hive::CurrentUser).unwrap()
+        };
+        let expected = RegistryDiff::new(&load(&old), &load(&new), &DiffOptions::new());
+
+        let body = form(&[
+            ("old", &old),
+            ("new", &new),
+            ("hive", b"HKEY_CURRENT_USER"),
+            ("format", b"reg"),
+        ]);
+        let reply = service.handle("POST", "/diff", content_type, &body);
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body, expected.serialize().unwrap());
+
+        let body = form(&[("old", &old), ("new", &new), ("hive", b"HKEY_CURRENT_USER")]);
+        let reply = service.handle("POST", "/diff?x=1", content_type, &body);
+        let json: serde_json::Value = serde_json::from_str(&reply.body).unwrap();
+        assert_eq!(
+            json["keys"].as_array().unwrap().len(),
+            expected.keys().len()
+        );
+
+        let body = form(&[("old", &old), ("new", &new)]);
+        let reply = service.handle("POST", "/diff", content_type, &body);
+        assert_eq!(reply.status, 400);
+        assert!(reply.body.contains("missing field 'hive'"));
+        assert_eq!(
+            service.handle("GET", "/diff", content_type, &body).status,
+            405
+        );
+        assert_eq!(service.handle("POST", "/", content_type, &body).status, 404);
+    }
+
+    #[test]
+    fn test_body_size_limit() {
+        assert_eq!(read_body(&b"abcd"[..], 4).unwrap(), b"abcd");
+        let reply = read_body(&b"abcde"[..], 4).unwrap_err();
+        assert_eq!(reply.status, 413);
+        assert_eq!(HttpService::new().max_body_size(), MAX_BODY_SIZE);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::atomic;
+use crate::prelude::{DiffEntry, EntryId, Error, KeyPattern, RegistryDiff};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// The version of the JSON layout written by [IgnoreStore::save].
+const VERSION: u64 = 1;
+
+/// Records the changes a user chose to ignore, so diffs of later snapshots don't report known
+/// noise again.
+///
+/// Changes are ignored by [EntryId], which survives recomputing the diff, or by [KeyPattern],
+/// ignoring every change of the matching keys. The decisions are saved as a JSON object
+/// holding a `version`, the `entries` as hex strings and the `patterns` as strings.
+#[derive(Clone, Debug)]
+pub struct IgnoreStore {
+    /// The file the decisions are saved to.
+    path: PathBuf,
+    /// The ignored entries.
+    entries: BTreeSet<EntryId>,
+    /// The patterns of the keys whose changes are ignored.
+    patterns: Vec<KeyPattern>,
+}
+
+impl IgnoreStore {
+    /// Opens the decisions saved in a file, starting with none if it doesn't exist.
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let mut store = Self {
+            path,
+            entries: BTreeSet::new(),
+            patterns: Vec::new(),
+        };
+        let text = match std::fs::read_to_string(&store.path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(store),
+            Err(error) => return Err(error.into()),
+        };
+        let json: Value = serde_json::from_str(&text).map_err(ignore_error)?;
+        if json["version"] != VERSION {
+            return Err(ignore_error("unsupported version"));
+        }
+        for id in strings(&json["entries"])? {
+            let id = EntryId::from_hex(id)
+                .ok_or_else(|| ignore_error(format!("invalid entry ID '{}'", id)))?;
+            store.entries.insert(id);
+        }
+        store.patterns = strings(&json["patterns"])?.map(KeyPattern::new).collect();
+        Ok(store)
+    }
+
+    /// Returns the file the decisions are saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the decisions to the file of the store.
+    pub fn save(&self) -> Result<(), Error> {
+        let entries: Vec<String> = self.entries.iter().map(EntryId::to_string).collect();
+        let patterns: Vec<&str> = self.patterns.iter().map(KeyPattern::raw).collect();
+        let json = json!({ "version": VERSION, "entries": entries, "patterns": patterns });
+        let text = serde_json::to_string_pretty(&json).map_err(ignore_error)?;
+        atomic::write(&self.path, text + "\n")?;
+        Ok(())
+    }
+
+    /// Ignores an entry.
+    ///
+    /// # Returns
+    ///
+    /// Whether the entry wasn't ignored yet.
+    pub fn ignore(&mut self, id: EntryId) -> bool {
+        self.entries.insert(id)
+    }
+
+    /// Stops ignoring an entry.
+    ///
+    /// # Returns
+    ///
+    /// Whether the entry was ignored.
+    pub fn unignore(&mut self, id: EntryId) -> bool {
+        self.entries.remove(&id)
+    }
+
+    /// Ignores every change of the keys matching a pattern.
+    ///
+    /// # Returns
+    ///
+    /// Whether the pattern wasn't ignored yet.
+    pub fn ignore_pattern<T: Into<KeyPattern>>(&mut self, pattern: T) -> bool {
+        let pattern = pattern.into();
+        if self.patterns.contains(&pattern) {
+            return false;
+        }
+        self.patterns.push(pattern);
+        true
+    }
+
+    /// Stops ignoring the keys matching a pattern.
+    ///
+    /// # Returns
+    ///
+    /// Whether the pattern was ignored.
+    pub fn unignore_pattern<T: Into<KeyPattern>>(&mut self, pattern: T) -> bool {
+        let pattern = pattern.into();
+        let len = self.patterns.len();
+        self.patterns.retain(|ignored| *ignored != pattern);
+        self.patterns.len() != len
+    }
+
+    /// Returns the ignored entries.
+    pub fn entries(&self) -> &BTreeSet<EntryId> {
+        &self.entries
+    }
+
+    /// Returns the patterns of the keys whose changes are ignored.
+    pub fn patterns(&self) -> &[KeyPattern] {
+        &self.patterns
+    }
+
+    /// Returns whether an entry is ignored, by its ID or a pattern matching its key.
+    pub fn is_ignored(&self, entry: &DiffEntry) -> bool {
+        self.entries.contains(&entry.id())
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.matches(entry.key_name()))
+    }
+
+    /// Returns the diff without the ignored entries, like [RegistryDiff::filtered].
+    pub fn filter(&self, diff: &RegistryDiff) -> RegistryDiff {
+        diff.filtered(|entry| !self.is_ignored(entry))
+    }
+}
+
+/// Reads a JSON array of strings, treating a missing field as empty.
+fn strings(json: &Value) -> Result<impl Iterator<Item = &str>, Error> {
+    let items = match json {
+        Value::Null => &[][..],
+        Value::Array(items) => items.as_slice(),
+        _ => return Err(ignore_error("expected an array")),
+    };
+    if items.iter().any(|item| !item.is_string()) {
+        return Err(ignore_error("expected an array of strings"));
+    }
+    Ok(items.iter().filter_map(Value::as_str))
+}
+
+/// Builds an [Error::Ignore] with the given message.
+fn ignore_error<T: ToString>(message: T) -> Error {
+    Error::Ignore {
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{DiffOptions, Hive, Registry};
+
+    #[test]
+    fn test_ignore_store_round_trip() {
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        let path = std::env::temp_dir().join(format!("regdiff-ignore-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = IgnoreStore::open(&path).unwrap();
+        assert_eq!(store.filter(&diff), diff);
+        let entry = diff.entries().find(|e| e.value_name().is_some()).unwrap();
+        let id = entry.id();
+        let pattern = diff.keys().keys().last().unwrap().raw().to_string();
+        assert!(store.ignore(id));
+        assert!(!store.ignore(id));
+        assert!(store.ignore_pattern(pattern.as_str()));
+        store.save().unwrap();
+
+        let store = IgnoreStore::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(store.patterns(), &[KeyPattern::new(&pattern)]);
+        let filtered = store.filter(&diff);
+        assert!(filtered.entry(id).is_none());
+        assert!(filtered.entries().all(|e| e.key_name().raw() != pattern));
+        assert!(!filtered.is_empty());
+
+        std::fs::write(&path, "{\"version\": 1, \"entries\": [\"xyz\"]}").unwrap();
+        assert!(IgnoreStore::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+use crate::names::same_value_name;
+use crate::parse::{self, Syntax};
+#[cfg(feature = "fs")]
+use crate::prelude::Encoding;
+use crate::prelude::{DiffOptions, Error, Hive, PatchMetadata, Registry, RegistryDiff};
+use regashii::ValueName;
+use std::collections::BTreeMap;
+
+/// The data a patch leaves a value with, `None` if it deletes the value.
+type Data = Option<(Option<u32>, regashii::Value)>;
+
+/// The effect of a patch on a key: whether the key is deleted first, and the data it leaves
+/// its values with, keyed by their lowercased name.
+type Effect = (bool, BTreeMap<String, Data>);
+
+impl RegistryDiff {
+    /// Reads a `.reg` patch file into a diff, against the registry it is meant to be applied to.
+    ///
+    /// See [RegistryDiff::from_patch_str] for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The patch, in any encoding [Encoding::detect] recognizes.
+    /// * `base` - The registry the patch applies to, providing the old data of the changes.
+    #[cfg(feature = "fs")]
+    pub fn from_patch_file<T: AsRef<std::path::Path>>(
+        file: T,
+        base: &Registry,
+    ) -> Result<Self, Error> {
+        let bytes = std::fs::read(file)?;
+        let text = Encoding::detect(&bytes).decode(&bytes)?;
+        Self::from_patch_str(&text, base)
+    }
+
+    /// Reads the text of a `.reg` patch into a diff, against the registry it is meant to be
+    /// applied to.
+    ///
+    /// Patches only hold new data, so the old data of the changes is taken from `base`. The
+    /// result is the diff between `base` and `base` with the patch applied the way regedit
+    /// imports it: `[-key]` sections delete the key and its subkeys, `"name"=-` lines delete a
+    /// value and other lines set one, creating the key if needed. Key and value names are
+    /// matched case-insensitively, and values keep the name `base` stores them under. Deleting
+    /// what `base` lacks is therefore no change at all. The [PatchMetadata] comments of the
+    /// patch are kept.
+    ///
+    /// Sections of keys outside of the hive of `base` are skipped, so a patch spanning several
+    /// hives is imported one hive at a time. Key names of Wine registry files are taken to be
+    /// relative to that hive.
+    ///
+    /// # Returns
+    ///
+    /// The diff, or an [Error] on the first malformed line of the patch.
+    pub fn from_patch_str(text: &str, base: &Registry) -> Result<Self, Error> {
+        let document = parse::parse(text, true)?;
+        let mut patched = base.clone();
+
+        for section in document.sections {
+            let path = match (document.syntax, Hive::split(&section.name)) {
+                (Syntax::Wine, _) => section.name.as_str(),
+                (_, Some((hive, path))) if hive == base.hive() => path,
+                _ => continue,
+            };
+            if section.deleted {
+                patched.remove_key(path);
+                continue;
+            }
+
+            let values = patched.key_mut(path).values_mut();
+            for value in section.values {
+                if let regashii::Value::Delete = value.value() {
+                    values.retain(|name, _| !same_value_name(name, value.name()));
+                    continue;
+                }
+                let name = values
+                    .keys()
+                    .find(|name| same_value_name(name, value.name()))
+                    .unwrap_or(value.name())
+                    .clone();
+                values.insert(name.clone(), value.with_name(name));
+            }
+        }
+
+        Ok(RegistryDiff::new(base, &patched, &DiffOptions::new())
+            .with_metadata(PatchMetadata::from_patch(text)))
+    }
+}
+
+/// Returns whether two `.reg` patches describe the same changes, regardless of the order of
+/// their sections and how they are formatted.
+///
+/// Each patch is folded into its final effect on every key: whether the key is deleted, and
+/// which of its values are set or deleted. Key and value names are compared
+/// case-insensitively like the registry does, and data by what it decodes to, so wrapped and
+/// unwrapped hex lines or `REGEDIT4` and version 5 headers make no difference. Deleting a key
+/// already deleted with its parent is no change. Order still matters where a patch changes
+/// the same key or value twice, as the later change wins.
+///
+/// # Returns
+///
+/// Whether the patches are equivalent, or an [Error] on the first malformed line of either.
+pub fn patch_equivalent(a: &str, b: &str) -> Result<bool, Error> {
+    Ok(effects(a)? == effects(b)?)
+}
+
+/// Folds a patch into its effect on each key, keyed by the lowercased key name.
+fn effects(text: &str) -> Result<BTreeMap<String, Effect>, Error> {
+    let document = parse::parse(text, true)?;
+    let mut effects: BTreeMap<String, Effect> = BTreeMap::new();
+
+    for section in document.sections {
+        let key = section.name.to_lowercase();
+        if section.deleted {
+            let subkeys = format!("{}\\", key);
+            effects.retain(|name, _| *name != key && !name.starts_with(&subkeys));
+            let parent_deleted = key.match_indices('\\').any(|(end, _)| {
+                effects
+                    .get(&key[..end])
+                    .is_some_and(|(deleted, _)| *deleted)
+            });
+            if !parent_deleted {
+                effects.insert(key, (true, BTreeMap::new()));
+            }
+            continue;
+        }
+
+        let (_, values) = effects.entry(key).or_default();
+        for value in section.values {
+            let raw_type = value.raw_type();
+            let (name, data) = value.into_regashii_value();
+            let name = match name {
+                ValueName::Default => String::new(),
+                ValueName::Named(name) => name.to_lowercase(),
+            };
+            let data = match data {
+                regashii::Value::Delete => None,
+                data => Some((raw_type, data)),
+            };
+            values.insert(name, data);
+        }
+    }
+    Ok(effects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{KeyChange, Operation};
+    use regashii::{KeyName, ValueName};
+
+    #[test]
+    fn test_import_patch() {
+        let base = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4)
+                .with(
+                    KeyName::new("Software\\App"),
+                    regashii::Key::new()
+                        .with(ValueName::named("Old"), regashii::Value::Dword(1))
+                        .with(ValueName::named("Size"), regashii::Value::Dword(2)),
+                )
+                .with(KeyName::new("Software\\App\\Cache"), regashii::Key::new()),
+            Hive::CurrentUser,
+        );
+        let patch = r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\App]
+"Old"=-
+"Size"=dword:00000003
+"Missing"=-
+
+[-HKEY_CURRENT_USER\Software\App\Cache]
+
+[-HKEY_CURRENT_USER\Software\Missing]
+
+[HKEY_LOCAL_MACHINE\Software\Other]
+"A"="B"
+"#;
+        let diff = RegistryDiff::from_patch_str(patch, &base).unwrap();
+
+        assert_eq!(diff.keys().len(), 2);
+        let cache = KeyName::new("HKEY_CURRENT_USER\\Software\\App\\Cache");
+        assert_eq!(diff.key(&cache).unwrap().change(), KeyChange::Deleted);
+
+        let app = diff
+            .key(&KeyName::new("HKEY_CURRENT_USER\\Software\\App"))
+            .unwrap();
+        assert!(matches!(
+            app.values()[&ValueName::named("Old")],
+            Operation::Delete { .. }
+        ));
+        match &app.values()[&ValueName::named("Size")] {
+            Operation::Modify { old_data, new_data } => {
+                assert_eq!(old_data.value(), &regashii::Value::Dword(2));
+                assert_eq!(new_data.value(), &regashii::Value::Dword(3));
+            }
+            operation => panic!("unexpected operation {:?}", operation),
+        }
+    }
+
+    #[test]
+    fn test_import_patch_matches_value_names_ignoring_case() {
+        let base = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4).with(
+                KeyName::new("Software\\App"),
+                regashii::Key::new()
+                    .with(ValueName::named("Old"), regashii::Value::Dword(1))
+                    .with(ValueName::named("Size"), regashii::Value::Dword(2)),
+            ),
+            Hive::CurrentUser,
+        );
+        let patch =
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\software\\app]\n\"old\"=-\n\"size\"=dword:00000003\n";
+        let diff = RegistryDiff::from_patch_str(patch, &base).unwrap();
+
+        let app = diff
+            .key(&KeyName::new("HKEY_CURRENT_USER\\Software\\App"))
+            .unwrap();
+        assert_eq!(app.values().len(), 2);
+        assert!(matches!(
+            app.values()[&ValueName::named("Old")],
+            Operation::Delete { .. }
+        ));
+        assert!(matches!(
+            app.values()[&ValueName::named("Size")],
+            Operation::Modify { .. }
+        ));
+    }
+
+    #[test]
+    fn test_patch_equivalent() {
+        let a = r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\App]
+"Size"=dword:00000003
+"Data"=hex:01,02,03
+
+[-HKEY_CURRENT_USER\Software\Old]
+
+[-HKEY_CURRENT_USER\Software\Old\Sub]
+"#;
+        let b = "REGEDIT4\r\n\r\n[-HKEY_CURRENT_USER\\Software\\old]\r\n\r\n\
+                 [HKEY_CURRENT_USER\\Software\\app]\r\n\"data\"=hex:01,\\\r\n  02,03\r\n\
+                 \"SIZE\"=dword:00000003\r\n";
+        assert!(patch_equivalent(a, b).unwrap());
+
+        let c = a.replace("dword:00000003", "dword:00000004");
+        assert!(!patch_equivalent(a, &c).unwrap());
+        let d = a.replace("[-HKEY_CURRENT_USER\\Software\\Old]\n", "");
+        assert!(!patch_equivalent(a, &d).unwrap());
+        assert!(patch_equivalent(a, "not a patch").is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::prelude::{Classifier, KeyPattern};
+use regashii::{KeyName, ValueName};
+
+/// A well-known registry key and what it controls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KnownKey {
+    /// The pattern of the key's full name.
+    pattern: &'static str,
+    /// A short, human-friendly name of the key.
+    title: &'static str,
+    /// What the key controls.
+    description: &'static str,
+    /// The well-known values of the key and what they control.
+    values: &'static [(&'static str, &'static str)],
+}
+
+impl KnownKey {
+    /// Returns the pattern of the key's full name.
+    pub fn pattern(&self) -> KeyPattern {
+        KeyPattern::new(self.pattern)
+    }
+
+    /// Returns a short, human-friendly name of the key, e.g. "Wine DLL overrides".
+    pub fn title(&self) -> &'static str {
+        self.title
+    }
+
+    /// Returns what the key controls.
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// Returns what a well-known value of the key controls, ignoring case.
+    pub fn value(&self, name: &ValueName) -> Option<&'static str> {
+        let name = match name {
+            ValueName::Named(name) => name.as_str(),
+            ValueName::Default => "@",
+        };
+        self.values
+            .iter()
+            .find(|(value, _)| value.eq_ignore_ascii_case(name))
+            .map(|(_, description)| *description)
+    }
+}
+
+/// The well-known keys, from the most to the least specific.
+const KNOWN_KEYS: &[KnownKey] = &[
+    KnownKey {
+        pattern: "*\\Software\\Wine\\AppDefaults\\*",
+        title: "Wine per-application settings",
+        description: "Overrides the Wine settings of the whole prefix for one executable, using \
+            the same subkeys as Software\\Wine.",
+        values: &[(
+            "Version",
+            "The Windows version reported to the application.",
+        )],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine\\Direct3D",
+        title: "Wine Direct3D",
+        description: "Configures wined3d, Wine's Direct3D implementation.",
+        values: &[
+            ("renderer", "The rendering backend: gl, vulkan or gdi."),
+            ("csmt", "Whether command stream multithreading is enabled."),
+            (
+                "VideoMemorySize",
+                "The video memory reported to applications, in MiB.",
+            ),
+            (
+                "OffscreenRenderingMode",
+                "How offscreen surfaces are rendered: fbo or backbuffer.",
+            ),
+            (
+                "MaxVersionGL",
+                "The highest OpenGL version wined3d may use.",
+            ),
+            (
+                "VideoPciDeviceID",
+                "The PCI device ID of the reported graphics card.",
+            ),
+            (
+                "VideoPciVendorID",
+                "The PCI vendor ID of the reported graphics card.",
+            ),
+            ("shader_backend", "The shader backend: glsl, arb or none."),
+        ],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine\\DllOverrides",
+        title: "Wine DLL overrides",
+        description: "Chooses between Wine's builtin and the application's native version of \
+            each DLL, by DLL name.",
+        values: &[],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine\\Drivers",
+        title: "Wine drivers",
+        description: "Selects the host drivers Wine uses.",
+        values: &[
+            ("Audio", "The audio driver, e.g. pulse or alsa."),
+            ("Graphics", "The graphics driver, e.g. x11 or wayland."),
+        ],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine\\Fonts\\Replacements",
+        title: "Wine font replacements",
+        description: "Substitutes fonts: each value names a requested font and holds the font \
+            used in its place.",
+        values: &[],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine\\Fonts\\External Fonts",
+        title: "Wine external fonts",
+        description: "Fonts found on the host system, maintained by Wine.",
+        values: &[],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine\\Fonts",
+        title: "Wine fonts",
+        description: "Configures font rendering and substitution.",
+        values: &[],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine\\X11 Driver",
+        title: "Wine X11 driver",
+        description: "Configures how Wine windows interact with the X11 window manager.",
+        values: &[
+            (
+                "Decorated",
+                "Whether the window manager decorates windows (Y/N).",
+            ),
+            (
+                "Managed",
+                "Whether the window manager may manage windows (Y/N).",
+            ),
+            (
+                "GrabFullscreen",
+                "Whether fullscreen windows grab the mouse (Y/N).",
+            ),
+            (
+                "UseXRandR",
+                "Whether XRandR is used to change display modes (Y/N).",
+            ),
+            (
+                "UseTakeFocus",
+                "Whether the WM_TAKE_FOCUS protocol is used (Y/N).",
+            ),
+            (
+                "KeyboardLayoutList",
+                "The keyboard layouts Wine detected, written by Wine itself.",
+            ),
+        ],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine\\Mac Driver",
+        title: "Wine Mac driver",
+        description: "Configures how Wine windows interact with macOS.",
+        values: &[],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine\\Debug",
+        title: "Wine debugging",
+        description: "Configures Wine's debug channels and relay tracing.",
+        values: &[
+            ("RelayExclude", "Functions left out of relay traces."),
+            (
+                "RelayFromExclude",
+                "Modules whose calls are left out of relay traces.",
+            ),
+        ],
+    },
+    KnownKey {
+        pattern: "*\\Software\\Wine",
+        title: "Wine",
+        description: "The Wine configuration of the prefix, as edited by winecfg.",
+        values: &[("Version", "The Windows version reported to applications.")],
+    },
+    KnownKey {
+        pattern: "HKEY_LOCAL_MACHINE\\Software\\Microsoft\\Windows NT\\CurrentVersion",
+        title: "Windows version",
+        description: "Identifies the emulated Windows installation.",
+        values: &[
+            ("ProductName", "The name of the Windows edition."),
+            ("CurrentVersion", "The Windows version number."),
+            ("CurrentBuildNumber", "The Windows build number."),
+        ],
+    },
+    KnownKey {
+        pattern:
+            "HKEY_LOCAL_MACHINE\\System\\CurrentControlSet\\Control\\Session Manager\\Environment",
+        title: "System environment",
+        description: "Environment variables set for every user.",
+        values: &[("PATH", "The directories searched for programs.")],
+    },
+    KnownKey {
+        pattern: "HKEY_CURRENT_USER\\Environment",
+        title: "User environment",
+        description: "Environment variables set for the user, on top of the system ones.",
+        values: &[],
+    },
+    KnownKey {
+        pattern: "HKEY_CURRENT_USER\\Control Panel\\Desktop",
+        title: "Desktop settings",
+        description:
+            "Appearance settings of the desktop, such as font smoothing and the wallpaper.",
+        values: &[
+            (
+                "FontSmoothing",
+                "Whether font smoothing is enabled (2) or not (0).",
+            ),
+            (
+                "FontSmoothingType",
+                "The font smoothing method: 1 standard, 2 subpixel.",
+            ),
+            ("LogPixels", "The screen resolution in DPI."),
+            ("Wallpaper", "The path of the wallpaper image."),
+        ],
+    },
+];
+
+/// Returns the well-known Wine and Windows keys, from the most to the least specific.
+pub fn known_keys() -> &'static [KnownKey] {
+    KNOWN_KEYS
+}
+
+/// Describes a key, from its full name.
+///
+/// # Returns
+///
+/// The most specific well-known key matching the name exactly, or `None` if the key isn't
+/// well-known.
+pub fn describe(name: &KeyName) -> Option<&'static KnownKey> {
+    KNOWN_KEYS
+        .iter()
+        .find(|known| known.pattern().matches(name))
+}
+
+/// Describes the area a key belongs to, from its full name.
+///
+/// Unlike [describe], subkeys of well-known keys are described by their closest well-known
+/// ancestor.
+pub fn describe_area(name: &KeyName) -> Option<&'static KnownKey> {
+    KNOWN_KEYS
+        .iter()
+        .find(|known| known.pattern().matches_subtree(name))
+}
+
+impl Classifier {
+    /// Constructs a new [Classifier] labeling keys with the titles of the well-known keys.
+    pub fn known() -> Self {
+        KNOWN_KEYS.iter().fold(Self::new(), |classifier, known| {
+            classifier.with_rule(known.pattern, known.title)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_keys_and_values() {
+        let x11 = describe(&KeyName::new(
+            "HKEY_CURRENT_USER\\Software\\Wine\\X11 Driver",
+        ))
+        .unwrap();
+        assert_eq!(x11.title(), "Wine X11 driver");
+        assert!(x11.value(&ValueName::named("decorated")).is_some());
+        assert_eq!(x11.value(&ValueName::named("Unknown")), None);
+
+        let name = KeyName::new("HKEY_CURRENT_USER\\Software\\Wine\\Fonts\\Replacements");
+        assert_eq!(describe(&name).unwrap().title(), "Wine font replacements");
+        assert_eq!(
+            Classifier::known().classify(&name),
+            Some("Wine font replacements")
+        );
+
+        let nested = KeyName::new("HKEY_CURRENT_USER\\Software\\Wine\\Direct3D\\Sub");
+        assert_eq!(describe(&nested), None);
+        assert_eq!(describe_area(&nested).unwrap().title(), "Wine Direct3D");
+    }
+}
@@ -0,0 +1,326 @@
+use crate::parse::{self, Document, Syntax};
+#[cfg(feature = "fs")]
+use crate::prelude::Encoding;
+use crate::prelude::{Error, Hive, Key, LoadOptions, Registry};
+use crate::registry::relative_name;
+use regashii::KeyName;
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// A section of a registry file, indexed but not parsed yet.
+#[derive(Clone, Debug)]
+struct Span {
+    /// The 1-based line number of the section header.
+    line: usize,
+    /// The byte range of the section in the text, from its header to the next one.
+    range: Range<usize>,
+}
+
+/// A key of a [LazyRegistry], parsed on first access.
+#[derive(Debug)]
+struct Entry {
+    /// The sections defining the key, in file order.
+    spans: Vec<Span>,
+    /// The key, once parsed.
+    key: OnceCell<Key>,
+}
+
+/// A registry loaded lazily: loading only indexes where each key is defined in the file, and
+/// the values of a key are parsed the first time it is accessed.
+///
+/// This makes loading two huge files to diff a small subtree much faster than loading them
+/// with [Registry::open]:
+/// `RegistryDiff::new(&old.subtree(&prefix)?, &new.subtree(&prefix)?, &options)` only parses
+/// the keys below `prefix`.
+///
+/// Malformed key headers are reported while loading, while malformed values are only
+/// reported once their key is parsed.
+///
+/// [Registry] itself stays eager: it lends out its keys and values by reference, through
+/// [Registry::keys] among others, which parsing on access can't do without parsing every key
+/// anyway. Lazy loading is therefore opt-in, by loading files with this type instead.
+#[derive(Debug)]
+pub struct LazyRegistry {
+    /// The hive of the registry.
+    hive: Hive,
+    /// The decoded text of the file.
+    text: String,
+    /// The syntax announced by the header of the file.
+    syntax: Syntax,
+    /// The options the file is loaded with.
+    options: LoadOptions,
+    /// The keys of the file, keyed by their name relative to the hive like [Registry::keys].
+    index: BTreeMap<KeyName, Entry>,
+}
+
+impl LazyRegistry {
+    /// Indexes a registry file, loading it with the default [LoadOptions].
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - A path to the registry file.
+    /// * `hive` - The registry hive the file holds.
+    #[cfg(feature = "fs")]
+    pub fn open<T: AsRef<std::path::Path>>(file: T, hive: Hive) -> Result<Self, Error> {
+        Self::open_with(file, hive, &LoadOptions::default())
+    }
+
+    /// Indexes a registry file, loading it according to `options`.
+    #[cfg(feature = "fs")]
+    pub fn open_with<T: AsRef<std::path::Path>>(
+        file: T,
+        hive: Hive,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        let bytes = std::fs::read(file)?;
+        let encoding = options
+            .encoding()
+            .unwrap_or_else(|| Encoding::detect(&bytes));
+        Self::from_text(encoding.decode(&bytes)?, hive, options)
+    }
+
+    /// Indexes the text of a registry file, loading it according to `options`. The encoding
+    /// of `options` is ignored.
+    pub fn from_text(text: String, hive: Hive, options: &LoadOptions) -> Result<Self, Error> {
+        let mut index: BTreeMap<KeyName, Entry> = BTreeMap::new();
+        let mut header: Option<Syntax> = None;
+        // The start, line and name of the section being indexed, `None` for the text before
+        // the first section or after a malformed header.
+        let mut current: Option<(usize, usize, Option<KeyName>)> = None;
+        let mut offset = 0;
+
+        for (index_of_line, line) in text.split_inclusive('\n').enumerate() {
+            let (number, start) = (index_of_line + 1, offset);
+            offset += line.len();
+            let raw = line.trim_end_matches(['\r', '\n']);
+
+            let syntax = match header {
+                Some(syntax) => syntax,
+                None if raw.trim().trim_start_matches('\u{feff}').is_empty() => continue,
+                None => {
+                    let syntax = parse::parse(&text[..offset], options.strict())?.syntax;
+                    header = Some(syntax);
+                    current = Some((offset, number + 1, None));
+                    continue;
+                }
+            };
+            if !raw.trim().starts_with('[') {
+                continue;
+            }
+
+            if let Some(previous) = current.take() {
+                close(&text, &mut index, previous, start, syntax, options)?;
+            }
+            let name = match parse::parse_header(number, raw, syntax) {
+                Ok(section) => Some(KeyName::new(relative_name(syntax, hive, &section.name))),
+                Err(_) if !options.strict() => None,
+                Err(error) => return Err(error),
+            };
+            current = Some((start, number, name));
+        }
+
+        let syntax = match header {
+            Some(syntax) => syntax,
+            // Let the parser report the missing header.
+            None => parse::parse(&text, options.strict())?.syntax,
+        };
+        if let Some(previous) = current.take() {
+            close(&text, &mut index, previous, text.len(), syntax, options)?;
+        }
+
+        Ok(Self {
+            hive,
+            text,
+            syntax,
+            options: options.clone(),
+            index,
+        })
+    }
+
+    /// Returns the hive of the registry.
+    pub fn hive(&self) -> Hive {
+        self.hive
+    }
+
+    /// Returns the number of keys in the registry.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Returns whether the registry holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Returns the names of the keys, relative to the hive like the names of
+    /// [Registry::keys], without parsing them.
+    pub fn names(&self) -> impl Iterator<Item = &KeyName> {
+        self.index.keys()
+    }
+
+    /// Retrieves a key by its name relative to the hive, parsing it on first access.
+    ///
+    /// # Returns
+    ///
+    /// The key, `None` if the registry doesn't hold it, or an [Error] if it is malformed.
+    pub fn key(&self, name: &KeyName) -> Result<Option<&Key>, Error> {
+        let entry = match self.index.get(name) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if entry.key.get().is_none() {
+            let key = self
+                .parse(entry.spans.iter())?
+                .into_keys()
+                .into_values()
+                .next();
+            if let Some(key) = key {
+                let _ = entry.key.set(key);
+            }
+        }
+        Ok(entry.key.get())
+    }
+
+    /// Parses the keys of a subtree into a registry, leaving other keys unparsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The name of the root of the subtree relative to the hive, matched
+    ///   case-insensitively.
+    pub fn subtree(&self, prefix: &KeyName) -> Result<Registry, Error> {
+        let prefix = prefix.raw().to_lowercase();
+        let mut spans: Vec<&Span> = self
+            .index
+            .iter()
+            .filter(|(name, _)| {
+                let name = name.raw().to_lowercase();
+                name == prefix || name.starts_with(&format!("{}\\", prefix))
+            })
+            .flat_map(|(_, entry)| &entry.spans)
+            .collect();
+        // Sections are parsed in file order, so duplicate keys resolve as when loading eagerly.
+        spans.sort_by_key(|span| span.line);
+        self.parse(spans.into_iter())
+    }
+
+    /// Parses every key into a registry, like [Registry::try_from_str_with] does.
+    pub fn to_registry(&self) -> Result<Registry, Error> {
+        Registry::try_from_str_with(&self.text, self.hive, &self.options)
+    }
+
+    /// Parses indexed sections into a registry.
+    fn parse<'a, I: Iterator<Item = &'a Span>>(&self, spans: I) -> Result<Registry, Error> {
+        let mut document = Document {
+            syntax: self.syntax,
+            sections: Vec::new(),
+            warnings: Vec::new(),
+        };
+        for span in spans {
+            let part = parse_span(&self.text, span, self.syntax, self.options.strict())?;
+            document.sections.extend(part.sections);
+            document.warnings.extend(part.warnings);
+        }
+        Registry::from_document(document, self.hive, &self.options)
+    }
+}
+
+/// Parses the lines of an indexed section.
+fn parse_span(text: &str, span: &Span, syntax: Syntax, strict: bool) -> Result<Document, Error> {
+    let lines = text[span.range.clone()]
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (span.line + index, line));
+    parse::parse_sections(lines, syntax, strict)
+}
+
+/// Ends the section being indexed at `end`, recording it under its key.
+///
+/// The text before the first section holds no key, but is parsed right away so values
+/// outside of a key are reported while loading.
+fn close(
+    text: &str,
+    index: &mut BTreeMap<KeyName, Entry>,
+    (start, line, name): (usize, usize, Option<KeyName>),
+    end: usize,
+    syntax: Syntax,
+    options: &LoadOptions,
+) -> Result<(), Error> {
+    let span = Span {
+        line,
+        range: start..end,
+    };
+    match name {
+        Some(name) => index
+            .entry(name)
+            .or_insert_with(|| Entry {
+                spans: Vec::new(),
+                key: OnceCell::new(),
+            })
+            .spans
+            .push(span),
+        None if text[start..end].trim_start().starts_with('[') => {}
+        None => {
+            parse_span(text, &span, syntax, options.strict())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::prelude::{DiffOptions, RegistryDiff};
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_lazy_registry_matches_eager_loading() {
+        let eager = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let lazy = LazyRegistry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
+        assert_eq!(lazy.len(), eager.keys().len());
+        for name in lazy.names() {
+            assert_eq!(lazy.key(name).unwrap(), eager.key(name));
+        }
+        assert_eq!(lazy.to_registry().unwrap().keys(), eager.keys());
+
+        let old = LazyRegistry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = LazyRegistry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let prefix = KeyName::new("Software");
+        let subtree = RegistryDiff::new(
+            &old.subtree(&prefix).unwrap(),
+            &new.subtree(&prefix).unwrap(),
+            &DiffOptions::new(),
+        );
+        let full = RegistryDiff::new(
+            &old.to_registry().unwrap(),
+            &new.to_registry().unwrap(),
+            &DiffOptions::new(),
+        );
+        let expected: Vec<_> = full
+            .keys()
+            .keys()
+            .filter(|name| name.raw().starts_with("HKEY_CURRENT_USER\\Software\\"))
+            .collect();
+        assert_eq!(subtree.keys().keys().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_lazy_registry_reports_malformed_values_on_access() {
+        let text = "REGEDIT4\n\n[Good]\n\"A\"=dword:00000001\n\n[Bad]\n\"B\"=dword:zz\n";
+        let lazy =
+            LazyRegistry::from_text(text.to_string(), Hive::CurrentUser, &LoadOptions::new())
+                .unwrap();
+        assert!(lazy.key(&KeyName::new("Good")).unwrap().is_some());
+        match lazy.key(&KeyName::new("Bad")) {
+            Err(Error::Parse { line, .. }) => assert_eq!(line, 7),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(LazyRegistry::from_text(
+            "[Key]\n".to_string(),
+            Hive::CurrentUser,
+            &LoadOptions::new()
+        )
+        .is_err());
+    }
+}
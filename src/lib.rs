@@ -1,8 +1,201 @@
+/// Enters a tracing span at the given level until the end of the enclosing block, when the
+/// `tracing` feature is enabled. Expands to nothing otherwise.
+macro_rules! span {
+    ($level:ident, $name:literal $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(tracing::Level::$level, $name $(, $($fields)*)?).entered();
+    };
+}
+
+/// Emits a tracing event at the given level when the `tracing` feature is enabled. Expands to
+/// nothing otherwise, so the fields are only evaluated with the feature.
+macro_rules! event {
+    ($level:ident, target: $target:expr, $($fields:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(target: $target, tracing::Level::$level, $($fields)*);
+    };
+    ($level:ident, $($fields:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(tracing::Level::$level, $($fields)*);
+    };
+}
+
+mod assertions;
+#[cfg(feature = "fs")]
+mod atomic;
+mod autostart;
+#[cfg(feature = "fs")]
+mod backup;
+#[cfg(feature = "fs")]
+mod batch;
+mod binary;
+#[cfg(feature = "store")]
+mod bisect;
+mod catalog;
+mod classes;
+mod classify;
+mod cleanup;
+mod compare;
+mod conflict;
+#[cfg(all(feature = "daemon", unix))]
+mod daemon;
 mod diff;
+mod drives;
+mod duplicates;
+mod encoding;
+mod entry;
+mod environment;
+mod error;
+mod explain;
+#[cfg(feature = "fs")]
+mod file;
+#[cfg(test)]
+mod fixtures;
+mod fonts;
+mod freeze;
+#[doc(hidden)]
+pub mod fuzz;
+#[cfg(feature = "store")]
+mod history;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "ignore")]
+mod ignore;
+mod import;
+#[cfg(feature = "known-keys")]
+mod known;
+mod lazy;
+#[cfg(feature = "fs")]
+mod lock;
+mod matrix;
+mod metadata;
+mod names;
+#[cfg(feature = "fs")]
+mod orphans;
+mod overrides;
+mod parse;
+mod path;
+mod pattern;
+mod plan;
+mod policy;
+#[cfg(feature = "fs")]
+mod prefix;
+mod preview;
+mod programs;
+mod provenance;
+mod rebase;
+mod reconcile;
 mod registry;
+mod rules;
+mod script;
+mod security;
+mod services;
+#[cfg(feature = "signing")]
+mod signing;
+mod size;
+#[cfg(feature = "compression")]
+mod snapshot;
+mod stats;
+#[cfg(feature = "store")]
+mod store;
+mod stream;
+#[cfg(any(feature = "toml", feature = "yaml"))]
+mod structured;
+mod template;
+#[cfg(feature = "testkit")]
+mod testkit;
+mod transaction;
+mod validate;
+mod variables;
+#[cfg(feature = "wine")]
+mod wine;
+mod write;
 
 pub mod prelude {
-    pub use crate::diff::Diff;
-    pub use crate::registry::{Hive, Key, Registry, Value};
+    pub use crate::assertions::{assert_registries_equal, compare_report};
+    pub use crate::autostart::{AutostartChange, AutostartLocation};
+    #[cfg(feature = "fs")]
+    pub use crate::backup::{backups, create_backup, restore_backup, Backup, BackupPolicy};
+    #[cfg(feature = "fs")]
+    pub use crate::batch::{diff_batch, diff_batch_with, BatchDiff, BatchResult, BatchSummary};
+    #[cfg(feature = "store")]
+    pub use crate::bisect::Bisection;
+    pub use crate::catalog::{ApplyOrder, Catalog, CatalogPatch, Overlap};
+    pub use crate::classes::{Association, AssociationChange, Classes, ProgId};
+    pub use crate::classify::Classifier;
+    pub use crate::cleanup::CleanupRules;
+    #[cfg(feature = "unicode")]
+    pub use crate::compare::NormalizeUnicode;
+    pub use crate::compare::{Comparator, ComparatorScope, IgnoreWhitespace};
+    pub use crate::conflict::{Conflict, ConflictKind};
+    #[cfg(all(feature = "daemon", unix))]
+    pub use crate::daemon::Daemon;
+    pub use crate::diff::{
+        merge_join, Diff, DiffOptions, KeyChange, KeyDiff, MergeJoin, Operation, RegistryDiff,
+    };
+    pub use crate::drives::DriveMapping;
+    pub use crate::duplicates::DuplicateData;
+    pub use crate::encoding::Encoding;
+    pub use crate::entry::{DiffEntry, EntryId};
+    pub use crate::environment::EnvironmentChange;
+    pub use crate::error::Error;
+    #[cfg(feature = "fs")]
+    pub use crate::file::RegistryFile;
+    pub use crate::fonts::{FontReplacementChanges, FontReplacements};
+    pub use crate::freeze::{FreezeList, FrozenAction};
+    #[cfg(feature = "store")]
+    pub use crate::history::{Commit, CommitId, History};
+    #[cfg(feature = "http")]
+    pub use crate::http::HttpService;
+    #[cfg(feature = "ignore")]
+    pub use crate::ignore::IgnoreStore;
+    pub use crate::import::patch_equivalent;
+    #[cfg(feature = "known-keys")]
+    pub use crate::known::{describe, describe_area, known_keys, KnownKey};
+    pub use crate::lazy::LazyRegistry;
+    #[cfg(feature = "fs")]
+    pub use crate::lock::{wineserver_running, LockPolicy, PrefixLock};
+    pub use crate::matrix::{ComparisonMatrix, MatrixRow};
+    pub use crate::metadata::PatchMetadata;
+    pub use crate::names::{
+        escape_value_name, key_components, key_name, unescape_value_name, wine_key_name,
+    };
+    #[cfg(feature = "fs")]
+    pub use crate::orphans::Orphan;
+    pub use crate::overrides::{DllOverride, DllOverrideChange, LoadOrder, SameLoadOrder};
+    pub use crate::parse::{DuplicateKeyPolicy, LoadOptions, ParseWarning};
+    pub use crate::path::KeyPath;
+    pub use crate::pattern::KeyPattern;
+    pub use crate::plan::{ApplyPlan, PlanAction, PlanStep};
+    pub use crate::policy::{Finding, Policy, PolicyPreset, PolicyRule, Severity};
+    #[cfg(feature = "fs")]
+    pub use crate::prefix::{HiveFile, Prefix, PrefixDiff};
+    pub use crate::preview::Preview;
+    pub use crate::programs::{InstalledProgram, InstalledProgramChanges};
+    pub use crate::provenance::Provenance;
+    pub use crate::reconcile::{reconcile, reconcile_with, ReconcileOptions};
+    pub use crate::registry::{Hive, Key, Registry, Value, ValueKind, ValueMap};
+    pub use crate::rules::{NoiseAction, RulePreset};
+    pub use crate::script::ScriptFormat;
+    pub use crate::security::SecurityDescriptor;
+    pub use crate::services::{Service, ServiceChange, ServiceChanges, StartType};
+    #[cfg(feature = "signing")]
+    pub use crate::signing::{sign_binary, sign_patch, verify_binary, verify_patch};
+    pub use crate::size::SizeChange;
+    #[cfg(feature = "compression")]
+    pub use crate::snapshot::Snapshot;
+    #[cfg(feature = "store")]
+    pub use crate::store::{SnapshotId, Store};
+    pub use crate::stream::PatchWriter;
+    pub use crate::template::{Parameter, ParameterKind, Template};
+    #[cfg(feature = "testkit")]
+    pub use crate::testkit::{assert_golden, assert_golden_diff};
+    pub use crate::transaction::Transaction;
+    pub use crate::validate::{Schema, Violation, ViolationKind};
+    #[cfg(feature = "wine")]
+    pub use crate::wine::{apply_with_wine, apply_with_wine_with, WineOutput};
+    pub use crate::write::{HiveNames, KeyOrder, WriteOptions};
+    #[cfg(feature = "signing")]
+    pub use ed25519_dalek::{SigningKey, VerifyingKey};
     pub use regashii::KeyName;
 }
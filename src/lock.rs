@@ -0,0 +1,237 @@
+use crate::prelude::Error;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The name of the file locked in prefixes.
+const LOCK_FILE: &str = ".regdiff.lock";
+
+/// How long to wait for a Wine prefix to be free before reading or writing its registry
+/// files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockPolicy {
+    /// How long to wait before giving up, `None` to wait indefinitely.
+    timeout: Option<Duration>,
+    /// How often the prefix is checked while waiting.
+    poll_interval: Duration,
+    /// Whether to wait for the wineserver of the prefix to exit.
+    wait_for_wineserver: bool,
+}
+
+impl Default for LockPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Some(Duration::from_secs(30)),
+            poll_interval: Duration::from_millis(100),
+            wait_for_wineserver: true,
+        }
+    }
+}
+
+impl LockPolicy {
+    /// Constructs a new [LockPolicy] waiting up to 30 seconds for the lock and for the
+    /// wineserver of the prefix to exit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long to wait before giving up, or `None` to wait indefinitely. A timeout of
+    /// zero fails right away if the prefix is busy.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns how long to wait before giving up, if limited.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Sets how often the prefix is checked while waiting.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Returns how often the prefix is checked while waiting.
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    /// Sets whether to wait for the wineserver of the prefix to exit. A running wineserver
+    /// holds the registry in memory and overwrites the files when it flushes, so files read
+    /// meanwhile may be outdated and files written lost.
+    pub fn with_wineserver_wait(mut self, wait: bool) -> Self {
+        self.wait_for_wineserver = wait;
+        self
+    }
+
+    /// Returns whether to wait for the wineserver of the prefix to exit.
+    pub fn wineserver_wait(&self) -> bool {
+        self.wait_for_wineserver
+    }
+}
+
+/// An advisory lock on a Wine prefix, held until dropped.
+///
+/// The lock is taken on a `.regdiff.lock` file in the prefix, so it only excludes processes
+/// using these locks, not Wine itself: see [LockPolicy::with_wineserver_wait] for that.
+#[derive(Debug)]
+pub struct PrefixLock {
+    /// The locked file.
+    file: File,
+    /// The path of the locked file.
+    path: PathBuf,
+}
+
+impl PrefixLock {
+    /// Locks a prefix exclusively, to write its registry files.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The path of the prefix directory.
+    /// * `policy` - How long to wait for the prefix to be free.
+    ///
+    /// # Returns
+    ///
+    /// The lock, or [Error::Locked] if the prefix is still busy once the policy's timeout
+    /// elapsed.
+    pub fn exclusive<T: AsRef<Path>>(prefix: T, policy: &LockPolicy) -> Result<Self, Error> {
+        Self::acquire(prefix.as_ref(), policy, false)
+    }
+
+    /// Locks a prefix for reading its registry files, which other readers may do at the same
+    /// time.
+    ///
+    /// See [PrefixLock::exclusive] for details.
+    pub fn shared<T: AsRef<Path>>(prefix: T, policy: &LockPolicy) -> Result<Self, Error> {
+        Self::acquire(prefix.as_ref(), policy, true)
+    }
+
+    /// Returns the path of the locked file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Waits for the lock and, if requested, for the wineserver of the prefix to exit.
+    fn acquire(prefix: &Path, policy: &LockPolicy, shared: bool) -> Result<Self, Error> {
+        let path = prefix.join(LOCK_FILE);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let deadline = policy.timeout.map(|timeout| Instant::now() + timeout);
+        let wait = |reason: &str| {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => policy.poll_interval,
+            };
+            if remaining.is_zero() {
+                return Err(Error::Locked {
+                    path: prefix.to_path_buf(),
+                    reason: reason.to_string(),
+                });
+            }
+            std::thread::sleep(remaining.min(policy.poll_interval));
+            Ok(())
+        };
+
+        loop {
+            let locked = if shared {
+                file.try_lock_shared()
+            } else {
+                file.try_lock()
+            };
+            match locked {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    wait("another process holds its lock")?;
+                    continue;
+                }
+                Err(TryLockError::Error(error)) => return Err(error.into()),
+            }
+            if policy.wait_for_wineserver && wineserver_running(prefix) {
+                file.unlock()?;
+                wait("its wineserver is running")?;
+                continue;
+            }
+            return Ok(Self { file, path });
+        }
+    }
+}
+
+impl Drop for PrefixLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Returns whether a wineserver is running for a prefix, holding its registry in memory.
+///
+/// The wineserver is found by its socket, which Wine creates in
+/// `/tmp/.wine-<uid>/server-<device>-<inode>` after the prefix directory. On Linux, a
+/// leftover socket of a crashed wineserver is told apart by looking for the process working
+/// in that directory. Always `false` on other platforms than Unix.
+pub fn wineserver_running<T: AsRef<Path>>(prefix: T) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = match std::fs::metadata(prefix) {
+            Ok(metadata) => metadata,
+            Err(_) => return false,
+        };
+        let server = Path::new("/tmp")
+            .join(format!(".wine-{}", metadata.uid()))
+            .join(format!("server-{:x}-{:x}", metadata.dev(), metadata.ino()));
+        if !server.join("socket").exists() {
+            return false;
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let processes = match std::fs::read_dir("/proc") {
+                Ok(processes) => processes,
+                Err(_) => return true,
+            };
+            processes
+                .filter_map(Result::ok)
+                .filter_map(|process| std::fs::read_link(process.path().join("cwd")).ok())
+                .any(|cwd| cwd == server)
+        }
+        #[cfg(not(target_os = "linux"))]
+        true
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = prefix;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_lock() {
+        let prefix = std::env::temp_dir().join(format!("regdiff-lock-{}", std::process::id()));
+        std::fs::create_dir_all(&prefix).unwrap();
+        let policy = LockPolicy::new()
+            .with_timeout(Some(Duration::from_millis(50)))
+            .with_poll_interval(Duration::from_millis(10));
+
+        let first = PrefixLock::shared(&prefix, &policy).unwrap();
+        let second = PrefixLock::shared(&prefix, &policy);
+        let blocked = PrefixLock::exclusive(&prefix, &policy);
+        drop((first, second));
+        let exclusive = PrefixLock::exclusive(&prefix, &policy).is_ok();
+        let running = wineserver_running(&prefix);
+        std::fs::remove_dir_all(&prefix).unwrap();
+
+        assert!(matches!(blocked, Err(Error::Locked { .. })));
+        assert!(exclusive);
+        assert!(!running);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::conflict::same_data;
+#[cfg(feature = "fs")]
+use crate::prelude::{HiveFile, Prefix};
+use crate::prelude::{KeyPattern, Registry, Value, WriteOptions};
+use crate::write;
+use regashii::{KeyName, ValueName};
+use std::collections::BTreeMap;
+
+/// A value of a [ComparisonMatrix] across all of its columns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatrixRow {
+    /// The full name of the key.
+    key: KeyName,
+    /// The name of the value.
+    value: ValueName,
+    /// The value in each column, `None` where it is missing.
+    cells: Vec<Option<Value>>,
+    /// The columns holding other data than most columns.
+    outliers: Vec<usize>,
+}
+
+impl MatrixRow {
+    /// Returns the full name of the key.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the name of the value.
+    pub fn value(&self) -> &ValueName {
+        &self.value
+    }
+
+    /// Returns the value in each column, in column order, `None` where it is missing.
+    pub fn cells(&self) -> &[Option<Value>] {
+        &self.cells
+    }
+
+    /// Returns the indexes of the columns holding other data than the most common data, a
+    /// missing value counting as data. Empty if the value is the same everywhere, or if no
+    /// data is more common than all others, like when two columns differ.
+    pub fn outliers(&self) -> &[usize] {
+        &self.outliers
+    }
+
+    /// Returns whether every column holds the same data.
+    pub fn is_uniform(&self) -> bool {
+        self.cells
+            .windows(2)
+            .all(|pair| same_data(pair[0].as_ref(), pair[1].as_ref()))
+    }
+}
+
+/// The values of a subtree across many registries, such as the Direct3D settings of every
+/// bottle, to find the registries configured differently.
+///
+/// It is displayed as the values that differ, each with its data in every column, outliers
+/// marked with `*`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ComparisonMatrix {
+    /// The labels of the columns.
+    columns: Vec<String>,
+    /// The values found in any column, by key and value name.
+    rows: Vec<MatrixRow>,
+}
+
+impl ComparisonMatrix {
+    /// Compares the values of a subtree across registries.
+    ///
+    /// Keys are matched case-insensitively, while value names must match exactly.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - The registries to compare, with the labels of their columns.
+    /// * `subtree` - The pattern of the keys to compare, along with their subkeys, such as
+    ///   `HKEY_CURRENT_USER\Software\Wine\Direct3D`.
+    ///
+    /// # Returns
+    ///
+    /// The matrix of the values found in any of the registries, by key and value name.
+    pub fn new<T: Into<KeyPattern>>(columns: &[(&str, &Registry)], subtree: T) -> Self {
+        let columns = columns
+            .iter()
+            .map(|(label, registry)| (label.to_string(), vec![*registry]))
+            .collect();
+        Self::build(columns, &subtree.into())
+    }
+
+    /// Builds the matrix of columns made of several registries, such as the hives of a prefix.
+    fn build(columns: Vec<(String, Vec<&Registry>)>, subtree: &KeyPattern) -> Self {
+        let mut rows: BTreeMap<(String, ValueName), MatrixRow> = BTreeMap::new();
+        let count = columns.len();
+        for (column, (_, registries)) in columns.iter().enumerate() {
+            let keys = registries
+                .iter()
+                .flat_map(|registry| registry.keys().values())
+                .filter(|key| subtree.matches_subtree(key.name()));
+            for key in keys {
+                for value in key.values().values() {
+                    let id = (key.name().raw().to_lowercase(), value.name().clone());
+                    rows.entry(id)
+                        .or_insert_with(|| MatrixRow {
+                            key: key.name().clone(),
+                            value: value.name().clone(),
+                            cells: vec![None; count],
+                            outliers: Vec::new(),
+                        })
+                        .cells[column] = Some(value.clone());
+                }
+            }
+        }
+
+        let mut rows: Vec<MatrixRow> = rows.into_values().collect();
+        for row in &mut rows {
+            row.outliers = outliers(&row.cells);
+        }
+        Self {
+            columns: columns.into_iter().map(|(label, _)| label).collect(),
+            rows,
+        }
+    }
+
+    /// Returns the labels of the columns.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Returns the values found in any column, by key and value name.
+    pub fn rows(&self) -> &[MatrixRow] {
+        &self.rows
+    }
+
+    /// Returns the values that aren't the same in every column.
+    pub fn differing(&self) -> impl Iterator<Item = &MatrixRow> {
+        self.rows.iter().filter(|row| !row.is_uniform())
+    }
+
+    /// Returns how many values of each column are outliers, to spot the registries
+    /// configured differently from most others.
+    pub fn outlier_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.columns.len()];
+        for column in self.rows.iter().flat_map(|row| &row.outliers) {
+            counts[*column] += 1;
+        }
+        counts
+    }
+}
+
+impl std::fmt::Display for ComparisonMatrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let options = WriteOptions::default();
+        for row in self.differing() {
+            match &row.value {
+                ValueName::Named(name) => writeln!(f, "{}\\\"{}\":", row.key.raw(), name)?,
+                ValueName::Default => writeln!(f, "{}\\@:", row.key.raw())?,
+            }
+            for (column, (label, cell)) in self.columns.iter().zip(&row.cells).enumerate() {
+                let data = cell
+                    .as_ref()
+                    .map(|value| write::data(value, &options))
+                    .unwrap_or_else(|| "(missing)".to_string());
+                let marker = if row.outliers.contains(&column) {
+                    " *"
+                } else {
+                    ""
+                };
+                writeln!(f, "  {}: {}{}", label, data, marker)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Prefix {
+    /// Compares the values of a subtree across prefixes, such as the Direct3D settings of
+    /// every bottle, with [ComparisonMatrix::new]. Columns are labelled by the paths of the
+    /// prefixes and hold all of their hives.
+    pub fn comparison_matrix<T: Into<KeyPattern>>(
+        prefixes: &[Prefix],
+        subtree: T,
+    ) -> ComparisonMatrix {
+        let columns = prefixes
+            .iter()
+            .map(|prefix| {
+                let registries = prefix.hives().values().map(HiveFile::registry).collect();
+                (prefix.path().display().to_string(), registries)
+            })
+            .collect();
+        ComparisonMatrix::build(columns, &subtree.into())
+    }
+}
+
+/// Returns the indexes of the cells holding other data than the strictly most common data.
+fn outliers(cells: &[Option<Value>]) -> Vec<usize> {
+    // The distinct data found, with the number of cells holding it.
+    let mut groups: Vec<(Option<&Value>, usize)> = Vec::new();
+    for cell in cells {
+        match groups
+            .iter_mut()
+            .find(|(data, _)| same_data(*data, cell.as_ref()))
+        {
+            Some((_, count)) => *count += 1,
+            None => groups.push((cell.as_ref(), 1)),
+        }
+    }
+    groups.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let majority = match groups.as_slice() {
+        [(majority, first), (_, second), ..] if first > second => *majority,
+        _ => return Vec::new(),
+    };
+    cells
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| !same_data(cell.as_ref(), majority))
+        .map(|(column, _)| column)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Hive;
+
+    #[test]
+    fn test_comparison_matrix() {
+        let bottle = |renderer: &str| {
+            Registry::try_from_str(
+                &format!(
+                    "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\Wine\\Direct3D]\n\
+                    \"renderer\"=\"{}\"\n\"csmt\"=dword:00000001\n\n\
+                    [HKEY_CURRENT_USER\\Software\\Other]\n\"X\"=\"{}\"\n",
+                    renderer, renderer
+                ),
+                Hive::CurrentUser,
+            )
+            .unwrap()
+        };
+        let (a, b, c) = (bottle("gl"), bottle("gl"), bottle("vulkan"));
+
+        let matrix = ComparisonMatrix::new(
+            &[("a", &a), ("b", &b), ("c", &c)],
+            "HKEY_CURRENT_USER\\Software\\Wine\\Direct3D",
+        );
+        assert_eq!(matrix.rows().len(), 2);
+        assert_eq!(matrix.differing().count(), 1);
+        assert_eq!(matrix.outlier_counts(), [0, 0, 1]);
+        assert_eq!(
+            matrix.to_string(),
+            "HKEY_CURRENT_USER\\Software\\Wine\\Direct3D\\\"renderer\":\n  a: \"gl\"\n  \
+            b: \"gl\"\n  c: \"vulkan\" *\n"
+        );
+
+        let tied = ComparisonMatrix::new(&[("a", &a), ("c", &c)], "HKEY_CURRENT_USER\\**");
+        assert_eq!(tied.differing().count(), 2);
+        assert!(tied.rows().iter().all(|row| row.outliers().is_empty()));
+    }
+}
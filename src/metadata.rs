@@ -0,0 +1,183 @@
+/// The labels of the metadata comments of a `.reg` patch, in the order they are written.
+const LABELS: [&str; 4] = ["Name", "Description", "Author", "Target-Version"];
+
+/// Descriptive information about a patch, such as a tweak of a collection, so it can be
+/// cataloged and displayed.
+///
+/// It is written as comments right after the header of `.reg` patches, such as
+/// `; Name: Disable DXVA`, which regedit ignores, and as fields of binary diffs and JSON
+/// output. Each line of a multi-line description gets its own `; Description:` comment.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PatchMetadata {
+    /// A short name for the patch.
+    name: Option<String>,
+    /// What the patch does.
+    description: Option<String>,
+    /// Who wrote the patch.
+    author: Option<String>,
+    /// The version of the prefix or Wine the patch is meant for.
+    target_version: Option<String>,
+}
+
+impl PatchMetadata {
+    /// Constructs empty metadata.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the name of the patch.
+    pub fn with_name<T: Into<String>>(mut self, name: T) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Returns the name of the patch.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Sets the description of the patch.
+    pub fn with_description<T: Into<String>>(mut self, description: T) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Returns the description of the patch.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Sets the author of the patch.
+    pub fn with_author<T: Into<String>>(mut self, author: T) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Returns the author of the patch.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// Sets the version of the prefix or Wine the patch is meant for, such as `wine-9.0`.
+    pub fn with_target_version<T: Into<String>>(mut self, version: T) -> Self {
+        self.target_version = Some(version.into());
+        self
+    }
+
+    /// Returns the version of the prefix or Wine the patch is meant for.
+    pub fn target_version(&self) -> Option<&str> {
+        self.target_version.as_deref()
+    }
+
+    /// Returns whether no field is set.
+    pub fn is_empty(&self) -> bool {
+        self.fields().iter().all(|field| field.is_none())
+    }
+
+    /// Reads the metadata comments of a `.reg` patch, those following its header up to the
+    /// first key. Unknown comments are ignored.
+    pub fn from_patch(text: &str) -> Self {
+        let mut metadata = Self::new();
+        let lines = text
+            .lines()
+            .map(|line| line.trim_start_matches('\u{feff}').trim())
+            .skip_while(|line| line.is_empty())
+            .skip(1)
+            .take_while(|line| !line.starts_with('['));
+        for line in lines {
+            let (label, value) = match line.strip_prefix(';').and_then(|c| c.split_once(':')) {
+                Some((label, value)) => (label.trim(), value.trim()),
+                None => continue,
+            };
+            let field = match LABELS
+                .iter()
+                .position(|known| known.eq_ignore_ascii_case(label))
+            {
+                Some(0) => &mut metadata.name,
+                Some(1) => &mut metadata.description,
+                Some(2) => &mut metadata.author,
+                Some(3) => &mut metadata.target_version,
+                _ => continue,
+            };
+            match field {
+                Some(existing) => {
+                    existing.push('\n');
+                    existing.push_str(value);
+                }
+                None => *field = Some(value.to_string()),
+            }
+        }
+        metadata
+    }
+
+    /// Returns the fields, in the order of [LABELS].
+    pub(crate) fn fields(&self) -> [Option<&str>; 4] {
+        [
+            self.name(),
+            self.description(),
+            self.author(),
+            self.target_version(),
+        ]
+    }
+
+    /// Sets the fields, in the order of [LABELS].
+    pub(crate) fn from_fields(
+        [name, description, author, target_version]: [Option<String>; 4],
+    ) -> Self {
+        Self {
+            name,
+            description,
+            author,
+            target_version,
+        }
+    }
+
+    /// Returns the comment lines written after the header of a `.reg` patch, each ending with
+    /// a line break.
+    pub(crate) fn comments(&self) -> String {
+        let mut comments = String::new();
+        for (label, field) in LABELS.iter().zip(self.fields()) {
+            for line in field.iter().flat_map(|field| field.lines()) {
+                comments.push_str(&format!("; {}: {}\n", label, line));
+            }
+        }
+        comments
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+    use crate::prelude::{DiffOptions, Hive, Registry, RegistryDiff};
+
+    #[test]
+    fn test_patch_metadata_round_trip() {
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let metadata = PatchMetadata::new()
+            .with_name("Disable DXVA")
+            .with_description("Turns DXVA off.\nHelps with video glitches.")
+            .with_author("Bottles")
+            .with_target_version("wine-9.0");
+        let diff =
+            RegistryDiff::new(&old, &new, &DiffOptions::new()).with_metadata(metadata.clone());
+
+        let patch = diff.serialize().unwrap();
+        assert!(patch.contains("\n; Name: Disable DXVA\n; Description: Turns DXVA off.\n"));
+        assert_eq!(patch.len(), diff.estimated_patch_size());
+        assert_eq!(PatchMetadata::from_patch(&patch), metadata);
+        assert_eq!(
+            RegistryDiff::from_patch_str(&patch, &old)
+                .unwrap()
+                .metadata(),
+            &metadata
+        );
+        assert_eq!(
+            RegistryDiff::from_binary(&diff.to_binary())
+                .unwrap()
+                .metadata(),
+            &metadata
+        );
+        assert!(PatchMetadata::from_patch("REGEDIT4\n\n[A]\n; Name: ignored\n").is_empty());
+    }
+}
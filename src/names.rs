@@ -0,0 +1,132 @@
+use crate::parse;
+use crate::prelude::{Error, WriteOptions};
+use crate::write;
+use regashii::{KeyName, ValueName};
+
+/// Builds a key name from its components, such as `["Software", "Wine", "Direct3D"]`.
+///
+/// Key names in registry files are written as-is between brackets, so a component may hold
+/// `]`, quotes and any non-ASCII character, but not the `\` separating components nor a line
+/// break. A name starting with `-` would be read back as the deletion of a key, so the first
+/// component can't start with one.
+///
+/// # Returns
+///
+/// The key name, or [Error::InvalidName] if a component can't be represented.
+pub fn key_name<I: IntoIterator<Item = S>, S: AsRef<str>>(components: I) -> Result<KeyName, Error> {
+    let mut name = String::new();
+    for component in components {
+        let component = component.as_ref();
+        let invalid = |message: &str| Error::InvalidName {
+            name: component.to_string(),
+            message: message.to_string(),
+        };
+        if component.is_empty() {
+            return Err(invalid("empty key name component"));
+        }
+        if component.contains('\\') {
+            return Err(invalid("key name components can't contain '\\'"));
+        }
+        if component.contains(['\r', '\n']) {
+            return Err(invalid("key names can't contain line breaks"));
+        }
+        if name.is_empty() && component.starts_with('-') {
+            return Err(invalid("key names can't start with '-'"));
+        }
+
+        if !name.is_empty() {
+            name.push('\\');
+        }
+        name.push_str(component);
+    }
+    if name.is_empty() {
+        return Err(Error::InvalidName {
+            name,
+            message: "empty key name".to_string(),
+        });
+    }
+    Ok(KeyName::new(name))
+}
+
+/// Returns the components of a key name, the reverse of [key_name].
+pub fn key_components(name: &KeyName) -> impl Iterator<Item = &str> {
+    name.raw().split('\\')
+}
+
+/// Builds a key name from its form in Wine's `system.reg`, `user.reg` and `userdef.reg` files,
+/// where backslashes are doubled and non-ASCII characters are written as `\x` codes, such as
+/// `Software\\Wine\\\x65e5\x672c`.
+pub fn wine_key_name<T: AsRef<str>>(escaped: T) -> KeyName {
+    KeyName::new(parse::unescape(escaped.as_ref(), parse::Syntax::Wine))
+}
+
+/// Escapes a value name or string data the way regedit writes them between quotes, doubling
+/// backslashes and escaping quotes.
+pub fn escape_value_name<T: AsRef<str>>(name: T) -> String {
+    write::escape(name.as_ref(), &WriteOptions::default())
+}
+
+/// Unescapes a value name or string data written between quotes by regedit, the reverse of
+/// [escape_value_name].
+pub fn unescape_value_name<T: AsRef<str>>(escaped: T) -> String {
+    parse::unescape(escaped.as_ref(), parse::Syntax::Regedit5)
+}
+
+/// Returns whether two value names are equal, ignoring case like Windows does.
+pub(crate) fn same_value_name(a: &ValueName, b: &ValueName) -> bool {
+    match (a, b) {
+        (ValueName::Default, ValueName::Default) => true,
+        (ValueName::Named(a), ValueName::Named(b)) => a.to_lowercase() == b.to_lowercase(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{Hive, Registry};
+
+    #[test]
+    fn test_key_name() {
+        let name = key_name(["Software", "Odd]Name", "\"Quoted\"", "日本語"]).unwrap();
+        assert_eq!(name.raw(), "Software\\Odd]Name\\\"Quoted\"\\日本語");
+        assert_eq!(
+            key_components(&name).collect::<Vec<_>>(),
+            ["Software", "Odd]Name", "\"Quoted\"", "日本語"]
+        );
+        assert!(key_name(["Software", "A\\B"]).is_err());
+        assert!(key_name(["Software", ""]).is_err());
+        assert!(key_name(["-Software"]).is_err());
+        assert!(key_name(Vec::<&str>::new()).is_err());
+        assert_eq!(
+            wine_key_name("Software\\\\Wine\\\\\\x65e5\\x672c").raw(),
+            "Software\\Wine\\日本"
+        );
+
+        let escaped = escape_value_name("C:\\\"a]\"");
+        assert_eq!(escaped, "C:\\\\\\\"a]\\\"");
+        assert_eq!(unescape_value_name(escaped), "C:\\\"a]\"");
+    }
+
+    #[test]
+    fn test_special_names_round_trip() {
+        let name = key_name(["Software", "Odd]Name", "日本語 \"x\""]).unwrap();
+        let registry = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4).with(
+                name.clone(),
+                regashii::Key::new()
+                    .with(
+                        ValueName::named("a\\b\"c]d"),
+                        regashii::Value::Sz("é\\\"".to_string()),
+                    )
+                    .with(ValueName::named("ü"), regashii::Value::Dword(1)),
+            ),
+            Hive::CurrentUser,
+        );
+        let text = registry.serialize().unwrap();
+        assert!(text.contains("\"a\\\\b\\\"c]d\"=\"é\\\\\\\"\""));
+        let loaded = Registry::try_from_str(&text, Hive::CurrentUser).unwrap();
+        assert_eq!(loaded.keys(), registry.keys());
+        assert!(loaded.key(&name).is_some());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::prelude::{DriveMapping, Registry};
+use regashii::{KeyName, ValueName};
+use std::path::{Component, Path, PathBuf};
+
+/// A value referring to a file missing from the host filesystem, as found by
+/// [Registry::orphans].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Orphan {
+    /// The full name of the key.
+    key: KeyName,
+    /// The name of the value.
+    value: ValueName,
+    /// The Windows path found in the value.
+    path: String,
+    /// The host path the Windows path maps to.
+    host: PathBuf,
+}
+
+impl Orphan {
+    /// Returns the full name of the key holding the value.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the name of the value.
+    pub fn value(&self) -> &ValueName {
+        &self.value
+    }
+
+    /// Returns the Windows path found in the value, such as `C:\Program Files\App\app.exe`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the missing host path the Windows path maps to.
+    pub fn host(&self) -> &Path {
+        &self.host
+    }
+}
+
+impl std::fmt::Display for Orphan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let value = match &self.value {
+            ValueName::Named(name) => format!("value '{}'", name),
+            ValueName::Default => "default value".to_string(),
+        };
+        write!(
+            f,
+            "{} of key '{}' refers to missing file '{}'",
+            value,
+            self.key.raw(),
+            self.path
+        )
+    }
+}
+
+impl Registry {
+    /// Finds the string values referring to files missing from the host filesystem, such as
+    /// leftovers of uninstalled programs. This reads the filesystem, so it can be slow on big
+    /// registries.
+    ///
+    /// A value refers to a file if its data is an absolute path on a drive of `drives`, such
+    /// as `C:\windows\notepad.exe`, possibly quoted and followed by arguments, like command
+    /// lines, or followed by an icon index, like `C:\app.dll,-101`. Paths are looked up
+    /// case-insensitively, like Wine does. Data holding environment variables, relative paths
+    /// and paths on unmapped drives is skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `drives` - The drives of the Wine prefix, e.g. from [DriveMapping::from_prefix].
+    ///
+    /// # Returns
+    ///
+    /// The values referring to missing files, in key order.
+    pub fn orphans(&self, drives: &DriveMapping) -> Vec<Orphan> {
+        let mut orphans = Vec::new();
+        for key in self.keys().values() {
+            for value in key.values().values() {
+                let data = match value.value() {
+                    regashii::Value::Sz(data) | regashii::Value::ExpandSz(data) => data,
+                    _ => continue,
+                };
+                if data.contains('%') {
+                    continue;
+                }
+                let candidates = candidates(data);
+                let found = candidates
+                    .iter()
+                    .filter_map(|path| Some((*path, drives.to_host(path)?)))
+                    .collect::<Vec<_>>();
+                let (path, host) = match found.first() {
+                    Some(first) => first.clone(),
+                    None => continue,
+                };
+                if found.iter().all(|(_, host)| !exists(host)) {
+                    orphans.push(Orphan {
+                        key: key.name().clone(),
+                        value: value.name().clone(),
+                        path: path.to_string(),
+                        host,
+                    });
+                }
+            }
+        }
+        orphans
+    }
+}
+
+/// Returns the paths a string may refer to, the most likely first: the whole string, or its
+/// quoted first part, then the string without an icon index and the parts of a command line
+/// ending before its arguments.
+fn candidates(data: &str) -> Vec<&str> {
+    let data = data.trim();
+    if let Some(quoted) = data.strip_prefix('"') {
+        return quoted.split('"').next().into_iter().collect();
+    }
+    let mut candidates = vec![data];
+    if let Some((path, index)) = data.rsplit_once(',') {
+        if index.trim().parse::<i64>().is_ok() {
+            candidates.push(path);
+        }
+    }
+    candidates.extend(data.match_indices(' ').map(|(end, _)| &data[..end]));
+    candidates
+}
+
+/// Returns whether a host path exists, matching each component case-insensitively if it
+/// doesn't exist as written.
+fn exists(path: &Path) -> bool {
+    if path.exists() {
+        return true;
+    }
+    let mut current = PathBuf::new();
+    for component in path.components() {
+        let name = match component {
+            Component::Normal(name) => name,
+            other => {
+                current.push(other);
+                continue;
+            }
+        };
+        let exact = current.join(name);
+        if exact.exists() {
+            current = exact;
+            continue;
+        }
+        let name = name.to_string_lossy().to_lowercase();
+        let entries = match std::fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(_) => return false,
+        };
+        match entries
+            .filter_map(Result::ok)
+            .find(|entry| entry.file_name().to_string_lossy().to_lowercase() == name)
+        {
+            Some(entry) => current = entry.path(),
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Hive;
+
+    #[test]
+    fn test_orphans() {
+        let drive = std::env::temp_dir().join(format!("regdiff-orphans-{}", std::process::id()));
+        std::fs::create_dir_all(drive.join("Program Files/App")).unwrap();
+        std::fs::write(drive.join("Program Files/App/app.exe"), b"").unwrap();
+        let registry = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\
+            \"Exe\"=\"C:\\\\program files\\\\app\\\\APP.exe\"\n\
+            \"Run\"=\"\\\"C:\\\\Program Files\\\\App\\\\app.exe\\\" /silent\"\n\
+            \"Args\"=\"C:\\\\Program Files\\\\App\\\\app.exe /silent\"\n\
+            \"Icon\"=\"C:\\\\Program Files\\\\App\\\\app.exe,-101\"\n\
+            \"Gone\"=\"C:\\\\Program Files\\\\Old\\\\old.exe\"\n\
+            \"Other\"=\"D:\\\\missing.exe\"\n\"Text\"=\"hello\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+
+        let orphans = registry.orphans(&DriveMapping::new().with('c', &drive));
+        std::fs::remove_dir_all(&drive).unwrap();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].value(), &ValueName::named("Gone"));
+        assert_eq!(orphans[0].host(), drive.join("Program Files/Old/old.exe"));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::prelude::{Comparator, KeyPattern, Operation, RegistryDiff, Value};
+use regashii::ValueName;
+
+/// The keys holding DLL overrides, for the whole prefix and for single applications.
+const OVERRIDE_KEYS: [&str; 2] = [
+    "*\\Software\\Wine\\DllOverrides",
+    "*\\Software\\Wine\\AppDefaults\\*\\DllOverrides",
+];
+
+/// A version of a DLL Wine may load.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LoadOrder {
+    /// The DLL shipped by Windows or the application (`native`, `n`).
+    Native,
+    /// The DLL implemented by Wine (`builtin`, `b`).
+    Builtin,
+}
+
+/// How Wine loads a DLL, as set in its `DllOverrides` key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DllOverride {
+    /// The versions tried, in order. Empty if the DLL is disabled.
+    order: Vec<LoadOrder>,
+}
+
+impl DllOverride {
+    /// Parses the data of an override, e.g. `native,builtin` or `n, b`.
+    ///
+    /// Like Wine, only the first letter of each entry is significant and unknown entries are
+    /// skipped, so empty data disables the DLL.
+    pub fn parse(data: &str) -> Self {
+        let order = data
+            .split(',')
+            .filter_map(|entry| match entry.trim().chars().next() {
+                Some('n' | 'N') => Some(LoadOrder::Native),
+                Some('b' | 'B') => Some(LoadOrder::Builtin),
+                _ => None,
+            })
+            .collect();
+        Self { order }
+    }
+
+    /// Returns the versions tried, in order.
+    pub fn order(&self) -> &[LoadOrder] {
+        &self.order
+    }
+
+    /// Returns whether the DLL is disabled, i.e. never loaded.
+    pub fn is_disabled(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Parses the override held by a value, if it holds a string.
+    fn of(value: &Value) -> Option<Self> {
+        match value.value() {
+            regashii::Value::Sz(data) => Some(Self::parse(data)),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for DllOverride {
+    /// Formats the override the way winecfg writes it, e.g. `native,builtin`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let entries: Vec<&str> = self
+            .order
+            .iter()
+            .map(|order| match order {
+                LoadOrder::Native => "native",
+                LoadOrder::Builtin => "builtin",
+            })
+            .collect();
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+/// A change to how Wine loads a DLL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DllOverrideChange {
+    /// The executable the override is specific to, or `None` for the whole prefix.
+    application: Option<String>,
+    /// The name of the DLL, e.g. `d3d11` or `*d3d9`.
+    dll: String,
+    /// The override before the change, or `None` if there was none.
+    before: Option<DllOverride>,
+    /// The override after the change, or `None` if it was removed.
+    after: Option<DllOverride>,
+}
+
+impl DllOverrideChange {
+    /// Returns the executable the override is specific to, or `None` for the whole prefix.
+    pub fn application(&self) -> Option<&str> {
+        self.application.as_deref()
+    }
+
+    /// Returns the name of the DLL, as written in the registry.
+    pub fn dll(&self) -> &str {
+        &self.dll
+    }
+
+    /// Returns the override before the change, or `None` if there was none.
+    pub fn before(&self) -> Option<&DllOverride> {
+        self.before.as_ref()
+    }
+
+    /// Returns the override after the change, or `None` if it was removed.
+    pub fn after(&self) -> Option<&DllOverride> {
+        self.after.as_ref()
+    }
+}
+
+impl std::fmt::Display for DllOverrideChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let describe = |order: Option<&DllOverride>| match order {
+            None => "default".to_string(),
+            Some(order) if order.is_disabled() => "disabled".to_string(),
+            Some(order) => order.to_string(),
+        };
+        if let Some(application) = &self.application {
+            write!(f, "{}: ", application)?;
+        }
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.dll,
+            describe(self.before.as_ref()),
+            describe(self.after.as_ref())
+        )
+    }
+}
+
+/// A [Comparator] treating DLL overrides as equal when they load the same versions in the
+/// same order, e.g. `native, builtin` and `n,b`.
+///
+/// Register it for the `DllOverrides` keys with
+/// [ComparatorScope::Key](crate::prelude::ComparatorScope::Key).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SameLoadOrder;
+
+impl Comparator for SameLoadOrder {
+    fn equal(&self, old: &regashii::Value, new: &regashii::Value) -> bool {
+        match (old, new) {
+            (regashii::Value::Sz(old), regashii::Value::Sz(new)) => {
+                DllOverride::parse(old) == DllOverride::parse(new)
+            }
+            _ => old == new,
+        }
+    }
+}
+
+impl RegistryDiff {
+    /// Returns the changes to Wine's DLL overrides, for the whole prefix and for single
+    /// applications.
+    ///
+    /// Overrides whose data changed without changing the load order, such as `native,builtin`
+    /// becoming `n,b`, are left out.
+    pub fn dll_overrides(&self) -> Vec<DllOverrideChange> {
+        let patterns = OVERRIDE_KEYS.map(KeyPattern::new);
+        let mut changes = Vec::new();
+
+        for key in self.keys().values() {
+            let application = if patterns[0].matches(key.name()) {
+                None
+            } else if patterns[1].matches(key.name()) {
+                key.name().raw().rsplit('\\').nth(1).map(str::to_string)
+            } else {
+                continue;
+            };
+
+            for (name, operation) in key.values() {
+                let dll = match name {
+                    ValueName::Named(dll) => dll.clone(),
+                    ValueName::Default => continue,
+                };
+                let (before, after) = match operation {
+                    Operation::Add { data } => (None, DllOverride::of(data)),
+                    Operation::Delete { data } => (DllOverride::of(data), None),
+                    Operation::Modify { old_data, new_data }
+                    | Operation::Volatile { old_data, new_data }
+                    | Operation::Reordered { old_data, new_data } => {
+                        (DllOverride::of(old_data), DllOverride::of(new_data))
+                    }
+                    Operation::Unchanged => continue,
+                };
+                if before != after {
+                    changes.push(DllOverrideChange {
+                        application: application.clone(),
+                        dll,
+                        before,
+                        after,
+                    });
+                }
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::registry;
+    use crate::prelude::{ComparatorScope, DiffOptions, Hive};
+    use regashii::KeyName;
+
+    #[test]
+    fn test_parse_override() {
+        let order = DllOverride::parse("n, builtin");
+        assert_eq!(order.order(), &[LoadOrder::Native, LoadOrder::Builtin]);
+        assert_eq!(order.to_string(), "native,builtin");
+        assert!(DllOverride::parse("").is_disabled());
+    }
+
+    #[test]
+    fn test_dll_override_changes() {
+        let old = registry(
+            Hive::CurrentUser,
+            &[
+                ("Software\\Wine\\DllOverrides", "d3d11", "builtin"),
+                ("Software\\Wine\\DllOverrides", "dxgi", "native,builtin"),
+            ],
+        );
+        let new = registry(
+            Hive::CurrentUser,
+            &[
+                ("Software\\Wine\\DllOverrides", "d3d11", "native,builtin"),
+                ("Software\\Wine\\DllOverrides", "dxgi", "n,b"),
+                (
+                    "Software\\Wine\\AppDefaults\\game.exe\\DllOverrides",
+                    "xinput1_3",
+                    "",
+                ),
+            ],
+        );
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+
+        let changes: Vec<String> = diff
+            .dll_overrides()
+            .iter()
+            .map(DllOverrideChange::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "game.exe: xinput1_3: default -> disabled",
+                "d3d11: builtin -> native,builtin",
+            ]
+        );
+
+        let options = DiffOptions::new().with_comparator(
+            ComparatorScope::Key(KeyPattern::new(OVERRIDE_KEYS[0])),
+            SameLoadOrder,
+        );
+        let diff = RegistryDiff::new(&old, &new, &options);
+        let key = diff
+            .key(&KeyName::new(
+                "HKEY_CURRENT_USER\\Software\\Wine\\DllOverrides",
+            ))
+            .unwrap();
+        assert_eq!(key.values().len(), 1);
+    }
+}
@@ -0,0 +1,1030 @@
+use crate::prelude::{Encoding, Error, SecurityDescriptor, Value};
+use regashii::ValueName;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// The syntaxes of textual registry files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Syntax {
+    /// `REGEDIT4` files, storing strings in the ANSI code page.
+    Regedit4,
+    /// `Windows Registry Editor Version 5.00` files.
+    Regedit5,
+    /// `WINE REGISTRY Version 2` files, as found in Wine prefixes.
+    Wine,
+}
+
+/// A `[key]` section of a registry file.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Section {
+    /// The unescaped key name, as written in the file.
+    pub(crate) name: String,
+    /// Whether the section deletes the key (`[-key]`).
+    pub(crate) deleted: bool,
+    /// The 1-based line number of the section header.
+    pub(crate) line: usize,
+    /// The values of the section, in file order.
+    pub(crate) values: Vec<Value>,
+    /// The 1-based line numbers of the values, in the same order.
+    pub(crate) lines: Vec<usize>,
+    /// The security descriptor from a `#security=` line, if any.
+    pub(crate) security: Option<SecurityDescriptor>,
+    /// The target of a symbolic link key, from its `SymbolicLinkValue`.
+    pub(crate) link: Option<String>,
+    /// The time the key was last written, in seconds since the Unix epoch, from the number
+    /// following the header in Wine registry files.
+    pub(crate) modified: Option<u64>,
+    /// A hash of the lines following the header, equal for sections written identically.
+    pub(crate) fingerprint: u64,
+}
+
+/// A parsed registry file.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Document {
+    /// The syntax the file was written in.
+    pub(crate) syntax: Syntax,
+    /// The key sections, in file order. The same key may appear in several sections.
+    pub(crate) sections: Vec<Section>,
+    /// The malformed lines skipped while parsing leniently.
+    pub(crate) warnings: Vec<ParseWarning>,
+}
+
+/// A malformed line skipped while loading a registry file leniently.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// The 1-based line number of the malformed data.
+    line: usize,
+    /// The 1-based column of the malformed data, counted in characters.
+    column: usize,
+    /// The malformed text.
+    text: String,
+    /// A description of the problem.
+    message: String,
+}
+
+impl ParseWarning {
+    /// Returns the 1-based line number of the malformed data.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the 1-based column of the malformed data, counted in characters.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Returns the malformed text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns a description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {} near '{}'",
+            self.line, self.column, self.message, self.text
+        )
+    }
+}
+
+impl From<Error> for ParseWarning {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Parse {
+                line,
+                column,
+                text,
+                message,
+            } => Self {
+                line,
+                column,
+                text,
+                message,
+            },
+            other => Self {
+                line: 0,
+                column: 0,
+                text: String::new(),
+                message: other.to_string(),
+            },
+        }
+    }
+}
+
+/// How to handle a key defined by more than one section of a registry file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateKeyPolicy {
+    /// The last section replaces earlier ones entirely.
+    LastWins,
+    /// The first section is kept and later ones are ignored.
+    FirstWins,
+    /// The values of all sections are combined, later values replacing earlier ones with the
+    /// same name. This is how both regedit and Wine import such files.
+    #[default]
+    Merge,
+    /// Loading fails with [Error::DuplicateKey].
+    Error,
+}
+
+/// Options controlling how registry files are loaded.
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    /// How keys defined more than once are handled.
+    duplicate_keys: DuplicateKeyPolicy,
+    /// Whether malformed lines abort loading instead of being skipped.
+    strict: bool,
+    /// The encoding of the file, or `None` to detect it.
+    encoding: Option<Encoding>,
+    /// Whether values record the file and line they were read from.
+    provenance: bool,
+    /// Whether doubly-encoded strings are repaired.
+    repair_mojibake: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            duplicate_keys: DuplicateKeyPolicy::default(),
+            strict: true,
+            encoding: None,
+            provenance: false,
+            repair_mojibake: false,
+        }
+    }
+}
+
+impl LoadOptions {
+    /// Constructs a new [LoadOptions] with the default behaviour.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the options with keys defined more than once handled according to `policy`.
+    pub fn with_duplicate_keys(mut self, policy: DuplicateKeyPolicy) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Returns how keys defined more than once are handled.
+    pub fn duplicate_keys(&self) -> DuplicateKeyPolicy {
+        self.duplicate_keys
+    }
+
+    /// Returns the options with strict parsing enabled or disabled.
+    ///
+    /// Strict parsing, the default, fails on the first malformed line (bad hex data,
+    /// unterminated strings, unknown value types...). Lenient parsing skips such lines and
+    /// collects them as [ParseWarning]s, available from [Registry::warnings](crate::prelude::Registry::warnings).
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Returns whether malformed lines abort loading.
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Returns the options with files decoded as `encoding` instead of detecting it.
+    ///
+    /// By default the encoding is detected with [Encoding::detect], which recognizes UTF-16LE
+    /// exports with or without a byte order mark, UTF-8 and ANSI files.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Returns the encoding files are decoded as, or `None` if it is detected.
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
+
+    /// Returns the options with values recording the file and line they were read from as
+    /// their [Provenance](crate::prelude::Provenance), or not, the default.
+    pub fn with_provenance(mut self, provenance: bool) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Returns whether values record the file and line they were read from.
+    pub fn provenance(&self) -> bool {
+        self.provenance
+    }
+
+    /// Returns the options with mojibake repaired, or not, the default.
+    ///
+    /// Old Wine versions and some installers stored UTF-8 text decoded as Windows-1252 or
+    /// Latin-1, such as `cafÃ©` for `café`. With repair enabled, key names, value names and
+    /// string data that decode as UTF-8 once mapped back to bytes are replaced with the
+    /// decoded text when loading, so diffs compare the repaired content. Text that doesn't
+    /// decode, such as ordinary accented text, is left alone.
+    pub fn with_repair_mojibake(mut self, repair: bool) -> Self {
+        self.repair_mojibake = repair;
+        self
+    }
+
+    /// Returns whether mojibake is repaired.
+    pub fn repair_mojibake(&self) -> bool {
+        self.repair_mojibake
+    }
+}
+
+/// The message of the warnings reporting the lines of a key with a malformed header.
+const UNDER_MALFORMED_KEY: &str = "line of a key with a malformed header";
+
+/// Parses the text of a registry file.
+///
+/// In strict mode the first malformed line aborts parsing. Otherwise malformed lines are
+/// skipped and reported as warnings, and so are the values and security descriptors of keys
+/// with a malformed header, each with its own warning.
+pub(crate) fn parse(text: &str, strict: bool) -> Result<Document, Error> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line));
+
+    let syntax = loop {
+        let (number, line) = lines
+            .next()
+            .ok_or_else(|| Logical::new(1, "").error(fault("", "missing file header")))?;
+        match line.trim() {
+            "" => continue,
+            "REGEDIT4" => break Syntax::Regedit4,
+            "Windows Registry Editor Version 5.00" => break Syntax::Regedit5,
+            "WINE REGISTRY Version 2" => break Syntax::Wine,
+            header => {
+                let logical = Logical::new(number, line);
+                return Err(logical.error(fault(header, "unknown file header")));
+            }
+        }
+    };
+
+    parse_sections(lines, syntax, strict)
+}
+
+/// Parses the key sections following the header of a registry file.
+///
+/// # Arguments
+///
+/// * `lines` - The lines to parse, with their 1-based line numbers in the file.
+/// * `syntax` - The syntax announced by the header of the file.
+/// * `strict` - Whether the first malformed line aborts parsing.
+pub(crate) fn parse_sections<'a, I: Iterator<Item = (usize, &'a str)>>(
+    lines: I,
+    syntax: Syntax,
+    strict: bool,
+) -> Result<Document, Error> {
+    // Hashes the lines of the current section, including continuation lines consumed while
+    // parsing values, so sections written identically can be recognized without comparing
+    // their values. Key headers start a new hash.
+    let new_hasher = || {
+        let mut hasher = DefaultHasher::new();
+        (syntax as u8).hash(&mut hasher);
+        hasher
+    };
+    let hasher = RefCell::new(new_hasher());
+    let mut lines = lines.inspect(|(_, line)| {
+        if !line.trim_start().starts_with('[') {
+            line.hash(&mut *hasher.borrow_mut());
+        }
+    });
+    let mut sections: Vec<Section> = Vec::new();
+    let mut warnings = Vec::new();
+    // Set while the values following a malformed key header are being skipped.
+    let mut skipping = false;
+
+    while let Some((number, raw)) = lines.next() {
+        let line = raw.trim();
+        if line.starts_with("#security=") {
+            let result = parse_security(number, raw, &mut lines).and_then(|security| {
+                match sections.last_mut() {
+                    _ if skipping => {
+                        let logical = Logical::new(number, raw);
+                        return Err(logical.error(fault(&logical.text, UNDER_MALFORMED_KEY)));
+                    }
+                    Some(section) => section.security = Some(security),
+                    None => {}
+                }
+                Ok(())
+            });
+            match result {
+                Ok(()) => {}
+                Err(error) if !strict => warnings.push(ParseWarning::from(error)),
+                Err(error) => return Err(error),
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+
+        let result = if line.starts_with('[') {
+            let fingerprint = hasher.replace(new_hasher()).finish();
+            match sections.last_mut() {
+                Some(section) if !skipping => section.fingerprint = fingerprint,
+                _ => {}
+            }
+            parse_header(number, raw, syntax).map(|section| {
+                sections.push(section);
+                skipping = false;
+            })
+        } else {
+            parse_value(number, raw, &mut lines, syntax).and_then(|(logical, value)| {
+                match sections.last_mut() {
+                    _ if skipping => {
+                        let text = logical.text.as_str();
+                        return Err(logical.error(fault(text, UNDER_MALFORMED_KEY)));
+                    }
+                    Some(section) => match link_target(&value) {
+                        Some(target) => section.link = Some(target),
+                        None => {
+                            section.values.push(value);
+                            section.lines.push(number);
+                        }
+                    },
+                    None => {
+                        let text = logical.text.as_str();
+                        return Err(logical.error(fault(text, "value outside of a key")));
+                    }
+                }
+                Ok(())
+            })
+        };
+
+        match result {
+            Ok(()) => {}
+            Err(error) if !strict => {
+                skipping |= line.starts_with('[');
+                warnings.push(ParseWarning::from(error));
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    drop(lines);
+    match sections.last_mut() {
+        Some(section) if !skipping => section.fingerprint = hasher.into_inner().finish(),
+        _ => {}
+    }
+    Ok(Document {
+        syntax,
+        sections,
+        warnings,
+    })
+}
+
+/// A logical line of a registry file, made of a physical line and its continuation lines.
+struct Logical<'a> {
+    /// The joined text, without the backslashes continuing lines and surrounding whitespace.
+    text: String,
+    /// The physical lines the text was joined from.
+    segments: Vec<Segment<'a>>,
+}
+
+/// A physical line forming part of a [Logical] line.
+struct Segment<'a> {
+    /// The byte offset of the segment in the joined text.
+    start: usize,
+    /// The 1-based line number of the physical line.
+    line: usize,
+    /// The physical line.
+    physical: &'a str,
+    /// The byte offset in the physical line where the segment starts.
+    offset: usize,
+}
+
+impl<'a> Logical<'a> {
+    /// Constructs a logical line from a single physical line.
+    fn new(line: usize, physical: &'a str) -> Self {
+        let mut logical = Self {
+            text: String::new(),
+            segments: Vec::new(),
+        };
+        logical.push(line, physical);
+        logical
+    }
+
+    /// Appends the trimmed contents of a physical line.
+    fn push(&mut self, line: usize, physical: &'a str) {
+        let trimmed = physical.trim();
+        self.segments.push(Segment {
+            start: self.text.len(),
+            line,
+            physical,
+            offset: physical.len() - physical.trim_start().len(),
+        });
+        self.text.push_str(trimmed);
+    }
+
+    /// Constructs a parse error for a fault, locating its text in the physical lines.
+    ///
+    /// Faults whose text isn't a slice of the joined text are reported at the start of the line.
+    fn error(&self, fault: Fault) -> Error {
+        let base = self.text.as_ptr() as usize;
+        let address = fault.text.as_ptr() as usize;
+        let offset = match address.checked_sub(base) {
+            Some(offset) if offset <= self.text.len() => offset,
+            _ => 0,
+        };
+
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|segment| segment.start <= offset)
+            .unwrap_or(&self.segments[0]);
+        let position = (segment.offset + offset - segment.start).min(segment.physical.len());
+        let column = segment
+            .physical
+            .char_indices()
+            .take_while(|(index, _)| *index < position)
+            .count()
+            + 1;
+
+        Error::Parse {
+            line: segment.line,
+            column,
+            text: fault.text.to_string(),
+            message: fault.message,
+        }
+    }
+}
+
+/// A problem found while parsing, pointing at the offending text.
+struct Fault<'a> {
+    /// The offending text, a slice of the line being parsed.
+    text: &'a str,
+    /// A description of the problem.
+    message: String,
+}
+
+/// Constructs a [Fault] for the given text.
+fn fault<T: Into<String>>(text: &str, message: T) -> Fault<'_> {
+    Fault {
+        text,
+        message: message.into(),
+    }
+}
+
+/// Parses a value line, consuming the continuation lines of hex data from `lines`.
+fn parse_value<'a, I: Iterator<Item = (usize, &'a str)>>(
+    number: usize,
+    raw: &'a str,
+    lines: &mut I,
+    syntax: Syntax,
+) -> Result<(Logical<'a>, Value), Error> {
+    let mut logical = Logical::new(number, raw);
+
+    // Hex data may continue over several lines, each ending with a backslash.
+    let hex = match parse_name(&logical.text, syntax) {
+        Ok((_, data)) => data.starts_with("hex"),
+        Err(fault) => return Err(logical.error(fault)),
+    };
+    if hex {
+        while logical.text.ends_with('\\') {
+            logical.text.pop();
+            match lines.next() {
+                Some((number, next)) => logical.push(number, next),
+                None => {
+                    let text = &logical.text[logical.text.len()..];
+                    return Err(logical.error(fault(text, "unterminated hex data")));
+                }
+            }
+        }
+    }
+
+    let value =
+        parse_name(&logical.text, syntax).and_then(|(name, data)| parse_data(name, data, syntax));
+    match value {
+        Ok(value) => Ok((logical, value)),
+        Err(fault) => Err(logical.error(fault)),
+    }
+}
+
+/// Parses a key header line.
+pub(crate) fn parse_header(number: usize, raw: &str, syntax: Syntax) -> Result<Section, Error> {
+    let logical = Logical::new(number, raw);
+    let header = &logical.text[1..];
+    let end = header
+        .rfind(']')
+        .ok_or_else(|| logical.error(fault(header, "unterminated key name")))?;
+    let (deleted, name) = match header[..end].strip_prefix('-') {
+        Some(name) => (true, name),
+        None => (false, &header[..end]),
+    };
+
+    let unescaped = match syntax {
+        Syntax::Wine => unescape_wine(name),
+        Syntax::Regedit4 | Syntax::Regedit5 => name.to_string(),
+    };
+    if unescaped.is_empty() {
+        return Err(logical.error(fault(&header[..=end], "empty key name")));
+    }
+
+    let modified = match syntax {
+        Syntax::Wine => header[end + 1..].trim().parse().ok(),
+        Syntax::Regedit4 | Syntax::Regedit5 => None,
+    };
+    Ok(Section {
+        name: unescaped,
+        deleted,
+        line: number,
+        values: Vec::new(),
+        lines: Vec::new(),
+        security: None,
+        link: None,
+        modified,
+        fingerprint: 0,
+    })
+}
+
+/// Returns the target of a symbolic link key if the value is its `REG_LINK` `SymbolicLinkValue`.
+///
+/// Link targets are native paths such as `\Registry\Machine\Software\Classes`, stored as
+/// UTF-16LE without a terminator.
+fn link_target(value: &Value) -> Option<String> {
+    let is_link_value = match value.name() {
+        ValueName::Named(name) => name.eq_ignore_ascii_case("SymbolicLinkValue"),
+        ValueName::Default => false,
+    };
+    if !is_link_value || value.raw_type() != Some(6) {
+        return None;
+    }
+
+    match value.value() {
+        regashii::Value::Binary(bytes) => {
+            let units: Vec<u16> = bytes
+                .chunks(2)
+                .map(|pair| u16::from_le_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+                .collect();
+            Some(
+                String::from_utf16_lossy(&units)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Parses a `#security=hex:...` key attribute line, consuming its continuation lines.
+fn parse_security<'a, I: Iterator<Item = (usize, &'a str)>>(
+    number: usize,
+    raw: &'a str,
+    lines: &mut I,
+) -> Result<SecurityDescriptor, Error> {
+    let mut logical = Logical::new(number, raw);
+    while logical.text.ends_with('\\') {
+        logical.text.pop();
+        match lines.next() {
+            Some((number, next)) => logical.push(number, next),
+            None => {
+                let text = &logical.text[logical.text.len()..];
+                return Err(logical.error(fault(text, "unterminated security descriptor")));
+            }
+        }
+    }
+
+    let data = &logical.text["#security=".len()..];
+    let bytes = data
+        .strip_prefix("hex:")
+        .ok_or_else(|| fault(data, "invalid security descriptor"))
+        .and_then(parse_bytes);
+    match bytes {
+        Ok(bytes) => Ok(SecurityDescriptor::new(bytes)),
+        Err(fault) => Err(logical.error(fault)),
+    }
+}
+
+/// Splits a value line into the value name and the unparsed data following the `=`.
+fn parse_name(line: &str, syntax: Syntax) -> Result<(ValueName, &str), Fault<'_>> {
+    if let Some(data) = line.strip_prefix("@=") {
+        return Ok((ValueName::Default, data));
+    }
+
+    let quoted = line
+        .strip_prefix('"')
+        .ok_or_else(|| fault(line, "expected a value name"))?;
+    let end = closing_quote(quoted).ok_or_else(|| fault(line, "unterminated value name"))?;
+    let name = unescape(&quoted[..end], syntax);
+    let data = quoted[end + 1..]
+        .strip_prefix('=')
+        .ok_or_else(|| fault(&quoted[end + 1..], "expected '=' after the value name"))?;
+
+    Ok((ValueName::Named(name), data))
+}
+
+/// Returns the byte index of the first unescaped `"` in `text`.
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses the data of a value.
+fn parse_data(name: ValueName, data: &str, syntax: Syntax) -> Result<Value, Fault<'_>> {
+    if data == "-" {
+        return Ok(Value::new(name, regashii::Value::Delete));
+    }
+
+    if let Some(quoted) = data.strip_prefix('"') {
+        let string = parse_string(quoted, syntax)?;
+        return Ok(Value::new(name, regashii::Value::Sz(string)));
+    }
+
+    if let Some(digits) = data.strip_prefix("dword:") {
+        if digits.is_empty() || digits.len() > 8 {
+            return Err(fault(digits, "invalid dword"));
+        }
+        let dword = u32::from_str_radix(digits, 16).map_err(|_| fault(digits, "invalid dword"))?;
+        return Ok(Value::new(name, regashii::Value::Dword(dword)));
+    }
+
+    if let Some(bytes) = data.strip_prefix("hex:") {
+        let bytes = parse_bytes(bytes)?;
+        return Ok(Value::new(name, regashii::Value::Binary(bytes)));
+    }
+
+    if let Some(rest) = data.strip_prefix("hex(") {
+        let (kind, bytes) = rest
+            .split_once("):")
+            .ok_or_else(|| fault(data, "invalid hex value type"))?;
+        let kind =
+            u32::from_str_radix(kind, 16).map_err(|_| fault(kind, "invalid hex value type"))?;
+        let bytes = parse_bytes(bytes)?;
+        return Ok(typed_bytes(name, kind, bytes, syntax));
+    }
+
+    if syntax == Syntax::Wine {
+        if let Some(rest) = data.strip_prefix("str(") {
+            let (kind, quoted) = rest
+                .split_once("):\"")
+                .ok_or_else(|| fault(data, "invalid string value type"))?;
+            let string = parse_string(quoted, syntax)?;
+            let value = match kind {
+                "1" => regashii::Value::Sz(string),
+                "2" => regashii::Value::ExpandSz(string),
+                "7" => regashii::Value::MultiSz(split_multi(&string)),
+                _ => return Err(fault(kind, "invalid string value type")),
+            };
+            return Ok(Value::new(name, value));
+        }
+    }
+
+    Err(fault(data, "unknown value data"))
+}
+
+/// Parses a quoted string, given the text following its opening quote.
+fn parse_string(quoted: &str, syntax: Syntax) -> Result<String, Fault<'_>> {
+    let end = closing_quote(quoted).ok_or_else(|| fault(quoted, "unterminated string"))?;
+    let rest = quoted[end + 1..].trim();
+    if !rest.is_empty() {
+        return Err(fault(rest, "unexpected data after string"));
+    }
+    Ok(unescape(&quoted[..end], syntax))
+}
+
+/// Parses a comma separated list of hex bytes.
+fn parse_bytes(bytes: &str) -> Result<Vec<u8>, Fault<'_>> {
+    bytes
+        .split(',')
+        .map(str::trim)
+        .filter(|byte| !byte.is_empty())
+        .map(|byte| match byte.len() {
+            1 | 2 => u8::from_str_radix(byte, 16).map_err(|_| fault(byte, "invalid hex byte")),
+            _ => Err(fault(byte, "invalid hex byte")),
+        })
+        .collect()
+}
+
+/// Converts the bytes of a `hex(kind):` value into the matching value.
+///
+/// Strings are stored as UTF-16LE, except in `REGEDIT4` files where they use the ANSI code page.
+/// Data of other types, or typed data that wouldn't be encoded back to the same bytes (missing
+/// terminators, wrong lengths...), is kept as a raw value. Raw string data of `REGEDIT4` files
+/// is converted to UTF-16LE, as regedit imports it, so raw values hold the same bytes whatever
+/// syntax they were read from and are written back with the same meaning.
+fn typed_bytes(name: ValueName, kind: u32, bytes: Vec<u8>, syntax: Syntax) -> Value {
+    let decode = |bytes: &[u8]| match syntax {
+        Syntax::Regedit4 => bytes.iter().map(|&b| b as char).collect::<String>(),
+        Syntax::Regedit5 | Syntax::Wine => {
+            let units: Vec<u16> = bytes
+                .chunks(2)
+                .map(|pair| u16::from_le_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+    };
+    let encode = |text: &str| -> Option<Vec<u8>> {
+        match syntax {
+            Syntax::Regedit4 => text.chars().map(|c| u8::try_from(c).ok()).collect(),
+            Syntax::Regedit5 | Syntax::Wine => {
+                Some(text.encode_utf16().flat_map(u16::to_le_bytes).collect())
+            }
+        }
+    };
+    let string = || {
+        let text = decode(&bytes);
+        let string = text.strip_suffix('\0')?;
+        (encode(&text)? == bytes).then(|| string.to_string())
+    };
+    let multi = || {
+        let text = decode(&bytes);
+        let strings = split_multi(&text);
+        let mut encoded: String = strings
+            .iter()
+            .map(|string| format!("{}\0", string))
+            .collect();
+        encoded.push('\0');
+        (encode(&encoded)? == bytes).then_some(strings)
+    };
+
+    let value = match kind {
+        1 => string().map(regashii::Value::Sz),
+        2 => string().map(regashii::Value::ExpandSz),
+        3 => Some(regashii::Value::Binary(bytes.clone())),
+        4 if bytes.len() == 4 => Some(regashii::Value::Dword(u32::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3],
+        ]))),
+        7 => multi().map(regashii::Value::MultiSz),
+        0xb if bytes.len() == 8 => {
+            let mut qword = [0; 8];
+            qword.copy_from_slice(&bytes);
+            Some(regashii::Value::Qword(u64::from_le_bytes(qword)))
+        }
+        _ => None,
+    };
+
+    match value {
+        Some(value) => Value::new(name, value),
+        None if syntax == Syntax::Regedit4 && matches!(kind, 1 | 2 | 7) => {
+            let units = bytes.iter().flat_map(|&byte| u16::from(byte).to_le_bytes());
+            Value::raw(name, kind, units.collect())
+        }
+        None => Value::raw(name, kind, bytes),
+    }
+}
+
+/// Splits the NUL separated strings of a multi-string, dropping the terminating empty strings.
+fn split_multi(data: &str) -> Vec<String> {
+    let mut strings: Vec<String> = data.split('\0').map(String::from).collect();
+    while strings.last().is_some_and(String::is_empty) {
+        strings.pop();
+    }
+    strings
+}
+
+/// Unescapes a string or name according to the file syntax.
+pub(crate) fn unescape(text: &str, syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Wine => unescape_wine(text),
+        Syntax::Regedit4 | Syntax::Regedit5 => unescape_regedit(text),
+    }
+}
+
+/// Unescapes the `\\` and `\"` sequences used by regedit.
+fn unescape_regedit(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&next)) if next == '\\' || next == '"' => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Unescapes the C-like escape sequences used by Wine, including `\xXXXX` hex
+/// and `\ooo` octal character codes.
+fn unescape_wine(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        let escape = match chars.next() {
+            Some(escape) => escape,
+            None => {
+                unescaped.push('\\');
+                break;
+            }
+        };
+        let code = |chars: &mut std::iter::Peekable<std::str::Chars>, radix: u32, max: usize| {
+            let mut code = 0;
+            for _ in 0..max {
+                match chars.peek().and_then(|c| c.to_digit(radix)) {
+                    Some(digit) => {
+                        code = code * radix + digit;
+                        chars.next();
+                    }
+                    None => break,
+                }
+            }
+            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+        };
+
+        match escape {
+            'a' => unescaped.push('\u{7}'),
+            'b' => unescaped.push('\u{8}'),
+            'e' => unescaped.push('\u{1b}'),
+            'f' => unescaped.push('\u{c}'),
+            'n' => unescaped.push('\n'),
+            'r' => unescaped.push('\r'),
+            't' => unescaped.push('\t'),
+            'v' => unescaped.push('\u{b}'),
+            'x' => unescaped.push(code(&mut chars, 16, 4)),
+            '0'..='7' => {
+                let mut digits = escape.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            digits = digits * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                unescaped.push(char::from_u32(digits).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            other => unescaped.push(other),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(section: &Section) -> Vec<(ValueName, regashii::Value)> {
+        section
+            .values
+            .iter()
+            .cloned()
+            .map(Value::into_regashii_value)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_regedit5() {
+        let text = "Windows Registry Editor Version 5.00\r\n\r\n\
+            [HKEY_CURRENT_USER\\Software\\Test]\r\n\
+            @=\"default\"\r\n\
+            \"Path\"=\"C:\\\\windows\"\r\n\
+            \"Count\"=dword:0000002a\r\n\
+            \"Data\"=hex:01,02,\\\r\n  03\r\n\
+            \"Removed\"=-\r\n\r\n\
+            [-HKEY_CURRENT_USER\\Software\\Gone]\r\n";
+        let document = parse(text, true).unwrap();
+
+        assert_eq!(document.syntax, Syntax::Regedit5);
+        assert_eq!(document.sections.len(), 2);
+        let section = &document.sections[0];
+        assert_eq!(section.name, "HKEY_CURRENT_USER\\Software\\Test");
+        assert_eq!(
+            pairs(section),
+            vec![
+                (
+                    ValueName::Default,
+                    regashii::Value::Sz("default".to_string())
+                ),
+                (
+                    ValueName::named("Path"),
+                    regashii::Value::Sz("C:\\windows".to_string())
+                ),
+                (ValueName::named("Count"), regashii::Value::Dword(42)),
+                (
+                    ValueName::named("Data"),
+                    regashii::Value::Binary(vec![1, 2, 3])
+                ),
+                (ValueName::named("Removed"), regashii::Value::Delete),
+            ]
+        );
+        assert!(document.sections[1].deleted);
+    }
+
+    #[test]
+    fn test_parse_wine() {
+        let text = "WINE REGISTRY Version 2\n\
+            ;; All keys relative to REGISTRY\\\\User\n\n\
+            #arch=win64\n\n\
+            [Software\\\\Wine\\\\Test] 1740588656\n\
+            #time=1db886e9ab1eb62\n\
+            \"Multi\"=str(7):\"a\\0b\\0\"\n\
+            \"Expand\"=str(2):\"%SystemRoot%\"\n\
+            \"Unicode\"=\"\\x4e2d\"\n";
+        let document = parse(text, true).unwrap();
+
+        assert_eq!(document.syntax, Syntax::Wine);
+        let section = &document.sections[0];
+        assert_eq!(section.name, "Software\\Wine\\Test");
+        assert_eq!(section.line, 6);
+        assert_eq!(section.modified, Some(1740588656));
+        assert_eq!(
+            pairs(section),
+            vec![
+                (
+                    ValueName::named("Multi"),
+                    regashii::Value::MultiSz(vec!["a".to_string(), "b".to_string()])
+                ),
+                (
+                    ValueName::named("Expand"),
+                    regashii::Value::ExpandSz("%SystemRoot%".to_string())
+                ),
+                (
+                    ValueName::named("Unicode"),
+                    regashii::Value::Sz("中".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_reports_line_of_malformed_value() {
+        let text = "REGEDIT4\n\n[Test]\n\"Good\"=dword:00000001\n\"Bad\"=dword:zz\n";
+        match parse(text, true) {
+            Err(Error::Parse { line, .. }) => assert_eq!(line, 5),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_reports_position_in_continuation_line() {
+        let text = "REGEDIT4\n\n[Test]\n\"Data\"=hex:01,02,\\\n  03,zz,04\n";
+        match parse(text, true) {
+            Err(Error::Parse {
+                line,
+                column,
+                text,
+                message,
+            }) => {
+                assert_eq!((line, column), (5, 6));
+                assert_eq!(text, "zz");
+                assert_eq!(message, "invalid hex byte");
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_security_descriptor() {
+        let text = "WINE REGISTRY Version 2\n\n[Software\\\\Test] 1740588656\n\
+            #security=hex:01,00,04,\\\n  80\n\"Name\"=\"value\"\n";
+        let document = parse(text, true).unwrap();
+
+        let section = &document.sections[0];
+        assert_eq!(
+            section.security,
+            Some(SecurityDescriptor::new(vec![1, 0, 4, 0x80]))
+        );
+        assert_eq!(section.values.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_symbolic_link() {
+        let text = "WINE REGISTRY Version 2\n\n[Software\\\\Wow6432Node\\\\Classes] 1740588656\n\
+            #link\n\"SymbolicLinkValue\"=hex(6):5c,00,52,00,65,00,67,00\n";
+        let document = parse(text, true).unwrap();
+
+        let section = &document.sections[0];
+        assert_eq!(section.link.as_deref(), Some("\\Reg"));
+        assert!(section.values.is_empty());
+    }
+
+    #[test]
+    fn test_parse_leniently_collects_warnings() {
+        let text = "REGEDIT4\n\n[Test]\n\"Good\"=dword:00000001\n\"Bad\"=\"unterminated\n\
+            [Broken\n\"Skipped\"=dword:00000001\n#security=hex:01\n[Next]\n\"Kept\"=hex(2):41,00\n";
+        let document = parse(text, false).unwrap();
+
+        let lines: Vec<usize> = document.warnings.iter().map(ParseWarning::line).collect();
+        assert_eq!(lines, vec![5, 6, 7, 8]);
+        assert_eq!(document.warnings[2].message(), UNDER_MALFORMED_KEY);
+        assert_eq!(document.sections.len(), 2);
+        assert_eq!(document.sections[0].values.len(), 1);
+        assert_eq!(document.sections[1].values.len(), 1);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::prelude::Hive;
+use regashii::KeyName;
+
+/// Helpers for working with key names as paths of `\`-separated components, so callers don't
+/// have to manipulate [KeyName::raw] themselves.
+///
+/// Comparisons ignore case like the registry does, so `HKEY_CURRENT_USER\Software` is an
+/// ancestor of `HKEY_CURRENT_USER\SOFTWARE\Wine`.
+pub trait KeyPath {
+    /// Returns the components of the name, from the root down.
+    fn components(&self) -> std::str::Split<'_, char>;
+
+    /// Returns the number of components of the name, 1 for a root key.
+    fn depth(&self) -> usize;
+
+    /// Returns the last component of the name, the name of the key within its parent.
+    fn leaf(&self) -> &str;
+
+    /// Returns the name of the parent key, or `None` for a root key.
+    fn parent(&self) -> Option<KeyName>;
+
+    /// Returns the name of a subkey. `child` may hold several components.
+    fn join(&self, child: &str) -> KeyName;
+
+    /// Returns whether this key strictly contains `other`, at any depth.
+    fn is_ancestor_of(&self, other: &KeyName) -> bool;
+
+    /// Returns the path of `self` relative to `ancestor`, or `None` if `ancestor` isn't one of
+    /// its ancestors.
+    fn strip_ancestor(&self, ancestor: &KeyName) -> Option<&str>;
+}
+
+impl KeyPath for KeyName {
+    fn components(&self) -> std::str::Split<'_, char> {
+        self.raw().split('\\')
+    }
+
+    fn depth(&self) -> usize {
+        self.components().count()
+    }
+
+    fn leaf(&self) -> &str {
+        let raw = self.raw();
+        raw.rsplit_once('\\').map_or(raw, |(_, leaf)| leaf)
+    }
+
+    fn parent(&self) -> Option<KeyName> {
+        let (parent, _) = self.raw().rsplit_once('\\')?;
+        Some(KeyName::new(parent))
+    }
+
+    fn join(&self, child: &str) -> KeyName {
+        KeyName::new(format!("{}\\{}", self.raw(), child))
+    }
+
+    fn is_ancestor_of(&self, other: &KeyName) -> bool {
+        other.strip_ancestor(self).is_some()
+    }
+
+    fn strip_ancestor(&self, ancestor: &KeyName) -> Option<&str> {
+        let (raw, prefix) = (self.raw(), ancestor.raw());
+        let head = raw.get(..prefix.len())?;
+        if head.to_lowercase() != prefix.to_lowercase() {
+            return None;
+        }
+        raw[prefix.len()..].strip_prefix('\\')
+    }
+}
+
+/// Returns the subtree of a key at a given depth: the hive followed by the first `depth`
+/// components of the name within it, or the first `depth + 1` components of names without a
+/// known hive.
+pub(crate) fn subtree(name: &KeyName, depth: usize) -> KeyName {
+    let prefix = match Hive::split(name.raw()) {
+        Some((hive, path)) => std::iter::once(hive.to_string())
+            .chain(path.split('\\').take(depth).map(str::to_string))
+            .collect::<Vec<_>>()
+            .join("\\"),
+        None => name
+            .raw()
+            .split('\\')
+            .take(depth + 1)
+            .collect::<Vec<_>>()
+            .join("\\"),
+    };
+    KeyName::new(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_path() {
+        let name = KeyName::new("HKEY_CURRENT_USER\\Software\\Wine\\Direct3D");
+        assert_eq!(
+            name.components().collect::<Vec<_>>(),
+            ["HKEY_CURRENT_USER", "Software", "Wine", "Direct3D"]
+        );
+        assert_eq!(name.depth(), 4);
+        assert_eq!(name.leaf(), "Direct3D");
+        assert_eq!(
+            name.parent(),
+            Some(KeyName::new("HKEY_CURRENT_USER\\Software\\Wine"))
+        );
+        assert_eq!(KeyName::new("HKEY_CURRENT_USER").parent(), None);
+        assert_eq!(
+            KeyName::new("HKEY_CURRENT_USER").leaf(),
+            "HKEY_CURRENT_USER"
+        );
+        assert_eq!(
+            KeyName::new("HKEY_CURRENT_USER").join("Software\\Wine"),
+            KeyName::new("HKEY_CURRENT_USER\\Software\\Wine")
+        );
+
+        let software = KeyName::new("hkey_current_user\\SOFTWARE");
+        assert!(software.is_ancestor_of(&name));
+        assert_eq!(name.strip_ancestor(&software), Some("Wine\\Direct3D"));
+        assert!(!name.is_ancestor_of(&name));
+        assert!(!KeyName::new("HKEY_CURRENT_USER\\Soft").is_ancestor_of(&name));
+    }
+}
@@ -0,0 +1,141 @@
+use regashii::KeyName;
+
+/// A glob-like pattern matched against full registry key names.
+///
+/// Patterns are split into components on `\`, and matched case-insensitively like the registry itself:
+/// - `*` matches any sequence of characters within a single component.
+/// - `?` matches exactly one character within a single component.
+/// - A `**` component matches any number of components, including none.
+///
+/// For example `HKEY_CURRENT_USER\Software\**\Uninstall\*` matches every direct subkey of any
+/// `Uninstall` key below `HKEY_CURRENT_USER\Software`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyPattern {
+    /// The original pattern text.
+    raw: String,
+    /// The lowercased pattern components.
+    components: Vec<String>,
+}
+
+impl KeyPattern {
+    /// Constructs a new [KeyPattern] from its textual form.
+    pub fn new<T: AsRef<str>>(pattern: T) -> Self {
+        let raw = pattern.as_ref().to_string();
+        let components = split(&raw.to_lowercase());
+        Self { raw, components }
+    }
+
+    /// Returns the original pattern text.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Returns whether the pattern matches the given key name.
+    pub fn matches(&self, name: &KeyName) -> bool {
+        let name = split(&name.raw().to_lowercase());
+        let patterns: Vec<&str> = self.components.iter().map(String::as_str).collect();
+        let names: Vec<&str> = name.iter().map(String::as_str).collect();
+        match_components(&patterns, &names)
+    }
+
+    /// Returns whether the pattern matches the given key name or one of its ancestors,
+    /// i.e. whether the key lies inside a subtree selected by the pattern.
+    pub fn matches_subtree(&self, name: &KeyName) -> bool {
+        let name = split(&name.raw().to_lowercase());
+        let patterns: Vec<&str> = self.components.iter().map(String::as_str).collect();
+        let names: Vec<&str> = name.iter().map(String::as_str).collect();
+        (1..=names.len()).any(|len| match_components(&patterns, &names[..len]))
+    }
+}
+
+impl std::fmt::Display for KeyPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl From<&str> for KeyPattern {
+    fn from(pattern: &str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+/// Splits a key path into its non-empty components.
+fn split(path: &str) -> Vec<String> {
+    path.split('\\')
+        .filter(|component| !component.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Matches a sequence of pattern components against a sequence of name components.
+fn match_components(patterns: &[&str], names: &[&str]) -> bool {
+    match patterns.split_first() {
+        None => names.is_empty(),
+        Some((&"**", rest)) => (0..=names.len()).any(|skip| match_components(rest, &names[skip..])),
+        Some((pattern, rest)) => match names.split_first() {
+            Some((name, names)) => match_glob(pattern, name) && match_components(rest, names),
+            None => false,
+        },
+    }
+}
+
+/// Matches a single component against a pattern containing `*` and `?` wildcards.
+fn match_glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_matches_case_insensitively() {
+        let pattern = KeyPattern::new("HKEY_CURRENT_USER\\Software\\Wine\\DllOverrides");
+        assert!(pattern.matches(&KeyName::new(
+            "hkey_current_user\\software\\wine\\dlloverrides"
+        )));
+        assert!(!pattern.matches(&KeyName::new("HKEY_CURRENT_USER\\Software\\Wine")));
+    }
+
+    #[test]
+    fn test_pattern_wildcards() {
+        let pattern = KeyPattern::new("HKEY_LOCAL_MACHINE\\**\\Uninstall\\*");
+        assert!(pattern.matches(&KeyName::new(
+            "HKEY_LOCAL_MACHINE\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\Foo"
+        )));
+        assert!(!pattern.matches(&KeyName::new("HKEY_LOCAL_MACHINE\\Software\\Uninstall")));
+        assert!(KeyPattern::new("*\\Software\\W?ne")
+            .matches(&KeyName::new("HKEY_CURRENT_USER\\Software\\Wine")));
+    }
+
+    #[test]
+    fn test_pattern_matches_subtree() {
+        let pattern = KeyPattern::new("HKEY_CURRENT_USER\\Software\\Bottles");
+        assert!(pattern.matches_subtree(&KeyName::new(
+            "HKEY_CURRENT_USER\\Software\\Bottles\\Settings"
+        )));
+        assert!(!pattern.matches_subtree(&KeyName::new("HKEY_CURRENT_USER\\Software")));
+    }
+}
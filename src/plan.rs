@@ -0,0 +1,294 @@
+use crate::prelude::{
+    Hive, Key, KeyChange, Operation, Registry, RegistryDiff, SecurityDescriptor, Value,
+};
+use regashii::KeyName;
+
+/// An operation [Registry::apply] performs on a key, as listed by an [ApplyPlan].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlanAction {
+    /// The key belongs to another hive, so its changes are skipped.
+    Skip,
+    /// The key is deleted along with its subkeys.
+    DeleteKey {
+        /// The number of existing subkeys deleted with it.
+        subkeys: usize,
+    },
+    /// The missing key is created.
+    CreateKey,
+    /// A value is set.
+    SetValue {
+        /// The value the key held before, if any.
+        old: Option<Value>,
+        /// The value set.
+        new: Value,
+    },
+    /// A value is deleted.
+    DeleteValue {
+        /// The value deleted, `None` if it was missing.
+        old: Option<Value>,
+    },
+    /// The security descriptor of the key is set.
+    SetSecurity(SecurityDescriptor),
+    /// The security descriptor of the key is removed.
+    DeleteSecurity,
+    /// The key is turned into a symbolic link to a native path.
+    SetLink(String),
+    /// The key stops being a symbolic link.
+    DeleteLink,
+}
+
+/// A single operation of an [ApplyPlan] and its expected effect.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlanStep {
+    /// The full name of the key.
+    key: KeyName,
+    /// What is done to the key.
+    action: PlanAction,
+    /// Whether the operation changes the registry.
+    changes: bool,
+}
+
+impl PlanStep {
+    /// Returns the full name of the key.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns what is done to the key.
+    pub fn action(&self) -> &PlanAction {
+        &self.action
+    }
+
+    /// Returns whether the operation changes the registry, `false` if the registry already
+    /// holds its result or for skipped keys.
+    pub fn changes(&self) -> bool {
+        self.changes
+    }
+}
+
+impl std::fmt::Display for PlanStep {
+    /// Renders the step as a line such as `set "A"=dword:00000002 in [HKEY_CURRENT_USER\App],
+    /// replacing "A"=dword:00000001`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let key = self.key.raw();
+        match &self.action {
+            PlanAction::Skip => write!(f, "skip [{}]: outside of the hive", key),
+            PlanAction::DeleteKey { .. } if !self.changes => {
+                write!(f, "delete [{}]: already missing", key)
+            }
+            PlanAction::DeleteKey { subkeys: 0 } => write!(f, "delete [{}]", key),
+            PlanAction::DeleteKey { subkeys: 1 } => write!(f, "delete [{}] with 1 subkey", key),
+            PlanAction::DeleteKey { subkeys } => {
+                write!(f, "delete [{}] with {} subkeys", key, subkeys)
+            }
+            PlanAction::CreateKey => write!(f, "create [{}]", key),
+            PlanAction::SetValue { new, .. } if !self.changes => {
+                write!(f, "set {} in [{}]: already set", new, key)
+            }
+            PlanAction::SetValue {
+                old: Some(old),
+                new,
+            } => {
+                write!(f, "set {} in [{}], replacing {}", new, key, old)
+            }
+            PlanAction::SetValue { old: None, new } => write!(f, "set {} in [{}]", new, key),
+            PlanAction::DeleteValue { old: Some(old) } => {
+                write!(f, "delete {} from [{}]", old, key)
+            }
+            PlanAction::DeleteValue { old: None } => {
+                write!(f, "delete a value from [{}]: already missing", key)
+            }
+            PlanAction::SetSecurity(_) if !self.changes => {
+                write!(f, "set the security of [{}]: already set", key)
+            }
+            PlanAction::SetSecurity(_) => write!(f, "set the security of [{}]", key),
+            PlanAction::DeleteSecurity if !self.changes => {
+                write!(f, "remove the security of [{}]: already missing", key)
+            }
+            PlanAction::DeleteSecurity => write!(f, "remove the security of [{}]", key),
+            PlanAction::SetLink(target) if !self.changes => {
+                write!(f, "link [{}] to {}: already linked", key, target)
+            }
+            PlanAction::SetLink(target) => write!(f, "link [{}] to {}", key, target),
+            PlanAction::DeleteLink if !self.changes => {
+                write!(f, "unlink [{}]: not a link", key)
+            }
+            PlanAction::DeleteLink => write!(f, "unlink [{}]", key),
+        }
+    }
+}
+
+/// Every operation applying a diff to a registry would perform, in order, as returned by
+/// [Registry::plan]. It is displayed as one step per line.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApplyPlan {
+    /// The operations, in the order they are performed.
+    steps: Vec<PlanStep>,
+}
+
+impl ApplyPlan {
+    /// Returns the operations, in the order they are performed.
+    pub fn steps(&self) -> &[PlanStep] {
+        &self.steps
+    }
+
+    /// Returns whether applying the diff would leave the registry as it is.
+    pub fn is_no_op(&self) -> bool {
+        self.steps.iter().all(|step| !step.changes)
+    }
+}
+
+impl std::fmt::Display for ApplyPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+impl Registry {
+    /// Lists every operation [Registry::apply] would perform with a diff, in order, with its
+    /// expected effect, without changing the registry. Each change is planned against the
+    /// registry as left by the previous ones, so deleting a key then setting one of its
+    /// values plans to create it again.
+    pub fn plan(&self, diff: &RegistryDiff) -> ApplyPlan {
+        let mut registry = self.clone();
+        let mut steps = Vec::new();
+        for change in diff.keys().values() {
+            if change.change() == KeyChange::Unchanged {
+                continue;
+            }
+            let mut step = |action, changes| {
+                steps.push(PlanStep {
+                    key: change.name().clone(),
+                    action,
+                    changes,
+                })
+            };
+            let path = match Hive::split(change.name().raw()) {
+                Some((hive, path)) if hive == registry.hive() => path,
+                _ => {
+                    step(PlanAction::Skip, false);
+                    continue;
+                }
+            };
+
+            let current = find(&registry, path);
+            if change.change() == KeyChange::Deleted {
+                let subkeys = format!("{}\\", path.to_lowercase());
+                let subkeys = registry
+                    .keys()
+                    .keys()
+                    .filter(|name| name.raw().to_lowercase().starts_with(&subkeys))
+                    .count();
+                step(PlanAction::DeleteKey { subkeys }, current.is_some());
+            } else {
+                if current.is_none() {
+                    step(PlanAction::CreateKey, true);
+                }
+                let current = current.map(Key::values);
+                for (name, operation) in change.values() {
+                    let old = current.and_then(|values| values.get(name)).cloned();
+                    match operation {
+                        Operation::Add { data } | Operation::Modify { new_data: data, .. } => {
+                            let changes = old.as_ref() != Some(data);
+                            step(
+                                PlanAction::SetValue {
+                                    old,
+                                    new: data.clone(),
+                                },
+                                changes,
+                            );
+                        }
+                        Operation::Delete { .. } => {
+                            let changes = old.is_some();
+                            step(PlanAction::DeleteValue { old }, changes);
+                        }
+                        Operation::Unchanged
+                        | Operation::Volatile { .. }
+                        | Operation::Reordered { .. } => {}
+                    }
+                }
+                let key = find(&registry, path);
+                match change.security() {
+                    Some(Operation::Add { data } | Operation::Modify { new_data: data, .. }) => {
+                        let changes = key.and_then(Key::security) != Some(data);
+                        step(PlanAction::SetSecurity(data.clone()), changes);
+                    }
+                    Some(Operation::Delete { .. }) => {
+                        let changes = key.and_then(Key::security).is_some();
+                        step(PlanAction::DeleteSecurity, changes);
+                    }
+                    _ => {}
+                }
+                match change.link() {
+                    Some(Operation::Add { data } | Operation::Modify { new_data: data, .. }) => {
+                        let changes = key.and_then(Key::link) != Some(data.as_str());
+                        step(PlanAction::SetLink(data.clone()), changes);
+                    }
+                    Some(Operation::Delete { .. }) => {
+                        let changes = key.and_then(Key::link).is_some();
+                        step(PlanAction::DeleteLink, changes);
+                    }
+                    _ => {}
+                }
+            }
+            registry.apply(&std::iter::once(change.clone()).collect::<RegistryDiff>());
+        }
+        ApplyPlan { steps }
+    }
+}
+
+/// Returns the key at `path` within the hive of a registry, matched case-insensitively.
+fn find<'a>(registry: &'a Registry, path: &str) -> Option<&'a Key> {
+    registry
+        .keys()
+        .iter()
+        .find(|(name, _)| name.raw().eq_ignore_ascii_case(path))
+        .map(|(_, key)| key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let registry = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000001\n\"B\"=\"b\"\n\n\
+            [HKEY_CURRENT_USER\\Gone]\n\n[HKEY_CURRENT_USER\\Gone\\Sub]\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let base = Registry::try_from_str("REGEDIT4\n", Hive::CurrentUser).unwrap();
+        let patch = RegistryDiff::from_patch_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000002\n\"B\"=\"b\"\n\n\
+            [HKEY_CURRENT_USER\\New]\n\"C\"=\"c\"\n",
+            &base,
+        )
+        .unwrap();
+        let deletion =
+            RegistryDiff::from_patch_str("REGEDIT4\n\n[-HKEY_CURRENT_USER\\Gone]\n", &registry)
+                .unwrap();
+        let diff: RegistryDiff = [patch, deletion].into_iter().collect();
+
+        let original = registry.clone();
+        let plan = registry.plan(&diff);
+        assert_eq!(registry.keys(), original.keys());
+        assert_eq!(
+            plan.to_string(),
+            "set \"A\"=dword:00000002 in [HKEY_CURRENT_USER\\App], replacing \"A\"=dword:00000001\n\
+            set \"B\"=\"b\" in [HKEY_CURRENT_USER\\App]: already set\n\
+            delete [HKEY_CURRENT_USER\\Gone] with 1 subkey\n\
+            delete [HKEY_CURRENT_USER\\Gone\\Sub]: already missing\n\
+            create [HKEY_CURRENT_USER\\New]\n\
+            set \"C\"=\"c\" in [HKEY_CURRENT_USER\\New]\n"
+        );
+        assert!(!plan.steps()[1].changes());
+
+        let mut applied = registry.clone();
+        applied.apply(&diff);
+        assert!(applied.plan(&diff).is_no_op());
+    }
+}
@@ -0,0 +1,447 @@
+use crate::names::same_value_name;
+use crate::prelude::{Error, KeyChange, KeyDiff, KeyPattern, Operation, RegistryDiff};
+use regashii::{KeyName, ValueName};
+
+/// How serious a change matched by a [PolicyRule] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The change is reported, but may be applied.
+    Warn,
+    /// The change must not be applied.
+    Block,
+}
+
+/// A rule flagging risky changes to a part of the registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyRule {
+    /// The keys guarded by the rule, including their subkeys.
+    pattern: KeyPattern,
+    /// The guarded value, or `None` to guard every value and the keys themselves.
+    value: Option<ValueName>,
+    /// Whether only deletions are flagged.
+    deletions_only: bool,
+    /// Whether only the keys matching the pattern themselves are guarded, without their subkeys.
+    exact_key: bool,
+    /// How serious a matched change is.
+    severity: Severity,
+    /// Why a matched change is risky.
+    reason: String,
+}
+
+impl PolicyRule {
+    /// Constructs a new [PolicyRule] flagging any change to the keys matching `pattern` and
+    /// their subkeys.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The keys guarded by the rule.
+    /// * `severity` - How serious a matched change is.
+    /// * `reason` - Why a matched change is risky, shown in the resulting [Finding].
+    pub fn new<P: Into<KeyPattern>, T: Into<String>>(
+        pattern: P,
+        severity: Severity,
+        reason: T,
+    ) -> Self {
+        Self {
+            pattern: pattern.into(),
+            value: None,
+            deletions_only: false,
+            exact_key: false,
+            severity,
+            reason: reason.into(),
+        }
+    }
+
+    /// Returns the rule restricted to changes of the value `name`.
+    pub fn with_value(mut self, name: ValueName) -> Self {
+        self.value = Some(name);
+        self
+    }
+
+    /// Returns the rule restricted to deletions of keys or values.
+    pub fn with_deletions_only(mut self, deletions_only: bool) -> Self {
+        self.deletions_only = deletions_only;
+        self
+    }
+
+    /// Returns the rule restricted to the keys matching its pattern themselves.
+    ///
+    /// Subkeys aren't guarded, and unless the rule guards a value, only creating or deleting the
+    /// keys is flagged, not changes to their values.
+    pub fn with_exact_key(mut self, exact_key: bool) -> Self {
+        self.exact_key = exact_key;
+        self
+    }
+
+    /// Returns the keys guarded by the rule.
+    pub fn pattern(&self) -> &KeyPattern {
+        &self.pattern
+    }
+
+    /// Returns the guarded value, if the rule is restricted to one.
+    pub fn value(&self) -> Option<&ValueName> {
+        self.value.as_ref()
+    }
+
+    /// Returns whether only deletions are flagged.
+    pub fn deletions_only(&self) -> bool {
+        self.deletions_only
+    }
+
+    /// Returns whether only the keys matching the pattern themselves are guarded.
+    pub fn exact_key(&self) -> bool {
+        self.exact_key
+    }
+
+    /// Returns how serious a matched change is.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns why a matched change is risky.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Appends the findings of the rule for a changed key.
+    fn check(&self, key: &KeyDiff, findings: &mut Vec<Finding>) {
+        let guarded = if self.exact_key {
+            self.pattern.matches(key.name())
+        } else {
+            self.pattern.matches_subtree(key.name())
+        };
+        if !guarded {
+            return;
+        }
+
+        let finding = |value: Option<ValueName>| Finding {
+            key: key.name().clone(),
+            value,
+            severity: self.severity,
+            reason: self.reason.clone(),
+        };
+
+        match &self.value {
+            None => {
+                let flagged = match key.change() {
+                    KeyChange::Deleted => true,
+                    _ if self.exact_key => false,
+                    _ if self.deletions_only => key
+                        .values()
+                        .values()
+                        .any(|operation| matches!(operation, Operation::Delete { .. })),
+                    _ => key.values().values().any(is_change),
+                };
+                if flagged || (!self.deletions_only && key.change() == KeyChange::Added) {
+                    findings.push(finding(None));
+                }
+            }
+            Some(name) => {
+                for (value_name, operation) in key.values() {
+                    let flagged = match operation {
+                        Operation::Delete { .. } => true,
+                        operation => !self.deletions_only && is_change(operation),
+                    };
+                    if flagged && same_value_name(value_name, name) {
+                        findings.push(finding(Some(value_name.clone())));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns whether an operation changes what gets applied.
+fn is_change<T>(operation: &Operation<T>) -> bool {
+    !matches!(
+        operation,
+        Operation::Unchanged | Operation::Volatile { .. } | Operation::Reordered { .. }
+    )
+}
+
+/// A built-in set of [PolicyRule]s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolicyPreset {
+    /// Changes that commonly break a Wine prefix, such as deleting the system configuration or
+    /// replacing the shell, are blocked; changes to services, autostart entries and the search
+    /// path are reported.
+    Wine,
+}
+
+impl PolicyPreset {
+    /// Returns the rules of the preset.
+    pub fn rules(&self) -> Vec<PolicyRule> {
+        match self {
+            PolicyPreset::Wine => vec![
+                PolicyRule::new(
+                    "HKEY_LOCAL_MACHINE\\System\\CurrentControlSet",
+                    Severity::Block,
+                    "deletes the system configuration",
+                )
+                .with_deletions_only(true)
+                .with_exact_key(true),
+                PolicyRule::new(
+                    "HKEY_LOCAL_MACHINE\\Software\\Microsoft\\Windows NT\\CurrentVersion",
+                    Severity::Block,
+                    "deletes the Windows version information",
+                )
+                .with_deletions_only(true)
+                .with_exact_key(true),
+                PolicyRule::new(
+                    "HKEY_LOCAL_MACHINE\\Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon",
+                    Severity::Block,
+                    "replaces the Windows shell",
+                )
+                .with_value(ValueName::named("Shell")),
+                PolicyRule::new(
+                    "HKEY_LOCAL_MACHINE\\Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon",
+                    Severity::Block,
+                    "replaces the logon program",
+                )
+                .with_value(ValueName::named("Userinit")),
+                PolicyRule::new(
+                    "HKEY_LOCAL_MACHINE\\System\\CurrentControlSet\\Services",
+                    Severity::Warn,
+                    "changes system services",
+                ),
+                PolicyRule::new(
+                    "HKEY_LOCAL_MACHINE\\System\\CurrentControlSet\\Control\\Session Manager\\Environment",
+                    Severity::Warn,
+                    "changes the program search path",
+                )
+                .with_value(ValueName::named("PATH")),
+                PolicyRule::new(
+                    "*\\Software\\Microsoft\\Windows\\CurrentVersion\\Run*",
+                    Severity::Warn,
+                    "changes programs started at logon",
+                ),
+                PolicyRule::new(
+                    "*\\Software\\Wine",
+                    Severity::Warn,
+                    "deletes Wine configuration",
+                )
+                .with_deletions_only(true),
+            ],
+        }
+    }
+}
+
+/// A risky change flagged by a [PolicyRule].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// The full name of the changed key.
+    key: KeyName,
+    /// The name of the changed value, for rules guarding a value.
+    value: Option<ValueName>,
+    /// How serious the change is.
+    severity: Severity,
+    /// Why the change is risky.
+    reason: String,
+}
+
+impl Finding {
+    /// Returns the full name of the changed key.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the name of the changed value, for rules guarding a value.
+    pub fn value(&self) -> Option<&ValueName> {
+        self.value.as_ref()
+    }
+
+    /// Returns how serious the change is.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns why the change is risky.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warn => "warning",
+            Severity::Block => "blocked",
+        };
+        match &self.value {
+            Some(ValueName::Named(name)) => write!(
+                f,
+                "{}: '{}' value '{}' {}",
+                severity,
+                self.key.raw(),
+                name,
+                self.reason
+            ),
+            Some(ValueName::Default) => write!(
+                f,
+                "{}: '{}' default value {}",
+                severity,
+                self.key.raw(),
+                self.reason
+            ),
+            None => write!(f, "{}: '{}' {}", severity, self.key.raw(), self.reason),
+        }
+    }
+}
+
+/// A set of rules inspecting a diff before it is applied.
+///
+/// Rules are added individually or from a [PolicyPreset], and matched against every changed key
+/// of the diff with [Policy::check] or [Policy::enforce].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Policy {
+    /// The rules, in the order they were added.
+    rules: Vec<PolicyRule>,
+}
+
+impl Policy {
+    /// Constructs a new [Policy] without any rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the policy with the rules of a preset added.
+    pub fn with_preset(mut self, preset: PolicyPreset) -> Self {
+        self.rules.extend(preset.rules());
+        self
+    }
+
+    /// Returns the policy with a rule added.
+    pub fn with_rule(mut self, rule: PolicyRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Returns the rules of the policy.
+    pub fn rules(&self) -> &[PolicyRule] {
+        &self.rules
+    }
+
+    /// Inspects a diff.
+    ///
+    /// # Returns
+    ///
+    /// The findings of every rule, in rule order.
+    pub fn check(&self, diff: &RegistryDiff) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for rule in &self.rules {
+            for key in diff.keys().values() {
+                rule.check(key, &mut findings);
+            }
+        }
+        findings
+    }
+
+    /// Inspects a diff, failing if any change is blocked.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the warnings, or [Error::Blocked] for the first blocked change.
+    pub fn enforce(&self, diff: &RegistryDiff) -> Result<Vec<Finding>, Error> {
+        let findings = self.check(diff);
+        if let Some(finding) = findings
+            .iter()
+            .find(|finding| finding.severity == Severity::Block)
+        {
+            return Err(Error::Blocked {
+                name: finding.key.clone(),
+                reason: finding.reason.clone(),
+            });
+        }
+        Ok(findings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::registry;
+    use crate::prelude::{DiffOptions, Hive};
+
+    #[test]
+    fn test_wine_preset() {
+        let winlogon = "Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon";
+        let old = registry(
+            Hive::LocalMachine,
+            &[
+                (winlogon, "Shell", "explorer.exe"),
+                ("System\\CurrentControlSet", "A", "1"),
+                ("System\\CurrentControlSet\\Services\\Foo", "Start", "2"),
+            ],
+        );
+        let new = registry(
+            Hive::LocalMachine,
+            &[
+                (winlogon, "Shell", "evil.exe"),
+                ("System\\CurrentControlSet\\Services\\Foo", "Start", "3"),
+            ],
+        );
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        let policy = Policy::new().with_preset(PolicyPreset::Wine);
+
+        let findings: Vec<String> = policy.check(&diff).iter().map(Finding::to_string).collect();
+        assert_eq!(
+            findings,
+            vec![
+                "blocked: 'HKEY_LOCAL_MACHINE\\System\\CurrentControlSet' deletes the system configuration",
+                "blocked: 'HKEY_LOCAL_MACHINE\\Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon' value 'Shell' replaces the Windows shell",
+                "warning: 'HKEY_LOCAL_MACHINE\\System\\CurrentControlSet\\Services\\Foo' changes system services",
+            ]
+        );
+        assert!(matches!(policy.enforce(&diff), Err(Error::Blocked { .. })));
+    }
+
+    #[test]
+    fn test_wine_preset_allows_deletions_below_guarded_keys() {
+        let layers = "Software\\Microsoft\\Windows NT\\CurrentVersion\\AppCompatFlags\\Layers";
+        let old = registry(
+            Hive::LocalMachine,
+            &[
+                ("System\\CurrentControlSet\\Services\\AppSvc", "Start", "2"),
+                (layers, "C:\\app.exe", "WINXP"),
+                (layers, "C:\\other.exe", "WIN7"),
+            ],
+        );
+        let new = registry(Hive::LocalMachine, &[(layers, "C:\\other.exe", "WIN7")]);
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        let policy = Policy::new().with_preset(PolicyPreset::Wine);
+
+        let findings = policy.enforce(&diff).unwrap();
+        assert!(findings
+            .iter()
+            .all(|finding| finding.severity() == Severity::Warn));
+    }
+
+    #[test]
+    fn test_user_rules() {
+        let old = registry(Hive::LocalMachine, &[("Software\\App", "Version", "1")]);
+        let new = registry(Hive::LocalMachine, &[("Software\\App", "Version", "2")]);
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+
+        let deletions = Policy::new().with_rule(
+            PolicyRule::new(
+                "HKEY_LOCAL_MACHINE\\Software",
+                Severity::Block,
+                "is protected",
+            )
+            .with_deletions_only(true),
+        );
+        assert_eq!(deletions.enforce(&diff).unwrap(), vec![]);
+
+        let changes = Policy::new().with_rule(
+            PolicyRule::new(
+                "HKEY_LOCAL_MACHINE\\Software\\*",
+                Severity::Warn,
+                "is tracked",
+            )
+            .with_value(ValueName::named("version")),
+        );
+        let findings = changes.enforce(&diff).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].value(), Some(&ValueName::named("Version")));
+    }
+}
@@ -0,0 +1,295 @@
+use crate::prelude::{
+    DiffOptions, Error, Hive, LoadOptions, LockPolicy, PrefixLock, Registry, RegistryDiff,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The registry files of a Wine prefix and the hives they hold.
+const HIVE_FILES: [(&str, Hive); 3] = [
+    ("system.reg", Hive::LocalMachine),
+    ("user.reg", Hive::CurrentUser),
+    ("userdef.reg", Hive::DefaultUser),
+];
+
+/// A registry file loaded from a Wine prefix.
+#[derive(Clone, Debug)]
+pub struct HiveFile {
+    /// The path of the registry file.
+    path: PathBuf,
+    /// When the registry file was last modified.
+    modified: SystemTime,
+    /// The registry loaded from the file.
+    registry: Registry,
+}
+
+impl HiveFile {
+    /// Returns the path of the registry file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns when the registry file was last modified.
+    pub fn modified(&self) -> SystemTime {
+        self.modified
+    }
+
+    /// Returns the registry loaded from the file.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+}
+
+/// The registries of a Wine prefix (`WINEPREFIX`).
+#[derive(Clone, Debug)]
+pub struct Prefix {
+    /// The path of the prefix directory.
+    path: PathBuf,
+    /// The registry files found in the prefix, by hive.
+    hives: BTreeMap<Hive, HiveFile>,
+}
+
+impl Prefix {
+    /// Opens a Wine prefix, loading its `system.reg`, `user.reg` and `userdef.reg` files into the
+    /// `HKEY_LOCAL_MACHINE`, `HKEY_CURRENT_USER` and `HKEY_USERS\.Default` hives.
+    ///
+    /// See [Prefix::open_with] for details.
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<Self, Error> {
+        Self::open_with(path, &LoadOptions::default())
+    }
+
+    /// Opens a Wine prefix, loading its registry files according to `options`.
+    ///
+    /// Registry files missing from the prefix are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the prefix directory.
+    /// * `options` - How the registry files are loaded.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the prefix, or an error if a registry file couldn't be loaded or
+    /// the directory holds no registry files at all.
+    pub fn open_with<T: AsRef<Path>>(path: T, options: &LoadOptions) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let mut hives = BTreeMap::new();
+
+        for (file_name, hive) in HIVE_FILES {
+            let file = path.join(file_name);
+            let metadata = match std::fs::metadata(&file) {
+                Ok(metadata) => metadata,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error.into()),
+            };
+            let registry = Registry::try_from_with(&file, hive, options)?;
+            hives.insert(
+                hive,
+                HiveFile {
+                    path: file,
+                    modified: metadata.modified()?,
+                    registry,
+                },
+            );
+        }
+
+        if hives.is_empty() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no registry files found in '{}'", path.display()),
+            )));
+        }
+
+        Ok(Self { path, hives })
+    }
+
+    /// Opens a Wine prefix like [Prefix::open_with], holding a shared [PrefixLock] while its
+    /// registry files are read so they aren't read while being rewritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the prefix directory.
+    /// * `options` - How the registry files are loaded.
+    /// * `lock` - How long to wait for the prefix to be free, including for its wineserver to
+    ///   flush the registry and exit.
+    ///
+    /// # Returns
+    ///
+    /// The same as [Prefix::open_with], or [Error::Locked] if the prefix stayed busy.
+    pub fn open_locked<T: AsRef<Path>>(
+        path: T,
+        options: &LoadOptions,
+        lock: &LockPolicy,
+    ) -> Result<Self, Error> {
+        let _lock = PrefixLock::shared(&path, lock)?;
+        Self::open_with(path, options)
+    }
+
+    /// Returns the path of the prefix directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the registry files found in the prefix, by hive.
+    pub fn hives(&self) -> &BTreeMap<Hive, HiveFile> {
+        &self.hives
+    }
+
+    /// Returns the registry of the given hive, if its file was found.
+    pub fn registry(&self, hive: Hive) -> Option<&Registry> {
+        self.hives.get(&hive).map(HiveFile::registry)
+    }
+
+    /// Returns when the registry file of the given hive was last modified, if it was found.
+    pub fn modified(&self, hive: Hive) -> Option<SystemTime> {
+        self.hives.get(&hive).map(HiveFile::modified)
+    }
+
+    /// Computes the diff between two prefixes, e.g. snapshots taken before and after installing
+    /// a program.
+    ///
+    /// See [Prefix::diff_with] for details.
+    pub fn diff(old: &Prefix, new: &Prefix) -> PrefixDiff {
+        Self::diff_with(old, new, &DiffOptions::default())
+    }
+
+    /// Computes the diff between two prefixes, comparing values according to `options`.
+    ///
+    /// Every hive found in either prefix is diffed, a hive missing from one of them being
+    /// treated as empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - The prefix before the changes.
+    /// * `new` - The prefix after the changes.
+    /// * `options` - How values are compared.
+    ///
+    /// # Returns
+    ///
+    /// The per-hive diffs, which can be combined into a single patch.
+    pub fn diff_with(old: &Prefix, new: &Prefix, options: &DiffOptions) -> PrefixDiff {
+        let empty = |hive| {
+            Registry::from_regashii(regashii::Registry::new(regashii::Format::Regedit4), hive)
+        };
+
+        let mut hives = BTreeMap::new();
+        for hive in old.hives.keys().chain(new.hives.keys()).copied() {
+            if hives.contains_key(&hive) {
+                continue;
+            }
+            let diff = match (old.registry(hive), new.registry(hive)) {
+                (Some(old), Some(new)) => RegistryDiff::new(old, new, options),
+                (Some(old), None) => RegistryDiff::new(old, &empty(hive), options),
+                (None, Some(new)) => RegistryDiff::new(&empty(hive), new, options),
+                (None, None) => continue,
+            };
+            hives.insert(hive, diff);
+        }
+
+        PrefixDiff { hives }
+    }
+}
+
+/// The result of diffing two Wine prefixes, holding one diff per hive.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrefixDiff {
+    /// The diffs of the hives found in either prefix.
+    hives: BTreeMap<Hive, RegistryDiff>,
+}
+
+impl PrefixDiff {
+    /// Returns the diffs of the hives found in either prefix.
+    pub fn hives(&self) -> &BTreeMap<Hive, RegistryDiff> {
+        &self.hives
+    }
+
+    /// Returns the diff of the given hive, if it was found in either prefix.
+    pub fn hive(&self, hive: Hive) -> Option<&RegistryDiff> {
+        self.hives.get(&hive)
+    }
+
+    /// Returns whether no hive changed.
+    pub fn is_empty(&self) -> bool {
+        self.hives.values().all(RegistryDiff::is_empty)
+    }
+
+    /// Combines the diffs of all hives into a single diff.
+    pub fn combined(&self) -> RegistryDiff {
+        self.hives.values().cloned().collect()
+    }
+
+    /// Converts the diffs of all hives into a single regashii registry patch.
+    pub fn to_patch(&self) -> regashii::Registry {
+        self.combined().to_patch()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::KeyChange;
+    use regashii::KeyName;
+
+    #[test]
+    fn test_open_prefix() {
+        let prefix = std::env::temp_dir().join(format!("regdiff-prefix-{}", std::process::id()));
+        std::fs::create_dir_all(&prefix).unwrap();
+        std::fs::copy("./registries/user.reg", prefix.join("user.reg")).unwrap();
+        std::fs::write(
+            prefix.join("userdef.reg"),
+            "WINE REGISTRY Version 2\n\n[Control Panel\\\\Desktop] 1740588656\n\"Wallpaper\"=\"\"\n",
+        )
+        .unwrap();
+
+        let opened = Prefix::open(&prefix);
+        let missing = Prefix::open(prefix.join("missing"));
+        std::fs::remove_dir_all(&prefix).unwrap();
+
+        let opened = opened.unwrap();
+        assert_eq!(
+            opened.hives().keys().copied().collect::<Vec<_>>(),
+            vec![Hive::CurrentUser, Hive::DefaultUser]
+        );
+        assert!(opened.registry(Hive::LocalMachine).is_none());
+        assert!(opened.modified(Hive::CurrentUser).is_some());
+
+        let desktop = opened
+            .registry(Hive::DefaultUser)
+            .unwrap()
+            .key(&KeyName::new("Control Panel\\Desktop"))
+            .unwrap();
+        assert_eq!(
+            desktop.name().raw(),
+            "HKEY_USERS\\.Default\\Control Panel\\Desktop"
+        );
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_diff_prefixes() {
+        let root = std::env::temp_dir().join(format!("regdiff-prefixes-{}", std::process::id()));
+        let (old, new) = (root.join("old"), root.join("new"));
+        std::fs::create_dir_all(&old).unwrap();
+        std::fs::create_dir_all(&new).unwrap();
+        std::fs::copy("./registries/old.reg", old.join("system.reg")).unwrap();
+        std::fs::copy("./registries/new.reg", new.join("system.reg")).unwrap();
+        std::fs::copy("./registries/user.reg", new.join("user.reg")).unwrap();
+
+        let prefixes = (Prefix::open(&old), Prefix::open(&new));
+        std::fs::remove_dir_all(&root).unwrap();
+        let diff = Prefix::diff(&prefixes.0.unwrap(), &prefixes.1.unwrap());
+
+        let system = diff.hive(Hive::LocalMachine).unwrap();
+        let user = diff.hive(Hive::CurrentUser).unwrap();
+        assert!(!system.is_empty());
+        assert!(user
+            .keys()
+            .values()
+            .all(|key| key.change() == KeyChange::Added));
+        assert_eq!(
+            diff.combined().keys().len(),
+            system.keys().len() + user.keys().len()
+        );
+        assert_eq!(diff.to_patch().keys().len(), diff.combined().keys().len());
+    }
+}
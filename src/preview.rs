@@ -0,0 +1,121 @@
+use crate::prelude::{DiffEntry, KeyChange, KeyDiff, Registry, RegistryDiff};
+use std::collections::BTreeMap;
+
+/// The entries of a patch sorted by whether applying it to a registry changes anything, as
+/// returned by [Registry::preview].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Preview<'a> {
+    /// The entries changing the registry.
+    effective: Vec<DiffEntry<'a>>,
+    /// The entries leaving the registry as it is.
+    no_op: Vec<DiffEntry<'a>>,
+}
+
+impl<'a> Preview<'a> {
+    /// Returns the entries changing the registry, in the order of [RegistryDiff::entries].
+    pub fn effective(&self) -> &[DiffEntry<'a>] {
+        &self.effective
+    }
+
+    /// Returns the entries leaving the registry as it is, such as values already set to the
+    /// data of the patch, in the order of [RegistryDiff::entries].
+    pub fn no_op(&self) -> &[DiffEntry<'a>] {
+        &self.no_op
+    }
+
+    /// Returns whether applying the patch would leave the registry as it is.
+    pub fn is_no_op(&self) -> bool {
+        self.effective.is_empty()
+    }
+}
+
+impl Registry {
+    /// Finds which entries of a patch would actually change this registry if applied with
+    /// [Registry::apply], without changing it.
+    ///
+    /// A value entry is effective if the value would get different data or be deleted. The
+    /// entry of a deleted key is effective if the key exists, and that of an added or modified
+    /// key if the key would be created or get a different security descriptor or link.
+    /// Entries for keys of other hives are no-ops.
+    pub fn preview<'a>(&self, patch: &'a RegistryDiff) -> Preview<'a> {
+        let undo = self.clone().apply(patch);
+        let changed: BTreeMap<String, &KeyDiff> = undo
+            .keys()
+            .values()
+            .map(|key| (key.name().raw().to_lowercase(), key))
+            .collect();
+
+        let (effective, no_op) = patch.entries().partition(|entry| {
+            let undo = match changed.get(&entry.key_name().raw().to_lowercase()) {
+                Some(undo) => undo,
+                None => return false,
+            };
+            match (entry.value_name(), entry.change()) {
+                (Some(name), _) => undo.values().contains_key(name),
+                (None, KeyChange::Deleted) => undo.change() == KeyChange::Added,
+                (None, _) => {
+                    undo.change() == KeyChange::Deleted
+                        || undo.security().is_some()
+                        || undo.link().is_some()
+                }
+            }
+        });
+        Preview { effective, no_op }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Hive;
+    use regashii::ValueName;
+
+    #[test]
+    fn test_preview() {
+        let registry = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000001\n\"B\"=\"b\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        // The patch was made for another state, where App had a C value and Gone existed.
+        let base = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"C\"=\"c\"\n\n[HKEY_CURRENT_USER\\Gone]\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let patch = RegistryDiff::from_patch_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000001\n\"B\"=\"c\"\n\"C\"=-\n\n\
+            [HKEY_CURRENT_USER\\New]\n\n[-HKEY_CURRENT_USER\\Gone]\n",
+            &base,
+        )
+        .unwrap();
+
+        let preview = registry.preview(&patch);
+        let names = |entries: &[DiffEntry]| -> Vec<String> {
+            entries
+                .iter()
+                .map(|entry| match entry.value_name() {
+                    Some(ValueName::Named(name)) => name.clone(),
+                    _ => entry.key_name().raw().to_string(),
+                })
+                .collect()
+        };
+        assert_eq!(names(preview.effective()), ["B", "HKEY_CURRENT_USER\\New"]);
+        let mut no_op = names(preview.no_op());
+        no_op.sort();
+        assert_eq!(
+            no_op,
+            [
+                "A",
+                "C",
+                "HKEY_CURRENT_USER\\App",
+                "HKEY_CURRENT_USER\\Gone"
+            ]
+        );
+        assert!(!preview.is_no_op());
+
+        let mut applied = registry.clone();
+        applied.apply(&patch);
+        assert!(applied.preview(&patch).is_no_op());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::prelude::{Key, KeyPattern, Registry};
+use regashii::{KeyName, ValueName};
+use std::collections::BTreeMap;
+
+/// The keys registering installed programs, one subkey per program, for native and 32-bit
+/// programs.
+const UNINSTALL_KEYS: [&str; 2] = [
+    "*\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\*",
+    "*\\Software\\Wow6432Node\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\*",
+];
+
+/// A program registered in an `Uninstall` key, as listed by "Add/Remove Programs".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstalledProgram {
+    /// The full name of the program's key.
+    key: KeyName,
+    /// The name of the program's key, identifying the program, often a product GUID.
+    id: String,
+    /// The `DisplayName` of the program.
+    name: String,
+    /// The `DisplayVersion` of the program.
+    version: Option<String>,
+    /// The `Publisher` of the program.
+    publisher: Option<String>,
+    /// The `InstallLocation` of the program.
+    install_location: Option<String>,
+    /// The `UninstallString` of the program.
+    uninstall_command: Option<String>,
+}
+
+impl InstalledProgram {
+    /// Reads a program from its key.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the key has no display name, like the hidden entries of updates and components.
+    fn from_key(key: &Key) -> Option<Self> {
+        let string = |name: &str| match key.values().get(&ValueName::named(name))?.value() {
+            regashii::Value::Sz(data) | regashii::Value::ExpandSz(data) => Some(data.clone()),
+            _ => None,
+        };
+
+        Some(Self {
+            key: key.name().clone(),
+            id: key.name().raw().rsplit('\\').next()?.to_string(),
+            name: string("DisplayName")?,
+            version: string("DisplayVersion"),
+            publisher: string("Publisher"),
+            install_location: string("InstallLocation"),
+            uninstall_command: string("UninstallString"),
+        })
+    }
+
+    /// Returns the full name of the program's key.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the name of the program's key, identifying the program.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the display name of the program.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the version of the program, if registered.
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Returns the publisher of the program, if registered.
+    pub fn publisher(&self) -> Option<&str> {
+        self.publisher.as_deref()
+    }
+
+    /// Returns the directory the program is installed in, if registered.
+    pub fn install_location(&self) -> Option<&str> {
+        self.install_location.as_deref()
+    }
+
+    /// Returns the command uninstalling the program, if registered.
+    pub fn uninstall_command(&self) -> Option<&str> {
+        self.uninstall_command.as_deref()
+    }
+}
+
+impl std::fmt::Display for InstalledProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, " {}", version)?;
+        }
+        Ok(())
+    }
+}
+
+/// The differences between the programs installed in two registries.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InstalledProgramChanges {
+    /// The programs only installed in the new registry.
+    installed: Vec<InstalledProgram>,
+    /// The programs only installed in the old registry.
+    removed: Vec<InstalledProgram>,
+    /// The programs installed in both registries whose details differ, as old and new.
+    updated: Vec<(InstalledProgram, InstalledProgram)>,
+}
+
+impl InstalledProgramChanges {
+    /// Compares the programs installed in two registries, matching them by key.
+    pub fn between(old: &Registry, new: &Registry) -> Self {
+        let by_key = |registry: &Registry| -> BTreeMap<String, InstalledProgram> {
+            registry
+                .installed_programs()
+                .into_iter()
+                .map(|program| (program.key.raw().to_lowercase(), program))
+                .collect()
+        };
+        let (mut old, new) = (by_key(old), by_key(new));
+
+        let mut changes = Self::default();
+        for (key, program) in new {
+            match old.remove(&key) {
+                Some(previous) if previous != program => changes.updated.push((previous, program)),
+                Some(_) => {}
+                None => changes.installed.push(program),
+            }
+        }
+        changes.removed.extend(old.into_values());
+        changes
+    }
+
+    /// Returns the programs only installed in the new registry.
+    pub fn installed(&self) -> &[InstalledProgram] {
+        &self.installed
+    }
+
+    /// Returns the programs only installed in the old registry.
+    pub fn removed(&self) -> &[InstalledProgram] {
+        &self.removed
+    }
+
+    /// Returns the programs installed in both registries whose details differ, as old and new.
+    pub fn updated(&self) -> &[(InstalledProgram, InstalledProgram)] {
+        &self.updated
+    }
+
+    /// Returns whether the same programs are installed in both registries.
+    pub fn is_empty(&self) -> bool {
+        self.installed.is_empty() && self.removed.is_empty() && self.updated.is_empty()
+    }
+}
+
+impl Registry {
+    /// Returns the programs registered in the `Uninstall` keys of the registry, sorted by key.
+    ///
+    /// Entries without a `DisplayName`, which "Add/Remove Programs" hides, are left out.
+    pub fn installed_programs(&self) -> Vec<InstalledProgram> {
+        let patterns = UNINSTALL_KEYS.map(KeyPattern::new);
+        self.keys()
+            .values()
+            .filter(|key| patterns.iter().any(|pattern| pattern.matches(key.name())))
+            .filter_map(InstalledProgram::from_key)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RegistryBuilder;
+    use crate::prelude::Hive;
+
+    fn registry(programs: &[(&str, &str, &str)]) -> Registry {
+        let uninstall = "Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall";
+        programs
+            .iter()
+            .fold(
+                RegistryBuilder::new(Hive::LocalMachine),
+                |builder, (id, name, version)| {
+                    let key = format!("{}\\{}", uninstall, id);
+                    builder.with_sz(&key, "DisplayName", name).with_sz(
+                        &key,
+                        "DisplayVersion",
+                        version,
+                    )
+                },
+            )
+            .build()
+    }
+
+    #[test]
+    fn test_installed_programs() {
+        let registry = registry(&[("Steam", "Steam", "2.10.91.91"), ("{ABC}", "Game", "1.0")])
+            .overlay(&Registry::from_regashii(
+                regashii::Registry::new(regashii::Format::Regedit4).with(
+                    KeyName::new("Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\KB1"),
+                    regashii::Key::new(),
+                ),
+                Hive::LocalMachine,
+            ));
+
+        let programs = registry.installed_programs();
+        let names: Vec<String> = programs.iter().map(InstalledProgram::to_string).collect();
+        assert_eq!(names, vec!["Steam 2.10.91.91", "Game 1.0"]);
+        assert_eq!(programs[1].id(), "{ABC}");
+        assert_eq!(programs[0].publisher(), None);
+    }
+
+    #[test]
+    fn test_installed_program_changes() {
+        let old = registry(&[("Steam", "Steam", "1"), ("Old", "Old", "1")]);
+        let new = registry(&[("Steam", "Steam", "2"), ("New", "New", "1")]);
+        let changes = InstalledProgramChanges::between(&old, &new);
+
+        assert_eq!(changes.installed()[0].name(), "New");
+        assert_eq!(changes.removed()[0].name(), "Old");
+        assert_eq!(changes.updated()[0].1.version(), Some("2"));
+        assert!(InstalledProgramChanges::between(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+use crate::prelude::{Operation, Value};
+use std::path::{Path, PathBuf};
+
+/// Where a [Value] came from, so reports can cite the file, line or snapshot it was read
+/// from.
+///
+/// Provenance is carried along with values through diffs, merges and patches, but is never
+/// compared: values holding the same data are equal wherever they came from.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Provenance {
+    /// The file the value was read from.
+    file: Option<PathBuf>,
+    /// The 1-based line number of the value in its file.
+    line: Option<usize>,
+    /// The identifier of the snapshot the value was read from.
+    snapshot: Option<String>,
+}
+
+impl Provenance {
+    /// Constructs an empty [Provenance], citing nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the provenance citing the file the value was read from.
+    pub fn with_file<T: Into<PathBuf>>(mut self, file: T) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Returns the provenance citing the 1-based line number of the value in its file.
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Returns the provenance citing the snapshot the value was read from, such as a
+    /// [SnapshotId](crate::prelude::SnapshotId) or a commit.
+    pub fn with_snapshot<T: Into<String>>(mut self, snapshot: T) -> Self {
+        self.snapshot = Some(snapshot.into());
+        self
+    }
+
+    /// Returns the file the value was read from.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Returns the 1-based line number of the value in its file.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Returns the identifier of the snapshot the value was read from.
+    pub fn snapshot(&self) -> Option<&str> {
+        self.snapshot.as_deref()
+    }
+
+    /// Fills in the fields this provenance doesn't cite from `other`.
+    pub(crate) fn fill(&mut self, other: &Provenance) {
+        if self.file.is_none() {
+            self.file = other.file.clone();
+        }
+        if self.line.is_none() {
+            self.line = other.line;
+        }
+        if self.snapshot.is_none() {
+            self.snapshot = other.snapshot.clone();
+        }
+    }
+}
+
+impl std::fmt::Display for Provenance {
+    /// Renders the provenance as `file:line (snapshot id)`, leaving out what isn't cited.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => parts.push(format!("{}:{}", file.display(), line)),
+            (Some(file), None) => parts.push(file.display().to_string()),
+            (None, Some(line)) => parts.push(format!("line {}", line)),
+            (None, None) => {}
+        }
+        match (&self.snapshot, parts.is_empty()) {
+            (Some(snapshot), true) => parts.push(format!("snapshot {}", snapshot)),
+            (Some(snapshot), false) => parts.push(format!("(snapshot {})", snapshot)),
+            (None, _) => {}
+        }
+        if parts.is_empty() {
+            write!(f, "unknown")
+        } else {
+            write!(f, "{}", parts.join(" "))
+        }
+    }
+}
+
+impl Operation<Value> {
+    /// Returns where the data the operation results in came from, or for deletions where
+    /// the deleted data came from.
+    pub fn provenance(&self) -> Option<&Provenance> {
+        match self {
+            Operation::Unchanged => None,
+            Operation::Add { data } | Operation::Delete { data } => data.provenance(),
+            Operation::Modify { new_data, .. }
+            | Operation::Volatile { new_data, .. }
+            | Operation::Reordered { new_data, .. } => new_data.provenance(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{DiffOptions, Hive, LoadOptions, Registry, RegistryDiff};
+    use regashii::{KeyName, ValueName};
+
+    #[test]
+    fn test_provenance() {
+        let options = LoadOptions::new().with_provenance(true);
+        let old = Registry::try_from_str_with(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=\"a\"\n",
+            Hive::CurrentUser,
+            &options,
+        )
+        .unwrap()
+        .with_provenance(Provenance::new().with_file("old.reg"));
+        let new = Registry::try_from_str_with(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\n\"A\"=\"b\"\n\"B\"=\"b\"\n",
+            Hive::CurrentUser,
+            &options,
+        )
+        .unwrap()
+        .with_provenance(Provenance::new().with_file("new.reg").with_snapshot("abc"));
+        let value = |registry: &Registry| {
+            registry.key(&KeyName::new("App")).unwrap().values()[&ValueName::named("A")].clone()
+        };
+        assert_eq!(value(&old).provenance().unwrap().to_string(), "old.reg:4");
+        assert_eq!(
+            value(&old),
+            Value::new(ValueName::named("A"), regashii::Value::Sz("a".into()))
+        );
+
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::default());
+        let app = &diff.keys()[&KeyName::new("HKEY_CURRENT_USER\\App")];
+        let cite = |name: &str| {
+            app.values()[&ValueName::named(name)]
+                .provenance()
+                .map(Provenance::to_string)
+        };
+        assert_eq!(cite("A").as_deref(), Some("new.reg:5 (snapshot abc)"));
+        assert_eq!(cite("B").as_deref(), Some("new.reg:6 (snapshot abc)"));
+
+        let merged = old.overlay(&new);
+        let key = merged.key(&KeyName::new("App")).unwrap();
+        assert_eq!(
+            key.values()[&ValueName::named("A")]
+                .provenance()
+                .and_then(Provenance::line),
+            Some(5)
+        );
+    }
+}
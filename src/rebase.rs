@@ -0,0 +1,156 @@
+use crate::conflict::{outcome, same_data};
+use crate::prelude::{
+    Conflict, ConflictKind, DiffOptions, Hive, Key, KeyChange, Operation, Registry, RegistryDiff,
+    Value,
+};
+use std::collections::BTreeMap;
+
+impl RegistryDiff {
+    /// Re-computes the diff relative to a baseline that drifted from the one it was made
+    /// against, like `git rebase` replays commits onto a newer branch.
+    ///
+    /// Changes `onto` already holds are dropped, and the others are expressed against the
+    /// data `onto` holds, so the rebased diff deletes and replaces the current data rather
+    /// than the data the diff was made against. A change conflicts when `onto` changed the same
+    /// value to other data, deleted a key this diff changes, or changed a key this diff
+    /// deletes. Conflicting changes are kept in the rebased diff, so applying it makes this
+    /// diff win; use [RegistryDiff::filtered] to drop them instead.
+    ///
+    /// Only the keys of the hive of `onto` are rebased, the others being dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `onto` - The new baseline.
+    ///
+    /// # Returns
+    ///
+    /// The rebased diff, keeping the metadata of this one, and the conflicts by key name.
+    /// In conflicts, `ours` is the data this diff sets and `theirs` the data `onto` holds.
+    pub fn rebase(&self, onto: &Registry) -> (RegistryDiff, Vec<Conflict>) {
+        let mut rebased = onto.clone();
+        rebased.apply(self);
+        let diff = RegistryDiff::new(onto, &rebased, &DiffOptions::new().with_security(true))
+            .with_metadata(self.metadata().clone());
+
+        let current: BTreeMap<String, &Key> = onto
+            .keys()
+            .values()
+            .map(|key| (key.name().raw().to_lowercase(), key))
+            .collect();
+        let mut conflicts = Vec::new();
+        for change in self.keys().values() {
+            match Hive::split(change.name().raw()) {
+                Some((hive, _)) if hive == onto.hive() => {}
+                _ => continue,
+            }
+            let key = current.get(&change.name().raw().to_lowercase()).copied();
+            match (change.change(), key) {
+                (KeyChange::Unchanged, _) | (KeyChange::Deleted, None) => {}
+                (KeyChange::Deleted, Some(key)) => {
+                    let changed = key.values().values().any(|value| {
+                        !matches!(change.values().get(value.name()),
+                            Some(Operation::Delete { data }) if same_data(Some(data), Some(value)))
+                    });
+                    if changed {
+                        conflicts.push(Conflict::new(
+                            change.name().clone(),
+                            None,
+                            ConflictKind::DeletedByOurs,
+                        ));
+                    }
+                }
+                (KeyChange::Modified, None) => conflicts.push(Conflict::new(
+                    change.name().clone(),
+                    None,
+                    ConflictKind::DeletedByTheirs,
+                )),
+                (KeyChange::Added | KeyChange::Modified, key) => {
+                    for (name, operation) in change.values() {
+                        let ours = match outcome(operation) {
+                            Some(ours) => ours,
+                            None => continue,
+                        };
+                        let theirs = key.and_then(|key| key.values().get(name));
+                        let expected = expected(operation);
+                        if !same_data(theirs, ours) && !same_data(theirs, expected) {
+                            conflicts.push(Conflict::new(
+                                change.name().clone(),
+                                Some(name.clone()),
+                                ConflictKind::Value {
+                                    ours: ours.cloned(),
+                                    theirs: theirs.cloned(),
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        conflicts.sort_by(|a, b| a.key().cmp(b.key()));
+        (diff, conflicts)
+    }
+}
+
+/// Returns the data an operation expects a value to hold before it, `None` if it expects
+/// the value to be missing.
+fn expected(operation: &Operation<Value>) -> Option<&Value> {
+    match operation {
+        Operation::Modify { old_data: data, .. } | Operation::Delete { data } => Some(data),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regashii::ValueName;
+
+    #[test]
+    fn test_rebase() {
+        let base = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000001\n\"B\"=\"b\"\n\
+            \"C\"=\"c\"\n\n[HKEY_CURRENT_USER\\Old]\n\"D\"=\"d\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let diff = RegistryDiff::from_patch_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000002\n\"B\"=\"ours\"\n\
+            \"C\"=-\n\"E\"=\"e\"\n\n[-HKEY_CURRENT_USER\\Old]\n",
+            &base,
+        )
+        .unwrap();
+        let onto = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000002\n\"B\"=\"theirs\"\n\
+            \"C\"=\"c\"\n\n[HKEY_CURRENT_USER\\Old]\n\"D\"=\"changed\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+
+        let (rebased, conflicts) = diff.rebase(&onto);
+        let app = &rebased.keys()[&regashii::KeyName::new("HKEY_CURRENT_USER\\App")];
+        let mut names: Vec<&ValueName> = app.values().keys().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                &ValueName::named("B"),
+                &ValueName::named("C"),
+                &ValueName::named("E")
+            ]
+        );
+        assert_eq!(
+            conflicts
+                .iter()
+                .map(|conflict| (conflict.key().raw(), conflict.value().cloned()))
+                .collect::<Vec<_>>(),
+            [
+                ("HKEY_CURRENT_USER\\App", Some(ValueName::named("B"))),
+                ("HKEY_CURRENT_USER\\Old", None),
+            ]
+        );
+
+        let mut applied = onto.clone();
+        applied.apply(&rebased);
+        assert!(diff.rebase(&applied).0.is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::prelude::{DiffOptions, KeyPattern, Registry, RegistryDiff};
+use regashii::KeyName;
+
+/// Options controlling how [reconcile_with] converges a registry.
+#[derive(Clone, Debug, Default)]
+pub struct ReconcileOptions {
+    /// How values are compared.
+    diff: DiffOptions,
+    /// The patterns of the subtrees owned by the desired state.
+    managed: Vec<KeyPattern>,
+}
+
+impl ReconcileOptions {
+    /// Constructs a new [ReconcileOptions], comparing values exactly and owning no subtree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the options with values compared according to `options`.
+    pub fn with_diff_options(mut self, options: DiffOptions) -> Self {
+        self.diff = options;
+        self
+    }
+
+    /// Returns the options with the subtrees matching a pattern owned by the desired state,
+    /// such as `HKEY_CURRENT_USER\Software\Bottles\*`: keys inside them that the desired
+    /// state doesn't mention are deleted.
+    pub fn with_managed<T: Into<KeyPattern>>(mut self, pattern: T) -> Self {
+        self.managed.push(pattern.into());
+        self
+    }
+
+    /// Returns how values are compared.
+    pub fn diff_options(&self) -> &DiffOptions {
+        &self.diff
+    }
+
+    /// Returns the patterns of the subtrees owned by the desired state.
+    pub fn managed(&self) -> &[KeyPattern] {
+        &self.managed
+    }
+}
+
+/// Computes the patch converging a registry to a desired state, like configuration
+/// management tools do.
+///
+/// See [reconcile_with] for details.
+pub fn reconcile(desired: &Registry, actual: &Registry) -> RegistryDiff {
+    reconcile_with(desired, actual, &ReconcileOptions::default())
+}
+
+/// Computes the patch converging a registry to a desired state according to `options`.
+///
+/// Unlike [RegistryDiff::new], keys `desired` doesn't mention are left untouched rather
+/// than deleted, so `desired` only has to describe the keys it manages, unless they lie in a
+/// subtree owned through [ReconcileOptions::with_managed]. The keys it mentions are
+/// converged exactly: missing keys and values are added, differing values are replaced and
+/// values `desired` doesn't hold are deleted. Key names are matched case-insensitively, like the
+/// registry does, and the patch uses the names `actual` stores the keys under.
+///
+/// # Arguments
+///
+/// * `desired` - The keys to converge to.
+/// * `actual` - The current registry.
+/// * `options` - How values are compared and which subtrees `desired` owns.
+///
+/// # Returns
+///
+/// The diff that, applied to `actual`, converges it to `desired`. It is empty when `actual`
+/// already holds every key of `desired` and no other key in the owned subtrees.
+pub fn reconcile_with(
+    desired: &Registry,
+    actual: &Registry,
+    options: &ReconcileOptions,
+) -> RegistryDiff {
+    // Keys are matched case-insensitively, so `desired` names them the way `actual` stores them.
+    let stored = |name: &KeyName| actual.stored_name(name.raw());
+    let mut aligned = desired.filter_keys(|name| stored(name) == *name);
+    for (name, key) in desired.keys() {
+        let stored = stored(name);
+        if stored != *name {
+            aligned.insert_key(stored.raw(), key.clone());
+        }
+    }
+
+    let managed = actual.filter_keys(|name| {
+        aligned.keys().contains_key(name)
+            || actual.keys().get(name).is_some_and(|key| {
+                options
+                    .managed
+                    .iter()
+                    .any(|pattern| pattern.matches_subtree(key.name()))
+            })
+    });
+    RegistryDiff::new(&managed, &aligned, &options.diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{Hive, KeyChange};
+
+    #[test]
+    fn test_reconcile() {
+        let desired = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\Wine\\Direct3D]\n\"renderer\"=\"vulkan\"\n\n\
+            [HKEY_CURRENT_USER\\Software\\Wine\\DllOverrides]\n\"d3d9\"=\"native\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let mut actual = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\Wine\\Direct3D]\n\"renderer\"=\"gl\"\n\
+            \"csmt\"=dword:00000001\n\n[HKEY_CURRENT_USER\\Software\\App]\n\"A\"=\"a\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+
+        let diff = reconcile(&desired, &actual);
+        assert_eq!(diff.keys().len(), 2);
+        let direct3d = &diff.keys()[&KeyName::new("HKEY_CURRENT_USER\\Software\\Wine\\Direct3D")];
+        assert_eq!(direct3d.change(), KeyChange::Modified);
+        assert_eq!(direct3d.values().len(), 2);
+        assert_eq!(
+            diff.keys()[&KeyName::new("HKEY_CURRENT_USER\\Software\\Wine\\DllOverrides")].change(),
+            KeyChange::Added
+        );
+
+        actual.apply(&diff);
+        assert!(actual.key(&KeyName::new("Software\\App")).is_some());
+        assert!(reconcile(&desired, &actual).is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_matches_keys_ignoring_case() {
+        let desired = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\software\\wine\\Direct3D]\n\"renderer\"=\"vulkan\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let mut actual = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\Wine\\Direct3D]\n\"renderer\"=\"gl\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+
+        let diff = reconcile(&desired, &actual);
+        assert_eq!(diff.keys().len(), 1);
+        let direct3d = &diff.keys()[&KeyName::new("HKEY_CURRENT_USER\\Software\\Wine\\Direct3D")];
+        assert_eq!(direct3d.change(), KeyChange::Modified);
+
+        actual.apply(&diff);
+        assert!(reconcile(&desired, &actual).is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_managed() {
+        let desired = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\Bottles\\Kept]\n\"A\"=\"a\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let actual = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\Bottles\\Kept]\n\"A\"=\"a\"\n\n\
+            [HKEY_CURRENT_USER\\Software\\Bottles\\Stale]\n\"B\"=\"b\"\n\n\
+            [HKEY_CURRENT_USER\\Software\\Other]\n\"C\"=\"c\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+
+        assert!(reconcile(&desired, &actual).is_empty());
+        let options =
+            ReconcileOptions::new().with_managed("HKEY_CURRENT_USER\\Software\\Bottles\\*");
+        let diff = reconcile_with(&desired, &actual, &options);
+        assert_eq!(
+            diff.keys()
+                .iter()
+                .map(|(name, key)| (name.raw(), key.change()))
+                .collect::<Vec<_>>(),
+            [(
+                "HKEY_CURRENT_USER\\Software\\Bottles\\Stale",
+                KeyChange::Deleted
+            )]
+        );
+    }
+}
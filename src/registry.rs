@@ -1,13 +1,92 @@
+use crate::drives::DriveMapping;
+use crate::encoding::repair_mojibake;
+use crate::parse::{self, Document, Syntax};
+#[cfg(feature = "fs")]
+use crate::prelude::Encoding;
+use crate::prelude::{
+    DiffOptions, DuplicateKeyPolicy, Error, KeyChange, KeyDiff, KeyPath, LoadOptions, Operation,
+    ParseWarning, Provenance, RegistryDiff, SecurityDescriptor, WriteOptions,
+};
+use crate::write;
 use regashii::{KeyName, ValueName};
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The maximum number of symbolic links followed when resolving a key.
+const MAX_LINK_DEPTH: usize = 16;
 
 /// The supported registry hives (root keys).
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Hive {
     /// Represents the HKEY_LOCAL_MACHINE hive.
     LocalMachine,
     /// Represents the HKEY_CURRENT_USER hive.
     CurrentUser,
+    /// Represents the default user profile `HKEY_USERS\.Default`, stored in Wine's `userdef.reg`.
+    DefaultUser,
+}
+
+impl Hive {
+    /// Returns the hive of a Wine registry file by its name: `system.reg`, `user.reg` or
+    /// `userdef.reg`.
+    pub fn of_file_name(name: &str) -> Option<Hive> {
+        match name {
+            "system.reg" => Some(Hive::LocalMachine),
+            "user.reg" => Some(Hive::CurrentUser),
+            "userdef.reg" => Some(Hive::DefaultUser),
+            _ => None,
+        }
+    }
+
+    /// Splits a full key name into its hive and the path of the key within the hive.
+    ///
+    /// Returns `None` for names outside of the supported hives.
+    pub fn split(name: &str) -> Option<(Hive, &str)> {
+        [Hive::LocalMachine, Hive::CurrentUser, Hive::DefaultUser]
+            .into_iter()
+            .find_map(|hive| {
+                let path = name.strip_prefix(&hive.to_string())?.strip_prefix('\\')?;
+                Some((hive, path))
+            })
+    }
+}
+
+/// Returns the name of a key section relative to the hive of the file, as keys of a
+/// [Registry] are indexed.
+///
+/// regedit files name keys in full, while Wine files are relative to their hive. Names of
+/// regedit files starting with another hive are left alone, and get prefixed like names of
+/// Wine files.
+pub(crate) fn relative_name(syntax: Syntax, hive: Hive, name: &str) -> &str {
+    match (syntax, Hive::split(name)) {
+        (Syntax::Regedit4 | Syntax::Regedit5, Some((own, path))) if own == hive => path,
+        _ => name,
+    }
+}
+
+/// Splits a native registry path, as used by link targets, into its hive and the path of the
+/// key within the hive.
+///
+/// `\Registry\Machine` is `HKEY_LOCAL_MACHINE`, `\Registry\User\.Default` is the default
+/// user and any other user SID is taken to be the current user.
+fn native_path(path: &str) -> Option<(Hive, &str)> {
+    fn strip<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+        let head = path.get(..prefix.len())?;
+        head.eq_ignore_ascii_case(prefix)
+            .then(|| &path[prefix.len()..])
+    }
+
+    if let Some(path) = strip(path, "\\Registry\\Machine\\") {
+        return Some((Hive::LocalMachine, path));
+    }
+    let user = strip(path, "\\Registry\\User\\")?;
+    if let Some(path) = strip(user, ".Default\\") {
+        return Some((Hive::DefaultUser, path));
+    }
+    let (_, path) = user.split_once('\\')?;
+    Some((Hive::CurrentUser, path))
 }
 
 impl std::fmt::Display for Hive {
@@ -18,29 +97,267 @@ impl std::fmt::Display for Hive {
             match self {
                 Hive::LocalMachine => "HKEY_LOCAL_MACHINE",
                 Hive::CurrentUser => "HKEY_CURRENT_USER",
+                Hive::DefaultUser => "HKEY_USERS\\.Default",
             }
         )
     }
 }
 
+/// The map type holding the values of a key, keyed by value name.
+///
+/// By default values are sorted by name. With the `preserve-order` feature values are kept
+/// in the order they were loaded or inserted in instead, so output and diffs follow the order
+/// of the source file.
+#[cfg(not(feature = "preserve-order"))]
+pub type ValueMap<T = Value> = BTreeMap<ValueName, T>;
+
+/// The map type holding the values of a key, keyed by value name.
+///
+/// By default values are sorted by name. With the `preserve-order` feature values are kept
+/// in the order they were loaded or inserted in instead, so output and diffs follow the order
+/// of the source file.
+#[cfg(feature = "preserve-order")]
+pub type ValueMap<T = Value> = indexmap::IndexMap<ValueName, T>;
+
+/// Feeds the values of a key into a hasher in name order, so maps equal regardless of their
+/// order hash the same with the `preserve-order` feature too.
+pub(crate) fn hash_values<T: Hash, H: Hasher>(values: &ValueMap<T>, state: &mut H) {
+    let mut entries: Vec<(&ValueName, &T)> = values.iter().collect();
+    entries.sort_by_key(|&(name, _)| name);
+    entries.hash(state);
+}
+
+/// Returns the outcome recorded in the `tracing` events of [Registry::apply] for a change,
+/// depending on whether it changed anything.
+#[cfg(feature = "tracing")]
+fn outcome(changed: bool) -> &'static str {
+    match changed {
+        true => "applied",
+        false => "unchanged",
+    }
+}
+
+/// Returns the name of a value as recorded in the `tracing` events of [Registry::apply], `@`
+/// for the default value.
+#[cfg(feature = "tracing")]
+fn value_label(name: &ValueName) -> &str {
+    match name {
+        ValueName::Named(name) => name,
+        ValueName::Default => "@",
+    }
+}
+
+/// The type of data held by a registry value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ValueKind {
+    /// A deletion marker, as found in patches.
+    Delete,
+    /// A REG_SZ string.
+    Sz,
+    /// A REG_EXPAND_SZ string containing unexpanded environment variables.
+    ExpandSz,
+    /// REG_BINARY data.
+    Binary,
+    /// A REG_DWORD number.
+    Dword,
+    /// A REG_MULTI_SZ list of strings.
+    MultiSz,
+    /// A REG_QWORD number.
+    Qword,
+    /// REG_NONE data, without a defined type.
+    None,
+    /// A REG_DWORD_BIG_ENDIAN number.
+    DwordBigEndian,
+    /// A REG_LINK native path, as held by the `SymbolicLinkValue` of link keys.
+    Link,
+    /// A REG_RESOURCE_LIST hardware resource list.
+    ResourceList,
+    /// A REG_FULL_RESOURCE_DESCRIPTOR hardware resource descriptor.
+    FullResourceDescriptor,
+    /// A REG_RESOURCE_REQUIREMENTS_LIST hardware resource requirements list.
+    ResourceRequirementsList,
+    /// Any other, unknown, value type.
+    Other,
+}
+
+impl ValueKind {
+    /// Returns the kind of the given regashii value.
+    pub fn of(value: &regashii::Value) -> Self {
+        match value {
+            regashii::Value::Delete => ValueKind::Delete,
+            regashii::Value::Sz(_) => ValueKind::Sz,
+            regashii::Value::ExpandSz(_) => ValueKind::ExpandSz,
+            regashii::Value::Binary(_) => ValueKind::Binary,
+            regashii::Value::Dword(_) => ValueKind::Dword,
+            regashii::Value::MultiSz(_) => ValueKind::MultiSz,
+            regashii::Value::Qword(_) => ValueKind::Qword,
+            regashii::Value::DwordBigEndian(_) => ValueKind::DwordBigEndian,
+            regashii::Value::Hex { kind, .. } => ValueKind::from_type(type_code(kind)),
+        }
+    }
+
+    /// Returns the kind of the given registry type code, as written in `hex(N):` data.
+    pub fn from_type(code: u32) -> Self {
+        match code {
+            0 => ValueKind::None,
+            1 => ValueKind::Sz,
+            2 => ValueKind::ExpandSz,
+            3 => ValueKind::Binary,
+            4 => ValueKind::Dword,
+            5 => ValueKind::DwordBigEndian,
+            6 => ValueKind::Link,
+            7 => ValueKind::MultiSz,
+            8 => ValueKind::ResourceList,
+            9 => ValueKind::FullResourceDescriptor,
+            10 => ValueKind::ResourceRequirementsList,
+            11 => ValueKind::Qword,
+            _ => ValueKind::Other,
+        }
+    }
+}
+
+/// Returns the registry type code of a regashii hex data kind, as written in `hex(N):` data.
+pub(crate) fn type_code(kind: &regashii::Kind) -> u32 {
+    match kind {
+        regashii::Kind::None => 0,
+        regashii::Kind::Sz => 1,
+        regashii::Kind::ExpandSz => 2,
+        regashii::Kind::Binary => 3,
+        regashii::Kind::Dword => 4,
+        regashii::Kind::DwordBigEndian => 5,
+        regashii::Kind::Link => 6,
+        regashii::Kind::MultiSz => 7,
+        regashii::Kind::ResourceList => 8,
+        regashii::Kind::FullResourceDescriptor => 9,
+        regashii::Kind::ResourceRequirementsList => 10,
+        regashii::Kind::Qword => 11,
+        regashii::Kind::Unknown(code) => *code,
+    }
+}
+
+/// Returns the regashii hex data kind of a registry type code, the reverse of [type_code].
+fn hex_kind(code: u32) -> regashii::Kind {
+    match code {
+        0 => regashii::Kind::None,
+        1 => regashii::Kind::Sz,
+        2 => regashii::Kind::ExpandSz,
+        3 => regashii::Kind::Binary,
+        4 => regashii::Kind::Dword,
+        5 => regashii::Kind::DwordBigEndian,
+        6 => regashii::Kind::Link,
+        7 => regashii::Kind::MultiSz,
+        8 => regashii::Kind::ResourceList,
+        9 => regashii::Kind::FullResourceDescriptor,
+        10 => regashii::Kind::ResourceRequirementsList,
+        11 => regashii::Kind::Qword,
+        code => regashii::Kind::Unknown(code),
+    }
+}
+
 /// Represents a registry value entry.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// Data of types regashii has no representation for, such as REG_NONE or resource lists, and
+/// typed data that can't be decoded without loss are kept as raw bytes along with their type
+/// code, so they are diffed and serialized exactly as found.
+///
+/// Values may cite where they came from as their [Provenance], which is ignored when
+/// comparing them.
+#[derive(Clone, Debug)]
 pub struct Value {
     /// The name of the registry value.
     name: ValueName,
-    /// The data associated with the value.
+    /// The data associated with the value, binary data for raw values.
     value: regashii::Value,
+    /// The registry type code of raw values.
+    raw_type: Option<u32>,
+    /// Where the value came from, if recorded.
+    provenance: Option<Box<Provenance>>,
 }
 
 impl Value {
     /// Constructs a new [Value] with the provided name and data.
     ///
+    /// Big-endian dwords and regashii hex data are kept as raw values of their type, like
+    /// values loaded from files.
+    ///
     /// # Arguments
     ///
     /// * `name` - The registry value name.
     /// * `value` - The registry data associated with this value.
     pub fn new(name: ValueName, value: regashii::Value) -> Self {
-        Self { name, value }
+        match value {
+            regashii::Value::DwordBigEndian(dword) => {
+                Self::raw(name, 5, dword.to_be_bytes().to_vec())
+            }
+            regashii::Value::Hex { kind, bytes } => Self::raw(name, type_code(&kind), bytes),
+            value => Self {
+                name,
+                value,
+                raw_type: None,
+                provenance: None,
+            },
+        }
+    }
+
+    /// Constructs a new [Value] holding raw data of the given registry type, as written in
+    /// `hex(N):` data.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The registry value name.
+    /// * `raw_type` - The registry type code, e.g. 0 for REG_NONE.
+    /// * `bytes` - The data, exactly as stored in the registry.
+    pub fn raw(name: ValueName, raw_type: u32, bytes: Vec<u8>) -> Self {
+        Self {
+            name,
+            value: regashii::Value::Binary(bytes),
+            raw_type: Some(raw_type),
+            provenance: None,
+        }
+    }
+
+    /// Returns the value with mojibake repaired in its name and string data.
+    fn repair_mojibake(self) -> Self {
+        let repair = |text: String| repair_mojibake(&text).unwrap_or(text);
+        let name = match self.name {
+            ValueName::Named(name) => ValueName::Named(repair(name)),
+            ValueName::Default => ValueName::Default,
+        };
+        let value = match self.value {
+            regashii::Value::Sz(data) => regashii::Value::Sz(repair(data)),
+            regashii::Value::ExpandSz(data) => regashii::Value::ExpandSz(repair(data)),
+            regashii::Value::MultiSz(data) => {
+                regashii::Value::MultiSz(data.into_iter().map(repair).collect())
+            }
+            value => value,
+        };
+        Self {
+            name,
+            value,
+            ..self
+        }
+    }
+
+    /// Returns the value stored under another name.
+    pub(crate) fn with_name(mut self, name: ValueName) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Returns the value citing where it came from.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = Some(Box::new(provenance));
+        self
+    }
+
+    /// Returns where the value came from, if recorded.
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_deref()
+    }
+
+    /// Returns the registry type code of raw values, or `None` for values held as regashii data.
+    pub fn raw_type(&self) -> Option<u32> {
+        self.raw_type
     }
 
     /// Returns a reference to the name of the registry value.
@@ -53,9 +370,37 @@ impl Value {
         &self.value
     }
 
+    /// Returns the type of the registry value's data.
+    pub fn kind(&self) -> ValueKind {
+        match self.raw_type {
+            Some(code) => ValueKind::from_type(code),
+            None => ValueKind::of(&self.value),
+        }
+    }
+
     /// Converts the [Value] into a tuple containing the underlying value name and data.
+    ///
+    /// Raw values are converted into regashii hex data of their type.
     pub fn into_regashii_value(self) -> (ValueName, regashii::Value) {
-        (self.name, self.value)
+        let data = self.regashii_data();
+        (self.name, data)
+    }
+
+    /// Returns the data of the value as regashii data, raw values becoming hex data of their
+    /// type.
+    pub(crate) fn regashii_data(&self) -> regashii::Value {
+        match (self.raw_type, &self.value) {
+            (Some(5), regashii::Value::Binary(bytes)) if bytes.len() == 4 => {
+                regashii::Value::DwordBigEndian(u32::from_be_bytes([
+                    bytes[0], bytes[1], bytes[2], bytes[3],
+                ]))
+            }
+            (Some(code), regashii::Value::Binary(bytes)) => regashii::Value::Hex {
+                kind: hex_kind(code),
+                bytes: bytes.clone(),
+            },
+            (_, value) => value.clone(),
+        }
     }
 
     /// Converts the [Value] into a tuple containing the underlying value name and a deleted value.
@@ -64,18 +409,90 @@ impl Value {
     }
 }
 
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value && self.raw_type == other.raw_type
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.raw_type.hash(state);
+        std::mem::discriminant(&self.value).hash(state);
+        match &self.value {
+            regashii::Value::Sz(string) | regashii::Value::ExpandSz(string) => string.hash(state),
+            regashii::Value::Binary(bytes) => bytes.hash(state),
+            regashii::Value::Dword(dword) => dword.hash(state),
+            regashii::Value::MultiSz(strings) => strings.hash(state),
+            regashii::Value::Qword(qword) => qword.hash(state),
+            // Equal data has the same variant, which is enough for a consistent hash.
+            _ => {}
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    /// Renders the value as its line in a `.reg` file, such as `"Name"=dword:00000001`.
+    ///
+    /// Names holding line breaks, which `.reg` files can't represent, are rendered as they are.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let options = WriteOptions::default();
+        write!(
+            f,
+            "{}={}",
+            write::value_name(&self.name, &options),
+            write::data(self, &options)
+        )
+    }
+}
+
 /// Represents a registry key, which can contain multiple values.
 #[derive(Clone, Debug)]
 pub struct Key {
     /// The full registry key name/path.
     name: KeyName,
     /// A map of registry values within the key.
-    values: BTreeMap<ValueName, Value>,
+    values: ValueMap,
+    /// The security descriptor of the key, if the file recorded one.
+    security: Option<SecurityDescriptor>,
+    /// The native path the key links to, if it is a symbolic link.
+    link: Option<String>,
+    /// The time the key was last written, in seconds since the Unix epoch, if the file
+    /// recorded it.
+    modified: Option<u64>,
+    /// A hash of the text the key was loaded from, if it was loaded from a single section of
+    /// a file and not modified since.
+    fingerprint: Option<u64>,
 }
 
 impl PartialEq for Key {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.values == other.values
+        self.name == other.name
+            && self.values == other.values
+            && self.security == other.security
+            && self.link == other.link
+    }
+}
+
+impl Eq for Key {}
+
+impl std::fmt::Display for Key {
+    /// Renders the name of the key in brackets, as in the header of its section in a `.reg`
+    /// file.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}]", self.name.raw())
+    }
+}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        hash_values(&self.values, state);
+        self.security.hash(state);
+        self.link.hash(state);
     }
 }
 
@@ -87,7 +504,7 @@ impl From<Key> for (KeyName, regashii::Key) {
         let mut key = regashii::Key::new();
 
         for (name, value) in values.into_iter() {
-            key = key.with(name, value.value)
+            key = key.with(name, value.regashii_data())
         }
 
         (name, key)
@@ -112,7 +529,49 @@ impl Key {
                 (key_name.clone(), new_value)
             })
             .collect();
-        Self { name, values }
+        Self {
+            name,
+            values,
+            security: None,
+            link: None,
+            modified: None,
+            fingerprint: None,
+        }
+    }
+
+    /// Returns the key with the given security descriptor.
+    pub fn with_security(mut self, security: SecurityDescriptor) -> Self {
+        self.security = Some(security);
+        self.fingerprint = None;
+        self
+    }
+
+    /// Returns a reference to the registry key's security descriptor, if it has one.
+    pub fn security(&self) -> Option<&SecurityDescriptor> {
+        self.security.as_ref()
+    }
+
+    /// Returns the key turned into a symbolic link to the given native path, such as
+    /// `\Registry\Machine\Software\Classes`.
+    pub fn with_link<T: Into<String>>(mut self, target: T) -> Self {
+        self.link = Some(target.into());
+        self.fingerprint = None;
+        self
+    }
+
+    /// Returns the native path the key links to, if it is a symbolic link.
+    ///
+    /// Links are loaded from the `REG_LINK` `SymbolicLinkValue` of the key, which is not
+    /// part of its values.
+    pub fn link(&self) -> Option<&str> {
+        self.link.as_deref()
+    }
+
+    /// Returns the time the key was last written, as recorded in Wine registry files. Not
+    /// part of the comparison of keys.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+            .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
     }
 
     /// Returns a reference to the registry key's name.
@@ -120,11 +579,38 @@ impl Key {
         &self.name
     }
 
-    /// Returns a reference to the sorted map of values in the registry key.
-    pub fn values(&self) -> &BTreeMap<ValueName, Value> {
+    /// Returns a reference to the map of values in the registry key.
+    pub fn values(&self) -> &ValueMap {
         &self.values
     }
 
+    /// Returns the map of values in the registry key for modification.
+    pub(crate) fn values_mut(&mut self) -> &mut ValueMap {
+        self.fingerprint = None;
+        &mut self.values
+    }
+
+    /// Returns a hash of the text the key was loaded from, equal for keys loaded from
+    /// identical text. `None` for keys built or modified in memory, or merged from several
+    /// sections of a file.
+    pub(crate) fn fingerprint(&self) -> Option<u64> {
+        self.fingerprint
+    }
+
+    /// Returns a copy of the key as a regashii key, to use features of regashii this crate
+    /// doesn't wrap.
+    ///
+    /// Raw values are converted into regashii hex data of their type, and the security
+    /// descriptor, link and provenance of values are dropped as regashii doesn't represent
+    /// them.
+    pub fn as_regashii(&self) -> regashii::Key {
+        self.values
+            .iter()
+            .fold(regashii::Key::new(), |key, (name, value)| {
+                key.with(name.clone(), value.regashii_data())
+            })
+    }
+
     /// Converts the [Key] into a tuple containing the underlying key name and the regashii key.
     pub fn into_regashii_key(self) -> (KeyName, regashii::Key) {
         let name = self.name;
@@ -133,7 +619,7 @@ impl Key {
         let mut key = regashii::Key::new();
 
         for (name, value) in values.into_iter() {
-            key = key.with(name, value.value)
+            key = key.with(name, value.regashii_data())
         }
 
         (name, key)
@@ -149,17 +635,78 @@ impl Key {
 ///
 /// This type is responsible for deserializing registry files and managing a collection
 /// of registry keys.
+#[derive(Clone, Debug)]
 pub struct Registry {
+    /// The hive the keys belong to.
+    hive: Hive,
     /// A map of registry keys keyed by their name.
     keys: BTreeMap<KeyName, Key>,
+    /// The malformed lines skipped while loading the registry leniently.
+    warnings: Vec<ParseWarning>,
 }
 
 impl Registry {
+    /// Returns the hive the keys belong to.
+    pub fn hive(&self) -> Hive {
+        self.hive
+    }
+
     /// Returns a reference to the entire collection of registry keys.
     pub fn keys(&self) -> &BTreeMap<KeyName, Key> {
         &self.keys
     }
 
+    /// Serializes the registry in the `Windows Registry Editor Version 5.00` syntax, with
+    /// hive-prefixed key names.
+    ///
+    /// Unlike converting keys into regashii keys, raw values keep their type, security
+    /// descriptors are written in `#security=` lines and link keys keep their
+    /// `SymbolicLinkValue`, so the output can be loaded back without losing data.
+    ///
+    /// # Returns
+    ///
+    /// The text of the file, or [Error::InvalidName] if a key or value name holds a line
+    /// break, which registry files can't represent.
+    pub fn serialize(&self) -> Result<String, Error> {
+        self.serialize_with(&WriteOptions::default())
+    }
+
+    /// Serializes the registry like [Registry::serialize], written according to `options`.
+    pub fn serialize_with(&self, options: &WriteOptions) -> Result<String, Error> {
+        let mut keys: Vec<&Key> = self.keys.values().collect();
+        options.sort_keys(&mut keys, |key| &key.name);
+        let mut out = format!("{}\n", write::HEADER);
+        for key in keys {
+            out.push('\n');
+            write::key_header(&mut out, &key.name, false, options)?;
+            if let Some(security) = &key.security {
+                write::security(&mut out, security, options);
+            }
+            if let Some(link) = &key.link {
+                write::link(&mut out, link, options);
+            }
+            let mut values: Vec<&Value> = key.values.values().collect();
+            options.sort_values(&mut values, |value| value.name());
+            for value in values {
+                write::value(&mut out, value, options)?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Consumes the registry, returning its keys.
+    pub(crate) fn into_keys(self) -> BTreeMap<KeyName, Key> {
+        self.keys
+    }
+
+    /// Returns the malformed lines skipped while loading the registry.
+    ///
+    /// This is always empty unless the registry was loaded with
+    /// [LoadOptions::with_strict] disabled.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
     /// Retrieves a specific registry key by its name.
     ///
     /// # Arguments
@@ -173,10 +720,428 @@ impl Registry {
         self.keys.get(name)
     }
 
+    /// Resolves a symbolic link key to the key it points to, following chains of links.
+    ///
+    /// Keys that aren't links resolve to themselves.
+    ///
+    /// # Returns
+    ///
+    /// `None` if a link points outside of this registry's hive, to a missing key, or if the
+    /// links form a cycle.
+    pub fn resolve_link<'a>(&'a self, key: &'a Key) -> Option<&'a Key> {
+        let mut current = key;
+        for _ in 0..MAX_LINK_DEPTH {
+            let target = match current.link() {
+                Some(target) => target,
+                None => return Some(current),
+            };
+            let (hive, path) = native_path(target)?;
+            if Hive::split(current.name.raw()).map(|(own, _)| own) != Some(hive) {
+                return None;
+            }
+            current = self.key_ignoring_case(path)?;
+        }
+        None
+    }
+
+    /// Returns the contents of the key a symbolic link key points to under the link's name,
+    /// or the key itself if it isn't a link or can't be resolved.
+    pub(crate) fn follow_link<'a>(&'a self, key: &'a Key) -> Cow<'a, Key> {
+        match (key.link(), self.resolve_link(key)) {
+            (Some(_), Some(target)) => Cow::Owned(Key {
+                name: key.name.clone(),
+                values: target.values.clone(),
+                security: key.security.clone(),
+                link: None,
+                modified: key.modified,
+                fingerprint: None,
+            }),
+            _ => Cow::Borrowed(key),
+        }
+    }
+
+    /// Layers another registry on top of this one, returning the combined registry.
+    ///
+    /// Keys present in only one registry are kept as-is. For keys present in both, the values
+    /// are merged, with values from `other` winning on conflicts. This mirrors how Wine layers
+    /// `user.reg` on top of the `userdef.reg` defaults, and can be used to build baselines
+    /// from templates.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The registry whose keys and values take precedence.
+    pub fn overlay(&self, other: &Registry) -> Self {
+        let mut keys = self.keys.clone();
+        for (name, key) in &other.keys {
+            match keys.get_mut(name) {
+                Some(existing) => {
+                    existing.name = key.name.clone();
+                    existing.fingerprint = None;
+                    existing
+                        .values
+                        .extend(key.values.iter().map(|(n, v)| (n.clone(), v.clone())));
+                    if key.security.is_some() {
+                        existing.security = key.security.clone();
+                    }
+                    if key.link.is_some() {
+                        existing.link = key.link.clone();
+                    }
+                }
+                None => {
+                    keys.insert(name.clone(), key.clone());
+                }
+            }
+        }
+
+        Self {
+            hive: self.hive,
+            keys,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Returns the keys present in both registries.
+    ///
+    /// Set operations compare keys by name only; the keys are taken from `self`.
+    /// Use [Registry::diff] to compare the values of common keys.
+    pub fn intersection(&self, other: &Registry) -> Self {
+        self.filter_keys(|name| other.keys.contains_key(name))
+    }
+
+    /// Returns the keys of this registry that are not present in `other`.
+    pub fn difference(&self, other: &Registry) -> Self {
+        self.filter_keys(|name| !other.keys.contains_key(name))
+    }
+
+    /// Returns the keys present in exactly one of the two registries.
+    pub fn symmetric_difference(&self, other: &Registry) -> Self {
+        let mut keys = self.difference(other).keys;
+        keys.extend(other.difference(self).keys);
+        Self {
+            hive: self.hive,
+            keys,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Returns a copy of the registry without the keys nested deeper than `max_depth`, to get
+    /// a high-level overview of a registry or of what changed between two before diffing.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_depth` - The number of components of the deepest names kept, relative to the
+    ///   hive: `1` keeps `Software` but drops `Software\Wine`.
+    pub fn prune(&self, max_depth: usize) -> Self {
+        self.filter_keys(|name| name.depth() <= max_depth)
+    }
+
+    /// Returns a copy of the registry without the keys that hold no values, security
+    /// descriptor or link, unless one of their subkeys does.
+    pub fn prune_empty_keys(&self) -> Self {
+        let mut kept = BTreeSet::new();
+        let filled = self.keys.iter().filter(|(_, key)| {
+            !key.values.is_empty() || key.security.is_some() || key.link.is_some()
+        });
+        for (name, _) in filled {
+            let mut name = Some(name.clone());
+            while let Some(current) = name {
+                if !kept.insert(current.raw().to_lowercase()) {
+                    break;
+                }
+                name = current.parent();
+            }
+        }
+        self.filter_keys(|name| kept.contains(&name.raw().to_lowercase()))
+    }
+
+    /// Returns the registry with its values citing where they came from.
+    ///
+    /// Values already citing a provenance, such as the lines recorded with
+    /// [LoadOptions::with_provenance], keep it, with the fields they don't cite filled in
+    /// from `provenance`.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        for value in self
+            .keys
+            .values_mut()
+            .flat_map(|key| key.values.values_mut())
+        {
+            match &mut value.provenance {
+                Some(existing) => existing.fill(&provenance),
+                None => value.provenance = Some(Box::new(provenance.clone())),
+            }
+        }
+        self
+    }
+
+    /// Returns a copy of the registry keeping only the keys whose name satisfies `predicate`.
+    pub(crate) fn filter_keys<F: Fn(&KeyName) -> bool>(&self, predicate: F) -> Self {
+        let keys = self
+            .keys
+            .iter()
+            .filter(|(name, _)| predicate(name))
+            .map(|(name, key)| (name.clone(), key.clone()))
+            .collect();
+        Self {
+            hive: self.hive,
+            keys,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Returns a copy of the registry with Windows paths in string values rewritten to host paths.
+    ///
+    /// # Arguments
+    ///
+    /// * `mapping` - The drive mapping of the Wine prefix the registry belongs to.
+    pub fn translate_paths(&self, mapping: &DriveMapping) -> Self {
+        let keys = self
+            .keys
+            .iter()
+            .map(|(name, key)| {
+                let values = key
+                    .values
+                    .iter()
+                    .map(|(value_name, value)| {
+                        let value = Value {
+                            value: mapping.translate_value(&value.value),
+                            ..value.clone()
+                        };
+                        (value_name.clone(), value)
+                    })
+                    .collect();
+                let key = Key {
+                    name: key.name.clone(),
+                    values,
+                    security: key.security.clone(),
+                    link: key.link.clone(),
+                    modified: key.modified,
+                    fingerprint: None,
+                };
+                (name.clone(), key)
+            })
+            .collect();
+
+        Self {
+            hive: self.hive,
+            keys,
+            warnings: self.warnings.clone(),
+        }
+    }
+
+    /// Applies the changes of a diff to the registry, returning the diff that reverts them.
+    ///
+    /// Changes to keys of other hives are skipped. Like regedit, deleting a key also deletes
+    /// its subkeys, and changing a missing key creates it. Values are set to the new data of
+    /// the diff whatever they held before; volatile changes are left alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `diff` - The changes to apply, e.g. from [RegistryDiff::new].
+    ///
+    /// # Returns
+    ///
+    /// The undo diff, restoring the registry to its state before the call when applied in turn.
+    /// It is computed from the data actually replaced rather than the old data recorded in
+    /// `diff`, so it is exact even if the registry drifted since the diff was made.
+    ///
+    /// With the `tracing` feature, every key and value change is recorded as an event of the
+    /// `regdiff::apply` target, with the `key`, `value`, `op`, `old` and `new` data and the
+    /// `outcome`: `applied`, `unchanged` if the registry already held the result, or
+    /// `skipped` for keys of other hives, as an audit trail.
+    pub fn apply(&mut self, diff: &RegistryDiff) -> RegistryDiff {
+        let mut before: BTreeMap<KeyName, Option<Key>> = BTreeMap::new();
+
+        for change in diff.keys().values() {
+            let path = match Hive::split(change.name().raw()) {
+                Some((hive, path)) if hive == self.hive => path,
+                _ => {
+                    event!(
+                        DEBUG,
+                        target: "regdiff::apply",
+                        key = change.name().raw(),
+                        op = "change_key",
+                        outcome = "skipped",
+                        "key outside of the hive"
+                    );
+                    continue;
+                }
+            };
+            match change.change() {
+                KeyChange::Unchanged => continue,
+                KeyChange::Deleted => {
+                    let removed = self.remove_key(path);
+                    event!(
+                        INFO,
+                        target: "regdiff::apply",
+                        key = change.name().raw(),
+                        op = "delete_key",
+                        old = removed.len(),
+                        outcome = outcome(!removed.is_empty()),
+                    );
+                    for (name, key) in removed {
+                        before.entry(name).or_insert(Some(key));
+                    }
+                    continue;
+                }
+                KeyChange::Added | KeyChange::Modified => {}
+            }
+
+            let name = self.stored_name(path);
+            let old = self.keys.get(&name).cloned();
+            event!(
+                INFO,
+                target: "regdiff::apply",
+                key = change.name().raw(),
+                op = "create_key",
+                outcome = outcome(old.is_none()),
+            );
+            before.entry(name).or_insert(old);
+            let key = self.key_mut(path);
+            key.fingerprint = None;
+
+            for (value_name, operation) in change.values() {
+                match operation {
+                    Operation::Add { data } | Operation::Modify { new_data: data, .. } => {
+                        event!(
+                            INFO,
+                            target: "regdiff::apply",
+                            key = change.name().raw(),
+                            value = value_label(value_name),
+                            op = "set_value",
+                            old = ?key.values.get(value_name).map(ToString::to_string),
+                            new = %data,
+                            outcome = outcome(key.values.get(value_name) != Some(data)),
+                        );
+                        key.values.insert(value_name.clone(), data.clone());
+                    }
+                    Operation::Delete { .. } => {
+                        event!(
+                            INFO,
+                            target: "regdiff::apply",
+                            key = change.name().raw(),
+                            value = value_label(value_name),
+                            op = "delete_value",
+                            old = ?key.values.get(value_name).map(ToString::to_string),
+                            outcome = outcome(key.values.contains_key(value_name)),
+                        );
+                        key.values.retain(|name, _| name != value_name)
+                    }
+                    Operation::Unchanged
+                    | Operation::Volatile { .. }
+                    | Operation::Reordered { .. } => {}
+                }
+            }
+            match change.security() {
+                Some(Operation::Add { data } | Operation::Modify { new_data: data, .. }) => {
+                    key.security = Some(data.clone());
+                }
+                Some(Operation::Delete { .. }) => key.security = None,
+                _ => {}
+            }
+            match change.link() {
+                Some(Operation::Add { data } | Operation::Modify { new_data: data, .. }) => {
+                    key.link = Some(data.clone());
+                }
+                Some(Operation::Delete { .. }) => key.link = None,
+                _ => {}
+            }
+        }
+
+        let options = DiffOptions::new().with_security(true);
+        let mut undo: Vec<KeyDiff> = before
+            .iter()
+            .filter_map(|(name, old)| KeyDiff::new(self.keys.get(name), old.as_ref(), &options))
+            .collect();
+        // Deleting a key the diff created also deletes its subkeys, so the subkeys the diff
+        // left alone are added back after it.
+        let created: Vec<String> = undo
+            .iter()
+            .filter(|key| key.change() == KeyChange::Deleted)
+            .map(|key| format!("{}\\", key.name().raw().to_lowercase()))
+            .collect();
+        if !created.is_empty() {
+            let kept = self.keys.iter().filter(|(name, key)| {
+                let full = key.name.raw().to_lowercase();
+                !before.contains_key(*name) && created.iter().any(|path| full.starts_with(path))
+            });
+            undo.extend(kept.filter_map(|(_, key)| KeyDiff::new(None, Some(key), &options)));
+        }
+        undo.into_iter().collect()
+    }
+
+    /// Retrieves the key at `path` within the hive, matching its name case-insensitively like
+    /// the registry does.
+    pub(crate) fn key_ignoring_case(&self, path: &str) -> Option<&Key> {
+        self.keys.get(&KeyName::new(path)).or_else(|| {
+            self.keys
+                .iter()
+                .find(|(name, _)| name.raw().eq_ignore_ascii_case(path))
+                .map(|(_, key)| key)
+        })
+    }
+
+    /// Returns the name the key at `path` within the hive is stored under, matching existing
+    /// keys case-insensitively like the registry does.
+    pub(crate) fn stored_name(&self, path: &str) -> KeyName {
+        self.keys
+            .keys()
+            .find(|name| name.raw().eq_ignore_ascii_case(path))
+            .cloned()
+            .unwrap_or_else(|| KeyName::new(path))
+    }
+
+    /// Returns the key at `path` within the hive for modification, creating it if missing.
+    pub(crate) fn key_mut(&mut self, path: &str) -> &mut Key {
+        let name = self.stored_name(path);
+        let hive = self.hive;
+        self.keys.entry(name).or_insert_with(|| Key {
+            name: KeyName::new(format!("{}\\{}", hive, path)),
+            values: ValueMap::default(),
+            security: None,
+            link: None,
+            modified: None,
+            fingerprint: None,
+        })
+    }
+
+    /// Stores a key at `path` within the hive, renaming it accordingly and replacing the key
+    /// stored there, if any.
+    pub(crate) fn insert_key(&mut self, path: &str, mut key: Key) {
+        key.name = KeyName::new(format!("{}\\{}", self.hive, path));
+        key.fingerprint = None;
+        let name = self.stored_name(path);
+        self.keys.insert(name, key);
+    }
+
+    /// Removes the key at `path` within the hive along with its subkeys.
+    ///
+    /// # Returns
+    ///
+    /// The removed keys, by the name they were stored under.
+    pub(crate) fn remove_key(&mut self, path: &str) -> Vec<(KeyName, Key)> {
+        let name = self.stored_name(path);
+        let subkeys = format!("{}\\", name.raw().to_lowercase());
+        let removed: Vec<KeyName> = self
+            .keys
+            .keys()
+            .filter(|key| **key == name || key.raw().to_lowercase().starts_with(&subkeys))
+            .cloned()
+            .collect();
+        removed
+            .into_iter()
+            .filter_map(|name| self.keys.remove(&name).map(|key| (name, key)))
+            .collect()
+    }
+
     /// Attempts to construct a `Registry` from a file.
     ///
-    /// This function deserializes a given file path using regashii and then converts the
-    /// resulting registry into our custom `Registry` type according to the specified `Hive`.
+    /// This function parses a given file path and then converts the resulting
+    /// keys into our custom `Registry` type according to the specified `Hive`.
+    /// Keys defined more than once have their values merged. Full key names in regedit files
+    /// that already start with the hive are not prefixed again.
+    ///
+    /// Every key is parsed while loading. To parse only the keys that are accessed, such as
+    /// a subtree of a huge file, load it with [LazyRegistry](crate::prelude::LazyRegistry).
     ///
     /// # Arguments
     ///
@@ -185,25 +1150,213 @@ impl Registry {
     ///
     /// # Returns
     ///
-    /// A `Result` containing the `Registry` or a `regashii::error::Read` error if deserialization fails.
+    /// A `Result` containing the `Registry` or an [Error] if the file can't be read or parsed.
+    #[cfg(feature = "fs")]
+    pub fn open<T: AsRef<std::path::Path>>(file: T, hive: Hive) -> Result<Self, Error> {
+        Self::try_from_with(file, hive, &LoadOptions::default())
+    }
+
+    /// Attempts to construct a `Registry` from a file, deserializing it with regashii.
+    ///
+    /// This shadows [TryFrom::try_from], which takes the file and hive as a tuple. It keeps
+    /// loading files like it always did, with regashii's error type, while [Registry::open]
+    /// parses them in this crate to apply [LoadOptions] and report lines in errors.
+    #[cfg(feature = "fs")]
+    #[deprecated(note = "use `Registry::open` or `(path, hive).try_into()` instead")]
     pub fn try_from<T: AsRef<std::path::Path>>(
         file: T,
         hive: Hive,
     ) -> Result<Self, regashii::error::Read> {
         let registry = regashii::Registry::deserialize_file(file)?;
 
-        Ok(Self::from(registry, hive))
+        Ok(Self::from_regashii(registry, hive))
+    }
+
+    /// Attempts to construct a `Registry` from a file, loading it according to `options`.
+    ///
+    /// See [Registry::open] for details.
+    #[cfg(feature = "fs")]
+    pub fn try_from_with<T: AsRef<std::path::Path>>(
+        file: T,
+        hive: Hive,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        span!(INFO, "load", file = %file.as_ref().display(), hive = %hive);
+        let bytes = std::fs::read(&file)?;
+        let encoding = options
+            .encoding()
+            .unwrap_or_else(|| Encoding::detect(&bytes));
+        let text = encoding.decode_borrowed(&bytes)?;
+        let registry = Self::try_from_str_with(&text, hive, options)?;
+        if !options.provenance() {
+            return Ok(registry);
+        }
+        Ok(registry.with_provenance(Provenance::new().with_file(file.as_ref())))
+    }
+
+    /// Loads a registry file through a memory map, parsing it in place.
+    ///
+    /// UTF-8 and ASCII files, such as the registry files of Wine prefixes, are parsed from
+    /// the mapped pages without being copied into memory first, lowering the peak memory and
+    /// load time of big `system.reg` files. Other encodings are decoded into a copy like
+    /// [Registry::open] does. The result is the same as [Registry::open]'s.
+    ///
+    /// The file must not be truncated by another process while it is loaded.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap<T: AsRef<std::path::Path>>(file: T, hive: Hive) -> Result<Self, Error> {
+        Self::open_mmap_with(file, hive, &LoadOptions::default())
+    }
+
+    /// Loads a registry file through a memory map according to `options`.
+    ///
+    /// See [Registry::open_mmap] for details.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap_with<T: AsRef<std::path::Path>>(
+        file: T,
+        hive: Hive,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        let file = std::fs::File::open(file)?;
+        // SAFETY: the mapping is only read while loading, and dropped before returning.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let encoding = options.encoding().unwrap_or_else(|| Encoding::detect(&map));
+        let text = encoding.decode_borrowed(&map)?;
+        Self::try_from_str_with(&text, hive, options)
+    }
+
+    /// Attempts to construct a `Registry` from the text of a registry file, such as an upload.
+    ///
+    /// See [Registry::open] for details.
+    pub fn try_from_str(text: &str, hive: Hive) -> Result<Self, Error> {
+        Self::try_from_str_with(text, hive, &LoadOptions::default())
+    }
+
+    /// Attempts to construct a `Registry` from the text of a registry file, loading it
+    /// according to `options`. The encoding of `options` is ignored.
+    ///
+    /// See [Registry::open] for details.
+    pub fn try_from_str_with(text: &str, hive: Hive, options: &LoadOptions) -> Result<Self, Error> {
+        span!(DEBUG, "parse", bytes = text.len());
+        let document = parse::parse(text, options.strict())?;
+        Self::from_document(document, hive, options)
+    }
+
+    /// Converts a parsed registry file into our custom `Registry` using the provided hive,
+    /// resolving keys defined more than once according to `options`.
+    pub(crate) fn from_document(
+        document: Document,
+        hive: Hive,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        let mut keys: BTreeMap<KeyName, Key> = BTreeMap::new();
+
+        for section in document.sections {
+            let repaired = options
+                .repair_mojibake()
+                .then(|| repair_mojibake(&section.name))
+                .flatten();
+            let section_name = repaired.as_deref().unwrap_or(&section.name);
+            let relative = relative_name(document.syntax, hive, section_name);
+            let name = KeyName::new(relative);
+            // The fingerprint hashes the text as read, so it can't stand for repaired content.
+            let mut rewritten = repaired.is_some();
+            let values: Vec<Value> = section
+                .values
+                .into_iter()
+                .map(|value| {
+                    if !options.repair_mojibake() {
+                        return value;
+                    }
+                    let fixed = value.clone().repair_mojibake();
+                    rewritten |= fixed != value;
+                    fixed
+                })
+                .collect();
+            let values = values
+                .into_iter()
+                .zip(section.lines)
+                .map(|(value, line)| {
+                    if options.provenance() {
+                        value.with_provenance(Provenance::new().with_line(line))
+                    } else {
+                        value
+                    }
+                })
+                .map(|value| (value.name().clone(), value));
+
+            match (keys.get_mut(&name), options.duplicate_keys()) {
+                (None, _) | (Some(_), DuplicateKeyPolicy::LastWins) => {
+                    let key = Key {
+                        name: KeyName::new(format!("{}\\{}", hive, relative)),
+                        values: values.collect(),
+                        security: section.security,
+                        link: section.link,
+                        modified: section.modified,
+                        fingerprint: (!rewritten).then_some(section.fingerprint),
+                    };
+                    keys.insert(name, key);
+                }
+                (Some(_), DuplicateKeyPolicy::FirstWins) => {}
+                (Some(key), DuplicateKeyPolicy::Merge) => {
+                    key.fingerprint = None;
+                    key.values.extend(values);
+                    if section.security.is_some() {
+                        key.security = section.security;
+                    }
+                    if section.link.is_some() {
+                        key.link = section.link;
+                    }
+                    key.modified = key.modified.max(section.modified);
+                }
+                (Some(_), DuplicateKeyPolicy::Error) => {
+                    return Err(Error::DuplicateKey {
+                        name,
+                        line: section.line,
+                    });
+                }
+            }
+        }
+
+        Ok(Self {
+            hive,
+            keys,
+            warnings: document.warnings,
+        })
+    }
+
+    /// Returns a copy of the registry as a regashii registry, to use features of regashii
+    /// this crate doesn't wrap without re-parsing the file.
+    ///
+    /// Keys are named relative to the hive, like [Registry::from_regashii] expects, and are
+    /// converted like [Key::as_regashii] does.
+    pub fn as_regashii(&self) -> regashii::Registry {
+        self.keys.iter().fold(
+            regashii::Registry::new(regashii::Format::Regedit4),
+            |registry, (name, key)| registry.with(name.clone(), key.as_regashii()),
+        )
+    }
+
+    /// Converts the registry into a regashii registry.
+    ///
+    /// See [Registry::as_regashii] for details.
+    pub fn into_regashii(self) -> regashii::Registry {
+        self.keys.into_iter().fold(
+            regashii::Registry::new(regashii::Format::Regedit4),
+            |registry, (name, key)| registry.with(name, key.into_regashii_key().1),
+        )
     }
 
     /// Converts a regashii registry into our custom `Registry` using the provided hive.
     ///
     /// It iterates over all registry keys, prepending the hive to the original key names.
+    /// regashii sorts values by name, so with the `preserve-order` feature the values are in
+    /// name order rather than the order of the file regashii loaded.
     ///
     /// # Arguments
     ///
     /// * `registry` - The regashii registry instance.
     /// * `hive` - The registry hive that serves as the prefix.
-    fn from(registry: regashii::Registry, hive: Hive) -> Self {
+    pub fn from_regashii(registry: regashii::Registry, hive: Hive) -> Self {
         let map = registry
             .keys()
             .into_iter()
@@ -216,7 +1369,63 @@ impl Registry {
             })
             .collect();
 
-        Self { keys: map }
+        Self {
+            hive,
+            keys: map,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl From<(regashii::Registry, Hive)> for Registry {
+    fn from((registry, hive): (regashii::Registry, Hive)) -> Self {
+        Self::from_regashii(registry, hive)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl TryFrom<(&std::path::Path, Hive)> for Registry {
+    type Error = Error;
+
+    fn try_from((file, hive): (&std::path::Path, Hive)) -> Result<Self, Error> {
+        Self::open(file, hive)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl TryFrom<&std::path::Path> for Registry {
+    type Error = Error;
+
+    /// Loads a registry file into the hive it holds: Wine's `system.reg`, `user.reg` and
+    /// `userdef.reg` files are recognized by their name, and regedit files by the hive their
+    /// first key is named in.
+    ///
+    /// Fails with an [Error::Io] of kind [InvalidInput](std::io::ErrorKind::InvalidInput) if
+    /// the hive can't be told.
+    fn try_from(file: &std::path::Path) -> Result<Self, Error> {
+        let named = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(Hive::of_file_name);
+        if let Some(hive) = named {
+            return Self::open(file, hive);
+        }
+
+        let bytes = std::fs::read(file)?;
+        let text = Encoding::detect(&bytes).decode_borrowed(&bytes)?;
+        let document = parse::parse(&text, LoadOptions::default().strict())?;
+        let hive = document
+            .sections
+            .first()
+            .and_then(|section| Hive::split(&section.name))
+            .map(|(hive, _)| hive)
+            .ok_or_else(|| {
+                Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("can't tell the hive of '{}'", file.display()),
+                ))
+            })?;
+        Self::from_document(document, hive, &LoadOptions::default())
     }
 }
 
@@ -224,47 +1433,84 @@ impl Registry {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_conversions() {
+        let path = std::path::Path::new("./registries/user.reg");
+        let registry = Registry::open(path, Hive::CurrentUser).unwrap();
+        let converted: Registry = (path, Hive::CurrentUser).try_into().unwrap();
+        assert_eq!(converted.keys(), registry.keys());
+        let detected: Registry = path.try_into().unwrap();
+        assert_eq!(detected.hive(), Hive::CurrentUser);
+        assert_eq!(detected.keys(), registry.keys());
+
+        let regashii = regashii::Registry::new(regashii::Format::Regedit4).with(
+            KeyName::new("Software"),
+            regashii::Key::new().with(ValueName::named("A"), regashii::Value::Dword(1)),
+        );
+        let converted: Registry = (regashii.clone(), Hive::LocalMachine).into();
+        assert_eq!(
+            converted.keys(),
+            Registry::from_regashii(regashii.clone(), Hive::LocalMachine).keys()
+        );
+
+        assert_eq!(converted.as_regashii(), regashii);
+        assert_eq!(
+            Registry::from_regashii(registry.as_regashii(), Hive::CurrentUser).keys(),
+            registry.keys()
+        );
+        let key = registry.keys().values().next().unwrap();
+        assert_eq!(key.as_regashii(), key.clone().into_regashii_key().1);
+        assert_eq!(registry.clone().into_regashii(), registry.as_regashii());
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn test_open_registry_success() {
-        let registry = Registry::try_from("./registries/user.reg", Hive::CurrentUser);
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser);
         assert!(registry.is_ok())
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_get_existing_registry_key() {
-        let registry = Registry::try_from("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
         let key = registry.key(&regashii::KeyName::new("Software\\Wine\\Fonts"));
         assert!(key.is_some());
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_registry_key_has_correct_name() {
-        let registry = Registry::try_from("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
         let key = registry
             .key(&regashii::KeyName::new("Software\\Wine\\Fonts"))
             .unwrap();
         assert_eq!(key.name().raw(), "HKEY_CURRENT_USER\\Software\\Wine\\Fonts");
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_get_nonexistent_registry_key_returns_none() {
-        let registry = Registry::try_from("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
         let key = registry.key(&regashii::KeyName::new("Software\\Wine\\NonExistent"));
         assert!(key.is_none());
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_registry_key_value_count_is_correct() {
-        let registry = Registry::try_from("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
         let key = registry
             .key(&regashii::KeyName::new("Software\\Wine\\X11 Driver"))
             .unwrap();
         assert_eq!(key.values().len(), 1);
     }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn test_registry_key_contains_expected_values() {
-        let registry = Registry::try_from("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
         let key = registry
             .key(&regashii::KeyName::new(
                 "Software\\Wine\\Fonts\\Replacements",
@@ -283,9 +1529,570 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_translate_paths_rewrites_string_values() {
+        let key = regashii::Key::new().with(
+            ValueName::named("Path"),
+            regashii::Value::Sz("C:\\windows\\system32".to_string()),
+        );
+        let registry = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(KeyName::new("Software\\Test"), key);
+        let registry = Registry::from_regashii(registry, Hive::CurrentUser);
+
+        let mapping = DriveMapping::new().with('c', "/prefix/drive_c");
+        let translated = registry.translate_paths(&mapping);
+        let value = translated
+            .key(&KeyName::new("Software\\Test"))
+            .unwrap()
+            .values()
+            .get(&ValueName::named("Path"))
+            .unwrap();
+
+        assert_eq!(
+            value.value(),
+            &regashii::Value::Sz("/prefix/drive_c/windows/system32".to_string())
+        );
+    }
+
+    #[test]
+    fn test_overlay_prefers_other_values() {
+        let base = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(
+                KeyName::new("Software\\Test"),
+                regashii::Key::new()
+                    .with(ValueName::named("Kept"), regashii::Value::Dword(1))
+                    .with(ValueName::named("Replaced"), regashii::Value::Dword(1)),
+            )
+            .with(KeyName::new("Software\\OnlyBase"), regashii::Key::new());
+        let top = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(
+                KeyName::new("Software\\Test"),
+                regashii::Key::new().with(ValueName::named("Replaced"), regashii::Value::Dword(2)),
+            )
+            .with(KeyName::new("Software\\OnlyTop"), regashii::Key::new());
+
+        let base = Registry::from_regashii(base, Hive::CurrentUser);
+        let top = Registry::from_regashii(top, Hive::CurrentUser);
+        let merged = base.overlay(&top);
+
+        assert_eq!(merged.keys().len(), 3);
+        let values = merged
+            .key(&KeyName::new("Software\\Test"))
+            .unwrap()
+            .values();
+        assert_eq!(
+            values[&ValueName::named("Kept")].value(),
+            &regashii::Value::Dword(1)
+        );
+        assert_eq!(
+            values[&ValueName::named("Replaced")].value(),
+            &regashii::Value::Dword(2)
+        );
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let registry = |names: &[&str]| {
+            let registry = names.iter().fold(
+                regashii::Registry::new(regashii::Format::Regedit4),
+                |registry, name| registry.with(KeyName::new(name), regashii::Key::new()),
+            );
+            Registry::from_regashii(registry, Hive::CurrentUser)
+        };
+        let names = |registry: Registry| -> Vec<String> {
+            registry
+                .keys()
+                .keys()
+                .map(|name| name.raw().to_string())
+                .collect()
+        };
+        let a = registry(&["A", "B"]);
+        let b = registry(&["B", "C"]);
+
+        assert_eq!(names(a.intersection(&b)), vec!["B"]);
+        assert_eq!(names(a.difference(&b)), vec!["A"]);
+        assert_eq!(names(a.symmetric_difference(&b)), vec!["A", "C"]);
+    }
+
+    #[test]
+    fn test_prune() {
+        let value = regashii::Key::new().with(ValueName::named("A"), regashii::Value::Dword(1));
+        let registry = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4)
+                .with(KeyName::new("Software"), regashii::Key::new())
+                .with(KeyName::new("Software\\Wine"), regashii::Key::new())
+                .with(KeyName::new("Software\\Wine\\Direct3D"), value)
+                .with(KeyName::new("Software\\Empty"), regashii::Key::new())
+                .with(
+                    KeyName::new("Software\\Empty\\Deeper"),
+                    regashii::Key::new(),
+                ),
+            Hive::CurrentUser,
+        );
+        let names = |registry: Registry| -> Vec<String> {
+            registry
+                .keys()
+                .keys()
+                .map(|name| name.raw().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            names(registry.prune(2)),
+            ["Software", "Software\\Empty", "Software\\Wine"]
+        );
+        assert_eq!(names(registry.prune(0)), Vec::<String>::new());
+        assert_eq!(
+            names(registry.prune_empty_keys()),
+            ["Software", "Software\\Wine", "Software\\Wine\\Direct3D"]
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_try_from_loads_with_regashii() {
+        let registry = Registry::try_from("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let opened = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        assert!(registry.keys().keys().eq(opened.keys().keys()));
+
+        let missing: Result<Registry, regashii::error::Read> =
+            Registry::try_from("./registries/missing.reg", Hive::CurrentUser);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_duplicate_key_policies() {
+        let text = "REGEDIT4\n\n[Test]\n\"A\"=dword:00000001\n\"B\"=dword:00000001\n\n\
+            [Test]\n\"A\"=dword:00000002\n";
+        let load = |policy| {
+            let document = parse::parse(text, true).unwrap();
+            let options = LoadOptions::new().with_duplicate_keys(policy);
+            Registry::from_document(document, Hive::CurrentUser, &options)
+        };
+        let values = |registry: Registry| {
+            let key = registry.key(&KeyName::new("Test")).unwrap();
+            key.values()
+                .values()
+                .map(|value| value.value().clone())
+                .collect::<Vec<_>>()
+        };
+
+        let merged = values(load(DuplicateKeyPolicy::Merge).unwrap());
+        assert_eq!(
+            merged,
+            vec![regashii::Value::Dword(2), regashii::Value::Dword(1)]
+        );
+
+        let last = values(load(DuplicateKeyPolicy::LastWins).unwrap());
+        assert_eq!(last, vec![regashii::Value::Dword(2)]);
+
+        let first = values(load(DuplicateKeyPolicy::FirstWins).unwrap());
+        assert_eq!(
+            first,
+            vec![regashii::Value::Dword(1), regashii::Value::Dword(1)]
+        );
+
+        match load(DuplicateKeyPolicy::Error) {
+            Err(Error::DuplicateKey { line, .. }) => assert_eq!(line, 7),
+            _ => panic!("expected a duplicate key error"),
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_load_utf16_export() {
+        let text = "Windows Registry Editor Version 5.00\r\n\r\n[Test]\r\n\"Name\"=\"Ünïcödé\"\r\n";
+        let mut bytes = vec![0xff, 0xfe];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        let file = std::env::temp_dir().join("regdiff-utf16-export.reg");
+        std::fs::write(&file, bytes).unwrap();
+
+        let registry = Registry::open(&file, Hive::CurrentUser).unwrap();
+        let forced = Registry::try_from_with(
+            &file,
+            Hive::CurrentUser,
+            &LoadOptions::new().with_encoding(Encoding::Utf8),
+        );
+        std::fs::remove_file(&file).unwrap();
+
+        let key = registry.key(&KeyName::new("Test")).unwrap();
+        let value = key.values().values().next().unwrap();
+        assert_eq!(value.value(), &regashii::Value::Sz("Ünïcödé".to_string()));
+        assert!(matches!(forced, Err(Error::Encoding(Encoding::Utf8))));
+    }
+
+    #[test]
+    fn test_repair_mojibake_on_load() {
+        let broken = "REGEDIT4\n\n[HKEY_CURRENT_USER\\Caf\u{c3}\u{a9}]\n\
+            \"Na\u{c3}\u{af}ve\"=\"\u{e2}\u{201a}\u{ac}5\"\n\"Plain\"=\"\u{e9}t\u{e9}\"\n";
+        let clean = "REGEDIT4\n\n[HKEY_CURRENT_USER\\Café]\n\"Naïve\"=\"€5\"\n\"Plain\"=\"été\"\n";
+        let options = LoadOptions::new().with_repair_mojibake(true);
+        let repaired = Registry::try_from_str_with(broken, Hive::CurrentUser, &options).unwrap();
+        let clean = Registry::try_from_str(clean, Hive::CurrentUser).unwrap();
+        assert!(RegistryDiff::new(&clean, &repaired, &DiffOptions::new()).is_empty());
+
+        let kept = Registry::try_from_str(broken, Hive::CurrentUser).unwrap();
+        assert!(!RegistryDiff::new(&clean, &kept, &DiffOptions::new()).is_empty());
+    }
+
+    #[test]
+    fn test_repaired_load_differs_from_plain_load() {
+        let text = "REGEDIT4\n\n[HKEY_CURRENT_USER\\Test]\n\"A\"=\"caf\u{c3}\u{a9}\"\n";
+        let options = LoadOptions::new().with_repair_mojibake(true);
+        let repaired = Registry::try_from_str_with(text, Hive::CurrentUser, &options).unwrap();
+        let plain = Registry::try_from_str(text, Hive::CurrentUser).unwrap();
+
+        let diff = RegistryDiff::new(&plain, &repaired, &DiffOptions::new());
+        assert_eq!(diff.keys().len(), 1);
+    }
+
+    #[cfg(feature = "preserve-order")]
+    #[test]
+    fn test_values_keep_load_order() {
+        let text = "REGEDIT4\n\n[Test]\n\"B\"=dword:00000001\n\"A\"=dword:00000002\n@=\"c\"\n";
+        let registry = Registry::try_from_str(text, Hive::CurrentUser).unwrap();
+        let key = registry.key(&KeyName::new("Test")).unwrap();
+        let names: Vec<&ValueName> = key.values().keys().collect();
+        assert_eq!(
+            names,
+            [
+                &ValueName::named("B"),
+                &ValueName::named("A"),
+                &ValueName::Default
+            ]
+        );
+
+        let serialized = registry.serialize().unwrap();
+        let b = serialized.find("\"B\"=").unwrap();
+        assert!(b < serialized.find("\"A\"=").unwrap());
+        assert!(serialized.find("\"A\"=").unwrap() < serialized.find("@=").unwrap());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_open_mmap_matches_try_from() {
+        for file in ["./registries/new.reg", "./registries/user.reg"] {
+            let mapped = Registry::open_mmap(file, Hive::CurrentUser).unwrap();
+            let read = Registry::open(file, Hive::CurrentUser).unwrap();
+            assert_eq!(mapped.keys(), read.keys());
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_exotic_values_round_trip() {
+        let text = "Windows Registry Editor Version 5.00\r\n\r\n[Test]\r\n\
+            \"None\"=hex(0):01,02\r\n\
+            \"Multi\"=hex(7):61,00,00,00,00,00\r\n\
+            \"Unterminated\"=hex(7):61,00\r\n\
+            \"Qword\"=hex(b):01,00,00,00,00,00,00,00\r\n\
+            \"Short\"=hex(b):01,00\r\n\
+            \"Resources\"=hex(8):00,01,02\r\n";
+        let file = std::env::temp_dir().join(format!("regdiff-exotic-{}.reg", std::process::id()));
+        std::fs::write(&file, text).unwrap();
+        let registry = Registry::open(&file, Hive::CurrentUser).unwrap();
+        let serialized = registry.serialize().unwrap();
+        std::fs::write(&file, &serialized).unwrap();
+        let reloaded = Registry::open(&file, Hive::CurrentUser).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        let key = registry.key(&KeyName::new("Test")).unwrap();
+        let kind = |name: &str| key.values().get(&ValueName::named(name)).unwrap().kind();
+        assert_eq!(kind("None"), ValueKind::None);
+        assert_eq!(kind("Multi"), ValueKind::MultiSz);
+        assert_eq!(kind("Resources"), ValueKind::ResourceList);
+        let raw = |name: &str| {
+            key.values()
+                .get(&ValueName::named(name))
+                .unwrap()
+                .raw_type()
+        };
+        assert_eq!(raw("Multi"), None);
+        assert_eq!(raw("Unterminated"), Some(7));
+        assert_eq!(raw("Qword"), None);
+        assert_eq!(raw("Short"), Some(0xb));
+
+        assert!(serialized.contains("\"None\"=hex(0):01,02\n"));
+        assert!(serialized.contains("\"Unterminated\"=hex(7):61,00\n"));
+        assert_eq!(registry.keys(), reloaded.keys());
+    }
+
+    #[test]
+    fn test_full_key_names_are_not_prefixed_twice() {
+        let regedit = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\A]\n\n[HKEY_LOCAL_MACHINE\\B]\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let names = |registry: &Registry| {
+            registry
+                .keys()
+                .iter()
+                .map(|(name, key)| (name.raw().to_string(), key.name().raw().to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&regedit),
+            [
+                (
+                    "HKEY_LOCAL_MACHINE\\B".to_string(),
+                    "HKEY_CURRENT_USER\\HKEY_LOCAL_MACHINE\\B".to_string()
+                ),
+                (
+                    "Software\\A".to_string(),
+                    "HKEY_CURRENT_USER\\Software\\A".to_string()
+                ),
+            ]
+        );
+
+        let wine = Registry::try_from_str(
+            "WINE REGISTRY Version 2\n\n[HKEY_CURRENT_USER\\\\Software]\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        assert_eq!(
+            names(&wine)[0].1,
+            "HKEY_CURRENT_USER\\HKEY_CURRENT_USER\\Software"
+        );
+        let reloaded =
+            Registry::try_from_str(&regedit.serialize().unwrap(), Hive::CurrentUser).unwrap();
+        assert_eq!(names(&reloaded), names(&regedit));
+    }
+
+    #[test]
+    fn test_regashii_values_round_trip() {
+        let registry = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4).with(
+                KeyName::new("Software\\A"),
+                regashii::Key::new()
+                    .with(
+                        ValueName::named("Big"),
+                        regashii::Value::DwordBigEndian(0x01020304),
+                    )
+                    .with(
+                        ValueName::named("Odd"),
+                        regashii::Value::Hex {
+                            kind: regashii::Kind::Unknown(0x20),
+                            bytes: vec![0xab],
+                        },
+                    ),
+            ),
+            Hive::CurrentUser,
+        );
+        let serialized = registry.serialize().unwrap();
+        assert!(serialized.contains("\"Big\"=hex(5):01,02,03,04\n"));
+        assert!(serialized.contains("\"Odd\"=hex(20):ab\n"));
+        let reloaded = Registry::try_from_str(&serialized, Hive::CurrentUser).unwrap();
+        assert_eq!(registry.keys(), reloaded.keys());
+
+        let empty = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4),
+            Hive::CurrentUser,
+        );
+        let diff = RegistryDiff::new(&empty, &registry, &DiffOptions::new());
+        assert!(diff.serialize().unwrap().contains("\"Odd\"=hex(20):ab\n"));
+        let patch = diff.to_patch();
+        let values = patch.keys()[&KeyName::new("HKEY_CURRENT_USER\\Software\\A")].values();
+        assert_eq!(
+            values[&ValueName::named("Big")],
+            regashii::Value::DwordBigEndian(0x01020304)
+        );
+        assert_eq!(
+            values[&ValueName::named("Odd")],
+            regashii::Value::Hex {
+                kind: regashii::Kind::Unknown(0x20),
+                bytes: vec![0xab],
+            }
+        );
+
+        let broken = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4).with(
+                KeyName::new("A"),
+                regashii::Key::new().with(ValueName::named("a\nb"), regashii::Value::Dword(1)),
+            ),
+            Hive::CurrentUser,
+        );
+        assert!(matches!(broken.serialize(), Err(Error::InvalidName { .. })));
+    }
+
+    #[test]
+    fn test_apply_returns_undo_diff() {
+        let registry = |keys: &[(&str, &str, u32)]| {
+            let mut registry = regashii::Registry::new(regashii::Format::Regedit4);
+            for (name, value, data) in keys {
+                registry = registry.with(
+                    KeyName::new(*name),
+                    regashii::Key::new()
+                        .with(ValueName::named(*value), regashii::Value::Dword(*data)),
+                );
+            }
+            Registry::from_regashii(registry, Hive::CurrentUser)
+        };
+        let old = registry(&[
+            ("Software\\App", "Version", 1),
+            ("Software\\App\\Plugins", "Count", 3),
+            ("Software\\Other", "Level", 1),
+        ]);
+        let new = registry(&[("Software\\Other", "Level", 2), ("Software\\New", "A", 1)]);
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+
+        // The current registry drifted from the old side of the diff.
+        let mut current = old.overlay(&registry(&[("Software\\Other", "Level", 5)]));
+        let original = current.clone();
+        let undo = current.apply(&diff);
+        assert_eq!(current.keys(), new.keys());
+
+        let other = undo.key(&KeyName::new("HKEY_CURRENT_USER\\Software\\Other"));
+        assert_eq!(
+            other.unwrap().values()[&ValueName::named("Level")],
+            Operation::Modify {
+                old_data: Value::new(ValueName::named("Level"), regashii::Value::Dword(2)),
+                new_data: Value::new(ValueName::named("Level"), regashii::Value::Dword(5)),
+            }
+        );
+        current.apply(&undo);
+        assert_eq!(current.keys(), original.keys());
+
+        // Undoing the creation of a key keeps its existing subkeys.
+        let mut nested = registry(&[("Software\\Parent\\Child", "A", 1)]);
+        let original = nested.clone();
+        let parent = registry(&[("Software\\Parent", "B", 1)]);
+        let undo = nested.apply(&RegistryDiff::new(
+            &original,
+            &original.overlay(&parent),
+            &DiffOptions::new(),
+        ));
+        nested.apply(&undo);
+        assert_eq!(nested.keys(), original.keys());
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn test_apply_emits_tracing_events() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Records the fields of the events, as `name=value` strings.
+        struct Recorder(Mutex<Vec<String>>);
+
+        /// Collects the fields of an event.
+        struct Fields(Vec<String>);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.push(format!("{}={:?}", field.name(), value));
+            }
+        }
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields(Vec::new());
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0.join(" "));
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let mut registry = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000001\n\"B\"=\"b\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let patch = RegistryDiff::from_patch_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=dword:00000002\n\"B\"=-\n",
+            &registry,
+        )
+        .unwrap();
+        let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+        tracing::subscriber::with_default(recorder.clone(), || registry.apply(&patch));
+
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(events[0].contains("op=\"create_key\" outcome=\"unchanged\""));
+        assert!(events[1].contains("value=\"A\" op=\"set_value\""));
+        assert!(events[1].ends_with("outcome=\"applied\""));
+        assert!(events[2].contains("op=\"delete_value\""));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_keys_and_diffs_are_hashable() {
+        use std::collections::HashSet;
+
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let keys: HashSet<&Key> = registry.keys().values().collect();
+        assert_eq!(keys.len(), registry.keys().len());
+        let reloaded = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
+        assert!(reloaded.keys().values().all(|key| keys.contains(key)));
+
+        let values: HashSet<Value> = [
+            Value::new(ValueName::named("A"), regashii::Value::Dword(1)),
+            Value::new(ValueName::named("A"), regashii::Value::Dword(1)),
+            Value::raw(ValueName::named("A"), 0, vec![0, 0, 0, 1]),
+        ]
+        .into();
+        assert_eq!(values.len(), 2);
+
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let diff = RegistryDiff::new(&old, &registry, &DiffOptions::new());
+        let copy = diff.clone();
+        let diffs: HashSet<&RegistryDiff> = [&diff, &copy].into();
+        assert_eq!(diffs.len(), 1);
+        let changes: std::collections::BTreeSet<KeyChange> =
+            diff.keys().values().map(KeyDiff::change).collect();
+        assert!(!changes.is_empty());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_display() {
+        let value = Value::new(ValueName::named("Level"), regashii::Value::Dword(1));
+        assert_eq!(value.to_string(), "\"Level\"=dword:00000001");
+        assert_eq!(
+            Value::new(ValueName::Default, regashii::Value::Delete).to_string(),
+            "@=-"
+        );
+        let modify = Operation::Modify {
+            old_data: value.clone(),
+            new_data: Value::new(ValueName::named("Level"), regashii::Value::Sz("a".into())),
+        };
+        assert_eq!(
+            modify.to_string(),
+            "\"Level\"=dword:00000001 -> \"Level\"=\"a\""
+        );
+        assert_eq!(
+            Operation::Add { data: value }.to_string(),
+            "+\"Level\"=dword:00000001"
+        );
+        let big = Value::new(ValueName::named("a\nb"), regashii::Value::DwordBigEndian(1));
+        assert_eq!(big.to_string(), "\"a\nb\"=hex(5):00,00,00,01");
+
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let key = registry
+            .key(&KeyName::new("Software\\Wine\\X11 Driver"))
+            .unwrap();
+        assert_eq!(
+            key.to_string(),
+            "[HKEY_CURRENT_USER\\Software\\Wine\\X11 Driver]"
+        );
+    }
+
+    #[cfg(feature = "fs")]
     #[test]
     fn test_registry_key_value_index_out_of_range_returns_none() {
-        let registry = Registry::try_from("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
         let key = registry
             .key(&regashii::KeyName::new("Software\\Wine\\X11 Driver"))
             .unwrap();
@@ -0,0 +1,136 @@
+use crate::prelude::Value;
+
+/// Names of values that are known to hold identifiers regenerated on every install.
+const VOLATILE_GUID_NAMES: &[&str] = &[
+    "MachineGuid",
+    "MachineId",
+    "InstallId",
+    "InstallationId",
+    "SusClientId",
+];
+
+/// Names of values that are known to hold timestamps updated as a side effect of normal use.
+const TIMESTAMP_NAMES: &[&str] = &[
+    "InstallDate",
+    "InstallTime",
+    "LastUsedTimeStart",
+    "LastUsedTimeStop",
+    "LastWriteTime",
+    "LastAccessTime",
+    "LastUpdate",
+];
+
+/// A built-in set of rules recognizing value modifications that are noise rather than
+/// meaningful changes.
+///
+/// Presets are opt-in and enabled through [DiffOptions::with_preset](crate::prelude::DiffOptions::with_preset).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RulePreset {
+    /// Freshly generated GUIDs, such as `MachineGuid` or values whose old and new data are both GUIDs.
+    Guids,
+    /// Well-known timestamp values rewritten by Wine and installers, such as `InstallDate`
+    /// or `LastUsedTimeStart`/`LastUsedTimeStop`.
+    WineNoise,
+}
+
+impl RulePreset {
+    /// Returns whether a modification from `old` to `new` is matched by this preset.
+    pub fn matches(&self, old: &Value, new: &Value) -> bool {
+        match self {
+            RulePreset::Guids => {
+                has_name(old, VOLATILE_GUID_NAMES)
+                    || (holds_guid(old.value()) && holds_guid(new.value()))
+            }
+            RulePreset::WineNoise => has_name(old, TIMESTAMP_NAMES),
+        }
+    }
+}
+
+/// Returns whether a value's name is one of `names`, ignoring ASCII case.
+fn has_name(value: &Value, names: &[&str]) -> bool {
+    match value.name() {
+        regashii::ValueName::Named(name) => names.iter().any(|n| n.eq_ignore_ascii_case(name)),
+        regashii::ValueName::Default => false,
+    }
+}
+
+/// What to do with value modifications matched by a [RulePreset].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NoiseAction {
+    /// Keep the change in the structured diff as an [Operation::Volatile](crate::prelude::Operation::Volatile),
+    /// but leave it out of generated patches.
+    #[default]
+    Mark,
+    /// Drop the change entirely, as if the value was unchanged.
+    Omit,
+}
+
+/// Returns whether a string value holds nothing but a GUID.
+fn holds_guid(value: &regashii::Value) -> bool {
+    match value {
+        regashii::Value::Sz(data) => is_guid(data),
+        _ => false,
+    }
+}
+
+/// Returns whether a string is GUID-shaped, e.g. `{0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0}`.
+///
+/// The surrounding braces are optional.
+fn is_guid(data: &str) -> bool {
+    let data = data
+        .strip_prefix('{')
+        .and_then(|data| data.strip_suffix('}'))
+        .unwrap_or(data);
+
+    let groups: Vec<&str> = data.split('-').collect();
+    let lengths = [8, 4, 4, 4, 12];
+    groups.len() == lengths.len()
+        && groups
+            .iter()
+            .zip(lengths)
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regashii::ValueName;
+
+    fn sz(name: &str, data: &str) -> Value {
+        Value::new(
+            ValueName::named(name),
+            regashii::Value::Sz(data.to_string()),
+        )
+    }
+
+    #[test]
+    fn test_is_guid() {
+        assert!(is_guid("{0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0}"));
+        assert!(is_guid("0F1E2D3C-4B5A-6978-8796-A5B4C3D2E1F0"));
+        assert!(!is_guid("{0f1e2d3c-4b5a-6978-8796}"));
+        assert!(!is_guid("not-a-guid"));
+    }
+
+    #[test]
+    fn test_guids_preset_matches_guid_shaped_change() {
+        let old = sz("Id", "{0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0}");
+        let new = sz("Id", "{11111111-2222-3333-4444-555555555555}");
+        assert!(RulePreset::Guids.matches(&old, &new));
+    }
+
+    #[test]
+    fn test_guids_preset_matches_known_value_name() {
+        let old = sz("MachineGuid", "a");
+        let new = sz("MachineGuid", "b");
+        assert!(RulePreset::Guids.matches(&old, &new));
+        assert!(!RulePreset::Guids.matches(&sz("Other", "a"), &sz("Other", "b")));
+    }
+
+    #[test]
+    fn test_wine_noise_preset_matches_timestamps() {
+        let old = Value::new(ValueName::named("InstallDate"), regashii::Value::Dword(1));
+        let new = Value::new(ValueName::named("InstallDate"), regashii::Value::Dword(2));
+        assert!(RulePreset::WineNoise.matches(&old, &new));
+        assert!(!RulePreset::WineNoise.matches(&sz("Version", "1"), &sz("Version", "2")));
+    }
+}
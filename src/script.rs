@@ -0,0 +1,383 @@
+use crate::prelude::{KeyChange, Operation, RegistryDiff, Value};
+use regashii::{KeyName, ValueName};
+use std::fmt::Write;
+
+/// A kind of script applying a diff with command line tools instead of a `.reg` import.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScriptFormat {
+    /// A Windows batch file of `reg add` and `reg delete` commands.
+    Batch,
+    /// A Windows PowerShell script using the registry provider.
+    PowerShell,
+    /// A POSIX shell script of `wine reg add` and `wine reg delete` commands, run in the prefix
+    /// set by `WINEPREFIX` with the Wine binary set by `WINE`, if any.
+    ///
+    /// Every failing command is reported on stderr, and the script exits with a failure
+    /// status once all commands ran.
+    Wine,
+}
+
+/// A single step of a script.
+enum Command<'a> {
+    /// Deletes a key along with its subkeys.
+    DeleteKey(&'a KeyName),
+    /// Creates a key, keeping it if it exists.
+    CreateKey(&'a KeyName),
+    /// Sets a value of a key.
+    SetValue(&'a KeyName, &'a Value),
+    /// Deletes a value of a key.
+    DeleteValue(&'a KeyName, &'a ValueName),
+}
+
+impl RegistryDiff {
+    /// Converts the diff into a script applying it, one command per change.
+    ///
+    /// Volatile value changes are left out, like in [RegistryDiff::to_patch]. Values of raw
+    /// types, such as REG_NONE data, can't be written by the tools and are left as comments.
+    pub fn to_script(&self, format: ScriptFormat) -> String {
+        let (mut out, newline) = match format {
+            ScriptFormat::Batch => ("@echo off\r\n".to_string(), "\r\n"),
+            ScriptFormat::PowerShell => ("$ErrorActionPreference = 'Stop'\r\n".to_string(), "\r\n"),
+            ScriptFormat::Wine => ("#!/bin/sh\nstatus=0\n".to_string(), "\n"),
+        };
+        for command in self.commands() {
+            let line = match format {
+                ScriptFormat::Batch => batch(&command),
+                ScriptFormat::PowerShell => powershell(&command),
+                ScriptFormat::Wine => wine(&command),
+            };
+            out.push_str(&line);
+            out.push_str(newline);
+        }
+        if format == ScriptFormat::Wine {
+            out.push_str("exit $status\n");
+        }
+        out
+    }
+
+    /// Returns the commands applying the diff, in key order.
+    fn commands(&self) -> Vec<Command<'_>> {
+        let mut commands = Vec::new();
+        for key in self.keys().values() {
+            match key.change() {
+                KeyChange::Deleted => {
+                    commands.push(Command::DeleteKey(key.name()));
+                    continue;
+                }
+                KeyChange::Added => commands.push(Command::CreateKey(key.name())),
+                KeyChange::Modified | KeyChange::Unchanged => {}
+            }
+
+            for (name, operation) in key.values() {
+                match operation {
+                    Operation::Add { data } | Operation::Modify { new_data: data, .. } => {
+                        commands.push(Command::SetValue(key.name(), data))
+                    }
+                    Operation::Delete { .. } => {
+                        commands.push(Command::DeleteValue(key.name(), name))
+                    }
+                    Operation::Unchanged
+                    | Operation::Volatile { .. }
+                    | Operation::Reordered { .. } => {}
+                }
+            }
+        }
+        commands
+    }
+}
+
+/// Returns the `reg` type name and data argument of a value, or `None` for values `reg`
+/// can't write.
+fn reg_data(value: &Value) -> Option<(&'static str, String)> {
+    if value.raw_type().is_some() {
+        return None;
+    }
+    let data = match value.value() {
+        regashii::Value::Sz(data) => ("REG_SZ", data.clone()),
+        regashii::Value::ExpandSz(data) => ("REG_EXPAND_SZ", data.clone()),
+        regashii::Value::Dword(data) => ("REG_DWORD", format!("0x{:08x}", data)),
+        regashii::Value::Qword(data) => ("REG_QWORD", format!("0x{:016x}", data)),
+        regashii::Value::Binary(bytes) => (
+            "REG_BINARY",
+            bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        ),
+        // `reg` separates strings with a literal `\0` by default.
+        regashii::Value::MultiSz(strings) => ("REG_MULTI_SZ", strings.join("\\0")),
+        _ => return None,
+    };
+    Some(data)
+}
+
+/// Quotes an argument of a Windows command line, so it is parsed back unchanged.
+fn quote(argument: &str) -> String {
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in argument.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes an argument of a command in a batch file, where `%` starts a variable.
+fn batch_quote(argument: &str) -> String {
+    quote(argument).replace('%', "%%")
+}
+
+/// Returns the `reg` arguments selecting a value.
+fn reg_value_name(name: &ValueName, quote: fn(&str) -> String) -> String {
+    match name {
+        ValueName::Default => "/ve".to_string(),
+        ValueName::Named(name) => format!("/v {}", quote(name)),
+    }
+}
+
+/// Formats a command as a line of a batch file.
+fn batch(command: &Command) -> String {
+    match command {
+        Command::DeleteKey(key) => format!("reg delete {} /f", batch_quote(key.raw())),
+        Command::CreateKey(key) => format!("reg add {} /f", batch_quote(key.raw())),
+        Command::SetValue(key, value) => match reg_data(value) {
+            // Line breaks would end the command.
+            Some((kind, data)) if !data.contains(['\r', '\n']) => format!(
+                "reg add {} {} /t {} /d {} /f",
+                batch_quote(key.raw()),
+                reg_value_name(value.name(), batch_quote),
+                kind,
+                batch_quote(&data)
+            ),
+            _ => format!(
+                "rem unsupported value {}",
+                describe_value(key, value.name())
+            ),
+        },
+        Command::DeleteValue(key, name) => format!(
+            "reg delete {} {} /f",
+            batch_quote(key.raw()),
+            reg_value_name(name, batch_quote)
+        ),
+    }
+}
+
+/// Quotes a literal string of a PowerShell script.
+fn powershell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// Returns the PowerShell path of a key, using the registry provider.
+fn powershell_path(key: &KeyName) -> String {
+    powershell_quote(&format!("Registry::{}", key.raw()))
+}
+
+/// Returns the PowerShell name of a value.
+fn powershell_name(name: &ValueName) -> String {
+    match name {
+        ValueName::Default => powershell_quote("(default)"),
+        ValueName::Named(name) => powershell_quote(name),
+    }
+}
+
+/// Returns the PowerShell property type and data expression of a value, or `None` for values
+/// PowerShell can't write.
+fn powershell_data(value: &Value) -> Option<(&'static str, String)> {
+    if value.raw_type().is_some() {
+        return None;
+    }
+    let data = match value.value() {
+        regashii::Value::Sz(data) => ("String", powershell_quote(data)),
+        regashii::Value::ExpandSz(data) => ("ExpandString", powershell_quote(data)),
+        // The registry provider takes signed numbers.
+        regashii::Value::Dword(data) => ("DWord", (*data as i32).to_string()),
+        regashii::Value::Qword(data) => ("QWord", (*data as i64).to_string()),
+        regashii::Value::Binary(bytes) => {
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+            ("Binary", format!("([byte[]]@({}))", bytes.join(",")))
+        }
+        regashii::Value::MultiSz(strings) => {
+            let strings: Vec<String> = strings.iter().map(|s| powershell_quote(s)).collect();
+            (
+                "MultiString",
+                format!("([string[]]@({}))", strings.join(",")),
+            )
+        }
+        _ => return None,
+    };
+    Some(data)
+}
+
+/// Formats a command as a line of a PowerShell script.
+fn powershell(command: &Command) -> String {
+    match command {
+        Command::DeleteKey(key) => format!(
+            "Remove-Item -LiteralPath {} -Recurse -Force",
+            powershell_path(key)
+        ),
+        // `New-Item -Force` would replace an existing key.
+        Command::CreateKey(key) => format!(
+            "if (-not (Test-Path -LiteralPath {path})) {{ New-Item -Path {path} -Force | Out-Null }}",
+            path = powershell_path(key)
+        ),
+        Command::SetValue(key, value) => match powershell_data(value) {
+            Some((kind, data)) => format!(
+                "New-ItemProperty -LiteralPath {} -Name {} -PropertyType {} -Value {} -Force | Out-Null",
+                powershell_path(key),
+                powershell_name(value.name()),
+                kind,
+                data
+            ),
+            None => format!("# unsupported value {}", describe_value(key, value.name())),
+        },
+        Command::DeleteValue(key, name) => format!(
+            "Remove-ItemProperty -LiteralPath {} -Name {} -Force",
+            powershell_path(key),
+            powershell_name(name)
+        ),
+    }
+}
+
+/// Quotes an argument of a POSIX shell command.
+///
+/// Wine passes the arguments on to `reg` unchanged, so no further quoting is needed.
+fn shell_quote(argument: &str) -> String {
+    format!("'{}'", argument.replace('\'', "'\\''"))
+}
+
+/// Formats a command as a line of a shell script, reporting its failure.
+fn wine(command: &Command) -> String {
+    let (arguments, description) = match command {
+        Command::DeleteKey(key) => (
+            format!("delete {} /f", shell_quote(key.raw())),
+            format!("delete key {}", key.raw()),
+        ),
+        Command::CreateKey(key) => (
+            format!("add {} /f", shell_quote(key.raw())),
+            format!("create key {}", key.raw()),
+        ),
+        Command::SetValue(key, value) => match reg_data(value) {
+            Some((kind, data)) => (
+                format!(
+                    "add {} {} /t {} /d {} /f",
+                    shell_quote(key.raw()),
+                    reg_value_name(value.name(), shell_quote),
+                    kind,
+                    shell_quote(&data)
+                ),
+                format!("set value {}", describe_value(key, value.name())),
+            ),
+            None => return format!("# unsupported value {}", describe_value(key, value.name())),
+        },
+        Command::DeleteValue(key, name) => (
+            format!(
+                "delete {} {} /f",
+                shell_quote(key.raw()),
+                reg_value_name(name, shell_quote)
+            ),
+            format!("delete value {}", describe_value(key, name)),
+        ),
+    };
+    format!(
+        "\"${{WINE:-wine}}\" reg {} || {{ echo {} >&2; status=1; }}",
+        arguments,
+        shell_quote(&format!("failed to {}", description))
+    )
+}
+
+/// Describes a value, for the comments and messages about its command.
+fn describe_value(key: &KeyName, name: &ValueName) -> String {
+    let mut text = String::new();
+    let _ = match name {
+        ValueName::Default => write!(text, "@ of {}", key.raw()),
+        ValueName::Named(name) => write!(text, "'{}' of {}", name, key.raw()),
+    };
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{DiffOptions, Hive, Registry};
+
+    fn diff() -> RegistryDiff {
+        let old = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4)
+                .with(
+                    KeyName::new("Software\\Old"),
+                    regashii::Key::new().with(ValueName::named("A"), regashii::Value::Dword(1)),
+                )
+                .with(
+                    KeyName::new("Software\\App"),
+                    regashii::Key::new().with(ValueName::named("Gone"), regashii::Value::Dword(1)),
+                ),
+            Hive::CurrentUser,
+        );
+        let new = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4).with(
+                KeyName::new("Software\\App"),
+                regashii::Key::new().with(
+                    ValueName::named("Path"),
+                    regashii::Value::ExpandSz("%ProgramFiles%\\App\\".to_string()),
+                ),
+            ),
+            Hive::CurrentUser,
+        );
+        RegistryDiff::new(&old, &new, &DiffOptions::new())
+    }
+
+    fn lines(script: &str) -> Vec<&str> {
+        let mut lines: Vec<&str> = script
+            .lines()
+            .filter(|line| line.contains("reg") || line.contains("Item"))
+            .collect();
+        lines.sort();
+        lines
+    }
+
+    #[test]
+    fn test_batch_script() {
+        assert_eq!(
+            lines(&diff().to_script(ScriptFormat::Batch)),
+            vec![
+                "reg add \"HKEY_CURRENT_USER\\Software\\App\" /v \"Path\" /t REG_EXPAND_SZ /d \"%%ProgramFiles%%\\App\\\\\" /f",
+                "reg delete \"HKEY_CURRENT_USER\\Software\\App\" /v \"Gone\" /f",
+                "reg delete \"HKEY_CURRENT_USER\\Software\\Old\" /f",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_powershell_script() {
+        assert_eq!(
+            lines(&diff().to_script(ScriptFormat::PowerShell)),
+            vec![
+                "New-ItemProperty -LiteralPath 'Registry::HKEY_CURRENT_USER\\Software\\App' -Name 'Path' -PropertyType ExpandString -Value '%ProgramFiles%\\App\\' -Force | Out-Null",
+                "Remove-Item -LiteralPath 'Registry::HKEY_CURRENT_USER\\Software\\Old' -Recurse -Force",
+                "Remove-ItemProperty -LiteralPath 'Registry::HKEY_CURRENT_USER\\Software\\App' -Name 'Gone' -Force",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wine_script() {
+        let script = diff().to_script(ScriptFormat::Wine);
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.ends_with("exit $status\n"));
+        assert_eq!(
+            lines(&script)[2],
+            "\"${WINE:-wine}\" reg delete 'HKEY_CURRENT_USER\\Software\\Old' /f || { echo 'failed to delete key HKEY_CURRENT_USER\\Software\\Old' >&2; status=1; }"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+/// The security descriptor of a registry key, controlling who may access it.
+///
+/// Descriptors are kept as the opaque bytes of a self-relative `SECURITY_DESCRIPTOR`, so they
+/// survive round trips unchanged. The basic fields are decoded on demand; malformed
+/// descriptors simply report them as missing.
+///
+/// Registry files store descriptors in a `#security=hex:...` line following the key header,
+/// which Wine and regedit ignore like any other comment.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SecurityDescriptor {
+    /// The bytes of the self-relative security descriptor.
+    raw: Vec<u8>,
+}
+
+/// The control flag indicating that a descriptor holds a discretionary ACL.
+const SE_DACL_PRESENT: u16 = 0x0004;
+/// The control flag indicating that a descriptor holds a system ACL.
+const SE_SACL_PRESENT: u16 = 0x0010;
+
+impl SecurityDescriptor {
+    /// Constructs a new [SecurityDescriptor] from the bytes of a self-relative descriptor.
+    pub fn new(raw: Vec<u8>) -> Self {
+        Self { raw }
+    }
+
+    /// Returns the bytes of the descriptor.
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Returns the revision of the descriptor format, normally 1.
+    pub fn revision(&self) -> Option<u8> {
+        self.raw.first().copied()
+    }
+
+    /// Returns the `SECURITY_DESCRIPTOR_CONTROL` flags.
+    pub fn control(&self) -> Option<u16> {
+        Some(u16::from_le_bytes([*self.raw.get(2)?, *self.raw.get(3)?]))
+    }
+
+    /// Returns the owner SID in its string form, e.g. `S-1-5-32-544`.
+    pub fn owner(&self) -> Option<String> {
+        self.sid_at(4)
+    }
+
+    /// Returns the primary group SID in its string form.
+    pub fn group(&self) -> Option<String> {
+        self.sid_at(8)
+    }
+
+    /// Returns whether the descriptor holds a discretionary ACL, granting or denying access.
+    pub fn has_dacl(&self) -> bool {
+        self.control()
+            .is_some_and(|control| control & SE_DACL_PRESENT != 0)
+    }
+
+    /// Returns whether the descriptor holds a system ACL, controlling auditing.
+    pub fn has_sacl(&self) -> bool {
+        self.control()
+            .is_some_and(|control| control & SE_SACL_PRESENT != 0)
+    }
+
+    /// Decodes the SID whose offset is stored at `field`.
+    fn sid_at(&self, field: usize) -> Option<String> {
+        let offset = u32::from_le_bytes(self.raw.get(field..field + 4)?.try_into().ok()?) as usize;
+        if offset == 0 {
+            return None;
+        }
+
+        let sid = self.raw.get(offset..)?;
+        let revision = *sid.first()?;
+        let count = *sid.get(1)? as usize;
+        let authority = sid
+            .get(2..8)?
+            .iter()
+            .fold(0u64, |authority, &byte| authority << 8 | byte as u64);
+
+        let mut text = format!("S-{}-{}", revision, authority);
+        for index in 0..count {
+            let start = 8 + index * 4;
+            let sub_authority = u32::from_le_bytes(sid.get(start..start + 4)?.try_into().ok()?);
+            text.push_str(&format!("-{}", sub_authority));
+        }
+        Some(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_security_descriptor_basics() {
+        // Owned by BUILTIN\Administrators, with an empty DACL.
+        let mut raw = vec![1, 0, 0x04, 0x80];
+        raw.extend(20u32.to_le_bytes());
+        raw.extend(0u32.to_le_bytes());
+        raw.extend(0u32.to_le_bytes());
+        raw.extend(0u32.to_le_bytes());
+        raw.extend([1, 2, 0, 0, 0, 0, 0, 5]);
+        raw.extend(32u32.to_le_bytes());
+        raw.extend(544u32.to_le_bytes());
+        let descriptor = SecurityDescriptor::new(raw);
+
+        assert_eq!(descriptor.revision(), Some(1));
+        assert_eq!(descriptor.owner().as_deref(), Some("S-1-5-32-544"));
+        assert_eq!(descriptor.group(), None);
+        assert!(descriptor.has_dacl());
+        assert!(!descriptor.has_sacl());
+        assert_eq!(SecurityDescriptor::new(vec![1]).owner(), None);
+    }
+
+    #[test]
+    fn test_security_descriptor_round_trip() {
+        use crate::prelude::{Hive, Registry, WriteOptions};
+        use regashii::KeyName;
+
+        let registry = Registry::try_from_str(
+            "WINE REGISTRY Version 2\n\n[Software\\\\Test] 1740588656\n\
+            #security=hex:01,00,04,80,00,00,00,00\n\"Name\"=\"value\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let serialized = registry.serialize().unwrap();
+        assert!(serialized.contains(
+            "[HKEY_CURRENT_USER\\Software\\Test]\n#security=hex:01,00,04,80,00,00,00,00\n"
+        ));
+        let wrapped = registry
+            .serialize_with(&WriteOptions::new().with_line_width(Some(20)))
+            .unwrap();
+        for text in [serialized, wrapped] {
+            let reloaded = Registry::try_from_str(&text, Hive::CurrentUser).unwrap();
+            assert_eq!(reloaded.keys(), registry.keys());
+            let key = reloaded.key(&KeyName::new("Software\\Test")).unwrap();
+            assert_eq!(key.security().unwrap().control(), Some(0x8004));
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use crate::prelude::{Key, KeyPattern, Registry};
+use regashii::{KeyName, ValueName};
+use std::collections::BTreeMap;
+
+/// The keys registering services and drivers, one subkey per service.
+const SERVICES_KEY: &str = "HKEY_LOCAL_MACHINE\\System\\CurrentControlSet\\Services\\*";
+
+/// When a service is started, from its `Start` value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StartType {
+    /// Loaded by the boot loader (`0`).
+    Boot,
+    /// Started while the kernel initializes (`1`).
+    System,
+    /// Started by the service control manager at startup (`2`).
+    Automatic,
+    /// Started on demand (`3`).
+    Manual,
+    /// Never started (`4`).
+    Disabled,
+    /// Any other start type.
+    Other(u32),
+}
+
+impl From<u32> for StartType {
+    fn from(start: u32) -> Self {
+        match start {
+            0 => Self::Boot,
+            1 => Self::System,
+            2 => Self::Automatic,
+            3 => Self::Manual,
+            4 => Self::Disabled,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl std::fmt::Display for StartType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Boot => write!(f, "boot"),
+            Self::System => write!(f, "system"),
+            Self::Automatic => write!(f, "automatic"),
+            Self::Manual => write!(f, "manual"),
+            Self::Disabled => write!(f, "disabled"),
+            Self::Other(start) => write!(f, "{}", start),
+        }
+    }
+}
+
+/// A service or driver registered in the `Services` key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Service {
+    /// The full name of the service's key.
+    key: KeyName,
+    /// The name of the service, i.e. of its key.
+    name: String,
+    /// The `DisplayName` of the service.
+    display_name: Option<String>,
+    /// The `Start` type of the service.
+    start: Option<StartType>,
+    /// The `ImagePath` of the service, the executable or driver it runs.
+    image_path: Option<String>,
+}
+
+impl Service {
+    /// Reads a service from its key.
+    fn from_key(key: &Key) -> Option<Self> {
+        let value = |name: &str| key.values().get(&ValueName::named(name)).map(|v| v.value());
+        let string = |name: &str| match value(name)? {
+            regashii::Value::Sz(data) | regashii::Value::ExpandSz(data) => Some(data.clone()),
+            _ => None,
+        };
+
+        Some(Self {
+            key: key.name().clone(),
+            name: key.name().raw().rsplit('\\').next()?.to_string(),
+            display_name: string("DisplayName"),
+            start: match value("Start") {
+                Some(regashii::Value::Dword(start)) => Some(StartType::from(*start)),
+                _ => None,
+            },
+            image_path: string("ImagePath"),
+        })
+    }
+
+    /// Returns the full name of the service's key.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the name of the service.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the display name of the service, if registered.
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_deref()
+    }
+
+    /// Returns when the service is started, if registered.
+    pub fn start(&self) -> Option<StartType> {
+        self.start
+    }
+
+    /// Returns the executable or driver the service runs, if registered.
+    pub fn image_path(&self) -> Option<&str> {
+        self.image_path.as_deref()
+    }
+}
+
+/// A change to a service present before and after the change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceChange {
+    /// The service before the change.
+    before: Service,
+    /// The service after the change.
+    after: Service,
+}
+
+impl ServiceChange {
+    /// Returns the service before the change.
+    pub fn before(&self) -> &Service {
+        &self.before
+    }
+
+    /// Returns the service after the change.
+    pub fn after(&self) -> &Service {
+        &self.after
+    }
+
+    /// Returns whether the start type changed.
+    pub fn start_changed(&self) -> bool {
+        self.before.start != self.after.start
+    }
+
+    /// Returns whether the image path changed.
+    pub fn image_path_changed(&self) -> bool {
+        self.before.image_path != self.after.image_path
+    }
+}
+
+impl std::fmt::Display for ServiceChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let describe = |value: Option<String>| value.unwrap_or_else(|| "(unset)".to_string());
+        let mut changes = Vec::new();
+        if self.start_changed() {
+            changes.push(format!(
+                "start {} -> {}",
+                describe(self.before.start.map(|start| start.to_string())),
+                describe(self.after.start.map(|start| start.to_string()))
+            ));
+        }
+        if self.image_path_changed() {
+            changes.push(format!(
+                "image path {} -> {}",
+                describe(self.before.image_path.clone()),
+                describe(self.after.image_path.clone())
+            ));
+        }
+        if changes.is_empty() {
+            changes.push("display name changed".to_string());
+        }
+        write!(f, "{}: {}", self.after.name, changes.join(", "))
+    }
+}
+
+/// The differences between the services registered in two registries.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServiceChanges {
+    /// The services only registered in the new registry.
+    added: Vec<Service>,
+    /// The services only registered in the old registry.
+    removed: Vec<Service>,
+    /// The services registered in both registries whose details differ.
+    changed: Vec<ServiceChange>,
+}
+
+impl ServiceChanges {
+    /// Compares the services registered in two registries, matching them by name.
+    pub fn between(old: &Registry, new: &Registry) -> Self {
+        let by_name = |registry: &Registry| -> BTreeMap<String, Service> {
+            registry
+                .services()
+                .into_iter()
+                .map(|service| (service.name.to_lowercase(), service))
+                .collect()
+        };
+        let (mut old, new) = (by_name(old), by_name(new));
+
+        let mut changes = Self::default();
+        for (name, service) in new {
+            match old.remove(&name) {
+                Some(before) if before != service => changes.changed.push(ServiceChange {
+                    before,
+                    after: service,
+                }),
+                Some(_) => {}
+                None => changes.added.push(service),
+            }
+        }
+        changes.removed.extend(old.into_values());
+        changes
+    }
+
+    /// Returns the services only registered in the new registry.
+    pub fn added(&self) -> &[Service] {
+        &self.added
+    }
+
+    /// Returns the services only registered in the old registry.
+    pub fn removed(&self) -> &[Service] {
+        &self.removed
+    }
+
+    /// Returns the services registered in both registries whose details differ.
+    pub fn changed(&self) -> &[ServiceChange] {
+        &self.changed
+    }
+
+    /// Returns whether the same services are registered in both registries.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Registry {
+    /// Returns the services and drivers registered in the registry's
+    /// `System\CurrentControlSet\Services` key.
+    pub fn services(&self) -> Vec<Service> {
+        let pattern = KeyPattern::new(SERVICES_KEY);
+        self.keys()
+            .values()
+            .filter(|key| pattern.matches(key.name()))
+            .filter_map(Service::from_key)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RegistryBuilder;
+    use crate::prelude::Hive;
+
+    fn registry(services: &[(&str, u32, &str)]) -> Registry {
+        services
+            .iter()
+            .fold(
+                RegistryBuilder::new(Hive::LocalMachine),
+                |builder, (name, start, image_path)| {
+                    let key = format!("System\\CurrentControlSet\\Services\\{}", name);
+                    builder
+                        .with(
+                            &key,
+                            ValueName::named("Start"),
+                            regashii::Value::Dword(*start),
+                        )
+                        .with(
+                            &key,
+                            ValueName::named("ImagePath"),
+                            regashii::Value::ExpandSz(image_path.to_string()),
+                        )
+                },
+            )
+            .build()
+    }
+
+    #[test]
+    fn test_service_changes() {
+        let old = registry(&[
+            ("Eventlog", 2, "C:\\windows\\system32\\services.exe"),
+            ("Spooler", 3, "C:\\windows\\system32\\spoolsv.exe"),
+        ]);
+        let new = registry(&[
+            ("Eventlog", 2, "C:\\windows\\system32\\services.exe"),
+            ("Spooler", 4, "C:\\windows\\system32\\spoolsv.exe"),
+            ("GameGuard", 1, "C:\\windows\\system32\\drivers\\guard.sys"),
+        ]);
+        let changes = ServiceChanges::between(&old, &new);
+
+        assert_eq!(changes.added()[0].start(), Some(StartType::System));
+        assert!(changes.removed().is_empty());
+        assert_eq!(
+            changes.changed()[0].to_string(),
+            "Spooler: start manual -> disabled"
+        );
+        assert!(ServiceChanges::between(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::prelude::Error;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+/// The first line of the signature block of a text patch.
+const BEGIN: &str = "; -----BEGIN REGDIFF SIGNATURE-----";
+
+/// The last line of the signature block of a text patch.
+const END: &str = "; -----END REGDIFF SIGNATURE-----";
+
+/// The prefix of the signature line of a text patch.
+const ALGORITHM: &str = "; ed25519:";
+
+/// The bytes marking the signature appended to a binary patch.
+const TRAILER: &[u8; 4] = b"RGDS";
+
+/// Signs a `.reg` patch, such as the text returned by
+/// [RegistryDiff::serialize](crate::prelude::RegistryDiff::serialize), so it can be shipped
+/// as a trusted tweak.
+///
+/// The signature is appended in a block of comment lines, so the signed patch still loads
+/// and imports like the original. It covers the exact text of the patch: re-encoding it or
+/// changing its line breaks invalidates it.
+///
+/// # Returns
+///
+/// The signed patch.
+pub fn sign_patch(patch: &str, key: &SigningKey) -> String {
+    let mut signed = patch.to_string();
+    if !signed.is_empty() && !signed.ends_with('\n') {
+        signed.push('\n');
+    }
+    let signature = key.sign(signed.as_bytes());
+    signed.push_str(&format!(
+        "{}\n{}{}\n{}\n",
+        BEGIN,
+        ALGORITHM,
+        hex(&signature.to_bytes()),
+        END
+    ));
+    signed
+}
+
+/// Verifies a patch signed by [sign_patch].
+///
+/// # Returns
+///
+/// The patch without its signature block, or [Error::Signature] if it isn't signed, was
+/// tampered with or was signed with another key.
+pub fn verify_patch<'a>(signed: &'a str, key: &VerifyingKey) -> Result<&'a str, Error> {
+    let start = signed
+        .rfind(BEGIN)
+        .filter(|&start| start == 0 || signed[..start].ends_with('\n'))
+        .ok_or_else(|| signature_error("the patch isn't signed"))?;
+    let (patch, block) = signed.split_at(start);
+
+    let mut lines = block.lines().map(str::trim_end).skip(1);
+    let signature = lines
+        .next()
+        .and_then(|line| line.strip_prefix(ALGORITHM))
+        .and_then(unhex)
+        .ok_or_else(|| signature_error("malformed signature block"))?;
+    if lines.next() != Some(END) || lines.any(|line| !line.trim().is_empty()) {
+        return Err(signature_error("malformed signature block"));
+    }
+    verify(patch.as_bytes(), &signature, key)?;
+    Ok(patch)
+}
+
+/// Signs a binary patch, such as the bytes returned by
+/// [RegistryDiff::to_binary](crate::prelude::RegistryDiff::to_binary), appending the
+/// signature to it.
+///
+/// Signed binary patches must go through [verify_binary] before being decoded.
+pub fn sign_binary(patch: &[u8], key: &SigningKey) -> Vec<u8> {
+    let mut signed = patch.to_vec();
+    signed.extend_from_slice(TRAILER);
+    signed.extend_from_slice(&key.sign(patch).to_bytes());
+    signed
+}
+
+/// Verifies a binary patch signed by [sign_binary].
+///
+/// # Returns
+///
+/// The patch without its signature, to decode with
+/// [RegistryDiff::from_binary](crate::prelude::RegistryDiff::from_binary), or
+/// [Error::Signature] if it isn't signed, was tampered with or was signed with another key.
+pub fn verify_binary<'a>(signed: &'a [u8], key: &VerifyingKey) -> Result<&'a [u8], Error> {
+    let length = TRAILER.len() + Signature::BYTE_SIZE;
+    let split = signed
+        .len()
+        .checked_sub(length)
+        .filter(|&split| signed[split..].starts_with(TRAILER))
+        .ok_or_else(|| signature_error("the patch isn't signed"))?;
+    let (patch, trailer) = signed.split_at(split);
+    verify(patch, &trailer[TRAILER.len()..], key)?;
+    Ok(patch)
+}
+
+/// Checks the signature of signed bytes.
+fn verify(bytes: &[u8], signature: &[u8], key: &VerifyingKey) -> Result<(), Error> {
+    let signature =
+        Signature::from_slice(signature).map_err(|_| signature_error("malformed signature"))?;
+    key.verify_strict(bytes, &signature)
+        .map_err(|_| signature_error("the signature doesn't match the patch"))
+}
+
+/// Builds an [Error::Signature] with the given message.
+fn signature_error(message: &str) -> Error {
+    Error::Signature {
+        message: message.to_string(),
+    }
+}
+
+/// Writes bytes as lowercase hex digits.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Reads bytes written as hex digits.
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::registry;
+    use crate::prelude::{DiffOptions, Hive, Registry, RegistryDiff};
+
+    fn diff() -> RegistryDiff {
+        let old = registry(
+            Hive::CurrentUser,
+            &[("Software\\App", "A", "1"), ("Software\\Old", "B", "2")],
+        );
+        let new = registry(
+            Hive::CurrentUser,
+            &[("Software\\App", "A", "2"), ("Software\\New", "C", "3")],
+        );
+        RegistryDiff::new(&old, &new, &DiffOptions::new())
+    }
+
+    #[test]
+    fn test_sign_patch() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let patch = diff().serialize().unwrap();
+        let signed = sign_patch(&patch, &key);
+        assert_eq!(verify_patch(&signed, &key.verifying_key()).unwrap(), patch);
+        assert_eq!(
+            Registry::try_from_str(&signed, Hive::CurrentUser)
+                .unwrap()
+                .keys(),
+            Registry::try_from_str(&patch, Hive::CurrentUser)
+                .unwrap()
+                .keys()
+        );
+
+        let tampered = signed.replacen("\n[", "\n[Tampered", 1);
+        assert!(verify_patch(&tampered, &key.verifying_key()).is_err());
+        let other = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        assert!(verify_patch(&signed, &other).is_err());
+        assert!(verify_patch(&patch, &key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_sign_binary() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let diff = diff();
+        let signed = sign_binary(&diff.to_binary(), &key);
+        let patch = verify_binary(&signed, &key.verifying_key()).unwrap();
+        assert_eq!(RegistryDiff::from_binary(patch).unwrap(), diff);
+
+        let mut tampered = signed.clone();
+        tampered[6] ^= 1;
+        assert!(verify_binary(&tampered, &key.verifying_key()).is_err());
+        assert!(verify_binary(&diff.to_binary(), &key.verifying_key()).is_err());
+    }
+}
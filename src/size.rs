@@ -0,0 +1,111 @@
+use crate::diff::merge_join;
+use crate::prelude::{Key, Registry};
+use crate::write;
+use regashii::KeyName;
+
+/// How the serialized size of a key changed between two registries, as reported by
+/// [Registry::size_changes].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SizeChange {
+    /// The full name of the key.
+    key: KeyName,
+    /// The size of the key in the old registry, 0 if it didn't exist.
+    old_size: usize,
+    /// The size of the key in the new registry, 0 if it doesn't exist.
+    new_size: usize,
+}
+
+impl SizeChange {
+    /// Returns the full name of the key.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the size in bytes of the key in the old registry, 0 if it didn't exist.
+    pub fn old_size(&self) -> usize {
+        self.old_size
+    }
+
+    /// Returns the size in bytes of the key in the new registry, 0 if it doesn't exist.
+    pub fn new_size(&self) -> usize {
+        self.new_size
+    }
+
+    /// Returns by how many bytes the key grew, negative if it shrank.
+    pub fn growth(&self) -> i64 {
+        self.new_size as i64 - self.old_size as i64
+    }
+}
+
+impl Key {
+    /// Returns the size in bytes of the key in a `.reg` file written with the default
+    /// options: its header, its values and the blank line separating it from the previous
+    /// key.
+    pub fn serialized_size(&self) -> usize {
+        let values: usize = self.values().values().map(write::value_len).sum();
+        let security = self.security().map_or(0, write::security_len);
+        let link = self.link().map_or(0, write::link_len);
+        write::key_header_len(self.name(), false) + security + link + values + 1
+    }
+}
+
+impl Registry {
+    /// Compares the serialized size of every key of this registry and a newer snapshot of it,
+    /// to find what bloats a registry file over time.
+    ///
+    /// # Arguments
+    ///
+    /// * `new` - The newer snapshot.
+    ///
+    /// # Returns
+    ///
+    /// The keys whose size changed, added and deleted keys included, the largest changes
+    /// first whether they grew or shrank.
+    pub fn size_changes(&self, new: &Registry) -> Vec<SizeChange> {
+        let mut changes: Vec<SizeChange> = merge_join(self.keys(), new.keys())
+            .map(|(_, old, new)| SizeChange {
+                key: old.or(new).map(Key::name).cloned().unwrap(),
+                old_size: old.map_or(0, Key::serialized_size),
+                new_size: new.map_or(0, Key::serialized_size),
+            })
+            .filter(|change| change.old_size != change.new_size)
+            .collect();
+        changes.sort_by_key(|change| std::cmp::Reverse(change.growth().unsigned_abs()));
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Hive;
+
+    #[test]
+    fn test_size_changes() {
+        let old = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Cache]\n\"A\"=\"a\"\n\n\
+            [HKEY_CURRENT_USER\\Gone]\n\n[HKEY_CURRENT_USER\\Same]\n\"B\"=\"b\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let new = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Cache]\n\"A\"=\"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\"\n\n\
+            [HKEY_CURRENT_USER\\Same]\n\"B\"=\"c\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let sizes: usize = new.keys().values().map(Key::serialized_size).sum();
+        assert_eq!(
+            new.serialize().unwrap().len(),
+            write::HEADER.len() + 1 + sizes
+        );
+
+        let changes = old.size_changes(&new);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].key().raw(), "HKEY_CURRENT_USER\\Cache");
+        assert_eq!(changes[0].growth(), 39);
+        assert_eq!(changes[1].key().raw(), "HKEY_CURRENT_USER\\Gone");
+        assert_eq!(changes[1].new_size(), 0);
+        assert_eq!(changes[1].growth(), -(changes[1].old_size() as i64));
+    }
+}
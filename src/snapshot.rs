@@ -0,0 +1,129 @@
+use crate::prelude::{DiffOptions, Error, Hive, Registry, RegistryDiff};
+use std::io::{Read, Write};
+
+/// The zstd compression level snapshots are saved with.
+const LEVEL: i32 = 9;
+
+/// A registry or diff persisted to disk.
+#[derive(Clone, Debug)]
+pub enum Snapshot {
+    /// The whole state of a registry.
+    Registry(Registry),
+    /// The changes between two registry states.
+    Diff(RegistryDiff),
+}
+
+impl Snapshot {
+    /// Saves the snapshot to a zstd-compressed file, compressing while writing. The file is
+    /// replaced atomically like with [RegistryDiff::serialize_file].
+    ///
+    /// Both registries and diffs are stored in the format of [RegistryDiff::to_binary], so
+    /// registries keep their raw value types, security descriptors and links.
+    pub fn save_compressed<T: AsRef<std::path::Path>>(&self, file: T) -> std::io::Result<()> {
+        let (tag, diff) = match self {
+            Snapshot::Registry(registry) => {
+                let empty = Registry::from_regashii(
+                    regashii::Registry::new(regashii::Format::Regedit4),
+                    registry.hive(),
+                );
+                let options = DiffOptions::new().with_security(true);
+                (
+                    hive_tag(registry.hive()),
+                    RegistryDiff::new(&empty, registry, &options),
+                )
+            }
+            Snapshot::Diff(diff) => (b'D', diff.clone()),
+        };
+
+        let file = crate::atomic::AtomicFile::create(file)?;
+        let mut encoder = zstd::stream::write::Encoder::new(file, LEVEL)?;
+        encoder.write_all(&[tag])?;
+        encoder.write_all(&diff.to_binary())?;
+        encoder.finish()?.commit()
+    }
+
+    /// Loads a snapshot saved by [Snapshot::save_compressed], decompressing while reading.
+    ///
+    /// # Returns
+    ///
+    /// The snapshot, or an [Error] if the file can't be read or doesn't hold a snapshot.
+    pub fn load_compressed<T: AsRef<std::path::Path>>(file: T) -> Result<Self, Error> {
+        let file = std::io::BufReader::new(std::fs::File::open(file)?);
+        let mut decoder = zstd::stream::read::Decoder::new(file)?;
+        let mut tag = [0];
+        decoder.read_exact(&mut tag)?;
+        let diff = RegistryDiff::read_binary(std::io::BufReader::new(decoder))?;
+
+        let hive = match tag[0] {
+            b'D' => return Ok(Snapshot::Diff(diff)),
+            b'M' => Hive::LocalMachine,
+            b'U' => Hive::CurrentUser,
+            b'd' => Hive::DefaultUser,
+            _ => {
+                return Err(Error::Binary {
+                    offset: 0,
+                    message: "unknown snapshot kind".to_string(),
+                })
+            }
+        };
+        let mut registry =
+            Registry::from_regashii(regashii::Registry::new(regashii::Format::Regedit4), hive);
+        registry.apply(&diff);
+        Ok(Snapshot::Registry(registry))
+    }
+}
+
+/// Returns the tag identifying a registry snapshot of a hive.
+fn hive_tag(hive: Hive) -> u8 {
+    match hive {
+        Hive::LocalMachine => b'M',
+        Hive::CurrentUser => b'U',
+        Hive::DefaultUser => b'd',
+    }
+}
+
+impl From<Registry> for Snapshot {
+    fn from(registry: Registry) -> Self {
+        Snapshot::Registry(registry)
+    }
+}
+
+impl From<RegistryDiff> for Snapshot {
+    fn from(diff: RegistryDiff) -> Self {
+        Snapshot::Diff(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compressed_snapshots() {
+        let directory =
+            std::env::temp_dir().join(format!("regdiff-snapshot-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+
+        Snapshot::from(new.clone())
+            .save_compressed(directory.join("new.zst"))
+            .unwrap();
+        let loaded = match Snapshot::load_compressed(directory.join("new.zst")).unwrap() {
+            Snapshot::Registry(registry) => registry,
+            Snapshot::Diff(_) => panic!("expected a registry"),
+        };
+        assert_eq!(loaded.keys(), new.keys());
+
+        let diff = RegistryDiff::new(&old, &loaded, &DiffOptions::new());
+        Snapshot::from(diff.clone())
+            .save_compressed(directory.join("diff.zst"))
+            .unwrap();
+        match Snapshot::load_compressed(directory.join("diff.zst")).unwrap() {
+            Snapshot::Diff(loaded) => assert_eq!(loaded, diff),
+            Snapshot::Registry(_) => panic!("expected a diff"),
+        }
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
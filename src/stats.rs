@@ -0,0 +1,88 @@
+use crate::path;
+use crate::prelude::{Registry, ValueKind};
+use regashii::KeyName;
+use std::collections::BTreeMap;
+
+impl Registry {
+    /// Counts the values of the registry by the type of their data.
+    ///
+    /// # Returns
+    ///
+    /// The number of values of each type found, types without values left out.
+    pub fn value_type_histogram(&self) -> BTreeMap<ValueKind, usize> {
+        let mut histogram = BTreeMap::new();
+        for value in self.keys().values().flat_map(|key| key.values().values()) {
+            *histogram.entry(value.kind()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Counts the values of the registry by subtree and type, to see where values of each
+    /// type are stored.
+    ///
+    /// Subtrees are named like the groups of [RegistryDiff::group_by](crate::prelude::RegistryDiff::group_by):
+    /// by the hive followed by the first `depth` components of the key names within it.
+    ///
+    /// # Returns
+    ///
+    /// The histogram of each subtree holding values, as returned by
+    /// [Registry::value_type_histogram].
+    pub fn value_type_histogram_by(
+        &self,
+        depth: usize,
+    ) -> BTreeMap<KeyName, BTreeMap<ValueKind, usize>> {
+        let mut histograms: BTreeMap<KeyName, BTreeMap<ValueKind, usize>> = BTreeMap::new();
+        for key in self.keys().values() {
+            if key.values().is_empty() {
+                continue;
+            }
+            let histogram = histograms
+                .entry(path::subtree(key.name(), depth))
+                .or_default();
+            for value in key.values().values() {
+                *histogram.entry(value.kind()).or_insert(0) += 1;
+            }
+        }
+        histograms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Hive;
+
+    #[test]
+    fn test_value_type_histogram() {
+        let registry = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\A]\n\"S\"=\"s\"\n\"D\"=dword:00000001\n\n\
+            [HKEY_CURRENT_USER\\Software\\A\\Sub]\n\"T\"=\"t\"\n\n\
+            [HKEY_CURRENT_USER\\Software\\B]\n\"N\"=hex(0):\n\"X\"=hex:01\n\n\
+            [HKEY_CURRENT_USER\\Software\\Empty]\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+
+        assert_eq!(
+            registry.value_type_histogram(),
+            BTreeMap::from([
+                (ValueKind::Sz, 2),
+                (ValueKind::Binary, 1),
+                (ValueKind::Dword, 1),
+                (ValueKind::None, 1),
+            ])
+        );
+        let by_subtree = registry.value_type_histogram_by(2);
+        assert_eq!(
+            by_subtree.keys().map(KeyName::raw).collect::<Vec<_>>(),
+            [
+                "HKEY_CURRENT_USER\\Software\\A",
+                "HKEY_CURRENT_USER\\Software\\B"
+            ]
+        );
+        assert_eq!(
+            by_subtree[&KeyName::new("HKEY_CURRENT_USER\\Software\\A")][&ValueKind::Sz],
+            2
+        );
+    }
+}
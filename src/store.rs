@@ -0,0 +1,220 @@
+use crate::atomic;
+use crate::prelude::{DiffOptions, Error, Hive, KeyDiff, Registry, RegistryDiff};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// The identifier of a snapshot in a [Store], the SHA-256 hash of its manifest.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SnapshotId(String);
+
+impl SnapshotId {
+    /// Wraps a hexadecimal identifier read from the store.
+    pub(crate) fn new(id: String) -> Self {
+        Self(id)
+    }
+
+    /// Returns the identifier as lowercase hexadecimal.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for SnapshotId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Returns the lowercase hexadecimal SHA-256 hash of some bytes.
+pub(crate) fn hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// A content-addressed repository of registry snapshots on disk.
+///
+/// Every key is stored once as an object named after the hash of its content, so keys
+/// unchanged between snapshots take no extra space. A snapshot is a manifest listing the
+/// objects of its keys, named after its own hash:
+///
+/// - `objects/<hash>` holds a key, encoded like [RegistryDiff::to_binary].
+/// - `snapshots/<id>` holds the hive of a snapshot and the hashes of its keys, one per line.
+/// - `index` lists the snapshot IDs in the order they were first committed.
+#[derive(Clone, Debug)]
+pub struct Store {
+    /// The directory holding the store.
+    root: PathBuf,
+}
+
+impl Store {
+    /// Opens the store in a directory, creating it if needed.
+    pub fn open<T: AsRef<Path>>(root: T) -> Result<Self, Error> {
+        let root = root.as_ref().to_path_buf();
+        std::fs::create_dir_all(root.join("objects"))?;
+        std::fs::create_dir_all(root.join("snapshots"))?;
+        Ok(Self { root })
+    }
+
+    /// Returns the directory holding the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Stores the state of a registry, with its security descriptors and links.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the snapshot. Committing the same state again returns the same ID without
+    /// storing anything.
+    pub fn commit(&self, registry: &Registry) -> Result<SnapshotId, Error> {
+        let options = DiffOptions::new().with_security(true);
+        let mut manifest = format!("{}\n", registry.hive());
+        for key in registry.keys().values() {
+            let object: RegistryDiff = KeyDiff::new(None, Some(key), &options)
+                .into_iter()
+                .collect();
+            let bytes = object.to_binary();
+            let object = hash(&bytes);
+            let path = self.root.join("objects").join(&object);
+            if !path.exists() {
+                atomic::write(path, bytes)?;
+            }
+            manifest.push_str(&object);
+            manifest.push('\n');
+        }
+
+        let id = SnapshotId(hash(manifest.as_bytes()));
+        let path = self.root.join("snapshots").join(id.as_str());
+        if !path.exists() {
+            atomic::write(path, manifest)?;
+            let mut index = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.root.join("index"))?;
+            std::io::Write::write_all(&mut index, format!("{}\n", id).as_bytes())?;
+        }
+        Ok(id)
+    }
+
+    /// Returns the IDs of the stored snapshots, in the order they were first committed.
+    pub fn snapshots(&self) -> Result<Vec<SnapshotId>, Error> {
+        let index = match std::fs::read_to_string(self.root.join("index")) {
+            Ok(index) => index,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(index
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| SnapshotId(line.to_string()))
+            .collect())
+    }
+
+    /// Finds the snapshot whose ID starts with the given hexadecimal prefix, like git's
+    /// abbreviated hashes.
+    ///
+    /// # Returns
+    ///
+    /// The ID, or [Error::Store] if no snapshot or more than one matches.
+    pub fn resolve(&self, prefix: &str) -> Result<SnapshotId, Error> {
+        let prefix = prefix.to_lowercase();
+        let mut matches = self
+            .snapshots()?
+            .into_iter()
+            .filter(|id| !prefix.is_empty() && id.as_str().starts_with(&prefix));
+        match (matches.next(), matches.next()) {
+            (Some(id), None) => Ok(id),
+            (Some(_), Some(_)) => Err(store_error(format!("snapshot '{}' is ambiguous", prefix))),
+            (None, _) => Err(store_error(format!("unknown snapshot '{}'", prefix))),
+        }
+    }
+
+    /// Loads the registry state stored in a snapshot.
+    pub fn load(&self, id: &SnapshotId) -> Result<Registry, Error> {
+        let manifest = std::fs::read_to_string(self.root.join("snapshots").join(id.as_str()))
+            .map_err(|error| match error.kind() {
+                std::io::ErrorKind::NotFound => store_error(format!("unknown snapshot '{}'", id)),
+                _ => error.into(),
+            })?;
+        let mut lines = manifest.lines();
+        let hive = lines
+            .next()
+            .and_then(|hive| {
+                [Hive::LocalMachine, Hive::CurrentUser, Hive::DefaultUser]
+                    .into_iter()
+                    .find(|known| known.to_string() == hive)
+            })
+            .ok_or_else(|| store_error(format!("snapshot '{}' names no known hive", id)))?;
+
+        let keys = lines
+            .map(|object| {
+                let bytes = std::fs::read(self.root.join("objects").join(object))?;
+                if hash(&bytes) != object {
+                    return Err(store_error(format!("object '{}' is corrupt", object)));
+                }
+                RegistryDiff::from_binary(&bytes)
+            })
+            .collect::<Result<RegistryDiff, Error>>()?;
+
+        let mut registry =
+            Registry::from_regashii(regashii::Registry::new(regashii::Format::Regedit4), hive);
+        registry.apply(&keys);
+        Ok(registry)
+    }
+
+    /// Computes the diff between two snapshots.
+    pub fn diff(
+        &self,
+        old: &SnapshotId,
+        new: &SnapshotId,
+        options: &DiffOptions,
+    ) -> Result<RegistryDiff, Error> {
+        Ok(RegistryDiff::new(
+            &self.load(old)?,
+            &self.load(new)?,
+            options,
+        ))
+    }
+}
+
+/// Constructs an [Error::Store] from a description of the problem.
+pub(crate) fn store_error(message: String) -> Error {
+    Error::Store { message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_commit_and_diff() {
+        let root = std::env::temp_dir().join(format!("regdiff-store-{}", std::process::id()));
+        let store = Store::open(&root).unwrap();
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+
+        let first = store.commit(&old).unwrap();
+        let objects = std::fs::read_dir(root.join("objects")).unwrap().count();
+        let second = store.commit(&new).unwrap();
+        assert_eq!(store.commit(&old).unwrap(), first);
+        assert_eq!(
+            store.snapshots().unwrap(),
+            vec![first.clone(), second.clone()]
+        );
+        // Keys unchanged between the snapshots are stored once.
+        let added = std::fs::read_dir(root.join("objects")).unwrap().count() - objects;
+        assert!(added < new.keys().len());
+
+        assert_eq!(store.load(&second).unwrap().keys(), new.keys());
+        assert_eq!(store.resolve(&second.as_str()[..8]).unwrap(), second);
+        let options = DiffOptions::new();
+        assert_eq!(
+            store.diff(&first, &second, &options).unwrap(),
+            RegistryDiff::new(&old, &new, &options)
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
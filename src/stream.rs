@@ -0,0 +1,176 @@
+use crate::prelude::{Encoding, KeyDiff, PatchMetadata, RegistryDiff, WriteOptions};
+use crate::write;
+use std::io::Write;
+
+/// The number of keys buffered between writes by default.
+const DEFAULT_CHUNK_SIZE: usize = 1024;
+
+/// Writes a `Windows Registry Editor Version 5.00` patch to any [Write] one key at a time,
+/// without holding the whole patch in memory.
+///
+/// Keys are buffered and written in chunks, flushing the writer after each chunk. The patch
+/// is the same as [RegistryDiff::serialize] would return for the keys written, in the order
+/// they were written.
+#[derive(Debug)]
+pub struct PatchWriter<W: Write> {
+    /// The destination of the patch.
+    writer: W,
+    /// The encoding the patch is written in.
+    encoding: Encoding,
+    /// The number of keys buffered before writing them.
+    chunk_size: usize,
+    /// How the keys are written.
+    options: WriteOptions,
+    /// The text of the keys not written yet.
+    buffer: String,
+    /// The number of keys in the buffer.
+    buffered: usize,
+    /// Whether anything, starting with the header, was written to the writer yet.
+    started: bool,
+}
+
+impl<W: Write> PatchWriter<W> {
+    /// Constructs a new [PatchWriter] writing UTF-8 in chunks of 1024 keys.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            encoding: Encoding::Utf8,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            options: WriteOptions::default(),
+            buffer: format!("{}\n", write::HEADER),
+            buffered: 0,
+            started: false,
+        }
+    }
+
+    /// Sets the encoding the patch is written in. A UTF-16LE patch starts with a byte order mark.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Sets the number of keys buffered between writes. A size of 0 writes every key at once.
+    pub fn with_chunk_size(mut self, keys: usize) -> Self {
+        self.chunk_size = keys;
+        self
+    }
+
+    /// Sets how the keys are written, like [RegistryDiff::serialize_with] does.
+    pub fn with_options(mut self, options: WriteOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Writes the comments of `metadata` after the header, like [RegistryDiff::serialize] does.
+    /// Must be called before writing keys.
+    pub fn with_metadata(mut self, metadata: &PatchMetadata) -> Self {
+        self.buffer.push_str(&metadata.comments());
+        self
+    }
+
+    /// Appends the lines applying the changes of a key, following the rules of
+    /// [KeyDiff::to_patch_key].
+    ///
+    /// # Returns
+    ///
+    /// An error if a full chunk couldn't be written to the writer, or one of kind
+    /// [InvalidData](std::io::ErrorKind::InvalidData) wrapping an [Error::InvalidName](crate::prelude::Error::InvalidName)
+    /// if a key or value name can't be written.
+    pub fn write_key(&mut self, key: &KeyDiff) -> std::io::Result<()> {
+        let mut lines = String::new();
+        key.write(&mut lines, &self.options)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        self.buffer.push('\n');
+        self.buffer.push_str(&lines);
+        self.buffered += 1;
+        if self.buffered >= self.chunk_size {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining keys and returns the writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.flush_chunk()?;
+        Ok(self.writer)
+    }
+
+    /// Writes the buffered keys, encoded, and flushes the writer.
+    fn flush_chunk(&mut self) -> std::io::Result<()> {
+        let bytes = self.encoding.encode(&self.buffer);
+        let bytes = match self.encoding {
+            // Only the start of the patch gets a byte order mark.
+            Encoding::Utf16Le if self.started => &bytes[2..],
+            _ => &bytes[..],
+        };
+        self.writer.write_all(bytes)?;
+        self.writer.flush()?;
+
+        self.buffer.clear();
+        self.buffered = 0;
+        self.started = true;
+        Ok(())
+    }
+}
+
+impl RegistryDiff {
+    /// Writes the patch returned by [RegistryDiff::serialize] to a writer as UTF-8, one chunk
+    /// of keys at a time.
+    ///
+    /// Use a [PatchWriter] to choose the encoding and chunk size.
+    pub fn write_to<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        span!(DEBUG, "serialize", keys = self.keys().len());
+        let mut patch = PatchWriter::new(writer);
+        for key in self.keys().values() {
+            patch.write_key(key)?;
+        }
+        patch.finish().map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::prelude::{DiffOptions, Hive, Registry};
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_patch_writer_matches_serialize() {
+        let old = Registry::open("./registries/old.reg", Hive::CurrentUser).unwrap();
+        let new = Registry::open("./registries/new.reg", Hive::CurrentUser).unwrap();
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+
+        let mut written = Vec::new();
+        diff.write_to(&mut written).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            diff.serialize().unwrap()
+        );
+
+        let mut patch = PatchWriter::new(Vec::new())
+            .with_encoding(Encoding::Utf16Le)
+            .with_chunk_size(1);
+        for key in diff.keys().values() {
+            patch.write_key(key).unwrap();
+        }
+        let written = patch.finish().unwrap();
+        assert_eq!(
+            written,
+            Encoding::Utf16Le.encode(&diff.serialize().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_patch_writer_empty() {
+        let written = PatchWriter::new(Vec::new()).finish().unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            RegistryDiff::default().serialize().unwrap()
+        );
+    }
+}
@@ -0,0 +1,435 @@
+use crate::prelude::{Error, Hive, Key, KeyPath, Registry, SecurityDescriptor, Value};
+use regashii::{KeyName, ValueName};
+use std::collections::BTreeMap;
+
+/// A document tree common to TOML and YAML, so registries are converted the same way to both.
+enum Node {
+    Integer(i64),
+    String(String),
+    List(Vec<Node>),
+    Table(BTreeMap<String, Node>),
+}
+
+impl Registry {
+    /// Converts the registry into TOML, for hand-edited fixtures and prefix templates.
+    ///
+    /// The document holds the `hive` name and a `keys` table. Each key is a table nested in
+    /// the `subkeys` table of its parent, holding its values in a `values` table, its default
+    /// value as `default`, its security descriptor as `security` and its link target as
+    /// `link`. A key table without `values` only holds subkeys and isn't a key itself.
+    ///
+    /// Each value is a table of its `type` and `data`, such as
+    /// `Version = { type = "sz", data = "win10" }`. The types are `sz`, `expand_sz`,
+    /// `multi_sz`, `dword`, `qword`, `binary`, and `hex` for raw values, with the registry type
+    /// code in `kind`. Binary data and security descriptors are written as comma-separated hex
+    /// bytes, like in `.reg` files.
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> String {
+        toml::to_string(&into_toml(tree(self))).expect("registry tables are valid TOML")
+    }
+
+    /// Loads a registry from its TOML representation, as written by [Registry::to_toml].
+    ///
+    /// # Returns
+    ///
+    /// The registry, or [Error::Structured] if the text isn't valid TOML or doesn't describe a
+    /// registry.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        let error = |message: String| Error::Structured {
+            format: "TOML",
+            message,
+        };
+        let document: toml::Table = toml::from_str(text).map_err(|e| error(e.to_string()))?;
+        registry(from_toml(toml::Value::Table(document)).map_err(error)?).map_err(error)
+    }
+
+    /// Converts the registry into YAML, laid out like [Registry::to_toml].
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml(&self) -> String {
+        serde_yaml::to_string(&into_yaml(tree(self))).expect("registry mappings are valid YAML")
+    }
+
+    /// Loads a registry from its YAML representation, as written by [Registry::to_yaml].
+    ///
+    /// # Returns
+    ///
+    /// The registry, or [Error::Structured] if the text isn't valid YAML or doesn't describe a
+    /// registry.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(text: &str) -> Result<Self, Error> {
+        let error = |message: String| Error::Structured {
+            format: "YAML",
+            message,
+        };
+        let document: serde_yaml::Value =
+            serde_yaml::from_str(text).map_err(|e| error(e.to_string()))?;
+        registry(from_yaml(document).map_err(error)?).map_err(error)
+    }
+}
+
+/// Builds the document tree of a registry.
+fn tree(registry: &Registry) -> Node {
+    let mut keys = BTreeMap::new();
+    for (name, key) in registry.keys() {
+        let components: Vec<&str> = name.components().collect();
+        let (leaf, parents) = components.split_last().expect("names have a component");
+        let mut subkeys = &mut keys;
+        for component in parents {
+            subkeys = child(child(subkeys, component), "subkeys");
+        }
+        let table = child(subkeys, leaf);
+
+        let mut values = BTreeMap::new();
+        for (name, value) in key.values() {
+            let node = match value_node(value) {
+                Some(node) => node,
+                None => continue,
+            };
+            match name {
+                ValueName::Default => table.insert("default".to_string(), node),
+                ValueName::Named(name) => values.insert(name.clone(), node),
+            };
+        }
+        table.insert("values".to_string(), Node::Table(values));
+        if let Some(security) = key.security() {
+            table.insert("security".to_string(), hex_node(security.raw()));
+        }
+        if let Some(link) = key.link() {
+            table.insert("link".to_string(), Node::String(link.to_string()));
+        }
+    }
+
+    Node::Table(BTreeMap::from([
+        (
+            "hive".to_string(),
+            Node::String(registry.hive().to_string()),
+        ),
+        ("keys".to_string(), Node::Table(keys)),
+    ]))
+}
+
+/// Returns the table of a table, creating it if missing.
+fn child<'a>(table: &'a mut BTreeMap<String, Node>, name: &str) -> &'a mut BTreeMap<String, Node> {
+    match table
+        .entry(name.to_string())
+        .or_insert_with(|| Node::Table(BTreeMap::new()))
+    {
+        Node::Table(child) => child,
+        _ => unreachable!("key tables only hold tables under subkey names"),
+    }
+}
+
+/// Builds the table of a value, `None` for data regashii holds in a form the registry files
+/// loaded by this crate never produce.
+fn value_node(value: &Value) -> Option<Node> {
+    let mut table = BTreeMap::new();
+    let (kind, data) = match (value.raw_type(), value.value()) {
+        (Some(code), regashii::Value::Binary(bytes)) => {
+            table.insert("kind".to_string(), Node::Integer(code.into()));
+            ("hex", hex_node(bytes))
+        }
+        (_, regashii::Value::Sz(string)) => ("sz", Node::String(string.clone())),
+        (_, regashii::Value::ExpandSz(string)) => ("expand_sz", Node::String(string.clone())),
+        (_, regashii::Value::MultiSz(strings)) => (
+            "multi_sz",
+            Node::List(strings.iter().cloned().map(Node::String).collect()),
+        ),
+        (_, regashii::Value::Dword(dword)) => ("dword", Node::Integer((*dword).into())),
+        (_, regashii::Value::Qword(qword)) => (
+            "qword",
+            match i64::try_from(*qword) {
+                Ok(qword) => Node::Integer(qword),
+                // Beyond the integers TOML can hold.
+                Err(_) => Node::String(qword.to_string()),
+            },
+        ),
+        (_, regashii::Value::Binary(bytes)) => ("binary", hex_node(bytes)),
+        _ => return None,
+    };
+    table.insert("type".to_string(), Node::String(kind.to_string()));
+    table.insert("data".to_string(), data);
+    Some(Node::Table(table))
+}
+
+/// Writes bytes as comma-separated hex pairs.
+fn hex_node(bytes: &[u8]) -> Node {
+    let pairs: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    Node::String(pairs.join(","))
+}
+
+/// Builds a registry from its document tree.
+fn registry(document: Node) -> Result<Registry, String> {
+    let mut document = table(document, "the document")?;
+    let hive = match document.remove("hive") {
+        Some(hive) => string(hive, "the hive")?,
+        None => return Err("missing hive".to_string()),
+    };
+    let hive = [Hive::LocalMachine, Hive::CurrentUser, Hive::DefaultUser]
+        .into_iter()
+        .find(|candidate| candidate.to_string() == hive)
+        .ok_or_else(|| format!("unknown hive '{}'", hive))?;
+
+    let mut registry =
+        Registry::from_regashii(regashii::Registry::new(regashii::Format::Regedit4), hive);
+    if let Some(keys) = document.remove("keys") {
+        add_keys(&mut registry, None, table(keys, "keys")?)?;
+    }
+    Ok(registry)
+}
+
+/// Adds the keys of a `subkeys` table to a registry, along with their own subkeys.
+fn add_keys(
+    registry: &mut Registry,
+    parent: Option<&str>,
+    subkeys: BTreeMap<String, Node>,
+) -> Result<(), String> {
+    for (component, node) in subkeys {
+        let path = match parent {
+            Some(parent) => format!("{}\\{}", parent, component),
+            None => component,
+        };
+        let mut entries = table(node, &format!("key '{}'", path))?;
+
+        if let Some(values) = entries.remove("values") {
+            let full_name = KeyName::new(format!("{}\\{}", registry.hive(), path));
+            let mut key = Key::new(full_name, regashii::Key::new());
+            if let Some(security) = entries.remove("security") {
+                let security = string(security, &format!("the security of key '{}'", path))?;
+                key = key.with_security(SecurityDescriptor::new(bytes(&security)?));
+            }
+            if let Some(link) = entries.remove("link") {
+                key = key.with_link(string(link, &format!("the link of key '{}'", path))?);
+            }
+            if let Some(default) = entries.remove("default") {
+                let value = value(ValueName::Default, default, &path)?;
+                key.values_mut().insert(ValueName::Default, value);
+            }
+            for (name, node) in table(values, &format!("the values of key '{}'", path))? {
+                let name = ValueName::Named(name);
+                let value = value(name.clone(), node, &path)?;
+                key.values_mut().insert(name, value);
+            }
+            *registry.key_mut(&path) = key;
+        }
+        if let Some(subkeys) = entries.remove("subkeys") {
+            let subkeys = table(subkeys, &format!("the subkeys of key '{}'", path))?;
+            add_keys(registry, Some(&path), subkeys)?;
+        }
+        if let Some(entry) = entries.keys().next() {
+            return Err(format!("unexpected '{}' in key '{}'", entry, path));
+        }
+    }
+    Ok(())
+}
+
+/// Builds a value from its table.
+fn value(name: ValueName, node: Node, key: &str) -> Result<Value, String> {
+    let what = match &name {
+        ValueName::Default => format!("the default value of key '{}'", key),
+        ValueName::Named(name) => format!("value '{}' of key '{}'", name, key),
+    };
+    let mut entries = table(node, &what)?;
+    let kind = match entries.remove("type") {
+        Some(kind) => string(kind, &format!("the type of {}", what))?,
+        None => return Err(format!("missing type for {}", what)),
+    };
+    let data = entries
+        .remove("data")
+        .ok_or_else(|| format!("missing data for {}", what))?;
+    let what = format!("the data of {}", what);
+
+    let data = match kind.as_str() {
+        "sz" => regashii::Value::Sz(string(data, &what)?),
+        "expand_sz" => regashii::Value::ExpandSz(string(data, &what)?),
+        "multi_sz" => match data {
+            Node::List(items) => regashii::Value::MultiSz(
+                items
+                    .into_iter()
+                    .map(|item| string(item, &what))
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => return Err(format!("expected a list for {}", what)),
+        },
+        "dword" => regashii::Value::Dword(
+            u32::try_from(integer(data, &what)?)
+                .map_err(|_| format!("{} is out of range", what))?,
+        ),
+        "qword" => regashii::Value::Qword(match data {
+            Node::Integer(qword) => {
+                u64::try_from(qword).map_err(|_| format!("{} is out of range", what))?
+            }
+            Node::String(qword) => qword
+                .parse()
+                .map_err(|_| format!("expected a number for {}", what))?,
+            _ => return Err(format!("expected a number for {}", what)),
+        }),
+        "binary" => regashii::Value::Binary(bytes(&string(data, &what)?)?),
+        "hex" => {
+            let code = match entries.remove("kind") {
+                Some(code) => integer(code, &what)?,
+                None => return Err(format!("missing kind for {}", what)),
+            };
+            let code = u32::try_from(code).map_err(|_| format!("{} has an invalid kind", what))?;
+            return Ok(Value::raw(name, code, bytes(&string(data, &what)?)?));
+        }
+        other => return Err(format!("unknown type '{}' for {}", other, what)),
+    };
+    Ok(Value::new(name, data))
+}
+
+/// Reads comma-separated hex pairs.
+fn bytes(text: &str) -> Result<Vec<u8>, String> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    text.split(',')
+        .map(|pair| {
+            u8::from_str_radix(pair, 16).map_err(|_| format!("invalid hex byte '{}'", pair))
+        })
+        .collect()
+}
+
+/// Reads a table, describing the expected node as `what` otherwise.
+fn table(node: Node, what: &str) -> Result<BTreeMap<String, Node>, String> {
+    match node {
+        Node::Table(table) => Ok(table),
+        _ => Err(format!("expected a table for {}", what)),
+    }
+}
+
+/// Reads a string, describing the expected node as `what` otherwise.
+fn string(node: Node, what: &str) -> Result<String, String> {
+    match node {
+        Node::String(string) => Ok(string),
+        _ => Err(format!("expected a string for {}", what)),
+    }
+}
+
+/// Reads an integer, describing the expected node as `what` otherwise.
+fn integer(node: Node, what: &str) -> Result<i64, String> {
+    match node {
+        Node::Integer(integer) => Ok(integer),
+        _ => Err(format!("expected an integer for {}", what)),
+    }
+}
+
+/// Converts a document tree into a TOML value.
+#[cfg(feature = "toml")]
+fn into_toml(node: Node) -> toml::Value {
+    match node {
+        Node::Integer(integer) => toml::Value::Integer(integer),
+        Node::String(string) => toml::Value::String(string),
+        Node::List(items) => toml::Value::Array(items.into_iter().map(into_toml).collect()),
+        Node::Table(table) => toml::Value::Table(
+            table
+                .into_iter()
+                .map(|(name, node)| (name, into_toml(node)))
+                .collect(),
+        ),
+    }
+}
+
+/// Converts a TOML value into a document tree, rejecting floats, booleans and dates.
+#[cfg(feature = "toml")]
+fn from_toml(value: toml::Value) -> Result<Node, String> {
+    Ok(match value {
+        toml::Value::Integer(integer) => Node::Integer(integer),
+        toml::Value::String(string) => Node::String(string),
+        toml::Value::Array(items) => {
+            Node::List(items.into_iter().map(from_toml).collect::<Result<_, _>>()?)
+        }
+        toml::Value::Table(table) => Node::Table(
+            table
+                .into_iter()
+                .map(|(name, value)| Ok((name, from_toml(value)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        other => return Err(format!("unexpected {} '{}'", other.type_str(), other)),
+    })
+}
+
+/// Converts a document tree into a YAML value.
+#[cfg(feature = "yaml")]
+fn into_yaml(node: Node) -> serde_yaml::Value {
+    match node {
+        Node::Integer(integer) => serde_yaml::Value::Number(integer.into()),
+        Node::String(string) => serde_yaml::Value::String(string),
+        Node::List(items) => {
+            serde_yaml::Value::Sequence(items.into_iter().map(into_yaml).collect())
+        }
+        Node::Table(table) => serde_yaml::Value::Mapping(
+            table
+                .into_iter()
+                .map(|(name, node)| (serde_yaml::Value::String(name), into_yaml(node)))
+                .collect(),
+        ),
+    }
+}
+
+/// Converts a YAML value into a document tree. Integers beyond an `i64` are kept as strings,
+/// as written for big REG_QWORD data.
+#[cfg(feature = "yaml")]
+fn from_yaml(value: serde_yaml::Value) -> Result<Node, String> {
+    Ok(match value {
+        serde_yaml::Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(integer), _) => Node::Integer(integer),
+            (None, Some(integer)) => Node::String(integer.to_string()),
+            _ => return Err(format!("unexpected number '{}'", number)),
+        },
+        serde_yaml::Value::String(string) => Node::String(string),
+        serde_yaml::Value::Sequence(items) => {
+            Node::List(items.into_iter().map(from_yaml).collect::<Result<_, _>>()?)
+        }
+        serde_yaml::Value::Mapping(mapping) => Node::Table(
+            mapping
+                .into_iter()
+                .map(|(name, value)| {
+                    let name = match name {
+                        serde_yaml::Value::String(name) => name,
+                        // Unquoted names such as `1` or `true` are read as scalars.
+                        serde_yaml::Value::Number(number) => number.to_string(),
+                        serde_yaml::Value::Bool(boolean) => boolean.to_string(),
+                        other => return Err(format!("unexpected name {:?}", other)),
+                    };
+                    Ok((name, from_yaml(value)?))
+                })
+                .collect::<Result<_, String>>()?,
+        ),
+        other => return Err(format!("unexpected {:?}", other)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> Registry {
+        let text = "REGEDIT4\n\n[HKEY_CURRENT_USER\\Software\\Wine\\Direct3D]\n\
+            @=\"default\"\n\"Renderer\"=\"vulkan\"\n\"Path\"=hex(2):25,00,00,00\n\
+            \"Level\"=dword:00000010\n\"Big\"=hex(b):ff,ff,ff,ff,ff,ff,ff,ff\n\
+            \"Paths\"=hex(7):61,00,00,00,62,00,00,00,00,00\n\"Blob\"=hex:00,01,ff\n\
+            \"None\"=hex(0):\n\n[HKEY_CURRENT_USER\\Software\\Empty]\n";
+        Registry::try_from_str(text, Hive::CurrentUser).unwrap()
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_round_trip() {
+        let registry = registry();
+        let text = registry.to_toml();
+        assert!(text.contains("[keys.Software.subkeys.Empty.values]"));
+        assert_eq!(Registry::from_toml(&text).unwrap().keys(), registry.keys());
+        assert!(Registry::from_toml(&text.replace("\"dword\"", "\"word\"")).is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_round_trip() {
+        let registry = registry();
+        let text = registry.to_yaml();
+        assert_eq!(Registry::from_yaml(&text).unwrap().keys(), registry.keys());
+        assert!(Registry::from_yaml("hive: HKEY_NOWHERE\n").is_err());
+    }
+}
@@ -0,0 +1,328 @@
+use crate::prelude::{DiffOptions, Error, Hive, Registry, RegistryDiff, Value};
+use crate::variables::expand;
+use regashii::ValueName;
+use std::collections::BTreeMap;
+
+/// The comment prefix declaring a template parameter.
+const DECLARATION: &str = ";@param";
+
+/// The type of a template [Parameter], checking the values given for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParameterKind {
+    /// Any text.
+    String,
+    /// A 32-bit unsigned integer, in decimal or `0x`-prefixed hexadecimal.
+    Dword,
+    /// A 64-bit unsigned integer, in decimal or `0x`-prefixed hexadecimal.
+    Qword,
+    /// `true` or `false`, stored as the dword `1` or `0`.
+    Bool,
+}
+
+impl ParameterKind {
+    /// Returns the kind by its name in declarations.
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "string" => Some(ParameterKind::String),
+            "dword" => Some(ParameterKind::Dword),
+            "qword" => Some(ParameterKind::Qword),
+            "bool" => Some(ParameterKind::Bool),
+            _ => None,
+        }
+    }
+
+    /// Parses a value of the kind.
+    ///
+    /// # Returns
+    ///
+    /// The value as substituted in text and, for numeric kinds, as an integer, or `None` if
+    /// it isn't a valid value of the kind.
+    fn parse(&self, value: &str) -> Option<(String, Option<u64>)> {
+        let integer = |value: &str| match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        };
+        let number = match self {
+            ParameterKind::String => return Some((value.to_string(), None)),
+            ParameterKind::Dword => integer(value).filter(|n| u32::try_from(*n).is_ok())?,
+            ParameterKind::Qword => integer(value)?,
+            ParameterKind::Bool => match value {
+                "true" => 1,
+                "false" => 0,
+                _ => return None,
+            },
+        };
+        Some((number.to_string(), Some(number)))
+    }
+}
+
+/// A parameter of a [Template], declared by a comment line such as
+/// `;@param RENDERER: string = gl`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Parameter {
+    /// The name of the parameter, as used in `${NAME}` placeholders.
+    name: String,
+    /// The type of the parameter.
+    kind: ParameterKind,
+    /// The value used when none is given, `None` for required parameters.
+    default: Option<String>,
+}
+
+impl Parameter {
+    /// Returns the name of the parameter, as used in `${NAME}` placeholders.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type of the parameter.
+    pub fn kind(&self) -> ParameterKind {
+        self.kind
+    }
+
+    /// Returns the value used when none is given, `None` for required parameters.
+    pub fn default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+}
+
+/// A baseline registry with `${NAME}` placeholders filled in by typed parameters, to build
+/// the registries expected of differently configured prefixes from a single file.
+///
+/// Parameters are declared by comment lines anywhere in the file, of the form
+/// `;@param NAME: TYPE` for required parameters or `;@param NAME: TYPE = DEFAULT`, where
+/// `TYPE` is `string`, `dword`, `qword` or `bool`. Placeholders are replaced in key names,
+/// value names and string data like [RegistryDiff::expand_variables] does. A string value
+/// made of a single placeholder of a numeric or boolean parameter, such as
+/// `"csmt"="${CSMT}"`, becomes a value of the type of the parameter.
+#[derive(Clone, Debug)]
+pub struct Template {
+    /// The registry holding the placeholders.
+    registry: Registry,
+    /// The declared parameters, in declaration order.
+    parameters: Vec<Parameter>,
+}
+
+impl Template {
+    /// Loads a template from the text of a registry file.
+    ///
+    /// # Returns
+    ///
+    /// The template, [Error::Template] if a parameter declaration is malformed or its default
+    /// isn't a valid value, or an [Error] if the registry can't be parsed.
+    pub fn try_from_str(text: &str, hive: Hive) -> Result<Self, Error> {
+        let mut parameters: Vec<Parameter> = Vec::new();
+        for line in text.lines() {
+            let declaration = match line.trim().strip_prefix(DECLARATION) {
+                Some(declaration) => declaration.trim(),
+                None => continue,
+            };
+            let invalid = || Error::Template {
+                message: format!("malformed parameter declaration '{}'", line.trim()),
+            };
+            let (name, rest) = declaration.split_once(':').ok_or_else(invalid)?;
+            let (kind, default) = match rest.split_once('=') {
+                Some((kind, default)) => (kind, Some(default.trim().to_string())),
+                None => (rest, None),
+            };
+            let name = name.trim().to_string();
+            let kind = ParameterKind::from_name(kind.trim()).ok_or_else(invalid)?;
+            if name.is_empty() || parameters.iter().any(|p| p.name == name) {
+                return Err(invalid());
+            }
+            if let Some(default) = &default {
+                kind.parse(default).ok_or_else(invalid)?;
+            }
+            parameters.push(Parameter {
+                name,
+                kind,
+                default,
+            });
+        }
+        Ok(Self {
+            registry: Registry::try_from_str(text, hive)?,
+            parameters,
+        })
+    }
+
+    /// Loads a template from a registry file.
+    ///
+    /// See [Template::try_from_str] for details.
+    #[cfg(feature = "fs")]
+    pub fn open<T: AsRef<std::path::Path>>(file: T, hive: Hive) -> Result<Self, Error> {
+        let bytes = std::fs::read(file)?;
+        let text = crate::prelude::Encoding::detect(&bytes).decode_borrowed(&bytes)?;
+        Self::try_from_str(&text, hive)
+    }
+
+    /// Returns the declared parameters, in declaration order.
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    /// Returns the registry holding the placeholders.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Builds the registry described by the template for the given parameter values.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The values of the parameters, by name. Parameters left out take their
+    ///   default value.
+    ///
+    /// # Returns
+    ///
+    /// The concrete registry, [Error::Template] if a value is invalid for its parameter, a
+    /// parameter is unknown or a required one is missing, or [Error::UndefinedVariable] if the
+    /// template uses an undeclared placeholder.
+    pub fn instantiate(&self, params: &BTreeMap<String, String>) -> Result<Registry, Error> {
+        if let Some(name) = params
+            .keys()
+            .find(|name| !self.parameters.iter().any(|p| &p.name == *name))
+        {
+            return Err(Error::Template {
+                message: format!("unknown parameter '{}'", name),
+            });
+        }
+        let mut text = BTreeMap::new();
+        let mut numbers = BTreeMap::new();
+        for parameter in &self.parameters {
+            let value = params
+                .get(&parameter.name)
+                .or(parameter.default.as_ref())
+                .ok_or_else(|| Error::Template {
+                    message: format!("missing required parameter '{}'", parameter.name),
+                })?;
+            let (value, number) = parameter.kind.parse(value).ok_or_else(|| Error::Template {
+                message: format!(
+                    "'{}' is not a valid {:?} for parameter '{}'",
+                    value, parameter.kind, parameter.name
+                ),
+            })?;
+            text.insert(parameter.name.clone(), value);
+            if let Some(number) = number {
+                numbers.insert(format!("${{{}}}", parameter.name), (parameter.kind, number));
+            }
+        }
+
+        let mut registry = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4),
+            self.registry.hive(),
+        );
+        for (path, key) in self.registry.keys() {
+            let undefined = |name| Error::UndefinedVariable {
+                name,
+                key: key.name().clone(),
+            };
+            let path = expand(path.raw(), &text).map_err(undefined)?;
+            let mut key = key.clone();
+            let values = std::mem::take(key.values_mut());
+            for value in values.into_values() {
+                let name = match value.name() {
+                    ValueName::Named(name) => {
+                        ValueName::Named(expand(name, &text).map_err(undefined)?)
+                    }
+                    ValueName::Default => ValueName::Default,
+                };
+                let data = match (value.raw_type(), value.value()) {
+                    (Some(raw_type), regashii::Value::Binary(bytes)) => {
+                        let value = Value::raw(name.clone(), raw_type, bytes.clone());
+                        key.values_mut().insert(name, value);
+                        continue;
+                    }
+                    (_, regashii::Value::Sz(data)) => match numbers.get(data.as_str()) {
+                        Some((ParameterKind::Qword, number)) => regashii::Value::Qword(*number),
+                        Some((_, number)) => regashii::Value::Dword(*number as u32),
+                        None => regashii::Value::Sz(expand(data, &text).map_err(undefined)?),
+                    },
+                    (_, regashii::Value::ExpandSz(data)) => {
+                        regashii::Value::ExpandSz(expand(data, &text).map_err(undefined)?)
+                    }
+                    (_, regashii::Value::MultiSz(data)) => regashii::Value::MultiSz(
+                        data.iter()
+                            .map(|data| expand(data, &text).map_err(undefined))
+                            .collect::<Result<_, _>>()?,
+                    ),
+                    (_, data) => data.clone(),
+                };
+                key.values_mut()
+                    .insert(name.clone(), Value::new(name, data));
+            }
+            registry.insert_key(&path, key);
+        }
+        Ok(registry)
+    }
+
+    /// Diffs an instantiated template against a registry, to see how a prefix departs from
+    /// the baseline expected of its configuration.
+    ///
+    /// # Returns
+    ///
+    /// The diff from the instantiated template to `registry`, or the error of
+    /// [Template::instantiate].
+    pub fn diff(
+        &self,
+        params: &BTreeMap<String, String>,
+        registry: &Registry,
+        options: &DiffOptions,
+    ) -> Result<RegistryDiff, Error> {
+        Ok(RegistryDiff::new(
+            &self.instantiate(params)?,
+            registry,
+            options,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regashii::KeyName;
+
+    #[test]
+    fn test_instantiate() {
+        let template = Template::try_from_str(
+            "REGEDIT4\n;@param USER: string\n;@param CSMT: bool = true\n\
+            ;@param SIZE: dword = 0x10\n\n\
+            [HKEY_CURRENT_USER\\Software\\${USER}]\n\"Home\"=\"C:\\\\users\\\\${USER}\"\n\
+            \"csmt\"=\"${CSMT}\"\n\"Size\"=\"${SIZE}\"\n\"Raw\"=hex:01\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        assert_eq!(template.parameters().len(), 3);
+        assert_eq!(template.parameters()[2].default(), Some("0x10"));
+
+        let params = BTreeMap::from([("USER".to_string(), "alice".to_string())]);
+        let registry = template.instantiate(&params).unwrap();
+        let key = registry
+            .key(&KeyName::new("Software\\alice"))
+            .expect("instantiated key");
+        assert_eq!(key.name().raw(), "HKEY_CURRENT_USER\\Software\\alice");
+        let value = |name: &str| key.values()[&ValueName::named(name)].value().clone();
+        assert_eq!(
+            value("Home"),
+            regashii::Value::Sz("C:\\users\\alice".to_string())
+        );
+        assert_eq!(value("csmt"), regashii::Value::Dword(1));
+        assert_eq!(value("Size"), regashii::Value::Dword(16));
+        assert_eq!(key.values().len(), 4);
+
+        assert!(template
+            .diff(&params, &registry, &DiffOptions::default())
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            template.instantiate(&BTreeMap::new()),
+            Err(Error::Template { .. })
+        ));
+        let invalid = BTreeMap::from([
+            ("USER".to_string(), "bob".to_string()),
+            ("SIZE".to_string(), "big".to_string()),
+        ]);
+        assert!(matches!(
+            template.instantiate(&invalid),
+            Err(Error::Template { .. })
+        ));
+    }
+}
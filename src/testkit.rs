@@ -0,0 +1,149 @@
+use crate::prelude::{compare_report, Registry, RegistryDiff};
+use std::path::Path;
+
+/// The environment variable rewriting golden files instead of comparing against them when
+/// set to `1`.
+const UPDATE_VARIABLE: &str = "UPDATE_GOLDEN";
+
+/// The number of differences listed when a registry doesn't match its golden file.
+const REPORTED: usize = 10;
+
+/// Asserts that a registry matches the one saved in a golden file, like snapshot testing
+/// libraries do for other data.
+///
+/// When the `UPDATE_GOLDEN` environment variable is `1`, the golden file is written from
+/// `registry` instead, to create it or accept a change. The file is loaded into the hive of
+/// `registry` and compared by keys and values, so formatting changes don't fail the test.
+///
+/// # Panics
+///
+/// If the golden file is missing or can't be loaded, or if the registries differ, with a
+/// message listing the first differences like [compare_report] does.
+#[track_caller]
+pub fn assert_golden<T: AsRef<Path>>(file: T, registry: &Registry) {
+    if let Err(message) = check_golden(file.as_ref(), registry, update()) {
+        panic!("{}", message);
+    }
+}
+
+/// Asserts that the patch of a diff matches the one saved in a golden file, such as the
+/// changes an installer is expected to make.
+///
+/// When the `UPDATE_GOLDEN` environment variable is `1`, the golden file is written from
+/// `diff` instead. Patches are compared line by line.
+///
+/// # Panics
+///
+/// If the golden file is missing or can't be read, or if the patches differ, with a message
+/// showing the first line that differs.
+#[track_caller]
+pub fn assert_golden_diff<T: AsRef<Path>>(file: T, diff: &RegistryDiff) {
+    if let Err(message) = check_golden_diff(file.as_ref(), diff, update()) {
+        panic!("{}", message);
+    }
+}
+
+/// Returns whether golden files are rewritten instead of compared against.
+fn update() -> bool {
+    std::env::var(UPDATE_VARIABLE).is_ok_and(|value| value == "1")
+}
+
+/// Compares a registry against a golden file, or rewrites the file if `update` is set.
+fn check_golden(file: &Path, registry: &Registry, update: bool) -> Result<(), String> {
+    if update {
+        let text = registry.serialize().map_err(|error| error.to_string())?;
+        return write(file, &text);
+    }
+    let golden =
+        Registry::open(file, registry.hive()).map_err(|error| missing(file, &error.to_string()))?;
+    match compare_report(&golden, registry, REPORTED) {
+        Some(report) => Err(format!("golden file '{}' {}", file.display(), report)),
+        None => Ok(()),
+    }
+}
+
+/// Compares the patch of a diff against a golden file, or rewrites the file if `update` is
+/// set.
+fn check_golden_diff(file: &Path, diff: &RegistryDiff, update: bool) -> Result<(), String> {
+    let patch = diff.serialize().map_err(|error| error.to_string())?;
+    if update {
+        return write(file, &patch);
+    }
+    let golden =
+        std::fs::read_to_string(file).map_err(|error| missing(file, &error.to_string()))?;
+    let mut expected = golden.lines();
+    let mut actual = patch.lines();
+    for line in 1.. {
+        match (expected.next(), actual.next()) {
+            (None, None) => return Ok(()),
+            (expected, actual) if expected == actual => {}
+            (expected, actual) => {
+                return Err(format!(
+                    "golden file '{}' differs at line {}:\n  expected: {}\n  actual:   {}",
+                    file.display(),
+                    line,
+                    expected.unwrap_or("(end of patch)"),
+                    actual.unwrap_or("(end of patch)")
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a golden file, creating its directory.
+fn write(file: &Path, contents: &str) -> Result<(), String> {
+    if let Some(directory) = file.parent() {
+        std::fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+    }
+    crate::atomic::write(file, contents.as_bytes())
+        .map_err(|error| format!("can't write golden file '{}': {}", file.display(), error))
+}
+
+/// Returns the message of a golden file that can't be loaded.
+fn missing(file: &Path, error: &str) -> String {
+    format!(
+        "can't load golden file '{}' ({}); run with {}=1 to create it",
+        file.display(),
+        error,
+        UPDATE_VARIABLE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{DiffOptions, Hive};
+
+    #[test]
+    fn test_golden() {
+        let dir = std::env::temp_dir().join(format!("regdiff-golden-{}", std::process::id()));
+        let old = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=\"a\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let new = Registry::try_from_str(
+            "REGEDIT4\n\n[HKEY_CURRENT_USER\\App]\n\"A\"=\"b\"\n",
+            Hive::CurrentUser,
+        )
+        .unwrap();
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::default());
+        let (registry_file, diff_file) = (dir.join("app.reg"), dir.join("app.patch.reg"));
+
+        let missing = check_golden(&registry_file, &old, false).unwrap_err();
+        assert!(missing.contains("UPDATE_GOLDEN=1"));
+        check_golden(&registry_file, &old, true).unwrap();
+        check_golden(&registry_file, &old, false).unwrap();
+        assert!(check_golden(&registry_file, &new, false)
+            .unwrap_err()
+            .contains("\"A\"=\"a\" -> \"A\"=\"b\""));
+
+        check_golden_diff(&diff_file, &diff, true).unwrap();
+        check_golden_diff(&diff_file, &diff, false).unwrap();
+        let reverted = RegistryDiff::new(&new, &old, &DiffOptions::default());
+        let mismatch = check_golden_diff(&diff_file, &reverted, false).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(mismatch.contains("expected: \"A\"=\"b\""));
+    }
+}
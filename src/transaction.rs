@@ -0,0 +1,170 @@
+use crate::prelude::{DiffOptions, Registry, RegistryDiff, Value};
+use regashii::{KeyName, ValueName};
+
+/// A group of modifications to a [Registry], applied all at once or not at all.
+///
+/// Modifications are staged on a copy of the registry, which [Transaction::registry] exposes,
+/// and only written back by [Transaction::commit]. Dropping the transaction without
+/// committing it, or calling [Transaction::rollback], discards them.
+///
+/// Like [Registry::key], keys are named by their path within the registry's hive.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    /// The registry the modifications are committed to.
+    registry: &'a mut Registry,
+    /// The registry with the modifications made so far.
+    staged: Registry,
+}
+
+impl Registry {
+    /// Starts a transaction grouping modifications to the registry.
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        let staged = self.clone();
+        Transaction {
+            registry: self,
+            staged,
+        }
+    }
+
+    /// Runs `f` in a transaction, committing its modifications if it succeeds and rolling them
+    /// back if it fails.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the result of `f` along with the diff of what changed, or the
+    /// error returned by `f`.
+    pub fn transact<T, E, F>(&mut self, f: F) -> Result<(T, RegistryDiff), E>
+    where
+        F: FnOnce(&mut Transaction) -> Result<T, E>,
+    {
+        let mut transaction = self.transaction();
+        let result = f(&mut transaction)?;
+        Ok((result, transaction.commit()))
+    }
+}
+
+impl Transaction<'_> {
+    /// Returns the registry with the modifications made so far.
+    pub fn registry(&self) -> &Registry {
+        &self.staged
+    }
+
+    /// Creates a key, if it doesn't exist yet.
+    pub fn create_key(&mut self, name: &KeyName) {
+        self.staged.key_mut(name.raw());
+    }
+
+    /// Deletes a key along with its subkeys, if it exists.
+    pub fn delete_key(&mut self, name: &KeyName) {
+        self.staged.remove_key(name.raw());
+    }
+
+    /// Sets a value of a key, creating the key if it doesn't exist yet.
+    pub fn set_value(&mut self, name: &KeyName, value: Value) {
+        self.staged
+            .key_mut(name.raw())
+            .values_mut()
+            .insert(value.name().clone(), value);
+    }
+
+    /// Deletes a value of a key, if it exists.
+    pub fn delete_value(&mut self, name: &KeyName, value: &ValueName) {
+        let exists = self
+            .staged
+            .keys()
+            .keys()
+            .any(|key| key.raw().eq_ignore_ascii_case(name.raw()));
+        if exists {
+            self.staged
+                .key_mut(name.raw())
+                .values_mut()
+                .retain(|value_name, _| value_name != value);
+        }
+    }
+
+    /// Applies the changes of a diff, as [Registry::apply] does.
+    pub fn apply(&mut self, diff: &RegistryDiff) {
+        self.staged.apply(diff);
+    }
+
+    /// Writes the modifications back to the registry.
+    ///
+    /// # Returns
+    ///
+    /// The diff between the registry before and after the transaction.
+    pub fn commit(self) -> RegistryDiff {
+        let diff = RegistryDiff::new(
+            self.registry,
+            &self.staged,
+            &DiffOptions::new().with_security(true),
+        );
+        *self.registry = self.staged;
+        diff
+    }
+
+    /// Discards the modifications, leaving the registry unchanged.
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RegistryBuilder;
+    use crate::prelude::{Hive, KeyChange};
+
+    #[test]
+    fn test_commit_returns_diff() {
+        let mut registry = RegistryBuilder::new(Hive::CurrentUser)
+            .with(
+                "Software\\App",
+                ValueName::named("Version"),
+                regashii::Value::Dword(1),
+            )
+            .build();
+        let mut transaction = registry.transaction();
+        transaction.set_value(
+            &KeyName::new("software\\app"),
+            Value::new(ValueName::named("Version"), regashii::Value::Dword(2)),
+        );
+        transaction.create_key(&KeyName::new("Software\\New"));
+        transaction.delete_value(&KeyName::new("Software\\Missing"), &ValueName::named("A"));
+        let diff = transaction.commit();
+
+        let changes: Vec<(&str, KeyChange)> = diff
+            .keys()
+            .values()
+            .map(|key| (key.name().raw(), key.change()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("HKEY_CURRENT_USER\\Software\\App", KeyChange::Modified),
+                ("HKEY_CURRENT_USER\\Software\\New", KeyChange::Added),
+            ]
+        );
+        let version = &registry
+            .key(&KeyName::new("Software\\App"))
+            .unwrap()
+            .values()[&ValueName::named("Version")];
+        assert_eq!(version.value(), &regashii::Value::Dword(2));
+    }
+
+    #[test]
+    fn test_rollback_on_error() {
+        let mut registry = RegistryBuilder::new(Hive::CurrentUser)
+            .with(
+                "Software\\App",
+                ValueName::named("Version"),
+                regashii::Value::Dword(1),
+            )
+            .build();
+        let result: Result<((), RegistryDiff), &str> = registry.transact(|transaction| {
+            transaction.delete_key(&KeyName::new("Software\\App"));
+            assert!(transaction.registry().keys().is_empty());
+            Err("failed")
+        });
+
+        assert_eq!(result.unwrap_err(), "failed");
+        assert!(registry.key(&KeyName::new("Software\\App")).is_some());
+    }
+}
@@ -0,0 +1,401 @@
+use crate::names::same_value_name;
+use crate::prelude::{KeyChange, KeyPattern, Operation, Registry, RegistryDiff, Value, ValueKind};
+use regashii::{KeyName, ValueName};
+
+/// A single expectation of a [Schema].
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Rule {
+    /// At least one key matches the pattern.
+    RequiredKey(KeyPattern),
+    /// Every key matching the pattern holds the value.
+    RequiredValue(KeyPattern, ValueName),
+    /// The value, where present in keys matching the pattern, is of one of the kinds.
+    ValueKinds(KeyPattern, ValueName, Vec<ValueKind>),
+    /// The numeric value, where present in keys matching the pattern, lies within the range.
+    ValueRange(KeyPattern, ValueName, u64, u64),
+}
+
+/// The expected structure of a registry, such as the keys and values of a known-good prefix.
+///
+/// Rules are built up with the `with_*` methods and checked with [Schema::validate] or
+/// [Schema::validate_diff]. Key patterns are matched against full key names, and value names
+/// are compared case-insensitively like the registry does.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schema {
+    /// The rules, in the order they were added.
+    rules: Vec<Rule>,
+}
+
+/// How a registry or diff fails to match a [Schema].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// No key matches a required key pattern.
+    MissingKey,
+    /// A key lacks a required value, or a diff deletes it.
+    MissingValue,
+    /// A value has a type that isn't allowed.
+    UnexpectedKind {
+        /// The type of the value.
+        found: ValueKind,
+    },
+    /// A numeric value lies outside of the allowed range.
+    OutOfRange {
+        /// The value.
+        found: u64,
+    },
+}
+
+/// A failed expectation of a [Schema].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// The full name of the offending key, or the pattern of a missing key.
+    key: KeyName,
+    /// The name of the offending value, for value rules.
+    value: Option<ValueName>,
+    /// How the expectation failed.
+    kind: ViolationKind,
+}
+
+impl Violation {
+    /// Returns the full name of the offending key, or the pattern of a missing key.
+    pub fn key(&self) -> &KeyName {
+        &self.key
+    }
+
+    /// Returns the name of the offending value, for value rules.
+    pub fn value(&self) -> Option<&ValueName> {
+        self.value.as_ref()
+    }
+
+    /// Returns how the expectation failed.
+    pub fn kind(&self) -> &ViolationKind {
+        &self.kind
+    }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let value = match &self.value {
+            Some(ValueName::Named(name)) => format!(" value '{}'", name),
+            Some(ValueName::Default) => " default value".to_string(),
+            None => String::new(),
+        };
+        match &self.kind {
+            ViolationKind::MissingKey => write!(f, "missing key '{}'", self.key.raw()),
+            ViolationKind::MissingValue => {
+                write!(f, "key '{}' is missing{}", self.key.raw(), value)
+            }
+            ViolationKind::UnexpectedKind { found } => write!(
+                f,
+                "key '{}'{} has unexpected type {:?}",
+                self.key.raw(),
+                value,
+                found
+            ),
+            ViolationKind::OutOfRange { found } => write!(
+                f,
+                "key '{}'{} is out of range: {}",
+                self.key.raw(),
+                value,
+                found
+            ),
+        }
+    }
+}
+
+impl Schema {
+    /// Constructs a new, empty [Schema].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the schema requiring at least one key matching `pattern`.
+    pub fn with_required_key<P: Into<KeyPattern>>(mut self, pattern: P) -> Self {
+        self.rules.push(Rule::RequiredKey(pattern.into()));
+        self
+    }
+
+    /// Returns the schema requiring every key matching `pattern` to hold the value `name`.
+    pub fn with_required_value<P: Into<KeyPattern>>(mut self, pattern: P, name: ValueName) -> Self {
+        self.rules.push(Rule::RequiredValue(pattern.into(), name));
+        self
+    }
+
+    /// Returns the schema restricting the value `name` of keys matching `pattern` to the given kinds.
+    pub fn with_value_kinds<P: Into<KeyPattern>>(
+        mut self,
+        pattern: P,
+        name: ValueName,
+        kinds: &[ValueKind],
+    ) -> Self {
+        self.rules
+            .push(Rule::ValueKinds(pattern.into(), name, kinds.to_vec()));
+        self
+    }
+
+    /// Returns the schema restricting the `Dword` or `Qword` value `name` of keys matching
+    /// `pattern` to the inclusive range from `min` to `max`.
+    pub fn with_value_range<P: Into<KeyPattern>>(
+        mut self,
+        pattern: P,
+        name: ValueName,
+        min: u64,
+        max: u64,
+    ) -> Self {
+        self.rules
+            .push(Rule::ValueRange(pattern.into(), name, min, max));
+        self
+    }
+
+    /// Checks a registry against the schema.
+    ///
+    /// # Returns
+    ///
+    /// The violations found, in rule order. An empty list means the registry matches.
+    pub fn validate(&self, registry: &Registry) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            match rule {
+                Rule::RequiredKey(pattern) => {
+                    if !registry
+                        .keys()
+                        .values()
+                        .any(|key| pattern.matches(key.name()))
+                    {
+                        violations.push(Violation {
+                            key: KeyName::new(pattern.raw()),
+                            value: None,
+                            kind: ViolationKind::MissingKey,
+                        });
+                    }
+                }
+                Rule::RequiredValue(pattern, name) => {
+                    for key in registry.keys().values() {
+                        if pattern.matches(key.name())
+                            && find(key.values().values(), name).is_none()
+                        {
+                            violations.push(Violation {
+                                key: key.name().clone(),
+                                value: Some(name.clone()),
+                                kind: ViolationKind::MissingValue,
+                            });
+                        }
+                    }
+                }
+                Rule::ValueKinds(pattern, name, _) | Rule::ValueRange(pattern, name, _, _) => {
+                    for key in registry.keys().values() {
+                        if !pattern.matches(key.name()) {
+                            continue;
+                        }
+                        if let Some(value) = find(key.values().values(), name) {
+                            violations.extend(check(rule, key.name(), value));
+                        }
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    /// Checks the changes of a diff against the schema, without access to the full registry.
+    ///
+    /// Added and modified values are checked against the type and range rules, and deleting
+    /// a required key or value is a violation.
+    ///
+    /// # Returns
+    ///
+    /// The violations found, in rule order.
+    pub fn validate_diff(&self, diff: &RegistryDiff) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            for key in diff.keys().values() {
+                match rule {
+                    Rule::RequiredKey(pattern) => {
+                        if key.change() == KeyChange::Deleted && pattern.matches(key.name()) {
+                            violations.push(Violation {
+                                key: key.name().clone(),
+                                value: None,
+                                kind: ViolationKind::MissingKey,
+                            });
+                        }
+                    }
+                    Rule::RequiredValue(pattern, name) => {
+                        if !pattern.matches(key.name()) {
+                            continue;
+                        }
+                        let deleted = key.change() == KeyChange::Deleted
+                            || key.values().iter().any(|(value_name, operation)| {
+                                same_value_name(value_name, name)
+                                    && matches!(operation, Operation::Delete { .. })
+                            });
+                        if deleted {
+                            violations.push(Violation {
+                                key: key.name().clone(),
+                                value: Some(name.clone()),
+                                kind: ViolationKind::MissingValue,
+                            });
+                        }
+                    }
+                    Rule::ValueKinds(pattern, name, _) | Rule::ValueRange(pattern, name, _, _) => {
+                        if !pattern.matches(key.name()) {
+                            continue;
+                        }
+                        let written =
+                            key.values()
+                                .values()
+                                .filter_map(|operation| match operation {
+                                    Operation::Add { data }
+                                    | Operation::Modify { new_data: data, .. } => Some(data),
+                                    _ => None,
+                                });
+                        if let Some(value) = find(written, name) {
+                            violations.extend(check(rule, key.name(), value));
+                        }
+                    }
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// Returns the value with the given name.
+fn find<'a, I: IntoIterator<Item = &'a Value>>(values: I, name: &ValueName) -> Option<&'a Value> {
+    values
+        .into_iter()
+        .find(|value| same_value_name(value.name(), name))
+}
+
+/// Checks a value against a type or range rule.
+fn check(rule: &Rule, key: &KeyName, value: &Value) -> Option<Violation> {
+    let kind = match rule {
+        Rule::ValueKinds(_, _, kinds) if !kinds.contains(&value.kind()) => {
+            ViolationKind::UnexpectedKind {
+                found: value.kind(),
+            }
+        }
+        Rule::ValueRange(_, _, min, max) => {
+            let found = match value.value() {
+                regashii::Value::Dword(dword) => *dword as u64,
+                regashii::Value::Qword(qword) => *qword,
+                _ => return None,
+            };
+            if (*min..=*max).contains(&found) {
+                return None;
+            }
+            ViolationKind::OutOfRange { found }
+        }
+        _ => return None,
+    };
+
+    Some(Violation {
+        key: key.clone(),
+        value: Some(value.name().clone()),
+        kind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{DiffOptions, Hive};
+
+    #[cfg(feature = "fs")]
+    fn schema() -> Schema {
+        Schema::new()
+            .with_required_key("HKEY_CURRENT_USER\\Software\\Wine\\X11 Driver")
+            .with_required_key("HKEY_CURRENT_USER\\Software\\Missing")
+            .with_required_value(
+                "HKEY_CURRENT_USER\\Software\\Wine\\X11 Driver",
+                ValueName::named("Decorated"),
+            )
+            .with_value_kinds(
+                "HKEY_CURRENT_USER\\Control Panel\\Desktop",
+                ValueName::named("FontSmoothing"),
+                &[ValueKind::Dword],
+            )
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_validate_registry() {
+        let registry = Registry::open("./registries/user.reg", Hive::CurrentUser).unwrap();
+        let violations = schema().validate(&registry);
+
+        let found: Vec<(&str, &ViolationKind)> = violations
+            .iter()
+            .map(|violation| (violation.key().raw(), violation.kind()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "HKEY_CURRENT_USER\\Software\\Missing",
+                    &ViolationKind::MissingKey
+                ),
+                (
+                    "HKEY_CURRENT_USER\\Software\\Wine\\X11 Driver",
+                    &ViolationKind::MissingValue
+                ),
+                (
+                    "HKEY_CURRENT_USER\\Control Panel\\Desktop",
+                    &ViolationKind::UnexpectedKind {
+                        found: ValueKind::Sz
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "key 'HKEY_CURRENT_USER\\Software\\Wine\\X11 Driver' is missing value 'Decorated'"
+        );
+    }
+
+    #[test]
+    fn test_validate_diff_ranges_and_deletions() {
+        let old = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4).with(
+                KeyName::new("Software\\Test"),
+                regashii::Key::new()
+                    .with(ValueName::named("Level"), regashii::Value::Dword(1))
+                    .with(
+                        ValueName::named("Name"),
+                        regashii::Value::Sz("a".to_string()),
+                    ),
+            ),
+            Hive::CurrentUser,
+        );
+        let new = Registry::from_regashii(
+            regashii::Registry::new(regashii::Format::Regedit4).with(
+                KeyName::new("Software\\Test"),
+                regashii::Key::new().with(ValueName::named("level"), regashii::Value::Dword(9)),
+            ),
+            Hive::CurrentUser,
+        );
+        let schema = Schema::new()
+            .with_required_value(
+                "HKEY_CURRENT_USER\\Software\\Test",
+                ValueName::named("Name"),
+            )
+            .with_value_range(
+                "HKEY_CURRENT_USER\\Software\\*",
+                ValueName::named("Level"),
+                0,
+                5,
+            );
+
+        let diff = RegistryDiff::new(&old, &new, &DiffOptions::new());
+        let kinds: Vec<ViolationKind> = schema
+            .validate_diff(&diff)
+            .into_iter()
+            .map(|violation| violation.kind().clone())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ViolationKind::MissingValue,
+                ViolationKind::OutOfRange { found: 9 }
+            ]
+        );
+    }
+}
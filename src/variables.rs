@@ -0,0 +1,180 @@
+use crate::prelude::{Error, KeyDiff, Operation, Registry, RegistryDiff, Value};
+use std::collections::BTreeMap;
+
+impl RegistryDiff {
+    /// Replaces the `${NAME}` placeholders in the new data of string values with their value
+    /// in `variables`, so a single patch template can be applied to many prefixes with
+    /// user-specific paths, such as `${USERNAME}` or `${PREFIX_DRIVE}`.
+    ///
+    /// Placeholders are expanded in REG_SZ, REG_EXPAND_SZ and REG_MULTI_SZ data; key names,
+    /// value names and other data are left alone. `$${` is written as a literal `${`, and a
+    /// `${` without a closing brace is kept as-is.
+    ///
+    /// # Returns
+    ///
+    /// The expanded diff, or [Error::UndefinedVariable] if a placeholder has no value.
+    pub fn expand_variables(&self, variables: &BTreeMap<String, String>) -> Result<Self, Error> {
+        self.keys()
+            .values()
+            .map(|key| {
+                let values = key
+                    .values()
+                    .iter()
+                    .map(|(name, operation)| {
+                        let operation = match operation {
+                            Operation::Add { data } => Operation::Add {
+                                data: expand_value(data, variables, key)?,
+                            },
+                            Operation::Modify { old_data, new_data } => Operation::Modify {
+                                old_data: old_data.clone(),
+                                new_data: expand_value(new_data, variables, key)?,
+                            },
+                            other => other.clone(),
+                        };
+                        Ok((name.clone(), operation))
+                    })
+                    .collect::<Result<_, Error>>()?;
+                Ok(KeyDiff::from_parts(
+                    key.name().clone(),
+                    key.change(),
+                    values,
+                    key.security().cloned(),
+                    key.link().cloned(),
+                ))
+            })
+            .collect::<Result<Self, Error>>()
+            .map(|diff| diff.with_metadata(self.metadata().clone()))
+    }
+}
+
+impl Registry {
+    /// Applies a patch template to the registry, expanding its placeholders with `variables`
+    /// first.
+    ///
+    /// See [RegistryDiff::expand_variables] for the placeholder syntax and [Registry::apply]
+    /// for how changes are applied.
+    ///
+    /// # Returns
+    ///
+    /// The undo diff, or [Error::UndefinedVariable] without changing the registry if a
+    /// placeholder has no value.
+    pub fn apply_with_variables(
+        &mut self,
+        diff: &RegistryDiff,
+        variables: &BTreeMap<String, String>,
+    ) -> Result<RegistryDiff, Error> {
+        Ok(self.apply(&diff.expand_variables(variables)?))
+    }
+}
+
+/// Expands the placeholders in the string data of a value. Raw values are left alone.
+fn expand_value(
+    value: &Value,
+    variables: &BTreeMap<String, String>,
+    key: &KeyDiff,
+) -> Result<Value, Error> {
+    let expand = |text: &str| {
+        expand(text, variables).map_err(|name| Error::UndefinedVariable {
+            name,
+            key: key.name().clone(),
+        })
+    };
+    let data = match (value.raw_type(), value.value()) {
+        (Some(_), _) => return Ok(value.clone()),
+        (_, regashii::Value::Sz(text)) => regashii::Value::Sz(expand(text)?),
+        (_, regashii::Value::ExpandSz(text)) => regashii::Value::ExpandSz(expand(text)?),
+        (_, regashii::Value::MultiSz(texts)) => regashii::Value::MultiSz(
+            texts
+                .iter()
+                .map(|text| expand(text))
+                .collect::<Result<_, _>>()?,
+        ),
+        _ => return Ok(value.clone()),
+    };
+    Ok(Value::new(value.name().clone(), data))
+}
+
+/// Expands the `${NAME}` placeholders of a text.
+///
+/// # Returns
+///
+/// The expanded text, or the name of the first placeholder missing from `variables`.
+pub(crate) fn expand(text: &str, variables: &BTreeMap<String, String>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            expanded.push_str(&rest[..start]);
+            expanded.push('{');
+            rest = &rest[start + 2..];
+            continue;
+        }
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let name = &rest[start + 2..end];
+        let value = variables.get(name).ok_or_else(|| name.to_string())?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(value);
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RegistryBuilder;
+    use crate::prelude::{DiffOptions, Hive};
+    use regashii::ValueName;
+
+    #[test]
+    fn test_expand() {
+        let variables = BTreeMap::from([("USERNAME".to_string(), "alice".to_string())]);
+        assert_eq!(
+            expand("C:\\users\\${USERNAME}\\${USERNAME}", &variables).unwrap(),
+            "C:\\users\\alice\\alice"
+        );
+        assert_eq!(
+            expand("$${USERNAME} ${", &variables).unwrap(),
+            "${USERNAME} ${"
+        );
+        assert_eq!(expand("${HOME}", &variables).unwrap_err(), "HOME");
+    }
+
+    #[test]
+    fn test_apply_with_variables() {
+        let registry = |home: &str| {
+            RegistryBuilder::new(Hive::CurrentUser)
+                .with_sz("Software\\App", "Home", home)
+                .with(
+                    "Software\\App",
+                    ValueName::named("Level"),
+                    regashii::Value::Dword(1),
+                )
+                .build()
+        };
+        let template = RegistryDiff::new(
+            &registry("C:\\users\\nobody"),
+            &registry("${PREFIX_DRIVE}:\\users\\${USERNAME}"),
+            &DiffOptions::new(),
+        );
+
+        let mut target = registry("C:\\users\\nobody");
+        let variables = BTreeMap::from([
+            ("PREFIX_DRIVE".to_string(), "D".to_string()),
+            ("USERNAME".to_string(), "alice".to_string()),
+        ]);
+        target.apply_with_variables(&template, &variables).unwrap();
+        assert_eq!(target.keys(), registry("D:\\users\\alice").keys());
+
+        let mut untouched = registry("C:\\users\\nobody");
+        match untouched.apply_with_variables(&template, &BTreeMap::new()) {
+            Err(Error::UndefinedVariable { name, .. }) => assert_eq!(name, "PREFIX_DRIVE"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(untouched.keys(), registry("C:\\users\\nobody").keys());
+    }
+}
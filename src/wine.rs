@@ -0,0 +1,275 @@
+use crate::names::same_value_name;
+use crate::prelude::{
+    create_backup, BackupPolicy, DiffOptions, Error, Hive, LockPolicy, Prefix, PrefixDiff,
+    PrefixLock, Registry,
+};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers the patches imported by the process, so concurrent imports each write their own
+/// temporary file.
+static IMPORTS: AtomicUsize = AtomicUsize::new(0);
+
+/// The output of a successful `wine regedit` run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WineOutput {
+    /// What regedit wrote to its standard output.
+    stdout: String,
+    /// What regedit wrote to its standard error, usually Wine debug messages.
+    stderr: String,
+    /// The diff reverting the import.
+    undo: PrefixDiff,
+}
+
+impl WineOutput {
+    /// Returns what regedit wrote to its standard output.
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// Returns what regedit wrote to its standard error.
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    /// Returns the diff reverting the import, from the registry files before and after it.
+    ///
+    /// Its [PrefixDiff::to_patch] can be imported in turn to restore the prefix.
+    pub fn undo(&self) -> &PrefixDiff {
+        &self.undo
+    }
+}
+
+/// Imports a patch into a Wine prefix with `wine regedit /S`.
+///
+/// The patch is written to a temporary file inside the prefix, imported, and the prefix's
+/// registry files are read back with [Prefix::open] once `wineserver` has flushed them to
+/// verify that every change of the patch was applied. Only keys of the hives stored in the
+/// prefix are verified. The registry files are also read beforehand, so the returned output
+/// holds the diff undoing the import.
+///
+/// # Arguments
+///
+/// * `prefix` - The path of the Wine prefix, as used for `WINEPREFIX`.
+/// * `patch` - The patch to import, e.g. from [Registry::diff](crate::prelude::Diff::diff).
+///
+/// # Returns
+///
+/// A `Result` containing the output of regedit, an error if the prefix holds no registry files,
+/// [Error::Wine] if regedit failed, or
+/// [Error::NotApplied] if the prefix doesn't reflect the patch afterwards.
+///
+/// With the `tracing` feature, the verification of every key is recorded as an event of the
+/// `regdiff::apply` target, like the changes of [Registry::apply].
+///
+/// Wine rewrites the registry files of the prefix, so they are backed up beforehand according
+/// to the default [BackupPolicy], and the prefix is locked exclusively according to the
+/// default [LockPolicy] for the whole import. See [apply_with_wine_with] to choose how.
+pub fn apply_with_wine<P: AsRef<Path>>(
+    prefix: P,
+    patch: &regashii::Registry,
+) -> Result<WineOutput, Error> {
+    apply_with_wine_with(
+        prefix,
+        patch,
+        &BackupPolicy::default(),
+        &LockPolicy::default(),
+    )
+}
+
+/// Imports a patch into a Wine prefix with `wine regedit /S` like [apply_with_wine], locking
+/// the prefix according to `lock` and backing up its registry files according to `backups`
+/// first.
+///
+/// # Arguments
+///
+/// * `prefix` - The path of the Wine prefix, as used for `WINEPREFIX`.
+/// * `patch` - The patch to import.
+/// * `backups` - Where the registry files are backed up and which backups are kept.
+/// * `lock` - How long to wait for the prefix to be free, including for a wineserver already
+///   running in it to exit.
+///
+/// # Returns
+///
+/// The same as [apply_with_wine], [Error::Locked] if the prefix stayed busy, or an error if a
+/// registry file couldn't be backed up, in which case regedit isn't run.
+pub fn apply_with_wine_with<P: AsRef<Path>>(
+    prefix: P,
+    patch: &regashii::Registry,
+    backups: &BackupPolicy,
+    lock: &LockPolicy,
+) -> Result<WineOutput, Error> {
+    let prefix = prefix.as_ref();
+    let _lock = PrefixLock::exclusive(prefix, lock)?;
+    let before = Prefix::open(prefix)?;
+    for file in before.hives().values() {
+        create_backup(file.path(), backups)?;
+    }
+
+    // regedit treats arguments starting with a slash as switches, so the file is placed
+    // where it has a DOS path.
+    let temp = prefix.join("drive_c").join("windows").join("temp");
+    std::fs::create_dir_all(&temp)?;
+    let file_name = loop {
+        let file_name = format!(
+            "regdiff-{}-{}.reg",
+            std::process::id(),
+            IMPORTS.fetch_add(1, Ordering::Relaxed)
+        );
+        // Reserve the name, so a file left behind by another process is never overwritten.
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(temp.join(&file_name))
+        {
+            Ok(_) => break file_name,
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error.into()),
+        }
+    };
+    if let Err(error) = patch.serialize_file(temp.join(&file_name)) {
+        let _ = std::fs::remove_file(temp.join(&file_name));
+        return Err(std::io::Error::other(error).into());
+    }
+
+    let output = Command::new("wine")
+        .arg("regedit")
+        .arg("/S")
+        .arg(format!("C:\\windows\\temp\\{}", file_name))
+        .env("WINEPREFIX", prefix)
+        .output();
+    let _ = std::fs::remove_file(temp.join(&file_name));
+    let output = output?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.status.success() {
+        return Err(Error::Wine {
+            status: output.status.code(),
+            stderr,
+        });
+    }
+
+    // The registry files are only written once the wineserver shuts down.
+    Command::new("wineserver")
+        .arg("-w")
+        .env("WINEPREFIX", prefix)
+        .status()?;
+
+    let after = Prefix::open(prefix)?;
+    verify(patch, |hive| after.registry(hive))?;
+
+    let undo = Prefix::diff_with(&after, &before, &DiffOptions::new().with_security(true));
+    Ok(WineOutput {
+        stdout,
+        stderr,
+        undo,
+    })
+}
+
+/// Checks that the registries of a prefix reflect every change of a patch, matching key and
+/// value names case-insensitively like Wine does.
+fn verify<'a, F: Fn(Hive) -> Option<&'a Registry>>(
+    patch: &regashii::Registry,
+    registry: F,
+) -> Result<(), Error> {
+    for (name, key) in patch.keys() {
+        let (registry, path) = match Hive::split(name.raw()) {
+            Some((hive, path)) => match registry(hive) {
+                Some(registry) => (registry, path),
+                None => continue,
+            },
+            None => continue,
+        };
+        let current = registry.key_ignoring_case(path);
+
+        let applied = match (key.kind(), current) {
+            (regashii::KeyKind::Delete, current) => current.is_none(),
+            (_, None) => key
+                .values()
+                .values()
+                .all(|value| *value == regashii::Value::Delete),
+            (_, Some(current)) => key.values().iter().all(|(value_name, value)| {
+                let stored = current
+                    .values()
+                    .iter()
+                    .find(|(name, _)| same_value_name(name, value_name))
+                    .map(|(_, stored)| stored);
+                match (value, stored) {
+                    (regashii::Value::Delete, current) => current.is_none(),
+                    (value, Some(current)) => current.value() == value,
+                    (_, None) => false,
+                }
+            }),
+        };
+
+        event!(
+            INFO,
+            target: "regdiff::apply",
+            key = name.raw(),
+            op = "import_key",
+            outcome = if applied { "applied" } else { "not_applied" },
+        );
+        if !applied {
+            return Err(Error::NotApplied { name: name.clone() });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::RegistryBuilder;
+    use regashii::{KeyName, ValueName};
+
+    #[test]
+    fn test_verify_detects_missing_changes() {
+        let user = RegistryBuilder::new(Hive::CurrentUser)
+            .with(
+                "Software\\Wine",
+                ValueName::named("Version"),
+                regashii::Value::Dword(10),
+            )
+            .build();
+        let system = RegistryBuilder::new(Hive::LocalMachine).build();
+        let registry = |hive| match hive {
+            Hive::CurrentUser => Some(&user),
+            Hive::LocalMachine => Some(&system),
+            Hive::DefaultUser => None,
+        };
+
+        let applied = regashii::Registry::new(regashii::Format::Regedit4)
+            .with(
+                KeyName::new("HKEY_CURRENT_USER\\Software\\Wine"),
+                regashii::Key::new()
+                    .with(ValueName::named("Version"), regashii::Value::Dword(10))
+                    .with(ValueName::named("Old"), regashii::Value::Delete),
+            )
+            .with(
+                KeyName::new("HKEY_LOCAL_MACHINE\\Software\\Gone"),
+                regashii::Key::deleted(),
+            );
+        assert!(verify(&applied, registry).is_ok());
+
+        let renamed = regashii::Registry::new(regashii::Format::Regedit4).with(
+            KeyName::new("HKEY_CURRENT_USER\\SOFTWARE\\wine"),
+            regashii::Key::new()
+                .with(ValueName::named("version"), regashii::Value::Dword(10))
+                .with(ValueName::named("old"), regashii::Value::Delete),
+        );
+        assert!(verify(&renamed, registry).is_ok());
+
+        let missing = regashii::Registry::new(regashii::Format::Regedit4).with(
+            KeyName::new("HKEY_CURRENT_USER\\Software\\Wine"),
+            regashii::Key::new().with(ValueName::named("Version"), regashii::Value::Dword(11)),
+        );
+        assert!(matches!(
+            verify(&missing, registry),
+            Err(Error::NotApplied { .. })
+        ));
+    }
+}
@@ -0,0 +1,594 @@
+use crate::prelude::{Error, SecurityDescriptor, Value};
+use crate::registry::type_code;
+use regashii::{KeyName, ValueName};
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+/// The header of the registry files written by this crate.
+pub(crate) const HEADER: &str = "Windows Registry Editor Version 5.00";
+
+/// The abbreviations of the root keys, as accepted by `reg` and other tools.
+const ABBREVIATIONS: [(&str, &str); 5] = [
+    ("HKEY_LOCAL_MACHINE", "HKLM"),
+    ("HKEY_CURRENT_USER", "HKCU"),
+    ("HKEY_USERS", "HKU"),
+    ("HKEY_CLASSES_ROOT", "HKCR"),
+    ("HKEY_CURRENT_CONFIG", "HKCC"),
+];
+
+/// How root keys are named in generated patches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HiveNames {
+    /// Full names, such as `HKEY_CURRENT_USER`, as regedit writes them.
+    #[default]
+    Full,
+    /// Abbreviated names, such as `HKCU`.
+    Abbreviated,
+}
+
+/// The order keys and values are written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum KeyOrder {
+    /// The order of the names' bytes, as the diff holds them.
+    #[default]
+    Ordinal,
+    /// The order regedit displays them in: component by component, case-insensitively and
+    /// regardless of the locale, so `Software\a` comes before `Software\B` and
+    /// `Software\A\x` before `Software\A B`.
+    Registry,
+}
+
+/// Options controlling how patches are written.
+#[derive(Clone, Debug)]
+pub struct WriteOptions {
+    /// How root keys are named.
+    hive_names: HiveNames,
+    /// Whether backslashes in quoted names and strings are doubled.
+    escape_backslashes: bool,
+    /// The order keys and values are written in.
+    order: KeyOrder,
+    /// The width hex data is wrapped at, if any.
+    line_width: Option<usize>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            hive_names: HiveNames::Full,
+            escape_backslashes: true,
+            order: KeyOrder::Ordinal,
+            line_width: None,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Constructs a new [WriteOptions] writing patches the way regedit does.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how root keys are named in key headers.
+    pub fn with_hive_names(mut self, names: HiveNames) -> Self {
+        self.hive_names = names;
+        self
+    }
+
+    /// Returns how root keys are named in key headers.
+    pub fn hive_names(&self) -> HiveNames {
+        self.hive_names
+    }
+
+    /// Sets whether backslashes in quoted value names and string data are doubled, as regedit
+    /// and Wine expect. Quotes are escaped either way.
+    ///
+    /// Disabling this writes a string such as `C:\windows` as `"C:\windows"` rather than
+    /// `"C:\\windows"`, for tools reading quoted strings verbatim. Backslashes right before a
+    /// quote or the end of the string are still doubled, so `C:\` is written as `"C:\\"`.
+    pub fn with_backslash_escaping(mut self, escape: bool) -> Self {
+        self.escape_backslashes = escape;
+        self
+    }
+
+    /// Returns whether backslashes in quoted value names and string data are doubled.
+    pub fn backslash_escaping(&self) -> bool {
+        self.escape_backslashes
+    }
+
+    /// Sets the order keys and values are written in.
+    ///
+    /// A [PatchWriter](crate::prelude::PatchWriter) writes keys in the order it is given them,
+    /// and only orders the values of each key.
+    pub fn with_key_order(mut self, order: KeyOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Returns the order keys and values are written in.
+    pub fn key_order(&self) -> KeyOrder {
+        self.order
+    }
+
+    /// Sets the width hex data lines are wrapped at with continuation backslashes, or `None`
+    /// to write each value on a single line.
+    ///
+    /// Lines are broken after the comma bringing them to `width - 3` characters, and
+    /// continuation lines are indented by two spaces. A width of 80 matches the exports of
+    /// Wine's regedit, so generated patches diff cleanly against them.
+    pub fn with_line_width(mut self, width: Option<usize>) -> Self {
+        self.line_width = width;
+        self
+    }
+
+    /// Returns the width hex data lines are wrapped at, if any.
+    pub fn line_width(&self) -> Option<usize> {
+        self.line_width
+    }
+
+    /// Sorts items by their key names according to the requested order. Items are assumed
+    /// to be in ordinal order already.
+    pub(crate) fn sort_keys<T>(&self, items: &mut [T], name: impl Fn(&T) -> &KeyName) {
+        if self.order == KeyOrder::Registry {
+            items.sort_by(|a, b| registry_order(name(a).raw(), name(b).raw()));
+        }
+    }
+
+    /// Sorts items by their value names according to the requested order. Items are assumed
+    /// to be in ordinal order already.
+    pub(crate) fn sort_values<T>(&self, items: &mut [T], name: impl Fn(&T) -> &ValueName) {
+        if self.order == KeyOrder::Registry {
+            items.sort_by(|a, b| match (name(a), name(b)) {
+                (ValueName::Default, ValueName::Default) => Ordering::Equal,
+                (ValueName::Default, _) => Ordering::Less,
+                (_, ValueName::Default) => Ordering::Greater,
+                (ValueName::Named(a), ValueName::Named(b)) => {
+                    a.to_uppercase().cmp(&b.to_uppercase())
+                }
+            });
+        }
+    }
+
+    /// Returns a key name with its root key named as requested.
+    fn key_name<'a>(&self, name: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.hive_names == HiveNames::Full {
+            return name.into();
+        }
+        let (root, path) = match name.split_once('\\') {
+            Some((root, path)) => (root, Some(path)),
+            None => (name, None),
+        };
+        match ABBREVIATIONS
+            .iter()
+            .find(|(full, _)| full.eq_ignore_ascii_case(root))
+        {
+            Some((_, short)) => match path {
+                Some(path) => format!("{}\\{}", short, path).into(),
+                None => (*short).into(),
+            },
+            None => name.into(),
+        }
+    }
+}
+
+/// Compares key names component by component, ignoring case like the registry does.
+fn registry_order(a: &str, b: &str) -> Ordering {
+    a.split('\\')
+        .map(str::to_uppercase)
+        .cmp(b.split('\\').map(str::to_uppercase))
+}
+
+/// Appends the header line of a key, or of a key deletion.
+///
+/// # Returns
+///
+/// [Error::InvalidName] if the name holds a line break, which can't be written in a header.
+pub(crate) fn key_header(
+    out: &mut String,
+    name: &KeyName,
+    deleted: bool,
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    check_line(name.raw(), "key names can't contain line breaks")?;
+    let name = options.key_name(name.raw());
+    let _ = writeln!(out, "[{}{}]", if deleted { "-" } else { "" }, name);
+    Ok(())
+}
+
+/// Appends the line deleting a value.
+///
+/// # Returns
+///
+/// [Error::InvalidName] if the name holds a line break, which can't be written between quotes.
+pub(crate) fn deleted_value(
+    out: &mut String,
+    name: &ValueName,
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    check_value_name(name)?;
+    let _ = writeln!(out, "{}=-", value_name(name, options));
+    Ok(())
+}
+
+/// Appends the line setting a value.
+///
+/// # Returns
+///
+/// [Error::InvalidName] if the name holds a line break, which can't be written between quotes.
+pub(crate) fn value(out: &mut String, value: &Value, options: &WriteOptions) -> Result<(), Error> {
+    check_value_name(value.name())?;
+    let data = data(value, options);
+    let name = value_name(value.name(), options);
+    let line = format!("{}={}", name, data);
+    match options.line_width {
+        Some(width) if data.starts_with("hex") => out.push_str(&wrap(&line, name.len(), width)),
+        _ => out.push_str(&line),
+    }
+    out.push('\n');
+    Ok(())
+}
+
+/// Appends the `#security=` line recording the security descriptor of a key, to follow its
+/// header.
+pub(crate) fn security(out: &mut String, security: &SecurityDescriptor, options: &WriteOptions) {
+    let line = format!("#security={}", hex(None, security.raw()));
+    match options.line_width {
+        Some(width) => out.push_str(&wrap(&line, "#security".len(), width)),
+        None => out.push_str(&line),
+    }
+    out.push('\n');
+}
+
+/// Appends the `SymbolicLinkValue` line turning a key into a symbolic link to a native path,
+/// stored as UTF-16LE without a terminator like Wine and Windows store it.
+pub(crate) fn link(out: &mut String, target: &str, options: &WriteOptions) {
+    let _ = value(out, &link_value(target), options);
+}
+
+/// Returns the `REG_LINK` value holding the target of a symbolic link key.
+fn link_value(target: &str) -> Value {
+    Value::raw(ValueName::named("SymbolicLinkValue"), 6, utf16(target))
+}
+
+/// Fails with [Error::InvalidName] if a value name can't be written between quotes.
+fn check_value_name(name: &ValueName) -> Result<(), Error> {
+    match name {
+        ValueName::Named(name) => check_line(name, "value names can't contain line breaks"),
+        ValueName::Default => Ok(()),
+    }
+}
+
+/// Fails with [Error::InvalidName] if a name holds a line break.
+fn check_line(name: &str, message: &str) -> Result<(), Error> {
+    if name.contains(['\r', '\n']) {
+        return Err(Error::InvalidName {
+            name: name.to_string(),
+            message: message.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Wraps the hex data of a value line, starting at byte `start`, with continuation
+/// backslashes.
+fn wrap(line: &str, start: usize, width: usize) -> String {
+    let mut wrapped = String::with_capacity(line.len() + line.len() / width.max(1) * 5);
+    let mut column = 0;
+    for (index, c) in line.char_indices() {
+        wrapped.push(c);
+        column += 1;
+        if c == ',' && index > start && column >= width.saturating_sub(3) {
+            wrapped.push_str("\\\n  ");
+            column = 2;
+        }
+    }
+    wrapped
+}
+
+/// Returns the length in bytes of the line [key_header] appends with the default options,
+/// without formatting it.
+pub(crate) fn key_header_len(name: &KeyName, deleted: bool) -> usize {
+    name.raw().len() + if deleted { 4 } else { 3 }
+}
+
+/// Returns the length in bytes of the line [security] appends with the default options,
+/// without formatting it.
+pub(crate) fn security_len(security: &SecurityDescriptor) -> usize {
+    "#security=".len() + hex_len(None, security.raw().len()) + 1
+}
+
+/// Returns the length in bytes of the line [link] appends with the default options, without
+/// formatting it.
+pub(crate) fn link_len(target: &str) -> usize {
+    value_len(&link_value(target))
+}
+
+/// Returns the length in bytes of the line [deleted_value] appends with the default options,
+/// without formatting it.
+pub(crate) fn deleted_value_len(name: &ValueName) -> usize {
+    value_name_len(name) + 3
+}
+
+/// Returns the length in bytes of the line [value] appends with the default options, without
+/// formatting it.
+pub(crate) fn value_len(value: &Value) -> usize {
+    value_name_len(value.name()) + data_len(value) + 2
+}
+
+/// Returns the length of a formatted value name.
+fn value_name_len(name: &ValueName) -> usize {
+    match name {
+        ValueName::Default => 1,
+        ValueName::Named(name) => escaped_len(name) + 2,
+    }
+}
+
+/// Returns the length of the formatted data of a value, following [data].
+fn data_len(value: &Value) -> usize {
+    if let (Some(raw_type), regashii::Value::Binary(bytes)) = (value.raw_type(), value.value()) {
+        return hex_len(Some(raw_type), bytes.len());
+    }
+
+    let units = |text: &str| text.encode_utf16().count();
+    match value.value() {
+        regashii::Value::Delete => 1,
+        regashii::Value::Sz(string) if string.contains(['\r', '\n', '\0']) => {
+            hex_len(Some(1), (units(string) + 1) * 2)
+        }
+        regashii::Value::Sz(string) => escaped_len(string) + 2,
+        regashii::Value::ExpandSz(string) => hex_len(Some(2), (units(string) + 1) * 2),
+        regashii::Value::Binary(bytes) => hex_len(None, bytes.len()),
+        regashii::Value::Dword(_) => 14,
+        regashii::Value::MultiSz(strings) => {
+            let units: usize = strings.iter().map(|s| units(s) + 1).sum();
+            hex_len(Some(7), (units + 1) * 2)
+        }
+        regashii::Value::Qword(_) => hex_len(Some(0xb), 8),
+        regashii::Value::DwordBigEndian(_) => hex_len(Some(5), 4),
+        regashii::Value::Hex { kind, bytes } => hex_len(Some(type_code(kind)), bytes.len()),
+    }
+}
+
+/// Returns the length of a string once escaped by [escape].
+fn escaped_len(text: &str) -> usize {
+    text.len() + text.matches(['\\', '"']).count()
+}
+
+/// Returns the length of [hex] data of the given type holding `len` bytes.
+fn hex_len(raw_type: Option<u32>, len: usize) -> usize {
+    let prefix = match raw_type {
+        Some(raw_type) => format!("hex({:x}):", raw_type).len(),
+        None => 4,
+    };
+    prefix + (len * 3).saturating_sub(1)
+}
+
+/// Formats a value name.
+pub(crate) fn value_name(name: &ValueName, options: &WriteOptions) -> String {
+    match name {
+        ValueName::Default => "@".to_string(),
+        ValueName::Named(name) => format!("\"{}\"", escape(name, options)),
+    }
+}
+
+/// Formats the data of a value.
+pub(crate) fn data(value: &Value, options: &WriteOptions) -> String {
+    if let (Some(raw_type), regashii::Value::Binary(bytes)) = (value.raw_type(), value.value()) {
+        return hex(Some(raw_type), bytes);
+    }
+
+    match value.value() {
+        regashii::Value::Delete => "-".to_string(),
+        // Line breaks and NULs can't be written inside quotes.
+        regashii::Value::Sz(string) if string.contains(['\r', '\n', '\0']) => {
+            hex(Some(1), &utf16(&format!("{}\0", string)))
+        }
+        regashii::Value::Sz(string) => format!("\"{}\"", escape(string, options)),
+        regashii::Value::ExpandSz(string) => hex(Some(2), &utf16(&format!("{}\0", string))),
+        regashii::Value::Binary(bytes) => hex(None, bytes),
+        regashii::Value::Dword(dword) => format!("dword:{:08x}", dword),
+        regashii::Value::MultiSz(strings) => {
+            let mut text: String = strings.iter().map(|s| format!("{}\0", s)).collect();
+            text.push('\0');
+            hex(Some(7), &utf16(&text))
+        }
+        regashii::Value::Qword(qword) => hex(Some(0xb), &qword.to_le_bytes()),
+        regashii::Value::DwordBigEndian(dword) => hex(Some(5), &dword.to_be_bytes()),
+        regashii::Value::Hex { kind, bytes } => hex(Some(type_code(kind)), bytes),
+    }
+}
+
+/// Escapes the quotes of a string, and its backslashes unless disabled by `options`.
+///
+/// Backslashes right before a quote or the end of the string are doubled either way, as they
+/// would otherwise escape the quote following them.
+pub(crate) fn escape(text: &str, options: &WriteOptions) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (index, c) in text.char_indices() {
+        escaped.push(c);
+        if c != '\\' {
+            continue;
+        }
+        let next = text[index..].trim_start_matches('\\').chars().next();
+        if options.escape_backslashes || matches!(next, None | Some('"')) {
+            escaped.push('\\');
+        }
+    }
+    escaped.replace('"', "\\\"")
+}
+
+/// Encodes a string as UTF-16LE.
+fn utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+/// Formats bytes as `hex:` data, or `hex(N):` data of the given type.
+fn hex(raw_type: Option<u32>, bytes: &[u8]) -> String {
+    let mut data = match raw_type {
+        Some(raw_type) => format!("hex({:x}):", raw_type),
+        None => "hex:".to_string(),
+    };
+    let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    data.push_str(&bytes.join(","));
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{Hive, Registry};
+
+    fn line(data: Value) -> String {
+        let mut out = String::new();
+        value(&mut out, &data, &WriteOptions::default()).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_write_values() {
+        let name = || ValueName::named("A\"B");
+        assert_eq!(
+            line(Value::new(name(), regashii::Value::Sz("C:\\".to_string()))),
+            "\"A\\\"B\"=\"C:\\\\\"\n"
+        );
+        assert_eq!(
+            line(Value::new(ValueName::Default, regashii::Value::Dword(42))),
+            "@=dword:0000002a\n"
+        );
+        assert_eq!(
+            line(Value::new(
+                name(),
+                regashii::Value::MultiSz(vec!["a".to_string()])
+            )),
+            "\"A\\\"B\"=hex(7):61,00,00,00,00,00\n"
+        );
+        assert_eq!(
+            line(Value::raw(name(), 0, vec![1, 2])),
+            "\"A\\\"B\"=hex(0):01,02\n"
+        );
+    }
+
+    #[test]
+    fn test_write_options() {
+        let options = WriteOptions::new()
+            .with_hive_names(HiveNames::Abbreviated)
+            .with_backslash_escaping(false);
+        let mut out = String::new();
+        key_header(
+            &mut out,
+            &KeyName::new("HKEY_USERS\\.Default\\Software"),
+            false,
+            &options,
+        )
+        .unwrap();
+        key_header(&mut out, &KeyName::new("HKEY_CURRENT_USER"), true, &options).unwrap();
+        key_header(&mut out, &KeyName::new("Software\\Wine"), false, &options).unwrap();
+        value(
+            &mut out,
+            &Value::new(
+                ValueName::named("A\\B"),
+                regashii::Value::Sz("\"C:\\\"".to_string()),
+            ),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            "[HKU\\.Default\\Software]\n[-HKCU]\n[Software\\Wine]\n\"A\\B\"=\"\\\"C:\\\\\\\"\"\n"
+        );
+
+        for data in ["C:\\", "\"C:\\\"", "C:\\windows", "a\\\\\"b\\"] {
+            let mut text = "REGEDIT4\n\n[HKEY_CURRENT_USER\\Test]\n".to_string();
+            let name = ValueName::named("A\\B");
+            let data = regashii::Value::Sz(data.to_string());
+            value(&mut text, &Value::new(name.clone(), data.clone()), &options).unwrap();
+
+            let registry = Registry::try_from_str(&text, Hive::CurrentUser).unwrap();
+            let key = registry.key(&KeyName::new("Test")).unwrap();
+            assert_eq!(key.values()[&name].value(), &data);
+        }
+    }
+
+    #[test]
+    fn test_registry_order() {
+        let mut names = [
+            "Software\\A B",
+            "Software\\b",
+            "Software\\A\\x",
+            "Software\\a",
+        ]
+        .map(KeyName::new)
+        .to_vec();
+        names.sort();
+        WriteOptions::new()
+            .with_key_order(KeyOrder::Registry)
+            .sort_keys(&mut names, |name| name);
+        assert_eq!(
+            names.iter().map(KeyName::raw).collect::<Vec<_>>(),
+            [
+                "Software\\a",
+                "Software\\A\\x",
+                "Software\\A B",
+                "Software\\b"
+            ]
+        );
+
+        let mut values = [
+            ValueName::named("b"),
+            ValueName::named("C"),
+            ValueName::Default,
+        ];
+        values.sort();
+        WriteOptions::new()
+            .with_key_order(KeyOrder::Registry)
+            .sort_values(&mut values, |name| name);
+        assert_eq!(
+            values,
+            [
+                ValueName::Default,
+                ValueName::named("b"),
+                ValueName::named("C")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_hex_data() {
+        let options = WriteOptions::new().with_line_width(Some(80));
+        let mut out = String::new();
+        value(
+            &mut out,
+            &Value::new(
+                ValueName::named("a,b"),
+                regashii::Value::Binary(vec![0xab; 60]),
+            ),
+            &options,
+        )
+        .unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("\"a,b\"=hex:ab,ab,"));
+        assert!(lines[..2]
+            .iter()
+            .all(|line| line.ends_with(",\\") && line.len() <= 80));
+        assert!(lines[1..].iter().all(|line| line.starts_with("  ab")));
+        assert_eq!(out.matches("ab").count(), 60);
+        let text = format!("{}\n\n[Software]\n{}", HEADER, out);
+        let registry =
+            crate::prelude::Registry::try_from_str(&text, crate::prelude::Hive::CurrentUser)
+                .unwrap();
+        let key = registry.key(&KeyName::new("Software")).unwrap();
+        assert_eq!(
+            key.values()[&ValueName::named("a,b")].value(),
+            &regashii::Value::Binary(vec![0xab; 60])
+        );
+
+        let mut short = String::new();
+        value(
+            &mut short,
+            &Value::new(ValueName::Default, regashii::Value::Binary(vec![1, 2])),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(short, "@=hex:01,02\n");
+    }
+}